@@ -2,8 +2,24 @@ use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use std::env::current_dir;
 use std::fs::create_dir_all;
 
-use secret_lottery::msg::{HandleMsg, InitMsg, PoolResponse, QueryMsg};
+use secret_lottery::aggregator::{
+    DepositResponseV1, InterfaceResponse, LotteryHandleV1, LotteryQueryV1, PrizeResponseV1,
+    RoundResponseV1,
+};
+use secret_lottery::hooks::{HookMsg, WinnerMsg};
+use secret_lottery::msg::{
+    AmITheWinnerResponse, BatchResponse, ChangesResponse, ChildrenResponse, ConfigResponse,
+    DashboardResponse, DelegatorCountResponse, DelegatorsResponse, ExportResponse, FeeInfoResponse,
+    HandleAnswer, HandleMsg, HealthResponse, HistoryResponse, InitMsg, LeaderboardResponse,
+    MigrateMsg, MigrationPreviewResponse, MyDepositResponse, OddsResponse, OwnerResponse,
+    PastWinnersResponse, PoolHistoryResponse, PoolResponse, PrizePotResponse, QueryMsg, ReceiveMsg,
+    RecoveryResponse, ReferralResponse, RewardBreakdownResponse, RoundResponse,
+    SettlementProgressResponse, StatisticsResponse, StatsResponse, TicketRateResponse,
+    TimeRemainingResponse, TotalValueLockedResponse, TransitionsResponse, UnbondingStatusResponse,
+    ValidatorSetResponse,
+};
 use secret_lottery::state::State;
+use secret_lottery::treasury::TreasuryHandleMsg;
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -13,7 +29,58 @@ fn main() {
 
     export_schema(&schema_for!(InitMsg), &out_dir);
     export_schema(&schema_for!(HandleMsg), &out_dir);
-    export_schema(&schema_for!(PoolResponse), &out_dir);
+    export_schema(&schema_for!(HandleAnswer), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
+
+    // One per query.
+    export_schema(&schema_for!(OwnerResponse), &out_dir);
+    export_schema(&schema_for!(RecoveryResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(PoolResponse), &out_dir);
+    export_schema(&schema_for!(TimeRemainingResponse), &out_dir);
+    export_schema(&schema_for!(PoolHistoryResponse), &out_dir);
+    export_schema(&schema_for!(PastWinnersResponse), &out_dir);
+    export_schema(&schema_for!(StatsResponse), &out_dir);
+    export_schema(&schema_for!(StatisticsResponse), &out_dir);
+    export_schema(&schema_for!(ChangesResponse), &out_dir);
+    export_schema(&schema_for!(TotalValueLockedResponse), &out_dir);
+    export_schema(&schema_for!(ChildrenResponse), &out_dir);
+    export_schema(&schema_for!(LeaderboardResponse), &out_dir);
+    export_schema(&schema_for!(RoundResponse), &out_dir);
+    export_schema(&schema_for!(UnbondingStatusResponse), &out_dir);
+    export_schema(&schema_for!(SettlementProgressResponse), &out_dir);
+    export_schema(&schema_for!(TicketRateResponse), &out_dir);
+    export_schema(&schema_for!(ValidatorSetResponse), &out_dir);
+    export_schema(&schema_for!(FeeInfoResponse), &out_dir);
+    export_schema(&schema_for!(RewardBreakdownResponse), &out_dir);
+    export_schema(&schema_for!(DashboardResponse), &out_dir);
+    export_schema(&schema_for!(PrizePotResponse), &out_dir);
+    export_schema(&schema_for!(DelegatorCountResponse), &out_dir);
+    export_schema(&schema_for!(TransitionsResponse), &out_dir);
+    export_schema(&schema_for!(ReferralResponse), &out_dir);
+    export_schema(&schema_for!(DelegatorsResponse), &out_dir);
+    export_schema(&schema_for!(MyDepositResponse), &out_dir);
+    export_schema(&schema_for!(OddsResponse), &out_dir);
+    export_schema(&schema_for!(HistoryResponse), &out_dir);
+    export_schema(&schema_for!(AmITheWinnerResponse), &out_dir);
+    export_schema(&schema_for!(BatchResponse), &out_dir);
+    export_schema(&schema_for!(HealthResponse), &out_dir);
+    export_schema(&schema_for!(MigrationPreviewResponse), &out_dir);
+    export_schema(&schema_for!(ExportResponse), &out_dir);
+
+    // The stable aggregator interface.
+    export_schema(&schema_for!(LotteryHandleV1), &out_dir);
+    export_schema(&schema_for!(LotteryQueryV1), &out_dir);
+    export_schema(&schema_for!(InterfaceResponse), &out_dir);
+    export_schema(&schema_for!(RoundResponseV1), &out_dir);
+    export_schema(&schema_for!(PrizeResponseV1), &out_dir);
+    export_schema(&schema_for!(DepositResponseV1), &out_dir);
+
+    // Messages the lottery sends to hook, winner and treasury contracts.
+    export_schema(&schema_for!(HookMsg), &out_dir);
+    export_schema(&schema_for!(WinnerMsg), &out_dir);
+    export_schema(&schema_for!(TreasuryHandleMsg), &out_dir);
 }
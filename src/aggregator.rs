@@ -0,0 +1,77 @@
+use cosmwasm_std::{HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Versions of the aggregator interface this contract answers, oldest first. A
+// version is frozen once released: changes go into a new `LotteryV{n}` variant
+// next to the old ones, so integrations never track `HandleMsg` or `QueryMsg`.
+pub const INTERFACE_NAME: &str = "secret_lottery";
+pub const INTERFACE_VERSIONS: &[u32] = &[1];
+
+// Sent as `{"lottery_v1": {...}}` to `handle`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LotteryHandleV1 {
+    // Enter the current round with the attached funds.
+    Deposit {
+        padding: Option<String>,
+    },
+    // Winner only. Pay out the prize of a drawn round to the sender.
+    Claim {
+        pool_id: u64,
+        padding: Option<String>,
+    },
+}
+
+// Sent as `{"lottery_v1": {...}}` to `query`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LotteryQueryV1 {
+    Interface {},
+    CurrentRound {},
+    // The current round's prize so far, or the prize a past round paid out.
+    Prize { pool_id: Option<u64> },
+    // Authenticated with the viewing key set by `address`.
+    Deposit { address: HumanAddr, key: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundPhaseV1 {
+    Open,
+    Locked,
+    Closed,
+    Drawn,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InterfaceResponse {
+    pub name: String,
+    pub versions: Vec<u32>,
+}
+
+// `pool_id` is None before the first round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundResponseV1 {
+    pub pool_id: Option<u64>,
+    pub phase: Option<RoundPhaseV1>,
+    pub denom: String,
+    pub accepts_deposits: bool,
+    pub total_deposits: Uint128,
+}
+
+// `amount` is final once the round is drawn.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrizeResponseV1 {
+    pub pool_id: Option<u64>,
+    pub amount: Uint128,
+    pub denom: String,
+    pub drawn: bool,
+    pub claimed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositResponseV1 {
+    pub pool_id: Option<u64>,
+    pub amount: Uint128,
+}
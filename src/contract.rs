@@ -1,21 +1,174 @@
-use crate::msg::{HandleMsg, InitMsg, OwnerResponse, PoolResponse, QueryMsg};
-use crate::state::{config, config_read, pool_read, pool_storage, Pool, PoolStatus, State, DAYS};
+use crate::aggregator::{
+    DepositResponseV1, InterfaceResponse, LotteryHandleV1, LotteryQueryV1, PrizeResponseV1,
+    RoundPhaseV1, RoundResponseV1, INTERFACE_NAME, INTERFACE_VERSIONS,
+};
+use crate::error::ContractError;
+use crate::events;
+use crate::hooks::{hook_messages, Hook, HookEvent, WinnerMsg, MAX_HOOKS};
+use crate::ics20::Ics20HandleMsg;
+use crate::math;
+use crate::msg::{
+    AmITheWinnerResponse, BatchAnswer, BatchItem, BatchResponse, Change, ChangesResponse,
+    ChildInfo, ChildrenResponse, ConfigResponse, DashboardResponse, DelegatorCountResponse,
+    DelegatorInfo, DelegatorsResponse, ExportCursor, ExportEntry, ExportHistory, ExportPlayer,
+    ExportReferral, ExportReferred, ExportResponse, FeeInfoResponse, HandleAnswer, HandleMsg,
+    HealthResponse, HistoryItem, HistoryResponse, InitMsg, InvariantCheck, LeaderboardEntry,
+    LeaderboardOrder, LeaderboardResponse, MigrateMsg, MigrationPreviewResponse, MyDepositResponse,
+    NextTransition, OddsResponse, OwnerResponse, ParentInfo, PastWinner, PastWinnersResponse,
+    PendingFee, PendingRecovery, PoolHistoryResponse, PoolInfo, PoolResponse, PoolSummary,
+    PoolTicketRate, PrizePotResponse, QueryMsg, ReceiveMsg, RecoveryResponse, ReferralResponse,
+    RewardBreakdownResponse, RoundResponse, ScheduledPhase, SettlementProgressResponse,
+    SettlementStage, StatisticsResponse, StatsResponse, TicketRateResponse, TimeRemainingResponse,
+    TotalValueLockedResponse, TransitionInfo, TransitionsResponse, UnbondingInfo,
+    UnbondingStatusResponse, ValidatorInfo, ValidatorSetResponse,
+};
+use crate::oracle::Oracle;
+use crate::payout;
+use crate::router::{Callback, RouterHandleMsg};
+use crate::snip20::Snip20HandleMsg;
+use crate::snip721::{Metadata, Snip721HandleMsg};
+use crate::state::{
+    add_coin, add_entropy, add_referred, advance_draw, announce_winner, check_invariants,
+    check_viewing_key, children, children_read, config, config_read, count_deposit, current_pool,
+    draw_randomness, events_read, generate_viewing_key, has_claimed_emission, has_claimed_referral,
+    has_prng_seed, legacy_config_read, legacy_pool_read, legacy_pool_storage, list_player,
+    listed_players_read, load_entry, load_entry_balances, load_referred, load_seen_keys,
+    mark_emission_claimed, mark_referral_claimed, max_fee_rate, max_unbonding_slash,
+    may_load_entry, may_load_memo, may_load_player, may_load_referral, pad_to_block, page_range,
+    page_range_rev, pending_hooks, pending_hooks_read, player_history_read, pool_delegators,
+    pool_index, pool_index_read, pool_key, pool_namespace, pool_read, pool_storage, push_delegator,
+    push_history, push_transition, read_delegators, read_history, read_keys, read_page,
+    read_transitions, read_winners, record_event, referral_code_hash, remove_entry,
+    rotate_prng_seed, round_to_step, save_entry, save_entry_balances, save_memo, save_player,
+    save_referral, seal_draw_seed, set_storage_version, split_by_weight, storage_version,
+    unlist_player, use_idempotency_key, validate_memo, validate_validators, winner_callbacks,
+    winner_callbacks_read, write_prng_seed, write_viewing_key, Action, Child, DepositLimit,
+    DrawProgress, Emission, Entry, EventKind, Factory, FeeChange, Fees, HistoryEntry, IbcDenom,
+    IbcPayout, Pool, PoolIndex, PoolMetadata, PoolStatus, Recovery, Referral, State, Stats, Timing,
+    TimingMode, Token, WinnerCallback, DEFAULT_LIMIT, DENOM, GENERIC_ERROR, LEGACY_STORAGE_VERSION,
+    MAX_DEPOSITORS, MAX_EXTRA_DENOMS, MAX_LIMIT, MAX_REFERRAL_CODE_LEN, MAX_RESPONSE_BLOCK_SIZE,
+    PLAYER_LIST_KEY, REFERRAL_COUNT_STEP, REFERRAL_LIST_KEY, REFERRED_LIST_NAMESPACE,
+    RESPONSE_BLOCK_SIZE, STORAGE_VERSION, UNBONDING_GRACE, UNBONDING_PERIOD,
+};
+use crate::transitions::{
+    self,
+    Transition::{Close, Lock, Settle},
+};
+use crate::treasury::{TreasuryHandleMsg, TreasuryPurpose};
 use cosmwasm_std::{
-    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HandleResult, InitResponse,
-    Querier, StdError, StdResult, Storage,
+    coin, coins, from_binary, log, to_binary, Api, BankMsg, Binary, CanonicalAddr, CosmosMsg,
+    Decimal, Env, Extern, HandleResponse, HumanAddr, InitResponse, LogAttribute, MigrateResponse,
+    Querier, QueryRequest, StakingMsg, StdError, StdResult, Storage, Uint128, WasmMsg, WasmQuery,
 };
+use std::cmp::Reverse;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    let validators = msg.validators.unwrap_or_default();
+    validate_validators(&validators)?;
+    let timing = msg.timing.unwrap_or_default();
+    timing.validate()?;
+    let response_block_size = msg.response_block_size.unwrap_or(RESPONSE_BLOCK_SIZE);
+    if response_block_size == 0 || response_block_size > MAX_RESPONSE_BLOCK_SIZE {
+        return Err(ContractError::InvalidBlockSize {
+            max: MAX_RESPONSE_BLOCK_SIZE,
+        }
+        .into());
+    }
+    let transparent = msg.transparent.unwrap_or(false);
+    let public_rounding = msg.public_rounding.unwrap_or(Uint128(1));
+    let delay_public_totals = msg.delay_public_totals.unwrap_or(false);
+    if transparent && (public_rounding > Uint128(1) || delay_public_totals) {
+        return Err(ContractError::TransparentRounding.into());
+    }
     let state = State {
-        count: 0,
         owner: deps.api.canonical_address(&env.message.sender)?,
+        pool_count: 0,
+        stats: Stats::default(),
+        announced_winners: 0,
+        listed_players: 0,
+        event_count: 0,
+        response_block_size,
+        fees: Fees::default(),
+        contract_address: Some(env.contract.address.clone()),
+        public_rounding,
+        delay_public_totals,
+        published_stats: None,
+        transparent,
+        generic_errors: msg.generic_errors.unwrap_or(false),
+        tokens: vec![],
+        ticket: None,
+        nft_contract: None,
+        emission: None,
+        hooks: vec![],
+        swap_router: None,
+        oracle: None,
+        wrapped_native: None,
+        factory: None,
+        child_count: 0,
+        parent: msg.parent.as_ref().map(|p| Token {
+            address: p.address.clone(),
+            code_hash: p.code_hash.clone(),
+        }),
+        ibc_gateway: None,
+        treasury: None,
+        pool_denoms: vec![],
+        cover_slashes: false,
+        deposit_limit: None,
+        max_depositors: MAX_DEPOSITORS,
+        unclaimed_prizes: 0,
+        max_unclaimed_prizes: None,
+        settled_principal: Uint128::zero(),
+        settled_prizes: Uint128::zero(),
+        carryover: Uint128::zero(),
+        referral_share: Decimal::zero(),
+        referral_rewards: Uint128::zero(),
+        guardian: match msg.guardian.as_ref() {
+            Some(guardian) => Some(deps.api.canonical_address(guardian)?),
+            None => None,
+        },
+        recovery: None,
+        timing,
+        validators,
     };
     config(&mut deps.storage).save(&state)?;
-    Ok(InitResponse::default())
+    pool_index(&mut deps.storage).save(&PoolIndex::default())?;
+    set_storage_version(&mut deps.storage)?;
+    let seed = match msg.prng_seed {
+        Some(seed) => seed.0,
+        None => block_seed(&env),
+    };
+    write_prng_seed(&mut deps.storage, &seed);
+    let mut messages = vec![];
+    if let Some(parent) = msg.parent {
+        let register = HandleMsg::RegisterChild {
+            index: parent.index,
+            padding: None,
+        };
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: parent.address,
+            callback_code_hash: parent.code_hash,
+            msg: to_binary(&register)?,
+            send: vec![],
+        }));
+    }
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
+}
+
+// Fallback viewing key seed. Public, but every generated key mixes in the sender's entropy.
+fn block_seed(env: &Env) -> Vec<u8> {
+    [
+        env.block.chain_id.as_bytes(),
+        &env.block.height.to_be_bytes(),
+        &env.block.time.to_be_bytes(),
+    ]
+    .concat()
 }
 
 pub fn handle<S: Storage, A: Api, Q: Querier>(
@@ -23,196 +176,10241 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    match msg {
-        HandleMsg::CrtePool {} => admin_create_pool(deps, env),
-        HandleMsg::LockPool {} => admin_lock_pool(deps, env),
-        HandleMsg::ClsePool {} => admin_close_pool(deps, env),
+    let sensitive = matches!(
+        msg,
+        HandleMsg::Deposit { .. }
+            | HandleMsg::Receive { .. }
+            | HandleMsg::Withdraw { .. }
+            | HandleMsg::WithdrawWrapped { .. }
+            | HandleMsg::AnnounceWin { .. }
+            | HandleMsg::ClaimPrize { .. }
+            | HandleMsg::ClaimPrincipal { .. }
+            | HandleMsg::LotteryV1(_)
+            | HandleMsg::ReceiveNft { .. }
+            | HandleMsg::ClaimEmissions { .. }
+            | HandleMsg::ClaimReferralRewards { .. }
+    );
+    let moves_balances = matches!(
+        msg,
+        HandleMsg::CrtePool { .. }
+            | HandleMsg::LockPool { .. }
+            | HandleMsg::ClsePool { .. }
+            | HandleMsg::Deposit { .. }
+            | HandleMsg::Withdraw { .. }
+            | HandleMsg::WithdrawWrapped { .. }
+            | HandleMsg::Sponsor { .. }
+            | HandleMsg::Draw { .. }
+            | HandleMsg::Receive { .. }
+            | HandleMsg::ReceiveNft { .. }
+            | HandleMsg::SwapProceeds { .. }
+            | HandleMsg::LotteryV1(LotteryHandleV1::Deposit { .. })
+    );
+    // Only these take native funds; coins sent anywhere else would be stranded.
+    // `ExecuteChild` relays its funds to the child round.
+    let takes_funds = matches!(
+        msg,
+        HandleMsg::CrtePool { .. }
+            | HandleMsg::Deposit { .. }
+            | HandleMsg::Sponsor { .. }
+            | HandleMsg::SwapProceeds { .. }
+            | HandleMsg::ExecuteChild { .. }
+            | HandleMsg::LotteryV1(LotteryHandleV1::Deposit { .. })
+    );
+    if !takes_funds && !env.message.sent_funds.is_empty() {
+        let response = Err(ContractError::FundsNotAccepted.into());
+        return if sensitive {
+            obscure_error(deps, response)
+        } else {
+            response
+        };
+    }
+    // A wallet retrying after a timeout resends the same key, so the retry fails
+    // instead of depositing or claiming twice.
+    let idempotency_key = match &msg {
+        HandleMsg::Deposit {
+            idempotency_key, ..
+        }
+        | HandleMsg::ClaimPrize {
+            idempotency_key, ..
+        }
+        | HandleMsg::ClaimPrincipal {
+            idempotency_key, ..
+        }
+        | HandleMsg::ClaimEmissions {
+            idempotency_key, ..
+        } => idempotency_key.as_ref(),
+        _ => None,
+    };
+    if let Some(key) = idempotency_key {
+        let sender = deps.api.canonical_address(&env.message.sender)?;
+        if let Err(err) = use_idempotency_key(&mut deps.storage, &sender, key, env.block.time) {
+            let response = Err(err);
+            return if sensitive {
+                obscure_error(deps, response)
+            } else {
+                response
+            };
+        }
+    }
+    let response = match msg {
+        HandleMsg::CrtePool {
+            metadata,
+            nft_tickets,
+            ibc_denom,
+            extra_denoms,
+            ..
+        } => admin_create_pool(
+            deps,
+            env,
+            metadata.unwrap_or_default(),
+            nft_tickets.unwrap_or(false),
+            ibc_denom,
+            extra_denoms.unwrap_or_default(),
+        ),
+        HandleMsg::LockPool { .. } => admin_lock_pool(deps, env),
+        HandleMsg::ClsePool { .. } => admin_close_pool(deps, env),
+        HandleMsg::Deposit { memo, referral, .. } => deposit(deps, env, memo, referral),
+        HandleMsg::Withdraw { amount, .. } => withdraw(deps, env, amount),
+        HandleMsg::CleanupPool { pool_id, limit, .. } => cleanup_pool(deps, env, pool_id, limit),
+        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
+        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps, env, entropy),
+        HandleMsg::Sponsor { .. } => sponsor(deps, env),
+        HandleMsg::SetPublicListing { public, .. } => set_public_listing(deps, env, public),
+        HandleMsg::SetFee {
+            rate, recipient, ..
+        } => admin_set_fee(deps, env, rate, recipient),
+        HandleMsg::RotateSeed { entropy, .. } => admin_rotate_seed(deps, env, entropy),
+        HandleMsg::Draw { .. } => admin_draw(deps, env),
+        HandleMsg::PrepareDraw { limit, .. } => prepare_draw(deps, env, limit),
+        HandleMsg::AnnounceWin { pool_id, .. } => announce_win(deps, env, pool_id),
+        HandleMsg::ClaimPrize {
+            pool_id,
+            ibc_payout,
+            ..
+        } => claim_prize(deps, env, pool_id, ibc_payout),
+        HandleMsg::ClaimPrincipal { pool_id, .. } => claim_principal(deps, env, pool_id),
+        HandleMsg::SetIbcGateway { gateway, .. } => admin_set_ibc_gateway(deps, env, gateway),
+        HandleMsg::SetTreasury { treasury, .. } => admin_set_treasury(deps, env, treasury),
+        HandleMsg::SetSlashCover { enabled, .. } => admin_set_slash_cover(deps, env, enabled),
+        HandleMsg::SetDepositLimit { limit, .. } => admin_set_deposit_limit(deps, env, limit),
+        HandleMsg::SetMaxDepositors { max, .. } => admin_set_max_depositors(deps, env, max),
+        HandleMsg::SetMaxUnclaimedPrizes { max, .. } => {
+            admin_set_max_unclaimed_prizes(deps, env, max)
+        }
+        HandleMsg::StartRecovery { new_owner, .. } => start_recovery(deps, env, new_owner),
+        HandleMsg::CancelRecovery { .. } => cancel_recovery(deps, env),
+        HandleMsg::FinishRecovery { .. } => finish_recovery(deps, env),
+        HandleMsg::CollectFees { .. } => admin_collect_fees(deps, env),
+        HandleMsg::SweepToTreasury { denom, .. } => admin_sweep_to_treasury(deps, env, denom),
+        HandleMsg::RegisterReferralCode { code, .. } => register_referral_code(deps, env, code),
+        HandleMsg::SetReferralShare { share, .. } => admin_set_referral_share(deps, env, share),
+        HandleMsg::ClaimReferralRewards { code, pool_ids, .. } => {
+            claim_referral_rewards(deps, env, code, pool_ids)
+        }
+        HandleMsg::RegisterToken {
+            address, code_hash, ..
+        } => admin_register_token(deps, env, address, code_hash),
+        HandleMsg::DeregisterToken { address, .. } => admin_deregister_token(deps, env, address),
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => receive(deps, env, from, amount, msg),
+        HandleMsg::SetTicketToken {
+            address, code_hash, ..
+        } => admin_set_ticket_token(deps, env, address, code_hash),
+        HandleMsg::SetNftContract {
+            address, code_hash, ..
+        } => admin_set_nft_contract(deps, env, address, code_hash),
+        HandleMsg::ReceiveNft { from, token_id, .. } => receive_nft(deps, env, from, token_id),
+        HandleMsg::SetEmission {
+            address,
+            code_hash,
+            per_round,
+            halving_interval,
+            ..
+        } => admin_set_emission(deps, env, address, code_hash, per_round, halving_interval),
+        HandleMsg::ClaimEmissions { pool_ids, .. } => claim_emissions(deps, env, pool_ids),
+        HandleMsg::RegisterHook { code_hash, .. } => register_hook(deps, env, code_hash),
+        HandleMsg::ApproveHook { address, .. } => admin_approve_hook(deps, env, address),
+        HandleMsg::RemoveHook { address, .. } => remove_hook(deps, env, address),
+        HandleMsg::SetWinnerCallback { callback, .. } => set_winner_callback(deps, env, callback),
+        HandleMsg::SetSwapRouter {
+            address, code_hash, ..
+        } => admin_set_swap_router(deps, env, address, code_hash),
+        HandleMsg::ConvertRewards { min_return, .. } => {
+            admin_convert_rewards(deps, env, min_return)
+        }
+        HandleMsg::SwapProceeds { pool_id, .. } => swap_proceeds(deps, env, pool_id),
+        HandleMsg::ResetConversion { .. } => admin_reset_conversion(deps, env),
+        HandleMsg::SetOracle { oracle, .. } => admin_set_oracle(deps, env, oracle),
+        HandleMsg::SetWrappedNative {
+            address, code_hash, ..
+        } => admin_set_wrapped_native(deps, env, address, code_hash),
+        HandleMsg::WithdrawWrapped { amount, .. } => withdraw_wrapped(deps, env, amount),
+        HandleMsg::SetFactory {
+            code_id, code_hash, ..
+        } => admin_set_factory(deps, env, code_id, code_hash),
+        HandleMsg::SpawnRound { label, init, .. } => admin_spawn_round(deps, env, label, init),
+        HandleMsg::RegisterChild { index, .. } => register_child(deps, env, index),
+        HandleMsg::ExecuteChild { index, msg, .. } => admin_execute_child(deps, env, index, msg),
+        HandleMsg::LotteryV1(msg) => match msg {
+            LotteryHandleV1::Deposit { .. } => deposit(deps, env, None, None),
+            LotteryHandleV1::Claim { pool_id, .. } => claim_prize(deps, env, pool_id, None),
+        },
+    }
+    .and_then(|response| {
+        if moves_balances {
+            check_invariants(&deps.storage)?;
+        }
+        Ok(response)
+    })
+    .map_err(StdError::from);
+    let mut response = if sensitive {
+        obscure_error(deps, response)?
+    } else {
+        response?
+    };
+    // Response data is padded like private query responses.
+    if let Some(data) = response.data.take() {
+        response.data = Some(padded(deps, Ok(data))?);
     }
+    Ok(response)
 }
 
-fn assert_sender_is_admin(sender: CanonicalAddr, owner: CanonicalAddr) -> StdResult<()> {
+fn assert_sender_is_admin(
+    sender: &CanonicalAddr,
+    owner: &CanonicalAddr,
+) -> Result<(), ContractError> {
     if owner != sender {
-        return Err(StdError::unauthorized());
+        return Err(ContractError::Unauthorized);
     }
     Ok(())
 }
 
+// Attributes every handler logs: `action` and `sender`, plus `pool_id` and `status`
+// when a pool is involved and `amount` when funds move.
+fn action_log(
+    action: &str,
+    sender: &HumanAddr,
+    pool: Option<&Pool>,
+    amount: Option<Uint128>,
+) -> Vec<LogAttribute> {
+    let mut attrs = vec![log("action", action), log("sender", sender)];
+    if let Some(pool) = pool {
+        attrs.push(log("pool_id", pool.id));
+        attrs.push(log("status", format!("{:?}", pool.status)));
+    }
+    if let Some(amount) = amount {
+        attrs.push(log("amount", amount));
+    }
+    attrs
+}
+
 // Create a new pool.
 pub fn admin_create_pool<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse> {
+    metadata: PoolMetadata,
+    nft_tickets: bool,
+    ibc_denom: Option<IbcDenom>,
+    extra_denoms: Vec<String>,
+) -> Result<HandleResponse, ContractError> {
     // Ensure that only contract owner can create the pool
-    let state = config_read(&deps.storage).load()?;
+    let mut state = config_read(&deps.storage).load()?;
     let sender_addr = deps.api.canonical_address(&env.message.sender)?;
-    assert_sender_is_admin(sender_addr, state.owner)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
     // Can only create a new pool if:
     // 1. No pool is available
-    // 2. Previous Pool is CLOSED.
-    let res = pool_read(&deps.storage).load();
-    let can_create = res.as_ref().map_or(true, |x| x.is_closed());
-    if !can_create {
-        return Err(StdError::generic_err("Cannot create"));
-    }
-    // Create the pool and persist it.
-    let new_pool = Pool::new(env.block.time);
-    pool_storage(&mut deps.storage).save(&new_pool)?;
-    Ok(HandleResponse::default())
-}
-
-// Lock the pool.
-// TODO:
-// - Send all funds to validator.
-// Edge Case:
-// - What happens if Pool has no delegators?
+    // 2. Previous Pool is CLOSED, done unbonding and settled.
+    // 3. Not too many prizes are left unclaimed.
+    let mut index = pool_index_read(&deps.storage).load()?;
+    if index.current_open.is_some() || index.current_locked.is_some() {
+        return Err(ContractError::CannotCreate);
+    }
+    if let Some(pool_id) = index.pending_settlement {
+        let previous = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+        if previous.unbonds_at.unwrap_or(0) > env.block.time {
+            return Err(ContractError::StillUnbonding);
+        }
+        return Err(ContractError::SettlementPending { pool_id });
+    }
+    if let Some(max) = state.max_unclaimed_prizes {
+        if state.unclaimed_prizes > max {
+            return Err(ContractError::TooManyUnclaimedPrizes { max });
+        }
+    }
+    // Create the pool under the next id and persist it.
+    state.pool_count += 1;
+    state.stats.pools_run += 1;
+    metadata.validate()?;
+    if nft_tickets && state.nft_contract.is_none() {
+        return Err(ContractError::NotSet {
+            what: "NFT contract".to_string(),
+        });
+    }
+    if let Some(ibc) = ibc_denom.as_ref() {
+        ibc.validate()?;
+    }
+    state.fees.apply_pending();
+    let mut new_pool = Pool::new(state.pool_count, &state.timing, &env.block);
+    new_pool.metadata = metadata;
+    new_pool.nft_tickets = nft_tickets;
+    new_pool.ibc_denom = ibc_denom;
+    if !extra_denoms.is_empty() {
+        if state.oracle.is_none() {
+            return Err(ContractError::OracleRequired);
+        }
+        if extra_denoms.len() > MAX_EXTRA_DENOMS {
+            return Err(ContractError::CapExceeded {
+                what: "extra denoms".to_string(),
+                max: MAX_EXTRA_DENOMS as u64,
+            });
+        }
+        for (i, denom) in extra_denoms.iter().enumerate() {
+            if denom.is_empty() || new_pool.accepts(denom) || extra_denoms[..i].contains(denom) {
+                return Err(ContractError::InvalidExtraDenom {
+                    denom: denom.clone(),
+                });
+            }
+        }
+        new_pool.extra_denoms = extra_denoms;
+    }
+    for denom in new_pool
+        .extra_denoms
+        .iter()
+        .chain(new_pool.ibc_denom.iter().map(|i| &i.denom))
+    {
+        if !state.pool_denoms.contains(denom) {
+            state.pool_denoms.push(denom.clone());
+        }
+    }
+    new_pool.fee_rate = state.fees.rate;
+    new_pool.seed_amount = sent_amount(&env, new_pool.denom())?;
+    // A prize that found no winner rolls over into the next pool in the same denom.
+    if new_pool.denom() == DENOM {
+        new_pool.carryover_amount = state.carryover;
+        state.carryover = Uint128::zero();
+    }
+    push_transition(
+        &mut deps.storage,
+        &mut new_pool,
+        None,
+        &sender_addr,
+        env.block.time,
+    )?;
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::PoolCreated,
+        new_pool.id,
+        env.block.time,
+    )?;
+    index.opened(new_pool.id);
+    pool_storage(&mut deps.storage).save(&pool_key(new_pool.id), &new_pool)?;
+    pool_index(&mut deps.storage).save(&index)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: hook_messages(&state.hooks, HookEvent::PoolOpened, new_pool.id)?,
+        log: [
+            action_log(
+                "create_pool",
+                &env.message.sender,
+                Some(&new_pool),
+                Some(new_pool.seed_amount),
+            ),
+            events::pool_created(&new_pool),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::CreatePool {
+            pool_id: new_pool.id,
+        })?),
+    })
+}
+
+// Lock the OPEN pool and stake its deposits across the validators. A pool with no
+// depositors locks with nothing delegated.
 pub fn admin_lock_pool<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse> {
-    // Ensure that only contract owner can create the pool
-    let state = config_read(&deps.storage).load()?;
+) -> Result<HandleResponse, ContractError> {
+    // Only the contract owner can lock the pool.
+    let mut state = config_read(&deps.storage).load()?;
     let sender_addr = deps.api.canonical_address(&env.message.sender)?;
-    assert_sender_is_admin(sender_addr, state.owner)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
     // Only OPEN pool can be locked.
-    let mut pool = pool_storage(&mut deps.storage).load()?;
-    if !pool.is_open() {
-        return Err(StdError::generic_err(
-            "Pool must be in OPEN status to be locked.",
-        ));
+    let mut index = pool_index_read(&deps.storage).load()?;
+    let pool_id = index.current_open.ok_or(ContractError::CannotLock)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    // The pool stays open for the configured duration before it can lock.
+    let (timing, owner) = (&state.timing, &state.owner);
+    transitions::assert_allowed(Lock, &pool, &sender_addr, owner, timing, &env.block)?;
+    pool.delegated_amt = pool.total_deposits;
+    pool.lock(&state.timing, &env.block);
+    seal_draw_seed(&mut deps.storage, pool.id);
+    push_transition(
+        &mut deps.storage,
+        &mut pool,
+        Some(PoolStatus::OPEN),
+        &sender_addr,
+        env.block.time,
+    )?;
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::PoolLocked,
+        pool.id,
+        env.block.time,
+    )?;
+    index.locked(pool.id);
+    if pool.ibc_denom.is_none() {
+        pool.delegations = split_by_weight(pool.delegated_amt, &state.validators)?;
     }
-    // Ensure that pool is open for 1 day before locking.
-    pool.assert_status_has_expired(env.block.time)?;
-    pool.lock(env.block.time);
-    pool_storage(&mut deps.storage).save(&pool)?;
-    // TODO: Send all funds to validator node.
-    Ok(HandleResponse::default())
+    let mut messages: Vec<CosmosMsg> = pool
+        .delegations
+        .iter()
+        .map(|d| {
+            CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: d.validator.clone(),
+                amount: coin(d.amount.u128(), DENOM),
+            })
+        })
+        .collect();
+    messages.extend(hook_messages(&state.hooks, HookEvent::PoolLocked, pool.id)?);
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    pool_index(&mut deps.storage).save(&index)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages,
+        log: [
+            action_log(
+                "lock_pool",
+                &env.message.sender,
+                Some(&pool),
+                Some(pool.delegated_amt),
+            ),
+            events::pool_locked(&pool),
+        ]
+        .concat(),
+        data: None,
+    })
 }
 
 pub fn admin_close_pool<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse> {
-    // Ensure that only contract owner can create the pool
-    let state = config_read(&deps.storage).load()?;
+) -> Result<HandleResponse, ContractError> {
+    // Only the contract owner can close the pool.
+    let mut state = config_read(&deps.storage).load()?;
     let sender_addr = deps.api.canonical_address(&env.message.sender)?;
-    assert_sender_is_admin(sender_addr, state.owner)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
     // Only LOCKED pool can be closed.
-    let mut pool = pool_storage(&mut deps.storage).load()?;
-    if !pool.is_locked() {
-        return Err(StdError::generic_err("Pool is not LOCKED."));
+    let mut index = pool_index_read(&deps.storage).load()?;
+    let pool_id = index.current_locked.ok_or(ContractError::PoolNotLocked)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    // The pool stays locked for the configured duration before it can close.
+    let (timing, owner) = (&state.timing, &state.owner);
+    transitions::assert_allowed(Close, &pool, &sender_addr, owner, timing, &env.block)?;
+    index.closed(pool.id)?;
+    pool.close(&state.timing, &env.block);
+    push_transition(
+        &mut deps.storage,
+        &mut pool,
+        Some(PoolStatus::LOCKED),
+        &sender_addr,
+        env.block.time,
+    )?;
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::PoolClosed,
+        pool.id,
+        env.block.time,
+    )?;
+    // Undelegating also pays the accrued rewards out to the contract.
+    for d in pool.delegations.iter() {
+        let delegation = deps
+            .querier
+            .query_delegation(env.contract.address.clone(), d.validator.clone())?;
+        if let Some(delegation) = delegation {
+            for reward in delegation.accumulated_rewards.iter() {
+                if reward.denom == DENOM {
+                    pool.harvested_rewards = math::add(pool.harvested_rewards, reward.amount)?;
+                    continue;
+                }
+                match pool
+                    .unconverted_rewards
+                    .iter_mut()
+                    .find(|c| c.denom == reward.denom)
+                {
+                    Some(coin) => coin.amount = math::add(coin.amount, reward.amount)?,
+                    None => pool.unconverted_rewards.push(reward.clone()),
+                }
+            }
+        }
     }
-    // Pool must remain locked for 2 days before closing.
-    pool.assert_status_has_expired(env.block.time)?;
-    pool.close(env.block.time);
-    pool_storage(&mut deps.storage).save(&pool)?;
-    Ok(HandleResponse::default())
+    let messages: Vec<CosmosMsg> = pool
+        .delegations
+        .iter()
+        .map(|d| {
+            CosmosMsg::Staking(StakingMsg::Undelegate {
+                validator: d.validator.clone(),
+                amount: coin(d.amount.u128(), DENOM),
+            })
+        })
+        .collect();
+    if !messages.is_empty() {
+        pool.unbonding_amount = pool.delegated_amt;
+        pool.unbonds_at = Some(env.block.time.saturating_add(UNBONDING_PERIOD));
+    }
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    pool_index(&mut deps.storage).save(&index)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages,
+        log: [
+            action_log(
+                "close_pool",
+                &env.message.sender,
+                Some(&pool),
+                Some(pool.unbonding_amount),
+            ),
+            events::rewards_harvested(&pool),
+        ]
+        .concat(),
+        data: None,
+    })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetOwner {} => to_binary(&query_owner(deps)?),
-        QueryMsg::GetCurrentPool {} => to_binary(&query_pool(deps)?),
+// Sum of the attached coins in the pool denom. Any other denom is rejected.
+fn sent_amount(env: &Env, denom: &str) -> StdResult<Uint128> {
+    let mut amount = Uint128::zero();
+    for coin in env.message.sent_funds.iter() {
+        if coin.denom != denom {
+            return Err(ContractError::WrongDenom {
+                expected: denom.to_string(),
+            }
+            .into());
+        }
+        amount = math::add(amount, coin.amount)?;
     }
+    Ok(amount)
 }
 
-// Get owner info
-fn query_owner<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<OwnerResponse> {
+fn open_pool<S: Storage>(storage: &S) -> StdResult<Pool> {
+    let pool_id = pool_index_read(storage)
+        .load()?
+        .current_open
+        .ok_or(ContractError::PoolNotOpen)?;
+    pool_read(storage).load(&pool_key(pool_id))
+}
+
+// Add the attached funds to the prize of the live pool. Sponsors get no entry.
+pub fn sponsor<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let index = pool_index_read(&deps.storage).load()?;
+    let pool_id = index
+        .current_open
+        .or(index.current_locked)
+        .ok_or(ContractError::NoPoolToSponsor)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    let amount = sent_amount(&env, pool.denom())?;
+    if amount.is_zero() {
+        return Err(ContractError::NoFunds);
+    }
+    pool.sponsor_amount = math::add(pool.sponsor_amount, amount)?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("sponsor", &env.message.sender, Some(&pool), Some(amount)),
+        data: None,
+    })
+}
+
+// Deposit the attached funds into the OPEN pool.
+pub fn deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    memo: Option<String>,
+    referral: Option<String>,
+) -> Result<HandleResponse, ContractError> {
+    // Multi-denom pools take any one of their denoms per deposit.
+    let pool = open_pool(&deps.storage)?;
+    let denom = match env.message.sent_funds.first() {
+        Some(coin) if pool.accepts(&coin.denom) => coin.denom.clone(),
+        _ => pool.denom().to_string(),
+    };
+    let amount = sent_amount(&env, &denom)?;
+    let depositor = env.message.sender.clone();
+    add_deposit(deps, env, depositor, &denom, amount, memo, referral)
+}
+
+// A registered token reports that `from` sent it `amount` of it.
+pub fn receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> Result<HandleResponse, ContractError> {
     let state = config_read(&deps.storage).load()?;
-    Ok(OwnerResponse {
-        owner: deps.api.human_address(&state.owner)?,
+    let is_ticket = state
+        .ticket
+        .as_ref()
+        .map_or(false, |t| t.address == env.message.sender);
+    let token = state
+        .tokens
+        .iter()
+        .chain(state.wrapped_native.iter())
+        .find(|t| t.address == env.message.sender)
+        .cloned();
+    if !is_ticket && token.is_none() {
+        return Err(ContractError::Unauthorized);
+    }
+    let msg = match msg {
+        Some(msg) => from_binary(&msg)?,
+        None => ReceiveMsg::Deposit {
+            memo: None,
+            referral: None,
+        },
+    };
+    match (msg, state.ticket, token) {
+        // `from` owned the tickets sent, so it is their holder now, whoever deposited.
+        (ReceiveMsg::Withdraw { pool_id }, Some(ticket), _) if is_ticket => {
+            let mut response = match pool_id {
+                Some(pool_id) => redeem_tickets(deps, env, from, pool_id, amount)?,
+                None => {
+                    // The OPEN pool can only give back the holder's own deposit, which
+                    // it takes odds away from. Transferred tickets wait for settlement.
+                    let pool = open_pool(&deps.storage)?;
+                    let holder = deps.api.canonical_address(&from)?;
+                    let deposited = may_load_entry(&deps.storage, pool.id, &holder)?
+                        .map_or(Uint128::zero(), |e| e.amount);
+                    if deposited < amount {
+                        return Err(ContractError::TicketsNotDeposited);
+                    }
+                    remove_deposit(deps, env, from, amount)?
+                }
+            };
+            let burn = Snip20HandleMsg::Burn {
+                amount,
+                padding: None,
+            };
+            response
+                .messages
+                .push(burn.to_cosmos_msg(ticket.address, ticket.code_hash)?);
+            Ok(response)
+        }
+        (ReceiveMsg::Deposit { memo, referral }, _, Some(token)) if !is_ticket => {
+            // Tokens count 1:1 with the native denom only.
+            if open_pool(&deps.storage)?.ibc_denom.is_some() {
+                return Err(ContractError::IbcDenomOnly);
+            }
+            let mut response = add_deposit(deps, env, from, DENOM, amount, memo, referral)?;
+            // Unwrap so the deposit is held and staked like a native one. A token that
+            // doesn't wrap the native denom fails here, and the deposit with it.
+            let redeem = Snip20HandleMsg::Redeem {
+                amount,
+                denom: Some(DENOM.to_string()),
+                padding: None,
+            };
+            response
+                .messages
+                .insert(0, redeem.to_cosmos_msg(token.address, token.code_hash)?);
+            Ok(response)
+        }
+        _ => Err(ContractError::TokenNotUsable),
+    }
+}
+
+pub fn admin_set_ticket_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    // Deposits made before would have no tickets to send back, so this is only
+    // allowed before the first pool.
+    if state.ticket.is_some() || state.pool_count > 0 {
+        return Err(ContractError::TicketAlreadySet);
+    }
+    if state.tokens.iter().any(|t| t.address == address) {
+        return Err(ContractError::DepositTokenAsTicket);
+    }
+    state.ticket = Some(Token {
+        address: address.clone(),
+        code_hash: code_hash.clone(),
+    });
+    config(&mut deps.storage).save(&state)?;
+    let register = Snip20HandleMsg::RegisterReceive {
+        code_hash: env.contract_code_hash.clone(),
+        padding: None,
+    };
+    let mut attrs = action_log("set_ticket_token", &env.message.sender, None, None);
+    attrs.push(log("token", &address));
+    Ok(HandleResponse {
+        messages: vec![register.to_cosmos_msg(address, code_hash)?],
+        log: attrs,
+        data: None,
     })
 }
 
-// Get Pool Info
-fn query_pool<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<PoolResponse> {
-    let pool = pool_read(&deps.storage).load().ok();
-    Ok(PoolResponse { pool })
+pub fn admin_set_nft_contract<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    // Tickets of an undrawn round must stay redeemable.
+    let index = pool_index_read(&deps.storage).load()?;
+    if index.current_open.is_some()
+        || index.current_locked.is_some()
+        || index.pending_settlement.is_some()
+    {
+        return Err(ContractError::NftContractLocked);
+    }
+    state.nft_contract = Some(Token {
+        address: address.clone(),
+        code_hash: code_hash.clone(),
+    });
+    config(&mut deps.storage).save(&state)?;
+    let register = Snip721HandleMsg::RegisterReceiveNft {
+        code_hash: env.contract_code_hash.clone(),
+        also_implements_batch_receive_nft: Some(false),
+        padding: None,
+    };
+    let mut attrs = action_log("set_nft_contract", &env.message.sender, None, None);
+    attrs.push(log("nft_contract", &address));
+    Ok(HandleResponse {
+        messages: vec![register.to_cosmos_msg(address, code_hash)?],
+        log: attrs,
+        data: None,
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::HumanAddr;
-    use cosmwasm_std::{coins, from_binary};
+// NFT tickets are named after the pool and the holder's delegator index.
+fn nft_token_id(pool_id: u64, index: u64) -> String {
+    format!("{}:{}", pool_id, index)
+}
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(20, &[]);
+fn parse_nft_token_id(token_id: &str) -> Option<(u64, u64)> {
+    let mut parts = token_id.splitn(2, ':');
+    let pool_id = parts.next()?.parse().ok()?;
+    let index = parts.next()?.parse().ok()?;
+    Some((pool_id, index))
+}
 
-        let msg = InitMsg {};
-        let env = mock_env("creator", &coins(1000, "earth"));
+// The NFT contract reports that `from` sent us `token_id`. Only the winning ticket
+// of a settled round is accepted: its prize goes to `from` and the ticket is burnt.
+pub fn receive_nft<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    token_id: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let nft = match state.nft_contract.clone() {
+        Some(nft) if nft.address == env.message.sender => nft,
+        _ => return Err(ContractError::Unauthorized),
+    };
+    let (pool_id, index) = parse_nft_token_id(&token_id).ok_or(ContractError::NotATicket)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.nft_tickets || !pool.is_settled() {
+        return Err(ContractError::NotDrawn);
+    }
+    let winner = pool.winner.clone().ok_or(ContractError::NoWinner)?;
+    if load_entry(&deps.storage, pool.id, &winner)?.index != index {
+        return Err(ContractError::TicketDidNotWin);
+    }
+    if pool.prize_claimed {
+        return Err(ContractError::AlreadyClaimed);
+    }
+    pool.prize_claimed = true;
+    state.unclaimed_prizes = state.unclaimed_prizes.saturating_sub(1);
+    release_settled_prize(&mut state, &pool);
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::PrizeClaimed,
+        pool.id,
+        env.block.time,
+    )?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    let burn = Snip721HandleMsg::BurnNft {
+        token_id,
+        memo: None,
+        padding: None,
+    };
+    Ok(HandleResponse {
+        messages: vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: from.clone(),
+                amount: coins(pool.prize_amount.u128(), pool.denom()),
+            }),
+            burn.to_cosmos_msg(nft.address, nft.code_hash)?,
+        ],
+        log: [
+            action_log("claim_prize", &from, Some(&pool), Some(pool.prize_amount)),
+            events::prize_claimed(pool.prize_amount),
+        ]
+        .concat(),
+        data: None,
+    })
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+pub fn admin_set_emission<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+    per_round: Uint128,
+    halving_interval: u64,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    // Unclaimed shares of past rounds are minted from whatever token is set.
+    if let Some(emission) = state.emission.as_ref() {
+        if emission.token.address != address {
+            return Err(ContractError::EmissionTokenFixed);
+        }
+    }
+    // The schedule restarts with the next pool.
+    state.emission = Some(Emission {
+        token: Token {
+            address: address.clone(),
+            code_hash,
+        },
+        per_round,
+        start_pool: state.pool_count + 1,
+        halving_interval,
+    });
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_emission", &env.message.sender, None, Some(per_round));
+    attrs.push(log("token", &address));
+    attrs.push(log("halving_interval", halving_interval));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        // it worked, let's query the state
-        let res = query(&deps, QueryMsg::GetOwner {}).unwrap();
-        let value: OwnerResponse = from_binary(&res).unwrap();
-        assert_eq!(HumanAddr::from("creator"), value.owner);
+// Mint the sender's share of each listed pool's emission in one go.
+pub fn claim_emissions<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_ids: Vec<u64>,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let emission = state.emission.ok_or(ContractError::NoEmission)?;
+    if pool_ids.len() > MAX_LIMIT as usize {
+        return Err(ContractError::CapExceeded {
+            what: "pools per claim".to_string(),
+            max: MAX_LIMIT.into(),
+        });
+    }
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let mut claimed = vec![];
+    let mut total = Uint128::zero();
+    for pool_id in pool_ids {
+        if claimed.contains(&pool_id) {
+            continue;
+        }
+        let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+        if !pool.is_settled() || has_claimed_emission(&deps.storage, pool.id, &sender_addr) {
+            continue;
+        }
+        let share = match may_load_entry(&deps.storage, pool.id, &sender_addr)? {
+            Some(entry) => pool.emission_share(&entry)?,
+            None => continue,
+        };
+        if share.is_zero() {
+            continue;
+        }
+        total = math::add(total, share)?;
+        claimed.push(pool_id);
+    }
+    if total.is_zero() {
+        return Err(ContractError::NothingToClaim);
     }
+    for pool_id in claimed.iter() {
+        mark_emission_claimed(&mut deps.storage, *pool_id, &sender_addr);
+    }
+    let mint = Snip20HandleMsg::Mint {
+        recipient: env.message.sender.clone(),
+        amount: total,
+        padding: None,
+    };
+    let mut attrs = action_log("claim_emissions", &env.message.sender, None, Some(total));
+    attrs.push(log("pools", claimed.len()));
+    Ok(HandleResponse {
+        messages: vec![mint.to_cosmos_msg(emission.token.address, emission.token.code_hash)?],
+        log: attrs,
+        data: None,
+    })
+}
 
-    #[test]
-    fn test_create_pool_admin() {
-        let mut deps = mock_dependencies(20, &coins(2, "earth"));
+pub fn register_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let address = env.message.sender.clone();
+    let sender_addr = deps.api.canonical_address(&address)?;
+    if state.hooks.iter().any(|h| h.contract.address == address)
+        || pending_hooks_read(&deps.storage)
+            .may_load(sender_addr.as_slice())?
+            .is_some()
+    {
+        return Err(ContractError::HookAlreadyRegistered);
+    }
+    // Anyone can register, so registrations wait apart from the capped list of hooks.
+    pending_hooks(&mut deps.storage).save(sender_addr.as_slice(), &Token { address, code_hash })?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("register_hook", &env.message.sender, None, None),
+        data: None,
+    })
+}
 
-        let msg = InitMsg {};
-        let env = mock_env("creator", &coins(2, "earth"));
-        init(&mut deps, env, msg).unwrap();
+pub fn admin_approve_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let hook_addr = deps.api.canonical_address(&address)?;
+    if let Some(hook) = state
+        .hooks
+        .iter_mut()
+        .find(|h| h.contract.address == address)
+    {
+        // Registered before registrations were kept apart.
+        hook.approved = true;
+    } else {
+        let contract = pending_hooks_read(&deps.storage)
+            .may_load(hook_addr.as_slice())?
+            .ok_or_else(|| StdError::not_found("Hook"))?;
+        if state.hooks.iter().filter(|h| h.approved).count() >= MAX_HOOKS {
+            return Err(ContractError::CapExceeded {
+                what: "hooks".to_string(),
+                max: MAX_HOOKS as u64,
+            });
+        }
+        state.hooks.push(Hook {
+            contract,
+            approved: true,
+        });
+        pending_hooks(&mut deps.storage).remove(hook_addr.as_slice());
+    }
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("approve_hook", &env.message.sender, None, None);
+    attrs.push(log("hook", &address));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        let mut env = mock_env("creator", &coins(2, "earth"));
-        env.block.time = 1000;
-        handle(&mut deps, env, HandleMsg::CrtePool {}).unwrap();
+pub fn remove_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    if env.message.sender != address {
+        assert_sender_is_admin(&sender_addr, &state.owner)?;
+    }
+    let hook_addr = deps.api.canonical_address(&address)?;
+    let pending = pending_hooks_read(&deps.storage).may_load(hook_addr.as_slice())?;
+    let count = state.hooks.len();
+    state.hooks.retain(|h| h.contract.address != address);
+    if state.hooks.len() == count && pending.is_none() {
+        return Err(StdError::not_found("Hook").into());
+    }
+    pending_hooks(&mut deps.storage).remove(hook_addr.as_slice());
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("remove_hook", &env.message.sender, None, None);
+    attrs.push(log("hook", &address));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        // Get the pool result
-        let res = query(&deps, QueryMsg::GetCurrentPool {}).unwrap();
-        let value: PoolResponse = from_binary(&res).unwrap();
-        assert_eq!(value.pool, Some(Pool::new(1000)));
+pub fn set_winner_callback<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    callback: Option<WinnerCallback>,
+) -> Result<HandleResponse, ContractError> {
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    match callback {
+        Some(callback) => {
+            winner_callbacks(&mut deps.storage).save(sender_addr.as_slice(), &callback)?
+        }
+        None => winner_callbacks(&mut deps.storage).remove(sender_addr.as_slice()),
     }
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_winner_callback", &env.message.sender, None, None),
+        data: None,
+    })
+}
 
-    #[test]
-    fn test_create_pool_errors() {
-        let mut deps = mock_dependencies(20, &coins(2, "earth"));
+// The `WinnerNotification` sent to a winner that registered a callback when it
+// claims, with the prize if `attach`.
+fn notify_winner(
+    pool: &Pool,
+    winner: HumanAddr,
+    callback: WinnerCallback,
+    attach: bool,
+) -> StdResult<CosmosMsg> {
+    let msg = WinnerMsg::WinnerNotification {
+        pool_id: pool.id,
+        prize_amount: pool.prize_amount,
+        denom: pool.denom().to_string(),
+        prize_attached: attach,
+    };
+    let send = if attach {
+        coins(pool.prize_amount.u128(), pool.denom())
+    } else {
+        vec![]
+    };
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: winner,
+        callback_code_hash: callback.code_hash,
+        msg: to_binary(&msg)?,
+        send,
+    }))
+}
 
-        let msg = InitMsg {};
-        let env = mock_env("creator", &coins(2, "earth"));
-        init(&mut deps, env, msg).unwrap();
+pub fn admin_set_swap_router<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.swap_router = Some(Token {
+        address: address.clone(),
+        code_hash,
+    });
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_swap_router", &env.message.sender, None, None);
+    attrs.push(log("swap_router", &address));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        // Only admin can create pool
-        let env = mock_env("voter", &coins(2, "earth"));
-        let res = handle(&mut deps, env, HandleMsg::CrtePool {});
+// Swap the pending pool's rewards in other denoms into its prize denom.
+pub fn admin_convert_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    min_return: Uint128,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let router = state.swap_router.ok_or_else(|| ContractError::NotSet {
+        what: "swap router".to_string(),
+    })?;
+    let pool_id = pool_index_read(&deps.storage)
+        .load()?
+        .pending_settlement
+        .ok_or(ContractError::NoPendingSettlement)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if pool.unbonds_at.unwrap_or(0) > env.block.time {
+        return Err(ContractError::StillUnbonding);
+    }
+    if pool.converting || pool.unconverted_rewards.is_empty() {
+        return Err(ContractError::NothingToConvert);
+    }
+    pool.converting = true;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    let swap = RouterHandleMsg::Swap {
+        ask_denom: pool.denom().to_string(),
+        min_return,
+        callback: Callback {
+            contract: env.contract.address.clone(),
+            code_hash: env.contract_code_hash.clone(),
+            msg: to_binary(&HandleMsg::SwapProceeds {
+                pool_id: pool.id,
+                padding: None,
+            })?,
+        },
+    };
+    Ok(HandleResponse {
+        messages: vec![swap.to_cosmos_msg(
+            router.address,
+            router.code_hash,
+            pool.unconverted_rewards.clone(),
+        )?],
+        log: action_log("convert_rewards", &env.message.sender, Some(&pool), None),
+        data: None,
+    })
+}
 
-        assert_eq!(res.is_err(), true);
-        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+// The swap router returns converted rewards, which join the pool's harvested rewards.
+pub fn swap_proceeds<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    if state.swap_router.map(|r| r.address) != Some(env.message.sender.clone()) {
+        return Err(ContractError::Unauthorized);
     }
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.converting {
+        return Err(ContractError::NoConversion);
+    }
+    let amount = sent_amount(&env, pool.denom())?;
+    pool.harvested_rewards = math::add(pool.harvested_rewards, amount)?;
+    pool.unconverted_rewards = vec![];
+    pool.converting = false;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log(
+            "swap_proceeds",
+            &env.message.sender,
+            Some(&pool),
+            Some(amount),
+        ),
+        data: None,
+    })
+}
 
-    #[test]
-    fn test_lock_pool() {
-        let mut deps = mock_dependencies(20, &coins(2, "scrt"));
+pub fn admin_reset_conversion<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let pool_id = pool_index_read(&deps.storage)
+        .load()?
+        .pending_settlement
+        .ok_or(ContractError::NoPendingSettlement)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.converting {
+        return Err(ContractError::NoConversion);
+    }
+    // The router has them, so they can't be sent again.
+    pool.unconverted_rewards = vec![];
+    pool.converting = false;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("reset_conversion", &env.message.sender, Some(&pool), None),
+        data: None,
+    })
+}
 
-        // Initialize the contract
-        let msg = InitMsg {};
-        let env = mock_env("creator", &coins(2, "scrt"));
-        init(&mut deps, env, msg).unwrap();
+pub fn admin_set_oracle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    oracle: Option<Oracle>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let mut attrs = action_log("set_oracle", &env.message.sender, None, None);
+    if let Some(oracle) = oracle.as_ref() {
+        attrs.push(log("oracle", &oracle.contract.address));
+    }
+    state.oracle = oracle;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        // Create the pool
-        let mut env = mock_env("creator", &coins(2, "scrt"));
-        env.block.time = 1000;
-        env.block.height = 1000;
-        handle(&mut deps, env, HandleMsg::CrtePool {}).unwrap();
+pub fn admin_set_wrapped_native<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    if state
+        .ticket
+        .as_ref()
+        .map_or(false, |t| t.address == address)
+    {
+        return Err(ContractError::WrappedNativeAsTicket);
+    }
+    state.wrapped_native = Some(Token {
+        address: address.clone(),
+        code_hash: code_hash.clone(),
+    });
+    config(&mut deps.storage).save(&state)?;
+    let register = Snip20HandleMsg::RegisterReceive {
+        code_hash: env.contract_code_hash.clone(),
+        padding: None,
+    };
+    let mut attrs = action_log("set_wrapped_native", &env.message.sender, None, None);
+    attrs.push(log("token", &address));
+    Ok(HandleResponse {
+        messages: vec![register.to_cosmos_msg(address, code_hash)?],
+        log: attrs,
+        data: None,
+    })
+}
 
-        // Lock the pool.
-        let mut env = mock_env("creator", &coins(2, "scrt"));
-        env.block.time = DAYS * 21 + 1001;
-        env.block.height = DAYS * 21 + 1001;
-        handle(&mut deps, env, HandleMsg::LockPool {}).unwrap();
+pub fn admin_set_factory<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_id: u64,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.factory = Some(Factory { code_id, code_hash });
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_factory", &env.message.sender, None, None);
+    attrs.push(log("code_id", code_id));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
 
-        let res = query(&deps, QueryMsg::GetCurrentPool {}).unwrap();
-        let value: PoolResponse = from_binary(&res).unwrap();
-        assert_eq!(value.pool.unwrap().is_locked(), true);
+// Each child is a full lottery of its own, so a failing round can't touch the funds
+// of another.
+pub fn admin_spawn_round<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    label: String,
+    mut init: InitMsg,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let factory = state.factory.clone().ok_or(ContractError::FactoryOff)?;
+    let index = state.child_count;
+    init.parent = Some(ParentInfo {
+        address: env.contract.address.clone(),
+        code_hash: env.contract_code_hash.clone(),
+        index,
+    });
+    let child = Child {
+        label: label.clone(),
+        address: None,
+        created_at: env.block.time,
+        code_hash: factory.code_hash.clone(),
+    };
+    children(&mut deps.storage).save(&index.to_be_bytes(), &child)?;
+    state.child_count += 1;
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("spawn_round", &env.message.sender, None, None);
+    attrs.push(log("child", index));
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+            code_id: factory.code_id,
+            callback_code_hash: factory.code_hash,
+            msg: to_binary(&init)?,
+            send: vec![],
+            label,
+        })],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn register_child<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    index: u64,
+) -> Result<HandleResponse, ContractError> {
+    let mut child = children_read(&deps.storage)
+        .may_load(&index.to_be_bytes())?
+        .ok_or(ContractError::Unauthorized)?;
+    if child.address.is_some() {
+        return Err(ContractError::Unauthorized);
+    }
+    child.address = Some(env.message.sender.clone());
+    children(&mut deps.storage).save(&index.to_be_bytes(), &child)?;
+    let mut attrs = action_log("register_child", &env.message.sender, None, None);
+    attrs.push(log("child", index));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_execute_child<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    index: u64,
+    msg: Binary,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let factory = state.factory.ok_or(ContractError::FactoryOff)?;
+    let child = children_read(&deps.storage)
+        .may_load(&index.to_be_bytes())?
+        .ok_or_else(|| StdError::not_found("Child"))?;
+    let address = child
+        .address
+        .clone()
+        .ok_or_else(|| StdError::not_found("Child"))?;
+    let mut attrs = action_log("execute_child", &env.message.sender, None, None);
+    attrs.push(log("child", index));
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address,
+            callback_code_hash: child.callback_code_hash(&factory),
+            msg,
+            send: env.message.sent_funds,
+        })],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    if state.tokens.iter().any(|t| t.address == address) {
+        return Err(ContractError::TokenAlreadyRegistered);
+    }
+    state.tokens.push(Token {
+        address: address.clone(),
+        code_hash: code_hash.clone(),
+    });
+    config(&mut deps.storage).save(&state)?;
+    let register = Snip20HandleMsg::RegisterReceive {
+        code_hash: env.contract_code_hash.clone(),
+        padding: None,
+    };
+    let mut attrs = action_log("register_token", &env.message.sender, None, None);
+    attrs.push(log("token", &address));
+    Ok(HandleResponse {
+        messages: vec![register.to_cosmos_msg(address, code_hash)?],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_deregister_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let count = state.tokens.len();
+    state.tokens.retain(|t| t.address != address);
+    if state.tokens.len() == count {
+        return Err(ContractError::TokenNotRegistered);
+    }
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("deregister_token", &env.message.sender, None, None);
+    attrs.push(log("token", &address));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+// Credit `amount` to `depositor` in the OPEN pool. Native deposits and SNIP-20
+// sends both end up here.
+fn add_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    depositor: HumanAddr,
+    denom: &str,
+    amount: Uint128,
+    memo: Option<String>,
+    referral: Option<String>,
+) -> Result<HandleResponse, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::NoFunds);
+    }
+    if let Some(memo) = memo.as_ref() {
+        validate_memo(memo)?;
+    }
+    let mut pool = open_pool(&deps.storage)?;
+    let sender_addr = deps.api.canonical_address(&depositor)?;
+    let referral = match referral {
+        Some(code) => {
+            let code_hash = referral_code_hash(&code);
+            let referral = may_load_referral(&deps.storage, &code_hash)?
+                .ok_or(ContractError::UnknownReferralCode)?;
+            if referral.referrer == sender_addr {
+                return Err(ContractError::OwnReferralCode);
+            }
+            Some((code_hash, referral))
+        }
+        None => None,
+    };
+    let mut state = config_read(&deps.storage).load()?;
+    if let Some(limit) = state.deposit_limit.as_ref() {
+        count_deposit(&mut deps.storage, limit, &sender_addr, env.block.height)?;
+    }
+    let weight = deposit_weight(deps, &env, &state, &pool, denom, amount)?;
+    let player = may_load_player(&deps.storage, &sender_addr)?;
+    if player.is_none() {
+        state.stats.unique_players += 1;
+    }
+    let mut player = player.unwrap_or_default();
+    let entry = may_load_entry(&deps.storage, pool.id, &sender_addr)?;
+    if pool.nft_tickets && entry.is_some() {
+        return Err(ContractError::OneNftDeposit);
+    }
+    let mut entry = match entry {
+        Some(entry) => entry,
+        None => {
+            if pool.delegator_count >= u64::from(state.max_depositors) {
+                return Err(ContractError::PoolFull {
+                    max: state.max_depositors,
+                });
+            }
+            player.rounds_played += 1;
+            Entry {
+                index: push_delegator(&mut deps.storage, &mut pool, &sender_addr)?,
+                amount: Uint128::zero(),
+                weight: Uint128::zero(),
+            }
+        }
+    };
+    if entry.weight.is_zero() {
+        pool.unique_depositors += 1;
+    }
+    let extra = denom != pool.denom();
+    if extra {
+        let mut balances = load_entry_balances(&deps.storage, pool.id, &sender_addr)?;
+        add_coin(&mut balances, denom, amount)?;
+        add_coin(&mut pool.denom_balances, denom, amount)?;
+        save_entry_balances(&mut deps.storage, pool.id, &sender_addr, &balances)?;
+    } else {
+        entry.amount = math::add(entry.amount, amount)?;
+        pool.total_deposits = math::add(pool.total_deposits, amount)?;
+        player.total_deposited = math::add(player.total_deposited, amount)?;
+        state.stats.total_deposited = math::add(state.stats.total_deposited, amount)?;
+    }
+    entry.weight = math::add(entry.weight, weight)?;
+    pool.total_weight = math::add(pool.total_weight, weight)?;
+    let history = HistoryEntry {
+        action: Action::Deposit,
+        pool_id: pool.id,
+        amount,
+        time: env.block.time,
+    };
+    push_history(&mut deps.storage, &sender_addr, &mut player, &history)?;
+    save_entry(&mut deps.storage, pool.id, &sender_addr, &entry)?;
+    if let Some(memo) = memo {
+        save_memo(&mut deps.storage, pool.id, &sender_addr, &memo)?;
+    }
+    save_player(&mut deps.storage, &sender_addr, &player)?;
+    if let Some((code_hash, mut referral)) = referral {
+        referral.deposits += 1;
+        referral.referred_amount = math::add(referral.referred_amount, amount)?;
+        save_referral(&mut deps.storage, &code_hash, &referral)?;
+        if !extra {
+            add_referred(&mut deps.storage, pool.id, &code_hash, amount)?;
+            pool.referred_deposits = math::add(pool.referred_deposits, amount)?;
+        }
+    }
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    add_entropy(
+        &mut deps.storage,
+        &[
+            sender_addr.as_slice(),
+            &amount.u128().to_be_bytes(),
+            &env.block.height.to_be_bytes(),
+            &env.block.time.to_be_bytes(),
+        ]
+        .concat(),
+    );
+    let mut messages = vec![];
+    let mut ticket_id = None;
+    if let (true, Some(nft)) = (pool.nft_tickets, state.nft_contract) {
+        ticket_id = Some(nft_token_id(pool.id, entry.index));
+        let mint = Snip721HandleMsg::MintNft {
+            token_id: ticket_id.clone(),
+            owner: Some(depositor.clone()),
+            public_metadata: Some(Metadata {
+                name: Some(format!("Pool {} ticket #{}", pool.id, entry.index)),
+                description: None,
+                image: None,
+            }),
+            private_metadata: Some(Metadata {
+                name: None,
+                description: Some(format!("weight {}", entry.weight)),
+                image: None,
+            }),
+            memo: None,
+            padding: None,
+        };
+        messages.push(mint.to_cosmos_msg(nft.address, nft.code_hash)?);
+    } else if let (false, Some(ticket)) = (extra, state.ticket) {
+        let mint = Snip20HandleMsg::Mint {
+            recipient: depositor.clone(),
+            amount,
+            padding: None,
+        };
+        messages.push(mint.to_cosmos_msg(ticket.address, ticket.code_hash)?);
+    }
+    Ok(HandleResponse {
+        messages,
+        log: action_log("deposit", &depositor, Some(&pool), Some(amount)),
+        data: Some(to_binary(&HandleAnswer::Deposit {
+            pool_id: pool.id,
+            amount,
+            ticket_id,
+        })?),
+    })
+}
+
+// Entries weigh one unit per deposited unit, or the deposit's value in the oracle's
+// quote unit in multi-denom pools.
+fn deposit_weight<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    state: &State,
+    pool: &Pool,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    if !pool.accepts(denom) {
+        return Err(ContractError::DenomNotAccepted {
+            denom: denom.to_string(),
+        }
+        .into());
+    }
+    if !pool.is_multi_denom() {
+        return Ok(amount);
+    }
+    let oracle = state.oracle.as_ref().ok_or_else(|| ContractError::NotSet {
+        what: "oracle".to_string(),
+    })?;
+    let weight = oracle.value(&deps.querier, amount, denom, env.block.time)?;
+    if weight.is_zero() {
+        return Err(ContractError::WorthNothing.into());
+    }
+    Ok(weight)
+}
+
+// Withdraw part or all of a deposit while the pool is still OPEN.
+pub fn withdraw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> Result<HandleResponse, ContractError> {
+    if config_read(&deps.storage).load()?.ticket.is_some() {
+        return Err(ContractError::WithdrawWithTickets);
+    }
+    let withdrawer = env.message.sender.clone();
+    remove_deposit(deps, env, withdrawer, amount)
+}
+
+// Withdraw, then wrap the coins and transfer the tokens instead of sending the coins.
+pub fn withdraw_wrapped<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let wrapped = state.wrapped_native.ok_or_else(|| ContractError::NotSet {
+        what: "wrapped native token".to_string(),
+    })?;
+    if state.ticket.is_some() {
+        return Err(ContractError::WithdrawWithTickets);
+    }
+    if open_pool(&deps.storage)?.denom() != DENOM {
+        return Err(ContractError::OnlyNativeWrapped);
+    }
+    let withdrawer = env.message.sender.clone();
+    let mut response = remove_deposit(deps, env, withdrawer.clone(), amount)?;
+    let wrap = Snip20HandleMsg::Deposit { padding: None };
+    let transfer = Snip20HandleMsg::Transfer {
+        recipient: withdrawer,
+        amount,
+        padding: None,
+    };
+    response.messages = vec![
+        wrap.to_cosmos_msg_with_funds(
+            wrapped.address.clone(),
+            wrapped.code_hash.clone(),
+            coins(amount.u128(), DENOM),
+        )?,
+        transfer.to_cosmos_msg(wrapped.address, wrapped.code_hash)?,
+    ];
+    Ok(response)
+}
+
+// Pay `amount` of `withdrawer`'s deposit in the OPEN pool back to them.
+fn remove_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    withdrawer: HumanAddr,
+    amount: Uint128,
+) -> Result<HandleResponse, ContractError> {
+    // Would only send an empty transfer and log a withdrawal that moved nothing.
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {
+            what: "withdraw".to_string(),
+        });
+    }
+    let mut pool = open_pool(&deps.storage)?;
+    if pool.nft_tickets {
+        return Err(ContractError::NftWithdrawal);
+    }
+    // Weights are values at deposit time, which a withdrawal can't unwind.
+    if pool.is_multi_denom() {
+        return Err(ContractError::MultiDenomWithdrawal);
+    }
+    let sender_addr = deps.api.canonical_address(&withdrawer)?;
+    let mut entry = load_entry(&deps.storage, pool.id, &sender_addr)?;
+    entry.amount = math::sub(entry.amount, amount)?;
+    entry.weight = math::sub(entry.weight, amount)?;
+    // Same test as `add_deposit`, which counts a depositor once their weight is above zero.
+    if entry.weight.is_zero() {
+        pool.unique_depositors = math::sub_count(pool.unique_depositors, 1)?;
+    }
+    pool.total_deposits = math::sub(pool.total_deposits, amount)?;
+    pool.total_weight = math::sub(pool.total_weight, amount)?;
+    let mut player = may_load_player(&deps.storage, &sender_addr)?.unwrap_or_default();
+    let history = HistoryEntry {
+        action: Action::Withdraw,
+        pool_id: pool.id,
+        amount,
+        time: env.block.time,
+    };
+    push_history(&mut deps.storage, &sender_addr, &mut player, &history)?;
+    save_player(&mut deps.storage, &sender_addr, &player)?;
+    save_entry(&mut deps.storage, pool.id, &sender_addr, &entry)?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    let attrs = action_log("withdraw", &withdrawer, Some(&pool), Some(amount));
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: withdrawer,
+            amount: coins(amount.u128(), pool.denom()),
+        })],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> Result<HandleResponse, ContractError> {
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    write_viewing_key(&mut deps.storage, &sender_addr, &key);
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_viewing_key", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> Result<HandleResponse, ContractError> {
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let key = generate_viewing_key(&mut deps.storage, &env, &entropy);
+    write_viewing_key(&mut deps.storage, &sender_addr, &key);
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("create_viewing_key", &env.message.sender, None, None),
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+    })
+}
+
+pub fn set_public_listing<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    public: bool,
+) -> Result<HandleResponse, ContractError> {
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let mut record =
+        may_load_player(&deps.storage, &sender_addr)?.ok_or(ContractError::OnlyPlayersListed)?;
+    let mut state = config_read(&deps.storage).load()?;
+    if public {
+        list_player(&mut deps.storage, &mut state, &sender_addr, &mut record)?;
+    } else {
+        unlist_player(&mut deps.storage, &mut state, &mut record)?;
+    }
+    save_player(&mut deps.storage, &sender_addr, &record)?;
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_public_listing", &env.message.sender, None, None);
+    attrs.push(log("public", public));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_set_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    rate: Decimal,
+    recipient: Option<HumanAddr>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    if rate > max_fee_rate() {
+        return Err(ContractError::FeeTooHigh {
+            max: max_fee_rate(),
+        });
+    }
+    let recipient = match recipient {
+        Some(addr) => Some(deps.api.canonical_address(&addr)?),
+        None => None,
+    };
+    state.fees.pending = Some(FeeChange { rate, recipient });
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_fee", &env.message.sender, None, None);
+    attrs.push(log("rate", rate));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+// Draw the winner of the CLOSED pool once its stake is back. The protocol fee comes
+// out of the harvested rewards and the rest is the prize.
+pub fn admin_draw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let mut index = pool_index_read(&deps.storage).load()?;
+    let pool_id = index
+        .pending_settlement
+        .ok_or(ContractError::NoPendingSettlement)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    let (timing, owner) = (&state.timing, &state.owner);
+    transitions::assert_allowed(Settle, &pool, &sender_addr, owner, timing, &env.block)?;
+    // Payouts are capped to what staking actually returned. A slash comes out of the
+    // rewards when covered, and out of every depositor's principal otherwise.
+    let mut rewards = pool.harvested_rewards;
+    if !pool.unbonding_amount.is_zero() {
+        let returned = unbonded_principal(deps, &env, &state, &index, &pool)?;
+        let shortfall = math::sub(pool.unbonding_amount, returned)?;
+        if state.cover_slashes {
+            pool.slash_covered = shortfall.min(rewards);
+            rewards = math::sub(rewards, pool.slash_covered)?;
+        }
+        pool.slashed_amount = math::sub(shortfall, pool.slash_covered)?;
+    }
+    let fee = payout::portion(rewards, pool.fee_rate)?;
+    let prize = math::add(math::sub(rewards, fee)?, pool.prize_funding()?)?;
+    let mut progress = match pool.draw.take() {
+        Some(progress) => progress,
+        None => DrawProgress::start(&pool, &draw_randomness(&deps.storage, pool.id)),
+    };
+    advance_draw(&deps.storage, &pool, &mut progress, pool.delegator_count)?;
+    let winner = progress.winner.clone();
+    pool.draw = Some(progress);
+    pool.settle(winner, rewards, prize, state.timing.now(&env.block))?;
+    // Only shown, so a stale or unreachable oracle leaves it out rather than holding up
+    // the settlement.
+    if let Some(oracle) = state.oracle.as_ref() {
+        pool.prize_value = oracle
+            .value(&deps.querier, prize, pool.denom(), env.block.time)
+            .ok();
+    }
+    if let Some(emission) = state.emission.as_ref() {
+        if !pool.total_weight.is_zero() {
+            pool.emission_amount = emission.amount_for(pool.id);
+        }
+    }
+    if state.transparent && pool.winner.is_some() {
+        announce_winner(&mut deps.storage, &mut state, &mut pool)?;
+    }
+    state.stats.record_settlement(&pool)?;
+    if pool.winner.is_some() && !pool.prize_claimed {
+        state.unclaimed_prizes += 1;
+    }
+    // What depositors and the winner can claim from now on. Staked principal is capped
+    // to what came back above; the rest is paid from funds already in the contract.
+    if pool.denom() == DENOM {
+        let principal = math::sub(pool.total_deposits, pool.slashed_amount)?;
+        state.settled_principal = math::add(state.settled_principal, principal)?;
+        if pool.winner.is_some() && !pool.prize_claimed {
+            state.settled_prizes = math::add(state.settled_prizes, pool.prize_amount)?;
+        }
+        if pool.winner.is_none() {
+            state.carryover = math::add(state.carryover, pool.prize_amount)?;
+        }
+    }
+    // Referrers get their part of the fee for the share of the pool their codes brought
+    // in. Withdrawn referred deposits still count, up to the pool's total.
+    if pool.denom() == DENOM && !pool.total_deposits.is_zero() {
+        let referred = pool.referred_deposits.min(pool.total_deposits);
+        let share = payout::portion(pool.fee_amount, state.referral_share)?;
+        pool.referral_amount = payout::pro_rata(share, referred, pool.total_deposits)?;
+        state.referral_rewards = math::add(state.referral_rewards, pool.referral_amount)?;
+    }
+    let fee = math::sub(pool.fee_amount, pool.referral_amount)?;
+    state.fees.accrued = math::add(state.fees.accrued, fee)?;
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::WinnerDrawn,
+        pool.id,
+        env.block.time,
+    )?;
+    index.settled(pool.id);
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    pool_index(&mut deps.storage).save(&index)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: hook_messages(&state.hooks, HookEvent::PoolSettled, pool.id)?,
+        log: [
+            action_log("draw", &env.message.sender, Some(&pool), Some(prize)),
+            events::winner_drawn(&pool),
+        ]
+        .concat(),
+        data: None,
+    })
+}
+
+pub fn prepare_draw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let pool_id = pool_index_read(&deps.storage)
+        .load()?
+        .pending_settlement
+        .ok_or(ContractError::NoPendingSettlement)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    transitions::assert_ready(Settle, &pool, &state.timing, &env.block)?;
+    let mut progress = match pool.draw.take() {
+        Some(progress) => progress,
+        None => DrawProgress::start(&pool, &draw_randomness(&deps.storage, pool.id)),
+    };
+    let (_, end) = page_range(progress.cursor.checked_sub(1), limit, pool.delegator_count);
+    advance_draw(&deps.storage, &pool, &mut progress, end)?;
+    let done = progress.is_done(&pool);
+    pool.draw = Some(progress);
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    let mut attrs = action_log("prepare_draw", &env.message.sender, Some(&pool), None);
+    attrs.push(log("done", done));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn claim_prize<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_id: u64,
+    ibc_payout: Option<IbcPayout>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if pool.winner.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized);
+    }
+    let gateway = match ibc_payout.as_ref() {
+        Some(payout) => {
+            payout.validate()?;
+            Some(
+                state
+                    .ibc_gateway
+                    .clone()
+                    .ok_or_else(|| ContractError::NotSet {
+                        what: "IBC gateway".to_string(),
+                    })?,
+            )
+        }
+        None => None,
+    };
+    // The ticket holder claims an NFT round, whoever deposited it.
+    if pool.nft_tickets {
+        return Err(ContractError::ClaimWithTicket);
+    }
+    if pool.prize_claimed {
+        return Err(ContractError::AlreadyClaimed);
+    }
+    pool.prize_claimed = true;
+    state.unclaimed_prizes = state.unclaimed_prizes.saturating_sub(1);
+    release_settled_prize(&mut state, &pool);
+    pool.ibc_payout = ibc_payout.clone();
+    record_event(
+        &mut deps.storage,
+        &mut state,
+        EventKind::PrizeClaimed,
+        pool.id,
+        env.block.time,
+    )?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    // A winner with a callback is paid through it, so if the callback fails only the
+    // claim does, and the winner can remove it and claim again.
+    let callback = match ibc_payout {
+        Some(_) => None,
+        None => winner_callbacks_read(&deps.storage).may_load(sender_addr.as_slice())?,
+    };
+    let attach = callback.as_ref().map_or(false, |c| c.attach_prize);
+    let mut messages = vec![];
+    let prize = coins(pool.prize_amount.u128(), pool.denom());
+    match (ibc_payout.as_ref(), gateway) {
+        _ if pool.prize_amount.is_zero() || attach => {}
+        (Some(payout), Some(gateway)) => {
+            let transfer = Ics20HandleMsg::Transfer {
+                channel: payout.channel.clone(),
+                remote_address: payout.recipient.clone(),
+                timeout: None,
+            };
+            messages.push(transfer.to_cosmos_msg(gateway.address, gateway.code_hash, prize)?);
+        }
+        _ => messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount: prize,
+        })),
+    }
+    if let Some(callback) = callback {
+        let attach = attach && !pool.prize_amount.is_zero();
+        let winner = env.message.sender.clone();
+        messages.push(notify_winner(&pool, winner, callback, attach)?);
+    }
+    let mut attrs = action_log(
+        "claim_prize",
+        &env.message.sender,
+        Some(&pool),
+        Some(pool.prize_amount),
+    );
+    if let Some(payout) = ibc_payout {
+        attrs.push(log("channel", payout.channel));
+        attrs.push(log("recipient", payout.recipient));
+    }
+    Ok(HandleResponse {
+        messages,
+        log: [attrs, events::prize_claimed(pool.prize_amount)].concat(),
+        data: Some(to_binary(&HandleAnswer::ClaimPrize {
+            pool_id: pool.id,
+            amount: pool.prize_amount,
+            denom: pool.denom().to_string(),
+        })?),
+    })
+}
+
+// A claimed prize is no longer owed. Pools settled before `settled_prizes` was kept
+// aren't in it, hence the floor at zero.
+fn release_settled_prize(state: &mut State, pool: &Pool) {
+    if pool.denom() == DENOM {
+        state.settled_prizes =
+            math::sub(state.settled_prizes, pool.prize_amount).unwrap_or_default();
+    }
+}
+
+// Take `share` of a settled pool's principal for a payout, once the unbonded stake is
+// in the contract on top of everything else it holds. Returns less than `share` only
+// when the pool's principal runs out.
+fn release_principal<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    state: &mut State,
+    pool: &mut Pool,
+    share: Uint128,
+) -> Result<Uint128, ContractError> {
+    // Shares are rounded down, but never pay out more than came back from staking.
+    let returned = math::sub(pool.total_deposits, pool.slashed_amount)?;
+    let share = share.min(math::sub(returned, pool.principal_claimed)?);
+    if share.is_zero() {
+        return Ok(share);
+    }
+    // The share is among the liabilities, so no other pool's funds can pay for it.
+    if pool.denom() == DENOM {
+        let index = pool_index_read(&deps.storage).load()?;
+        let owed = liquid_liabilities(&deps.storage, state, &index)?;
+        let balance = deps
+            .querier
+            .query_balance(env.contract.address.clone(), DENOM)?
+            .amount;
+        if balance < owed {
+            return Err(ContractError::UnbondedFundsMissing);
+        }
+        state.settled_principal = math::sub(state.settled_principal, share)?;
+    }
+    pool.principal_claimed = math::add(pool.principal_claimed, share)?;
+    Ok(share)
+}
+
+// Tickets carry the claim on the principal they were minted against, whoever holds
+// them now, see `redeem_tickets`. NFT rounds and extra denoms mint none.
+fn principal_in_tickets(state: &State, pool: &Pool) -> bool {
+    state.ticket.is_some() && !pool.nft_tickets
+}
+
+// Pay the sender's principal in a settled pool back, less their share of any slash.
+pub fn claim_principal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.is_settled() {
+        return Err(ContractError::PoolNotSettled);
+    }
+    let mut entry = may_load_entry(&deps.storage, pool.id, &sender_addr)?
+        .ok_or(ContractError::NothingToClaim)?;
+    let ticketed = principal_in_tickets(&state, &pool);
+    let share = if ticketed {
+        Uint128::zero()
+    } else {
+        pool.principal_share(&entry)?
+    };
+    let share = release_principal(deps, &env, &mut state, &mut pool, share)?;
+    let mut extra = load_entry_balances(&deps.storage, pool.id, &sender_addr)?;
+    extra.retain(|c| !c.amount.is_zero());
+    if share.is_zero() && extra.is_empty() {
+        return Err(ContractError::NothingToClaim);
+    }
+    for paid in extra.iter() {
+        if let Some(held) = pool
+            .denom_balances
+            .iter_mut()
+            .find(|c| c.denom == paid.denom)
+        {
+            held.amount = math::sub(held.amount, paid.amount)?;
+        }
+    }
+    // The weight stays, so emission shares see the entry as before.
+    if !ticketed {
+        entry.amount = Uint128::zero();
+    }
+    let mut player = may_load_player(&deps.storage, &sender_addr)?.unwrap_or_default();
+    let history = HistoryEntry {
+        action: Action::Withdraw,
+        pool_id: pool.id,
+        amount: share,
+        time: env.block.time,
+    };
+    push_history(&mut deps.storage, &sender_addr, &mut player, &history)?;
+    save_player(&mut deps.storage, &sender_addr, &player)?;
+    save_entry(&mut deps.storage, pool.id, &sender_addr, &entry)?;
+    if !extra.is_empty() {
+        save_entry_balances(&mut deps.storage, pool.id, &sender_addr, &[])?;
+    }
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    let mut amount = extra;
+    if !share.is_zero() {
+        amount.insert(0, coin(share.u128(), pool.denom()));
+    }
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount,
+        })],
+        log: action_log(
+            "claim_principal",
+            &env.message.sender,
+            Some(&pool),
+            Some(share),
+        ),
+        data: Some(to_binary(&HandleAnswer::ClaimPrincipal {
+            pool_id: pool.id,
+            amount: share,
+            denom: pool.denom().to_string(),
+        })?),
+    })
+}
+
+// Redeem `amount` tickets for a settled pool's principal, less its share of any slash.
+// Tickets are fungible, so whoever holds them can, whoever deposited; a pool pays out
+// no more than its own principal.
+fn redeem_tickets<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    holder: HumanAddr,
+    pool_id: u64,
+    amount: Uint128,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.is_settled() {
+        return Err(ContractError::PoolNotSettled);
+    }
+    // Their principal is claimed by entry, see `claim_principal`.
+    if pool.nft_tickets {
+        return Err(ContractError::NftWithdrawal);
+    }
+    let share = pool.principal_for(amount)?;
+    if share.is_zero() {
+        return Err(ContractError::NothingToClaim);
+    }
+    if release_principal(deps, &env, &mut state, &mut pool, share)? < share {
+        return Err(ContractError::TicketsExceedPrincipal { pool_id });
+    }
+    let holder_addr = deps.api.canonical_address(&holder)?;
+    let mut player = may_load_player(&deps.storage, &holder_addr)?.unwrap_or_default();
+    let history = HistoryEntry {
+        action: Action::Withdraw,
+        pool_id: pool.id,
+        amount: share,
+        time: env.block.time,
+    };
+    push_history(&mut deps.storage, &holder_addr, &mut player, &history)?;
+    save_player(&mut deps.storage, &holder_addr, &player)?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: holder.clone(),
+            amount: coins(share.u128(), pool.denom()),
+        })],
+        log: action_log("redeem_tickets", &holder, Some(&pool), Some(share)),
+        data: None,
+    })
+}
+
+pub fn admin_set_slash_cover<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.cover_slashes = enabled;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_slash_cover", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn admin_set_deposit_limit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limit: Option<DepositLimit>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.deposit_limit = limit;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_deposit_limit", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn admin_set_max_depositors<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    max: u32,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    if max == 0 || max > MAX_DEPOSITORS {
+        return Err(ContractError::InvalidMaxDepositors {
+            max: MAX_DEPOSITORS,
+        });
+    }
+    state.max_depositors = max;
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_max_depositors", &env.message.sender, None, None);
+    attrs.push(log("max", max));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_set_max_unclaimed_prizes<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    max: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.max_unclaimed_prizes = max;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_max_unclaimed_prizes", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn start_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    new_owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    if state.guardian.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized);
+    }
+    if state.recovery.is_some() {
+        return Err(ContractError::RecoveryPending);
+    }
+    let executable_at = state
+        .timing
+        .deadline(&env.block, state.timing.recovery_delay());
+    state.recovery = Some(Recovery {
+        new_owner: deps.api.canonical_address(&new_owner)?,
+        executable_at,
+    });
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("start_recovery", &env.message.sender, None, None);
+    attrs.push(log("new_owner", &new_owner));
+    attrs.push(log("executable_at", executable_at));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn cancel_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    if state.guardian.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized);
+    }
+    state.recovery.take().ok_or(ContractError::NoRecovery)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("cancel_recovery", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn finish_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    if state.guardian.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized);
+    }
+    let recovery = state.recovery.take().ok_or(ContractError::NoRecovery)?;
+    let now = state.timing.now(&env.block);
+    if now < recovery.executable_at {
+        return Err(ContractError::RecoveryNotReady {
+            remaining: recovery.executable_at - now,
+            unit: state.timing.unit().to_string(),
+        });
+    }
+    state.owner = recovery.new_owner;
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("finish_recovery", &env.message.sender, None, None);
+    attrs.push(log("owner", deps.api.human_address(&state.owner)?));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_set_treasury<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    treasury: Option<Token>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.treasury = treasury;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_treasury", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn admin_collect_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let amount = state.fees.accrued;
+    if amount.is_zero() {
+        return Err(ContractError::NoFeesToCollect);
+    }
+    let fees = coins(amount.u128(), DENOM);
+    let msg = match state.treasury.as_ref() {
+        Some(treasury) => {
+            let deposit = TreasuryHandleMsg::Deposit {
+                purpose: TreasuryPurpose::Fees,
+            };
+            deposit.to_cosmos_msg(treasury.address.clone(), treasury.code_hash.clone(), fees)?
+        }
+        None => {
+            let recipient = state.fees.recipient.as_ref().unwrap_or(&state.owner);
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: deps.api.human_address(recipient)?,
+                amount: fees,
+            })
+        }
+    };
+    state.fees.accrued = Uint128::zero();
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![msg],
+        log: action_log("collect_fees", &env.message.sender, None, Some(amount)),
+        data: None,
+    })
+}
+
+// Refused for any denom a pool holds, so deposits and prizes can't be swept.
+pub fn admin_sweep_to_treasury<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    denom: String,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    let treasury = state
+        .treasury
+        .clone()
+        .ok_or_else(|| ContractError::NotSet {
+            what: "treasury".to_string(),
+        })?;
+    let mut held = denom == DENOM || state.pool_denoms.contains(&denom);
+    let index = pool_index_read(&deps.storage).load()?;
+    for pool_id in [
+        index.current_open,
+        index.current_locked,
+        index.pending_settlement,
+    ]
+    .iter()
+    .flatten()
+    {
+        let pool = pool_read(&deps.storage).load(&pool_key(*pool_id))?;
+        held |= pool.unconverted_rewards.iter().any(|c| c.denom == denom);
+    }
+    if held {
+        return Err(ContractError::HeldForPools { denom });
+    }
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address.clone(), &denom)?;
+    if balance.amount.is_zero() {
+        return Err(ContractError::NothingToSweep { denom });
+    }
+    let deposit = TreasuryHandleMsg::Deposit {
+        purpose: TreasuryPurpose::Sweep,
+    };
+    let mut attrs = action_log(
+        "sweep_to_treasury",
+        &env.message.sender,
+        None,
+        Some(balance.amount),
+    );
+    attrs.push(log("denom", &denom));
+    Ok(HandleResponse {
+        messages: vec![deposit.to_cosmos_msg(
+            treasury.address,
+            treasury.code_hash,
+            vec![balance],
+        )?],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_set_ibc_gateway<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    gateway: Option<Token>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    state.ibc_gateway = gateway;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("set_ibc_gateway", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+// Winners stay anonymous unless they opt in here.
+pub fn announce_win<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if pool.winner.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized);
+    }
+    announce_winner(&mut deps.storage, &mut state, &mut pool)?;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    config(&mut deps.storage).save(&state)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("announce_win", &env.message.sender, Some(&pool), None),
+        data: None,
+    })
+}
+
+pub fn register_referral_code<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code: String,
+) -> Result<HandleResponse, ContractError> {
+    if code.is_empty() || code.len() > MAX_REFERRAL_CODE_LEN {
+        return Err(ContractError::ReferralCodeLength {
+            max: MAX_REFERRAL_CODE_LEN,
+        });
+    }
+    let code_hash = referral_code_hash(&code);
+    if may_load_referral(&deps.storage, &code_hash)?.is_some() {
+        return Err(ContractError::ReferralCodeTaken);
+    }
+    let referral = Referral {
+        referrer: deps.api.canonical_address(&env.message.sender)?,
+        deposits: 0,
+        referred_amount: Uint128::zero(),
+    };
+    save_referral(&mut deps.storage, &code_hash, &referral)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("register_referral_code", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+pub fn admin_set_referral_share<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    share: Decimal,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    if share > Decimal::one() {
+        return Err(ContractError::InvalidRate { rate: share });
+    }
+    state.referral_share = share;
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log("set_referral_share", &env.message.sender, None, None);
+    attrs.push(log("share", share));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+// Pay a referrer what their code earned in each listed settled pool, at most
+// `MAX_LIMIT` of them. A pool's referral rewards are split by what each code
+// brought into it.
+pub fn claim_referral_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code: String,
+    pool_ids: Vec<u64>,
+) -> Result<HandleResponse, ContractError> {
+    let mut state = config_read(&deps.storage).load()?;
+    if pool_ids.len() > MAX_LIMIT as usize {
+        return Err(ContractError::CapExceeded {
+            what: "pools per claim".to_string(),
+            max: MAX_LIMIT.into(),
+        });
+    }
+    let code_hash = referral_code_hash(&code);
+    let referral =
+        may_load_referral(&deps.storage, &code_hash)?.ok_or(ContractError::UnknownReferralCode)?;
+    if referral.referrer != deps.api.canonical_address(&env.message.sender)? {
+        return Err(ContractError::Unauthorized);
+    }
+    let mut claimed = vec![];
+    let mut total = Uint128::zero();
+    for pool_id in pool_ids {
+        if claimed.contains(&pool_id) {
+            continue;
+        }
+        let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+        if !pool.is_settled() || has_claimed_referral(&deps.storage, pool.id, &code_hash) {
+            continue;
+        }
+        let referred = load_referred(&deps.storage, pool.id, &code_hash)?;
+        if referred.is_zero() {
+            continue;
+        }
+        let reward = payout::pro_rata(pool.referral_amount, referred, pool.referred_deposits)?;
+        if reward.is_zero() {
+            continue;
+        }
+        total = math::add(total, reward)?;
+        claimed.push(pool_id);
+    }
+    if total.is_zero() {
+        return Err(ContractError::NothingToClaim);
+    }
+    for pool_id in claimed.iter() {
+        mark_referral_claimed(&mut deps.storage, *pool_id, &code_hash);
+    }
+    // Floored like `settled_prizes`, for pools settled before rewards were set aside.
+    state.referral_rewards = math::sub(state.referral_rewards, total).unwrap_or_default();
+    config(&mut deps.storage).save(&state)?;
+    let mut attrs = action_log(
+        "claim_referral_rewards",
+        &env.message.sender,
+        None,
+        Some(total),
+    );
+    attrs.push(log("pools", claimed.len()));
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender,
+            amount: coins(total.u128(), DENOM),
+        })],
+        log: attrs,
+        data: None,
+    })
+}
+
+pub fn admin_rotate_seed<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> Result<HandleResponse, ContractError> {
+    let state = config_read(&deps.storage).load()?;
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    assert_sender_is_admin(&sender_addr, &state.owner)?;
+    // Allowed at any time: a locked round draws from the seed sealed when it locked.
+    rotate_prng_seed(&mut deps.storage, entropy.as_bytes());
+    Ok(HandleResponse {
+        messages: vec![],
+        log: action_log("rotate_seed", &env.message.sender, None, None),
+        data: None,
+    })
+}
+
+// Delete zero-balance entries of a settled pool, at most `limit` delegators per call.
+// Progress is kept on the pool so anyone can call again to resume.
+pub fn cleanup_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool_id: u64,
+    limit: Option<u32>,
+) -> Result<HandleResponse, ContractError> {
+    let mut pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.is_settled() {
+        return Err(ContractError::PoolNotSettled);
+    }
+    // The principal of a ticketed pool is redeemed by ticket, never by entry.
+    let ticketed = principal_in_tickets(&config_read(&deps.storage).load()?, &pool);
+    let start_after = pool.cleanup_cursor.checked_sub(1);
+    let (_, end) = page_range(start_after, limit, pool.delegator_count);
+    let delegators = read_delegators(&deps.storage, &pool, start_after, limit)?;
+    let mut removed = 0u64;
+    for (index, delegator) in delegators.iter() {
+        // An entry goes once nothing is left to claim from it. In NFT rounds the winner's
+        // stays until the prize is claimed, since the winning ticket is looked up by it.
+        let holds_prize = pool.nft_tickets && !pool.prize_claimed;
+        let spent = match may_load_entry(&deps.storage, pool.id, delegator)? {
+            Some(_) if holds_prize && pool.winner.as_ref() == Some(delegator) => false,
+            Some(entry) => {
+                let balances = load_entry_balances(&deps.storage, pool.id, delegator)?;
+                (ticketed || entry.amount.is_zero())
+                    && balances.iter().all(|c| c.amount.is_zero())
+                    && (has_claimed_emission(&deps.storage, pool.id, delegator)
+                        || pool.emission_share(&entry)?.is_zero())
+            }
+            None => true,
+        };
+        if spent {
+            remove_entry(&mut deps.storage, pool.id, delegator);
+            pool_delegators(&mut deps.storage, pool.id).remove(&index.to_be_bytes());
+            removed += 1;
+        }
+    }
+    pool.cleanup_cursor = end;
+    pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+    let mut attrs = action_log("cleanup_pool", &env.message.sender, Some(&pool), None);
+    attrs.push(log("removed", removed));
+    attrs.push(log("done", pool.cleanup_cursor == pool.delegator_count));
+    Ok(HandleResponse {
+        messages: vec![],
+        log: attrs,
+        data: None,
+    })
+}
+
+// What `migrate` would write, worked out from storage alone so `SimulateMigration`
+// can report it on the new code before anything is changed.
+struct MigrationPlan {
+    // The converted config. None when storage is already in the current layout.
+    state: Option<State>,
+    // The converted pool, if there was one, and its delegators.
+    pool: Option<(Pool, Vec<CanonicalAddr>)>,
+    changes: Vec<String>,
+    unconvertible: Vec<String>,
+}
+
+fn plan_migration<S: Storage>(storage: &S) -> StdResult<MigrationPlan> {
+    let mut changes = vec![];
+    match storage_version(storage)? {
+        STORAGE_VERSION => {
+            let state = config_read(storage).load()?;
+            if state.contract_address.is_none() {
+                changes.push("contract address will be recorded".to_string());
+            }
+            return Ok(MigrationPlan {
+                state: None,
+                pool: None,
+                changes,
+                unconvertible: vec![],
+            });
+        }
+        LEGACY_STORAGE_VERSION => {}
+        version => return Err(ContractError::UnknownStorageVersion { version }.into()),
+    }
+    let legacy_state = legacy_config_read(storage).load()?;
+    let legacy_pool = legacy_pool_read(storage).may_load()?;
+    let mut state = State {
+        owner: legacy_state.owner,
+        pool_count: 0,
+        stats: Stats::default(),
+        announced_winners: 0,
+        listed_players: 0,
+        event_count: 0,
+        response_block_size: RESPONSE_BLOCK_SIZE,
+        fees: Fees::default(),
+        contract_address: None,
+        public_rounding: Uint128(1),
+        delay_public_totals: false,
+        published_stats: None,
+        transparent: false,
+        generic_errors: false,
+        tokens: vec![],
+        ticket: None,
+        nft_contract: None,
+        emission: None,
+        hooks: vec![],
+        swap_router: None,
+        oracle: None,
+        wrapped_native: None,
+        factory: None,
+        child_count: 0,
+        parent: None,
+        ibc_gateway: None,
+        treasury: None,
+        pool_denoms: vec![],
+        cover_slashes: false,
+        deposit_limit: None,
+        max_depositors: MAX_DEPOSITORS,
+        unclaimed_prizes: 0,
+        max_unclaimed_prizes: None,
+        settled_principal: Uint128::zero(),
+        settled_prizes: Uint128::zero(),
+        carryover: Uint128::zero(),
+        referral_share: Decimal::zero(),
+        referral_rewards: Uint128::zero(),
+        guardian: None,
+        recovery: None,
+        timing: Timing::default(),
+        validators: vec![],
+    };
+    changes.push("config will move to the current layout".to_string());
+    let mut unconvertible = vec![];
+    let mut pool = None;
+    if let Some(legacy_pool) = legacy_pool {
+        state.pool_count = 1;
+        state.stats.pools_run = 1;
+        let mut new_pool = legacy_pool.to_pool(state.pool_count, &state.timing);
+        // The legacy list was never deduplicated; keep one slot per address.
+        let mut listed = vec![];
+        for delegator in legacy_pool.delegators.iter() {
+            if !listed.contains(delegator) {
+                listed.push(delegator.clone());
+            }
+        }
+        new_pool.unique_depositors = listed.len() as u64;
+        changes.push(format!(
+            "pool 1 will be created {:?} with {} delegators",
+            new_pool.status,
+            listed.len()
+        ));
+        let dropped = legacy_pool.delegators.len() - listed.len();
+        if dropped > 0 {
+            changes.push(format!(
+                "{} duplicate delegator listings will be dropped",
+                dropped
+            ));
+        }
+        // Legacy pools kept a list of addresses, not what each of them deposited.
+        if !listed.is_empty() {
+            unconvertible.push(format!(
+                "{} delegators have no recorded deposit and will be listed without an entry",
+                listed.len()
+            ));
+        }
+        let mut check = new_pool.clone();
+        check.delegator_count = listed.len() as u64;
+        if let Err(err) = check.check_totals() {
+            unconvertible.push(err.to_string());
+        }
+        pool = Some((new_pool, listed));
+    }
+    Ok(MigrationPlan {
+        state: Some(state),
+        pool,
+        changes,
+        unconvertible,
+    })
+}
+
+// One-time migration from the singleton pool layout to pools keyed by id.
+// The in-flight pool becomes pool 1 and the owner is carried over.
+// Running it against an already migrated contract is a no-op.
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: MigrateMsg,
+) -> StdResult<MigrateResponse> {
+    let plan = plan_migration(&deps.storage)?;
+    // Legacy configs carry their owner over, so either way only the stored owner migrates.
+    let sender_addr = deps.api.canonical_address(&env.message.sender)?;
+    let owner = match plan.state.as_ref() {
+        Some(state) => state.owner.clone(),
+        None => config_read(&deps.storage).load()?.owner,
+    };
+    if sender_addr != owner {
+        return Err(StdError::unauthorized());
+    }
+    // What can't be carried over would leave pools that fail their invariants.
+    if !plan.unconvertible.is_empty() {
+        let reasons = plan.unconvertible;
+        return Err(ContractError::Unconvertible { reasons }.into());
+    }
+    // The seed is secret; a public fallback such as `block_seed` would let anyone
+    // predict viewing keys and draws.
+    match msg.prng_seed {
+        Some(seed) if has_prng_seed(&deps.storage) => rotate_prng_seed(&mut deps.storage, &seed.0),
+        Some(seed) => write_prng_seed(&mut deps.storage, &seed.0),
+        None if has_prng_seed(&deps.storage) => {}
+        None => return Err(ContractError::SeedRequired.into()),
+    }
+    let mut state = match plan.state {
+        Some(state) => state,
+        None => {
+            let mut state = config_read(&deps.storage).load()?;
+            if state.contract_address.is_none() {
+                state.contract_address = Some(env.contract.address);
+                config(&mut deps.storage).save(&state)?;
+            }
+            return Ok(MigrateResponse::default());
+        }
+    };
+    state.contract_address = Some(env.contract.address.clone());
+    let mut index = PoolIndex::default();
+    if let Some((mut pool, delegators)) = plan.pool {
+        for delegator in delegators.iter() {
+            push_delegator(&mut deps.storage, &mut pool, delegator)?;
+        }
+        index = PoolIndex::from_pool(&pool)?;
+        pool_storage(&mut deps.storage).save(&pool_key(pool.id), &pool)?;
+        legacy_pool_storage(&mut deps.storage).remove();
+    }
+    config(&mut deps.storage).save(&state)?;
+    pool_index(&mut deps.storage).save(&index)?;
+    set_storage_version(&mut deps.storage)?;
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![log("migrated_pools", state.pool_count)],
+        data: None,
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let sensitive = is_authenticated(&msg);
+    let response = dispatch_query(deps, msg);
+    if sensitive {
+        obscure_error(deps, response)
+    } else {
+        response
+    }
+}
+
+// Queries that check a viewing key.
+fn is_authenticated(msg: &QueryMsg) -> bool {
+    matches!(
+        msg,
+        QueryMsg::GetMyDeposit { .. }
+            | QueryMsg::GetOdds { .. }
+            | QueryMsg::GetMyHistory { .. }
+            | QueryMsg::AmITheWinner { .. }
+            | QueryMsg::ExportState { .. }
+            | QueryMsg::LotteryV1(LotteryQueryV1::Deposit { .. })
+    )
+}
+
+fn dispatch_query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetOwner {} => to_binary(&query_owner(deps)?),
+        QueryMsg::GetRecovery {} => to_binary(&query_recovery(deps)?),
+        QueryMsg::GetConfig {} => to_binary(&query_config(deps)?),
+        QueryMsg::GetCurrentPool { caller } => to_binary(&query_pool(deps, caller)?),
+        QueryMsg::GetPool { pool_id, caller } => {
+            to_binary(&query_pool_by_id(deps, pool_id, caller)?)
+        }
+        QueryMsg::GetTimeRemaining { time, height } => {
+            to_binary(&query_time_remaining(deps, time, height)?)
+        }
+        QueryMsg::GetPoolHistory { start_after, limit } => {
+            to_binary(&query_pool_history(deps, start_after, limit)?)
+        }
+        QueryMsg::GetPastWinners { start_after, limit } => {
+            to_binary(&query_past_winners(deps, start_after, limit)?)
+        }
+        QueryMsg::GetStats {} => to_binary(&query_stats(deps)?),
+        QueryMsg::GetStatistics {} => to_binary(&query_statistics(deps)?),
+        QueryMsg::ChangesSince { start_after, limit } => {
+            to_binary(&query_changes_since(deps, start_after, limit)?)
+        }
+        QueryMsg::GetTotalValueLocked {} => to_binary(&query_total_value_locked(deps)?),
+        QueryMsg::GetChildren { start_after, limit } => {
+            to_binary(&query_children(deps, start_after, limit)?)
+        }
+        QueryMsg::GetChildrenTotalValueLocked { start_after, limit } => {
+            to_binary(&query_children_tvl(deps, start_after, limit)?)
+        }
+        QueryMsg::GetLeaderboard {
+            order_by,
+            start_after,
+            limit,
+        } => to_binary(&query_leaderboard(deps, order_by, start_after, limit)?),
+        QueryMsg::GetRound {} => to_binary(&query_round(deps)?),
+        QueryMsg::GetUnbondingStatus {} => to_binary(&query_unbonding_status(deps)?),
+        QueryMsg::GetSettlementProgress { pool_id } => {
+            to_binary(&query_settlement_progress(deps, pool_id)?)
+        }
+        QueryMsg::GetTicketRate { pool_id } => to_binary(&query_ticket_rate(deps, pool_id)?),
+        QueryMsg::GetValidatorSet {} => to_binary(&query_validator_set(deps)?),
+        QueryMsg::GetFeeInfo {} => to_binary(&query_fee_info(deps)?),
+        QueryMsg::GetRewardBreakdown { pool_id } => {
+            to_binary(&query_reward_breakdown(deps, pool_id)?)
+        }
+        QueryMsg::Dashboard { time, height } => to_binary(&query_dashboard(deps, time, height)?),
+        QueryMsg::GetCurrentPrizePot {} => to_binary(&query_prize_pot(deps)?),
+        QueryMsg::GetDelegatorCount { pool_id } => {
+            to_binary(&query_delegator_count(deps, pool_id)?)
+        }
+        QueryMsg::GetTransitions {
+            pool_id,
+            start_after,
+            limit,
+        } => to_binary(&query_transitions(deps, pool_id, start_after, limit)?),
+        QueryMsg::GetReferral { code } => to_binary(&query_referral(deps, code)?),
+        QueryMsg::GetDelegators {
+            pool_id,
+            start_after,
+            limit,
+        } => to_binary(&query_delegators(deps, pool_id, start_after, limit)?),
+        QueryMsg::GetMyDeposit { address, key } => {
+            let addr = authenticate(deps, &address, &key)?;
+            padded(deps, to_binary(&query_my_deposit(deps, &addr)?))
+        }
+        QueryMsg::GetOdds { address, key } => {
+            let addr = authenticate(deps, &address, &key)?;
+            padded(deps, to_binary(&query_odds(deps, &addr)?))
+        }
+        QueryMsg::GetMyHistory {
+            address,
+            key,
+            start_after,
+            limit,
+        } => {
+            let addr = authenticate(deps, &address, &key)?;
+            padded(
+                deps,
+                to_binary(&query_my_history(deps, &addr, start_after, limit)?),
+            )
+        }
+        QueryMsg::AmITheWinner {
+            address,
+            key,
+            pool_id,
+        } => {
+            let addr = authenticate(deps, &address, &key)?;
+            padded(
+                deps,
+                to_binary(&query_am_i_the_winner(deps, &addr, pool_id)?),
+            )
+        }
+        QueryMsg::Batch { queries } => padded(deps, to_binary(&query_batch(deps, queries)?)),
+        QueryMsg::LotteryV1(msg) => query_lottery_v1(deps, msg),
+        QueryMsg::HealthCheck {} => to_binary(&query_health(deps)?),
+        QueryMsg::SimulateMigration {} => to_binary(&query_simulate_migration(deps)),
+        QueryMsg::ExportState {
+            address,
+            key,
+            cursor,
+            limit,
+        } => {
+            let addr = authenticate_key(deps, &address, &key)?;
+            to_binary(&query_export_state(deps, &addr, cursor, limit)?)
+        }
+    }
+}
+
+fn query_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    queries: Vec<QueryMsg>,
+) -> StdResult<BatchResponse> {
+    if queries.len() > MAX_LIMIT as usize {
+        return Err(ContractError::CapExceeded {
+            what: "queries per batch".to_string(),
+            max: MAX_LIMIT.into(),
+        }
+        .into());
+    }
+    // A batch of keyed queries would try many keys in one call, each answered unpadded.
+    if queries.iter().any(is_authenticated) {
+        return Err(ContractError::AuthenticatedInBatch.into());
+    }
+    let mut results = vec![];
+    for msg in queries {
+        if let QueryMsg::Batch { .. } = msg {
+            return Err(ContractError::NestedBatch.into());
+        }
+        results.push(match batch_answer(deps, msg) {
+            Ok(answer) => BatchItem {
+                ok: Some(answer),
+                error: None,
+            },
+            Err(err) => BatchItem {
+                ok: None,
+                error: Some(err.to_string()),
+            },
+        });
+    }
+    Ok(BatchResponse { results })
+}
+
+// Answer a batched sub-query with its typed response. Keyed and nested queries are
+// turned away before this.
+fn batch_answer<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<BatchAnswer> {
+    Ok(match msg {
+        QueryMsg::GetOwner {} => BatchAnswer::GetOwner(query_owner(deps)?),
+        QueryMsg::GetRecovery {} => BatchAnswer::GetRecovery(query_recovery(deps)?),
+        QueryMsg::GetConfig {} => BatchAnswer::GetConfig(query_config(deps)?),
+        QueryMsg::GetCurrentPool { caller } => {
+            BatchAnswer::GetCurrentPool(query_pool(deps, caller)?)
+        }
+        QueryMsg::GetPool { pool_id, caller } => {
+            BatchAnswer::GetPool(query_pool_by_id(deps, pool_id, caller)?)
+        }
+        QueryMsg::GetTimeRemaining { time, height } => {
+            BatchAnswer::GetTimeRemaining(query_time_remaining(deps, time, height)?)
+        }
+        QueryMsg::GetPoolHistory { start_after, limit } => {
+            BatchAnswer::GetPoolHistory(query_pool_history(deps, start_after, limit)?)
+        }
+        QueryMsg::GetPastWinners { start_after, limit } => {
+            BatchAnswer::GetPastWinners(query_past_winners(deps, start_after, limit)?)
+        }
+        QueryMsg::GetStats {} => BatchAnswer::GetStats(query_stats(deps)?),
+        QueryMsg::GetStatistics {} => BatchAnswer::GetStatistics(query_statistics(deps)?),
+        QueryMsg::ChangesSince { start_after, limit } => {
+            BatchAnswer::ChangesSince(query_changes_since(deps, start_after, limit)?)
+        }
+        QueryMsg::GetTotalValueLocked {} => {
+            BatchAnswer::GetTotalValueLocked(query_total_value_locked(deps)?)
+        }
+        QueryMsg::GetChildren { start_after, limit } => {
+            BatchAnswer::GetChildren(query_children(deps, start_after, limit)?)
+        }
+        QueryMsg::GetChildrenTotalValueLocked { start_after, limit } => {
+            BatchAnswer::GetChildrenTotalValueLocked(query_children_tvl(deps, start_after, limit)?)
+        }
+        QueryMsg::GetLeaderboard {
+            order_by,
+            start_after,
+            limit,
+        } => BatchAnswer::GetLeaderboard(query_leaderboard(deps, order_by, start_after, limit)?),
+        QueryMsg::GetRound {} => BatchAnswer::GetRound(query_round(deps)?),
+        QueryMsg::GetUnbondingStatus {} => {
+            BatchAnswer::GetUnbondingStatus(query_unbonding_status(deps)?)
+        }
+        QueryMsg::GetSettlementProgress { pool_id } => {
+            BatchAnswer::GetSettlementProgress(query_settlement_progress(deps, pool_id)?)
+        }
+        QueryMsg::GetTicketRate { pool_id } => {
+            BatchAnswer::GetTicketRate(query_ticket_rate(deps, pool_id)?)
+        }
+        QueryMsg::GetValidatorSet {} => BatchAnswer::GetValidatorSet(query_validator_set(deps)?),
+        QueryMsg::GetFeeInfo {} => BatchAnswer::GetFeeInfo(query_fee_info(deps)?),
+        QueryMsg::GetRewardBreakdown { pool_id } => {
+            BatchAnswer::GetRewardBreakdown(query_reward_breakdown(deps, pool_id)?)
+        }
+        QueryMsg::Dashboard { time, height } => {
+            BatchAnswer::Dashboard(query_dashboard(deps, time, height)?)
+        }
+        QueryMsg::GetCurrentPrizePot {} => BatchAnswer::GetCurrentPrizePot(query_prize_pot(deps)?),
+        QueryMsg::GetDelegatorCount { pool_id } => {
+            BatchAnswer::GetDelegatorCount(query_delegator_count(deps, pool_id)?)
+        }
+        QueryMsg::GetTransitions {
+            pool_id,
+            start_after,
+            limit,
+        } => BatchAnswer::GetTransitions(query_transitions(deps, pool_id, start_after, limit)?),
+        QueryMsg::GetReferral { code } => BatchAnswer::GetReferral(query_referral(deps, code)?),
+        QueryMsg::GetDelegators {
+            pool_id,
+            start_after,
+            limit,
+        } => BatchAnswer::GetDelegators(query_delegators(deps, pool_id, start_after, limit)?),
+        QueryMsg::HealthCheck {} => BatchAnswer::HealthCheck(query_health(deps)?),
+        QueryMsg::SimulateMigration {} => {
+            BatchAnswer::SimulateMigration(query_simulate_migration(deps))
+        }
+        // The aggregator interface has its own response types.
+        _ => return Err(ContractError::NotBatchable.into()),
+    })
+}
+
+// With `generic_errors` on, every failure reads the same, whether it was a bad key,
+// a pool in the wrong status or a missing entry.
+fn obscure_error<S: Storage, A: Api, Q: Querier, T>(
+    deps: &Extern<S, A, Q>,
+    result: StdResult<T>,
+) -> StdResult<T> {
+    match result {
+        Err(_) if config_read(&deps.storage).load()?.generic_errors => {
+            Err(StdError::generic_err(GENERIC_ERROR))
+        }
+        result => result,
+    }
+}
+
+// Pad a private response with trailing spaces, which JSON ignores, so its
+// length doesn't reveal how much data it holds.
+fn padded<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    response: StdResult<Binary>,
+) -> StdResult<Binary> {
+    let block_size = config_read(&deps.storage).load()?.response_block_size as usize;
+    let mut data = response?.0;
+    pad_to_block(&mut data, block_size, b' ');
+    Ok(Binary(data))
+}
+
+// Get owner info
+fn query_owner<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<OwnerResponse> {
+    let state = config_read(&deps.storage).load()?;
+    Ok(OwnerResponse {
+        owner: deps.api.human_address(&state.owner)?,
+    })
+}
+
+fn query_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<RecoveryResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let guardian = match state.guardian.as_ref() {
+        Some(addr) => Some(deps.api.human_address(addr)?),
+        None => None,
+    };
+    let pending = match state.recovery.as_ref() {
+        Some(recovery) => Some(PendingRecovery {
+            new_owner: deps.api.human_address(&recovery.new_owner)?,
+            executable_at: recovery.executable_at,
+        }),
+        None => None,
+    };
+    Ok(RecoveryResponse { guardian, pending })
+}
+
+fn query_settlement_progress<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: Option<u64>,
+) -> StdResult<SettlementProgressResponse> {
+    let pool_id = match pool_id {
+        Some(pool_id) => pool_id,
+        None => pool_index_read(&deps.storage)
+            .load()?
+            .pending_settlement
+            .ok_or(ContractError::NoPendingSettlement)?,
+    };
+    let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    if !pool.is_closed() {
+        return Err(ContractError::PoolNotClosed.into());
+    }
+    let draw_cursor = pool.draw.as_ref().map_or(0, |draw| draw.cursor);
+    let stage = if pool.is_settled() {
+        if pool.cleanup_cursor < pool.delegator_count {
+            SettlementStage::CleaningUp
+        } else {
+            SettlementStage::Done
+        }
+    } else if pool.converting {
+        SettlementStage::ConvertingRewards
+    } else {
+        match pool.draw.as_ref() {
+            Some(draw) if draw.is_done(&pool) => SettlementStage::ReadyToSettle,
+            Some(_) => SettlementStage::PreparingDraw,
+            None => SettlementStage::Pending,
+        }
+    };
+    Ok(SettlementProgressResponse {
+        pool_id: pool.id,
+        stage,
+        unbonds_at: pool.unbonds_at,
+        harvested_rewards: pool.harvested_rewards,
+        unconverted_rewards: pool.unconverted_rewards,
+        draw_cursor,
+        cleanup_cursor: pool.cleanup_cursor,
+        delegator_count: pool.delegator_count,
+    })
+}
+
+// Get the stake still unbonding for CLOSED pools
+fn query_unbonding_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<UnbondingStatusResponse> {
+    let index = pool_index_read(&deps.storage).load()?;
+    let mut pools = vec![];
+    if let Some(pool_id) = index.pending_settlement {
+        let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+        if let Some(matures_at) = pool.unbonds_at {
+            pools.push(UnbondingInfo {
+                pool_id,
+                amount: pool.unbonding_amount,
+                matures_at,
+                undelegations: pool.delegations,
+            });
+        }
+    }
+    Ok(UnbondingStatusResponse { pools })
+}
+
+// Get the redemption rate and schedule of ticket tokens in the live pools, or in `pool_id`
+fn query_ticket_rate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: Option<u64>,
+) -> StdResult<TicketRateResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let pool_ids = match pool_id {
+        Some(pool_id) => vec![pool_id],
+        None => {
+            let index = pool_index_read(&deps.storage).load()?;
+            [
+                index.pending_settlement,
+                index.current_locked,
+                index.current_open,
+            ]
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+        }
+    };
+    let mut pools = vec![];
+    for pool_id in pool_ids {
+        let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+        let ticketed = principal_in_tickets(&state, &pool);
+        let rate = if !ticketed {
+            Decimal::zero()
+        } else if pool.total_deposits.is_zero() {
+            Decimal::one()
+        } else {
+            let returned = pool.principal_for(pool.total_deposits)?;
+            Decimal::from_ratio(returned, pool.total_deposits)
+        };
+        pools.push(PoolTicketRate {
+            pool_id,
+            rate,
+            redeemable: ticketed && (pool.is_open() || pool.is_settled()),
+            closes_at: pool.closes_at,
+            unbonds_at: pool.unbonds_at,
+        });
+    }
+    Ok(TicketRateResponse {
+        ticket: state.ticket,
+        unbonding_period: UNBONDING_PERIOD,
+        pools,
+    })
+}
+
+// Get the validators deposits are staked with
+fn query_validator_set<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ValidatorSetResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let index = pool_index_read(&deps.storage).load()?;
+    let delegations = match index.current_locked {
+        Some(pool_id) => {
+            pool_read(&deps.storage)
+                .load(&pool_key(pool_id))?
+                .delegations
+        }
+        None => vec![],
+    };
+    let mut validators = vec![];
+    for validator in state.validators {
+        let mut delegated = Uint128::zero();
+        for d in delegations
+            .iter()
+            .filter(|d| d.validator == validator.address)
+        {
+            delegated = math::add(delegated, d.amount)?;
+        }
+        validators.push(ValidatorInfo {
+            address: validator.address,
+            weight: validator.weight,
+            delegated: round_to_step(delegated, state.public_rounding),
+        });
+    }
+    Ok(ValidatorSetResponse { validators })
+}
+
+fn query_fee_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<FeeInfoResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let recipient = |addr: &Option<CanonicalAddr>| {
+        deps.api
+            .human_address(addr.as_ref().unwrap_or(&state.owner))
+    };
+    let pending = match &state.fees.pending {
+        Some(change) => Some(PendingFee {
+            rate: change.rate,
+            recipient: recipient(&change.recipient)?,
+            effective_from_pool: state.pool_count + 1,
+        }),
+        None => None,
+    };
+    Ok(FeeInfoResponse {
+        rate: state.fees.rate,
+        max_rate: max_fee_rate(),
+        recipient: recipient(&state.fees.recipient)?,
+        accrued: state.fees.accrued,
+        pending,
+    })
+}
+
+fn query_reward_breakdown<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: Option<u64>,
+) -> StdResult<RewardBreakdownResponse> {
+    let pools = pool_read(&deps.storage);
+    let pool = match pool_id {
+        Some(id) => pools.load(&pool_key(id))?,
+        None => {
+            let pool_id = pool_index_read(&deps.storage)
+                .load()?
+                .last_settled
+                .ok_or(ContractError::NoSettledPool)?;
+            pools.load(&pool_key(pool_id))?
+        }
+    };
+    if !pool.is_settled() {
+        return Err(ContractError::PoolNotSettled.into());
+    }
+    let (winner, carryover) = match pool.winner {
+        Some(_) => (pool.prize_amount, Uint128::zero()),
+        None => (Uint128::zero(), pool.prize_amount),
+    };
+    // The seed, sponsorship and carryover in the prize didn't come from the rewards.
+    // Pools drawn before they were paid out hold a prize made of rewards alone.
+    let from_rewards =
+        math::sub(pool.prize_amount, pool.prize_funding()?).unwrap_or(pool.prize_amount);
+    let distributed = math::add(pool.fee_amount, from_rewards)?;
+    Ok(RewardBreakdownResponse {
+        pool_id: pool.id,
+        rewards_realized: pool.rewards_realized,
+        fee_rate: pool.fee_rate,
+        fees: pool.fee_amount,
+        winner,
+        carryover,
+        reserve: math::sub(pool.rewards_realized, distributed)?,
+        slashed: pool.slashed_amount,
+        slash_covered: pool.slash_covered,
+        charity: Uint128::zero(),
+    })
+}
+
+fn invariant(name: String, expected: Uint128, actual: Uint128, passed: bool) -> InvariantCheck {
+    InvariantCheck {
+        name,
+        expected,
+        actual,
+        passed,
+    }
+}
+
+// How much of the closed pool's stake came back. Undelegated stake leaves the
+// delegation list at once, so only the balance shows whether it arrived: whatever is
+// there on top of the funds held for everything else. A shortfall is only taken as a
+// slash up to `max_unbonding_slash` and once `UNBONDING_GRACE` has passed; until then
+// it may still be on its way, and settling on it would cut everyone's principal.
+fn unbonded_principal<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    state: &State,
+    index: &PoolIndex,
+    pool: &Pool,
+) -> Result<Uint128, ContractError> {
+    let locked = match index.current_locked {
+        Some(id) => pool_read(&deps.storage)
+            .load(&pool_key(id))?
+            .staked_amount()?,
+        None => Uint128::zero(),
+    };
+    let mut staked = Uint128::zero();
+    for d in deps
+        .querier
+        .query_all_delegations(env.contract.address.clone())?
+    {
+        if d.amount.denom == DENOM {
+            staked = math::add(staked, d.amount.amount)?;
+        }
+    }
+    if staked > locked {
+        return Err(ContractError::StillUnbonding);
+    }
+    // Rewards were paid out when the pool closed, so they must be here already.
+    let owed = liquid_liabilities(&deps.storage, state, index)?;
+    let owed = math::add(owed, pool.harvested_rewards)?;
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address.clone(), DENOM)?
+        .amount;
+    let returned = math::sub(balance, owed).map_err(|_| ContractError::UnbondedFundsMissing)?;
+    let returned = returned.min(pool.unbonding_amount);
+    let shortfall = math::sub(pool.unbonding_amount, returned)?;
+    if shortfall > payout::portion(pool.unbonding_amount, max_unbonding_slash())? {
+        return Err(ContractError::UnbondedFundsMissing);
+    }
+    let final_at = pool.unbonds_at.unwrap_or(0).saturating_add(UNBONDING_GRACE);
+    if !shortfall.is_zero() && env.block.time < final_at {
+        return Err(ContractError::UnbondedFundsMissing);
+    }
+    Ok(returned)
+}
+
+// Native funds the contract must hold itself: accrued fees, the unstaked deposits and
+// prize funding of the live pools, and whatever settled pools still owe their
+// depositors, winners and referrers. Staked and unbonding principal are not included.
+fn liquid_liabilities<S: Storage>(
+    storage: &S,
+    state: &State,
+    index: &PoolIndex,
+) -> StdResult<Uint128> {
+    let live = [
+        index.current_open,
+        index.current_locked,
+        index.pending_settlement,
+    ];
+    let mut liquid = math::add(state.fees.accrued, state.settled_principal)?;
+    liquid = math::add(liquid, state.settled_prizes)?;
+    liquid = math::add(liquid, state.carryover)?;
+    liquid = math::add(liquid, state.referral_rewards)?;
+    for pool_id in live.iter().flatten() {
+        let pool = pool_read(storage).load(&pool_key(*pool_id))?;
+        if pool.denom() != DENOM {
+            continue;
+        }
+        liquid = math::add(liquid, pool.seed_amount)?;
+        liquid = math::add(liquid, pool.sponsor_amount)?;
+        liquid = math::add(liquid, pool.carryover_amount)?;
+        // OPEN pools, and pools that stake nothing once locked, keep their deposits here.
+        let unstaked = math::sub(pool.total_deposits, pool.staked_amount()?)?;
+        liquid = math::add(liquid, unstaked)?;
+    }
+    Ok(liquid)
+}
+
+// Report what `migrate` would do to the current storage, without writing anything.
+fn query_simulate_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> MigrationPreviewResponse {
+    match plan_migration(&deps.storage) {
+        Ok(plan) => MigrationPreviewResponse {
+            layout: if plan.state.is_some() {
+                "legacy"
+            } else {
+                "current"
+            }
+            .to_string(),
+            changes: plan.changes,
+            unconvertible: plan.unconvertible,
+            error: None,
+        },
+        Err(err) => MigrationPreviewResponse {
+            layout: "unknown".to_string(),
+            changes: vec![],
+            unconvertible: vec![],
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+// Check that the staking module holds what LOCKED pools delegated, and that the
+// contract balance covers the funds it should be holding itself. CLOSED principal may
+// still be unbonding, so it is not required to be liquid. Only the live pools' totals
+// are read, so the cost doesn't grow with the number of depositors.
+fn query_health<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<HealthResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let contract = state
+        .contract_address
+        .clone()
+        .ok_or(ContractError::NotMigrated)?;
+    let index = pool_index_read(&deps.storage).load()?;
+    let live = [
+        index.current_open,
+        index.current_locked,
+        index.pending_settlement,
+    ];
+    let mut checks = vec![];
+    let liquid = liquid_liabilities(&deps.storage, &state, &index)?;
+    let mut delegated = Uint128::zero();
+    for pool_id in live.iter().flatten() {
+        let pool = pool_read(&deps.storage).load(&pool_key(*pool_id))?;
+        if pool.status == PoolStatus::LOCKED {
+            delegated = math::add(delegated, pool.staked_amount()?)?;
+        }
+    }
+    let mut staked = Uint128::zero();
+    for d in deps.querier.query_all_delegations(contract.clone())? {
+        if d.amount.denom == DENOM {
+            staked = math::add(staked, d.amount.amount)?;
+        }
+    }
+    checks.push(invariant(
+        "delegations".to_string(),
+        delegated,
+        staked,
+        staked == delegated,
+    ));
+    let balance = deps.querier.query_balance(contract, DENOM)?.amount;
+    checks.push(invariant(
+        "liquid_balance".to_string(),
+        liquid,
+        balance,
+        balance >= liquid,
+    ));
+    // Checks pass or fail on exact amounts; the amounts shown are rounded like other totals.
+    for check in checks.iter_mut() {
+        check.expected = round_to_step(check.expected, state.public_rounding);
+        check.actual = round_to_step(check.actual, state.public_rounding);
+    }
+    Ok(HealthResponse {
+        healthy: checks.iter().all(|c| c.passed),
+        checks,
+    })
+}
+
+// Get the principal held across all pools, and the prizes settled pools still owe
+fn query_total_value_locked<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<TotalValueLockedResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let step = state.public_rounding;
+    let index = pool_index_read(&deps.storage).load()?;
+    let pools_bucket = pool_read(&deps.storage);
+    let principal = |pool_id: Option<u64>| -> StdResult<Uint128> {
+        match pool_id {
+            Some(id) => Ok(public_pool(&state, pools_bucket.load(&pool_key(id))?).total_deposits),
+            None => Ok(Uint128::zero()),
+        }
+    };
+    let open = principal(index.current_open)?;
+    let locked = principal(index.current_locked)?;
+    let closed = principal(index.pending_settlement)?;
+    let settled = math::add(state.settled_principal, state.settled_prizes)?;
+    let settled = math::add(settled, state.carryover)?;
+    let total = math::add(math::add(open, locked)?, math::add(closed, settled)?)?;
+    Ok(TotalValueLockedResponse {
+        open: round_to_step(open, step),
+        locked: round_to_step(locked, step),
+        closed: round_to_step(closed, step),
+        settled: round_to_step(settled, step),
+        total: round_to_step(total, step),
+    })
+}
+
+// Get the composition of the current pool's prize
+fn query_lottery_v1<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: LotteryQueryV1,
+) -> StdResult<Binary> {
+    match msg {
+        LotteryQueryV1::Interface {} => to_binary(&InterfaceResponse {
+            name: INTERFACE_NAME.to_string(),
+            versions: INTERFACE_VERSIONS.to_vec(),
+        }),
+        LotteryQueryV1::CurrentRound {} => {
+            let state = config_read(&deps.storage).load()?;
+            let pool = current_pool(&deps.storage)?.map(|p| public_pool(&state, p));
+            to_binary(&RoundResponseV1 {
+                pool_id: pool.as_ref().map(|p| p.id),
+                phase: pool.as_ref().map(round_phase_v1),
+                denom: pool.as_ref().map_or(DENOM, |p| p.denom()).to_string(),
+                accepts_deposits: pool.as_ref().map_or(false, |p| p.is_open()),
+                total_deposits: pool.as_ref().map_or(Uint128::zero(), |p| {
+                    round_to_step(p.total_deposits, state.public_rounding)
+                }),
+            })
+        }
+        LotteryQueryV1::Prize { pool_id: None } => {
+            let pot = query_prize_pot(deps)?;
+            let denom =
+                current_pool(&deps.storage)?.map_or(DENOM.to_string(), |p| p.denom().to_string());
+            to_binary(&PrizeResponseV1 {
+                pool_id: pot.pool_id,
+                amount: pot.total,
+                denom,
+                drawn: false,
+                claimed: false,
+            })
+        }
+        LotteryQueryV1::Prize {
+            pool_id: Some(pool_id),
+        } => {
+            let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+            to_binary(&PrizeResponseV1 {
+                pool_id: Some(pool.id),
+                amount: pool.prize_amount,
+                denom: pool.denom().to_string(),
+                drawn: pool.is_settled(),
+                claimed: pool.prize_claimed,
+            })
+        }
+        LotteryQueryV1::Deposit { address, key } => {
+            let addr = authenticate(deps, &address, &key)?;
+            let deposit = query_my_deposit(deps, &addr)?;
+            padded(
+                deps,
+                to_binary(&DepositResponseV1 {
+                    pool_id: deposit.pool_id,
+                    amount: deposit.balance,
+                }),
+            )
+        }
+    }
+}
+
+// Decoupled from `PoolStatus`, which may grow phases v1 can't name.
+fn round_phase_v1(pool: &Pool) -> RoundPhaseV1 {
+    if pool.is_settled() {
+        RoundPhaseV1::Drawn
+    } else {
+        match pool.status {
+            PoolStatus::OPEN => RoundPhaseV1::Open,
+            PoolStatus::LOCKED => RoundPhaseV1::Locked,
+            PoolStatus::CLOSED => RoundPhaseV1::Closed,
+        }
+    }
+}
+
+fn query_children<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ChildrenResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let page = read_page(
+        &children_read(&deps.storage),
+        state.child_count,
+        start_after,
+        limit,
+    )?;
+    let children = page
+        .into_iter()
+        .map(|(index, child)| ChildInfo {
+            index,
+            label: child.label,
+            address: child.address,
+            created_at: child.created_at,
+        })
+        .collect();
+    Ok(ChildrenResponse { children })
+}
+
+// Children that have not registered yet count as empty.
+fn query_children_tvl<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TotalValueLockedResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let factory = state.factory.ok_or(ContractError::FactoryOff)?;
+    let page = read_page(
+        &children_read(&deps.storage),
+        state.child_count,
+        start_after,
+        limit,
+    )?;
+    let mut total = TotalValueLockedResponse {
+        open: Uint128::zero(),
+        locked: Uint128::zero(),
+        closed: Uint128::zero(),
+        settled: Uint128::zero(),
+        total: Uint128::zero(),
+    };
+    for (_, child) in page {
+        let code_hash = child.callback_code_hash(&factory);
+        let address = match child.address {
+            Some(address) => address,
+            None => continue,
+        };
+        let tvl: TotalValueLockedResponse =
+            deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: address,
+                callback_code_hash: code_hash,
+                msg: to_binary(&QueryMsg::GetTotalValueLocked {})?,
+            }))?;
+        total.open = math::add(total.open, tvl.open)?;
+        total.locked = math::add(total.locked, tvl.locked)?;
+        total.closed = math::add(total.closed, tvl.closed)?;
+        total.settled = math::add(total.settled, tvl.settled)?;
+        total.total = math::add(total.total, tvl.total)?;
+    }
+    Ok(total)
+}
+
+fn query_prize_pot<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PrizePotResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let step = state.public_rounding;
+    let pool = current_pool(&deps.storage)?.map(|p| public_pool(&state, p));
+    let field = |f: fn(&Pool) -> Uint128| pool.as_ref().map_or(Uint128::zero(), f);
+    let seed = field(|p| p.seed_amount);
+    let sponsored = field(|p| p.sponsor_amount);
+    let carryover = field(|p| p.carryover_amount);
+    let rewards = match pool.as_ref() {
+        Some(p) if p.is_settled() => p.rewards_realized,
+        Some(p) => math::add(p.harvested_rewards, accrued_rewards(deps, &state, p)?)?,
+        None => Uint128::zero(),
+    };
+    let total = math::add(math::add(seed, sponsored)?, math::add(carryover, rewards)?)?;
+    Ok(PrizePotResponse {
+        pool_id: pool.as_ref().map(|p| p.id),
+        seed: round_to_step(seed, step),
+        sponsored: round_to_step(sponsored, step),
+        carryover: round_to_step(carryover, step),
+        rewards: round_to_step(rewards, step),
+        total: round_to_step(total, step),
+    })
+}
+
+// Staking rewards a LOCKED pool's delegations have accrued but not paid out yet.
+fn accrued_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    state: &State,
+    pool: &Pool,
+) -> StdResult<Uint128> {
+    let contract = match state.contract_address.as_ref() {
+        Some(contract) if pool.status == PoolStatus::LOCKED => contract,
+        _ => return Ok(Uint128::zero()),
+    };
+    let mut accrued = Uint128::zero();
+    for d in pool.delegations.iter() {
+        let delegation = deps
+            .querier
+            .query_delegation(contract.clone(), d.validator.clone())?;
+        if let Some(delegation) = delegation {
+            for reward in delegation.accumulated_rewards.iter() {
+                if reward.denom == DENOM {
+                    accrued = math::add(accrued, reward.amount)?;
+                }
+            }
+        }
+    }
+    Ok(accrued)
+}
+
+// Get the number of participants in a pool without listing them
+fn query_delegator_count<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: Option<u64>,
+) -> StdResult<DelegatorCountResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let pool = match pool_id {
+        Some(id) => Some(pool_read(&deps.storage).load(&pool_key(id))?),
+        None => current_pool(&deps.storage)?,
+    }
+    .map(|p| public_pool(&state, p));
+    Ok(DelegatorCountResponse {
+        pool_id: pool.as_ref().map(|p| p.id),
+        delegator_count: pool.as_ref().map_or(0, |p| p.delegator_count),
+        unique_depositors: pool.as_ref().map_or(0, |p| p.unique_depositors),
+    })
+}
+
+// Get the effective configuration
+fn query_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ConfigResponse> {
+    let state = config_read(&deps.storage).load()?;
+    Ok(ConfigResponse {
+        owner: deps.api.human_address(&state.owner)?,
+        denom: DENOM.to_string(),
+        timing: state.timing,
+        default_limit: DEFAULT_LIMIT,
+        max_limit: MAX_LIMIT,
+        response_block_size: state.response_block_size,
+        public_rounding: state.public_rounding,
+        delay_public_totals: state.delay_public_totals,
+        transparent: state.transparent,
+        generic_errors: state.generic_errors,
+        tokens: state.tokens,
+        ticket: state.ticket,
+        nft_contract: state.nft_contract,
+        emission: state.emission,
+        hooks: state.hooks,
+        swap_router: state.swap_router,
+        oracle: state.oracle,
+        wrapped_native: state.wrapped_native,
+        factory: state.factory,
+        parent: state.parent,
+        ibc_gateway: state.ibc_gateway,
+        treasury: state.treasury,
+        cover_slashes: state.cover_slashes,
+        deposit_limit: state.deposit_limit,
+        max_depositors: state.max_depositors,
+        max_unclaimed_prizes: state.max_unclaimed_prizes,
+    })
+}
+
+// Get lifetime statistics
+fn query_stats<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<StatsResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let stats = public_stats(&state);
+    Ok(StatsResponse {
+        pools_run: stats.pools_run,
+        total_deposited: round_to_step(stats.total_deposited, state.public_rounding),
+        total_prizes_paid: stats.total_prizes_paid,
+        unique_players: stats.unique_players,
+    })
+}
+
+// Get lifetime protocol metrics
+fn query_statistics<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<StatisticsResponse> {
+    let stats = public_stats(&config_read(&deps.storage).load()?);
+    let average_prize = match stats.prizes_awarded {
+        0 => Uint128::zero(),
+        n => Uint128(stats.total_prizes_paid.u128() / u128::from(n)),
+    };
+    Ok(StatisticsResponse {
+        pools_run: stats.pools_run,
+        prizes_awarded: stats.prizes_awarded,
+        total_prizes_paid: stats.total_prizes_paid,
+        average_prize,
+        total_rewards: stats.total_rewards,
+        unique_players: stats.unique_players,
+    })
+}
+
+// Get the events recorded after a sequence number
+fn query_changes_since<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ChangesResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let events = events_read(&deps.storage);
+    let changes = read_page(&events, state.event_count, start_after, limit)?
+        .into_iter()
+        .map(|(seq, event)| Change {
+            seq,
+            kind: event.kind,
+            pool_id: event.pool_id,
+            time: event.time,
+        })
+        .collect();
+    Ok(ChangesResponse {
+        changes,
+        event_count: state.event_count,
+    })
+}
+
+// Resolve the address of a private query, rejecting a missing or wrong viewing key.
+// Transparent deployments have nothing private, so the key isn't checked.
+fn authenticate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    key: &str,
+) -> StdResult<CanonicalAddr> {
+    if config_read(&deps.storage).load()?.transparent {
+        return deps.api.canonical_address(address);
+    }
+    authenticate_key(deps, address, key)
+}
+
+// Like `authenticate`, but always checks the key. For queries that act on the
+// caller's authority rather than reading their own data.
+fn authenticate_key<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    key: &str,
+) -> StdResult<CanonicalAddr> {
+    let addr = deps.api.canonical_address(address)?;
+    if !check_viewing_key(&deps.storage, &addr, key) {
+        return Err(StdError::unauthorized());
+    }
+    Ok(addr)
+}
+
+// The `start_after` of the next page of a list of `len`, if the list goes on.
+fn export_next_start(start_after: Option<u64>, limit: Option<u32>, len: u64) -> Option<u64> {
+    let (_, end) = page_range(start_after, limit, len);
+    if end < len {
+        Some(end - 1)
+    } else {
+        None
+    }
+}
+
+// Walk every namespace a page at a time, in the order described at `ExportCursor`.
+fn query_export_state<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    sender: &CanonicalAddr,
+    cursor: Option<ExportCursor>,
+    limit: Option<u32>,
+) -> StdResult<ExportResponse> {
+    let state = config_read(&deps.storage).load()?;
+    assert_sender_is_admin(sender, &state.owner)?;
+    // A page never comes back empty-handed, or the walk would stall.
+    let limit = Some(limit.unwrap_or(DEFAULT_LIMIT).max(1));
+    let first_pool = |pool_id: u64| {
+        if pool_id <= state.pool_count {
+            ExportCursor::Entries {
+                pool_id,
+                start_after: None,
+            }
+        } else {
+            ExportCursor::Players { start_after: None }
+        }
+    };
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => {
+            return Ok(ExportResponse {
+                config: Some(state.clone()),
+                pool_index: Some(pool_index_read(&deps.storage).load()?),
+                next: Some(first_pool(1)),
+                ..ExportResponse::default()
+            })
+        }
+    };
+    let mut page = ExportResponse::default();
+    let next = match cursor {
+        ExportCursor::Entries {
+            pool_id,
+            start_after,
+        } => {
+            let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+            if start_after.is_none() {
+                let transitions = read_transitions(&deps.storage, &pool, None, Some(MAX_LIMIT))?;
+                page.transitions = transitions.into_iter().map(|(_, t)| t).collect();
+            }
+            for (_, delegator) in read_delegators(&deps.storage, &pool, start_after, limit)? {
+                // Entries emptied by a cleanup are gone for good.
+                if let Some(entry) = may_load_entry(&deps.storage, pool.id, &delegator)? {
+                    page.entries.push(ExportEntry {
+                        address: deps.api.human_address(&delegator)?,
+                        entry,
+                        memo: may_load_memo(&deps.storage, pool.id, &delegator)?,
+                        balances: load_entry_balances(&deps.storage, pool.id, &delegator)?,
+                        emission_claimed: has_claimed_emission(&deps.storage, pool.id, &delegator),
+                    });
+                }
+            }
+            let next = match export_next_start(start_after, limit, pool.delegator_count) {
+                Some(start_after) => ExportCursor::Entries {
+                    pool_id,
+                    start_after: Some(start_after),
+                },
+                None => ExportCursor::Referred {
+                    pool_id,
+                    start_after: None,
+                },
+            };
+            page.pool = Some(pool);
+            Some(next)
+        }
+        ExportCursor::Referred {
+            pool_id,
+            start_after,
+        } => {
+            let namespace = pool_namespace(pool_id);
+            let list: [&[u8]; 2] = [&namespace, REFERRED_LIST_NAMESPACE];
+            let (codes, len) = read_keys(&deps.storage, &list, start_after, limit)?;
+            for (_, code_hash) in codes {
+                page.referred.push(ExportReferred {
+                    amount: load_referred(&deps.storage, pool_id, &code_hash)?,
+                    claimed: has_claimed_referral(&deps.storage, pool_id, &code_hash),
+                    code_hash: Binary::from(code_hash),
+                });
+            }
+            Some(match export_next_start(start_after, limit, len) {
+                Some(start_after) => ExportCursor::Referred {
+                    pool_id,
+                    start_after: Some(start_after),
+                },
+                None => first_pool(pool_id + 1),
+            })
+        }
+        ExportCursor::Players { start_after } => {
+            let (players, len) = read_keys(&deps.storage, &[PLAYER_LIST_KEY], start_after, limit)?;
+            for (_, player) in players {
+                let player = CanonicalAddr::from(player);
+                page.players.push(ExportPlayer {
+                    address: deps.api.human_address(&player)?,
+                    player: may_load_player(&deps.storage, &player)?.unwrap_or_default(),
+                    idempotency_keys: load_seen_keys(&deps.storage, &player)?,
+                    winner_callback: winner_callbacks_read(&deps.storage)
+                        .may_load(player.as_slice())?,
+                });
+            }
+            Some(match export_next_start(start_after, limit, len) {
+                Some(start_after) => ExportCursor::Players {
+                    start_after: Some(start_after),
+                },
+                None if len > 0 => ExportCursor::History {
+                    player: 0,
+                    start_after: None,
+                },
+                None => ExportCursor::Referrals { start_after: None },
+            })
+        }
+        ExportCursor::History {
+            player,
+            start_after,
+        } => {
+            let (players, len) = read_keys(
+                &deps.storage,
+                &[PLAYER_LIST_KEY],
+                player.checked_sub(1),
+                Some(1),
+            )?;
+            let address = match players.into_iter().next() {
+                Some((_, address)) => CanonicalAddr::from(address),
+                None => return Err(StdError::not_found("Player")),
+            };
+            let record = may_load_player(&deps.storage, &address)?.unwrap_or_default();
+            let list = player_history_read(&deps.storage, &address);
+            let entries = read_page(&list, record.history_count, start_after, limit)?;
+            page.history = Some(ExportHistory {
+                address: deps.api.human_address(&address)?,
+                start: start_after.map_or(0, |index| index + 1),
+                entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+            });
+            Some(
+                match export_next_start(start_after, limit, record.history_count) {
+                    Some(start_after) => ExportCursor::History {
+                        player,
+                        start_after: Some(start_after),
+                    },
+                    None if player + 1 < len => ExportCursor::History {
+                        player: player + 1,
+                        start_after: None,
+                    },
+                    None => ExportCursor::Referrals { start_after: None },
+                },
+            )
+        }
+        ExportCursor::Referrals { start_after } => {
+            let (codes, len) = read_keys(&deps.storage, &[REFERRAL_LIST_KEY], start_after, limit)?;
+            for (_, code_hash) in codes {
+                let referral = may_load_referral(&deps.storage, &code_hash)?
+                    .ok_or_else(|| StdError::not_found("Referral"))?;
+                page.referrals.push(ExportReferral {
+                    code_hash: Binary::from(code_hash),
+                    referrer: deps.api.human_address(&referral.referrer)?,
+                    deposits: referral.deposits,
+                    referred_amount: referral.referred_amount,
+                });
+            }
+            Some(match export_next_start(start_after, limit, len) {
+                Some(start_after) => ExportCursor::Referrals {
+                    start_after: Some(start_after),
+                },
+                None => ExportCursor::Children { start_after: None },
+            })
+        }
+        ExportCursor::Children { start_after } => {
+            let list = children_read(&deps.storage);
+            let children = read_page(&list, state.child_count, start_after, limit)?;
+            page.children = children.into_iter().map(|(_, child)| child).collect();
+            Some(
+                match export_next_start(start_after, limit, state.child_count) {
+                    Some(start_after) => ExportCursor::Children {
+                        start_after: Some(start_after),
+                    },
+                    None => ExportCursor::Winners { start_after: None },
+                },
+            )
+        }
+        ExportCursor::Winners { start_after } => {
+            let winners = read_winners(&deps.storage, &state, start_after, limit)?;
+            page.winners = winners.into_iter().map(|(_, pool_id)| pool_id).collect();
+            Some(
+                match export_next_start(start_after, limit, state.announced_winners) {
+                    Some(start_after) => ExportCursor::Winners {
+                        start_after: Some(start_after),
+                    },
+                    None => ExportCursor::Events { start_after: None },
+                },
+            )
+        }
+        ExportCursor::Events { start_after } => {
+            let list = events_read(&deps.storage);
+            let events = read_page(&list, state.event_count, start_after, limit)?;
+            page.events = events.into_iter().map(|(_, event)| event).collect();
+            export_next_start(start_after, limit, state.event_count).map(|start_after| {
+                ExportCursor::Events {
+                    start_after: Some(start_after),
+                }
+            })
+        }
+    };
+    Ok(ExportResponse { next, ..page })
+}
+
+// Get the caller's position in the current pool
+fn query_my_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    addr: &CanonicalAddr,
+) -> StdResult<MyDepositResponse> {
+    let pool = match current_pool(&deps.storage)? {
+        Some(pool) => pool,
+        None => {
+            return Ok(MyDepositResponse {
+                pool_id: None,
+                balance: Uint128::zero(),
+                weight: Uint128::zero(),
+                odds: Decimal::zero(),
+                memo: None,
+                other_balances: vec![],
+            })
+        }
+    };
+    // After a slash the balance is what the entry gets back.
+    let (balance, weight) = match may_load_entry(&deps.storage, pool.id, addr)? {
+        Some(entry) => (pool.principal_share(&entry)?, entry.weight),
+        None => (Uint128::zero(), Uint128::zero()),
+    };
+    let state = config_read(&deps.storage).load()?;
+    let total_weight = public_total_weight(&state, &pool);
+    Ok(MyDepositResponse {
+        pool_id: Some(pool.id),
+        balance,
+        weight,
+        odds: win_probability(weight, total_weight),
+        memo: may_load_memo(&deps.storage, pool.id, addr)?,
+        other_balances: load_entry_balances(&deps.storage, pool.id, addr)?,
+    })
+}
+
+// Get a page of the caller's activity across all pools
+fn query_my_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    addr: &CanonicalAddr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<HistoryResponse> {
+    let player = may_load_player(&deps.storage, addr)?.unwrap_or_default();
+    let history = read_history(&deps.storage, addr, &player, start_after, limit)?
+        .into_iter()
+        .map(|(index, entry)| HistoryItem {
+            index,
+            action: entry.action,
+            pool_id: entry.pool_id,
+            amount: entry.amount,
+            time: entry.time,
+        })
+        .collect();
+    Ok(HistoryResponse { history })
+}
+
+fn query_am_i_the_winner<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    addr: &CanonicalAddr,
+    pool_id: u64,
+) -> StdResult<AmITheWinnerResponse> {
+    let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    let won = pool.winner.as_ref() == Some(addr);
+    Ok(AmITheWinnerResponse {
+        pool_id,
+        settled: pool.is_settled(),
+        won,
+        prize_amount: if won {
+            pool.prize_amount
+        } else {
+            Uint128::zero()
+        },
+    })
+}
+
+// Get the caller's chance of winning the current pool
+fn query_odds<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    addr: &CanonicalAddr,
+) -> StdResult<OddsResponse> {
+    let pool = match current_pool(&deps.storage)? {
+        Some(pool) => pool,
+        None => {
+            return Ok(OddsResponse {
+                pool_id: None,
+                weight: Uint128::zero(),
+                total_weight: Uint128::zero(),
+                odds: Decimal::zero(),
+                one_in: None,
+            })
+        }
+    };
+    let weight =
+        may_load_entry(&deps.storage, pool.id, addr)?.map_or(Uint128::zero(), |e| e.weight);
+    // Odds against the public total, so they can't be used to read the live one.
+    let state = config_read(&deps.storage).load()?;
+    let total_weight = public_total_weight(&state, &pool);
+    let one_in = if weight.is_zero() {
+        None
+    } else {
+        // Rounded to the nearest whole number, and never better than certain.
+        let rounded = math::add(total_weight.max(weight), Uint128(weight.u128() / 2))?;
+        Some(math::mul_ratio(rounded, 1, weight.u128())?)
+    };
+    Ok(OddsResponse {
+        pool_id: Some(pool.id),
+        weight,
+        total_weight,
+        odds: win_probability(weight, total_weight),
+        one_in,
+    })
+}
+
+// The pool's total weight as public queries show it: published in delayed mode, and
+// rounded like every public total.
+fn public_total_weight(state: &State, pool: &Pool) -> Uint128 {
+    let total_weight = public_pool(state, pool.clone()).total_weight;
+    round_to_step(total_weight, state.public_rounding)
+}
+
+// The public total may lag behind the caller's own deposits, so the odds are capped.
+fn win_probability(weight: Uint128, total_weight: Uint128) -> Decimal {
+    if weight.is_zero() {
+        Decimal::zero()
+    } else if total_weight <= weight {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(weight, total_weight)
+    }
+}
+
+// Get a page of a pool's status transition log
+fn query_transitions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TransitionsResponse> {
+    let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    let transitions = read_transitions(&deps.storage, &pool, start_after, limit)?
+        .into_iter()
+        .map(|(index, t)| {
+            Ok(TransitionInfo {
+                index,
+                from: t.from,
+                to: t.to,
+                time: t.time,
+                sender: deps.api.human_address(&t.sender)?,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(TransitionsResponse { transitions })
+}
+
+// Get the credit of a referral code
+fn query_referral<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    code: String,
+) -> StdResult<ReferralResponse> {
+    let code_hash = referral_code_hash(&code);
+    let referral = may_load_referral(&deps.storage, &code_hash)?
+        .ok_or_else(|| StdError::not_found("Referral"))?;
+    let step = config_read(&deps.storage).load()?.public_rounding;
+    // An exact count would show each referred deposit as it lands.
+    let deposits = if step == Uint128(1) {
+        referral.deposits
+    } else {
+        referral.deposits - referral.deposits % REFERRAL_COUNT_STEP
+    };
+    Ok(ReferralResponse {
+        code_hash: Binary::from(code_hash),
+        deposits,
+        referred_amount: round_to_step(referral.referred_amount, step),
+    })
+}
+
+// Get a page of a pool's delegators and their balances
+fn query_delegators<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: Option<u64>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<DelegatorsResponse> {
+    if !config_read(&deps.storage).load()?.transparent {
+        return Err(ContractError::DelegatorsPrivate.into());
+    }
+    let pool = match pool_id {
+        Some(id) => Some(pool_read(&deps.storage).load(&pool_key(id))?),
+        None => current_pool(&deps.storage)?,
+    };
+    let pool = match pool {
+        Some(pool) => pool,
+        None => {
+            return Ok(DelegatorsResponse {
+                pool_id: None,
+                delegators: vec![],
+            })
+        }
+    };
+    let mut delegators = vec![];
+    for (index, addr) in read_delegators(&deps.storage, &pool, start_after, limit)? {
+        if let Some(entry) = may_load_entry(&deps.storage, pool.id, &addr)? {
+            delegators.push(DelegatorInfo {
+                index,
+                address: deps.api.human_address(&addr)?,
+                amount: entry.amount,
+                weight: entry.weight,
+            });
+        }
+    }
+    Ok(DelegatorsResponse {
+        pool_id: Some(pool.id),
+        delegators,
+    })
+}
+
+// Get Pool Info
+fn query_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    caller: Option<HumanAddr>,
+) -> StdResult<PoolResponse> {
+    let pool = current_pool(&deps.storage)?;
+    pool_response(deps, pool, caller)
+}
+
+// Get Pool Info of any past or current pool
+fn query_pool_by_id<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_id: u64,
+    caller: Option<HumanAddr>,
+) -> StdResult<PoolResponse> {
+    let pool = pool_read(&deps.storage).load(&pool_key(pool_id))?;
+    pool_response(deps, Some(pool), caller)
+}
+
+// Get the countdowns to the current pool's deadlines
+fn query_time_remaining<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    time: u64,
+    height: u64,
+) -> StdResult<TimeRemainingResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let now = match state.timing.mode {
+        TimingMode::Time => time,
+        TimingMode::Height => height,
+    };
+    let remaining = |deadline: Option<u64>| deadline.map(|d| d.saturating_sub(now));
+    let pool = current_pool(&deps.storage)?;
+    Ok(TimeRemainingResponse {
+        pool_id: pool.as_ref().map(|p| p.id),
+        mode: state.timing.mode,
+        until_lock: remaining(pool.as_ref().and_then(|p| p.locks_at)),
+        until_close: remaining(pool.as_ref().and_then(|p| p.closes_at)),
+        until_settle: remaining(pool.as_ref().and_then(|p| p.settles_at)),
+    })
+}
+
+// Get the top listed players
+fn query_leaderboard<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    order_by: LeaderboardOrder,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<LeaderboardResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let list = listed_players_read(&deps.storage);
+    let mut players = vec![];
+    for index in 0..state.listed_players {
+        let addr = list.load(&index.to_be_bytes())?;
+        let record = may_load_player(&deps.storage, &addr)?.unwrap_or_default();
+        players.push((addr, record));
+    }
+    match order_by {
+        LeaderboardOrder::TotalDeposited => players.sort_by_key(|p| Reverse(p.1.total_deposited)),
+        LeaderboardOrder::RoundsPlayed => players.sort_by_key(|p| Reverse(p.1.rounds_played)),
+    }
+    let (start, end) = page_range(start_after, limit, players.len() as u64);
+    let entries = players
+        .into_iter()
+        .enumerate()
+        .skip(start as usize)
+        .take((end - start) as usize)
+        .map(|(index, (addr, record))| {
+            Ok(LeaderboardEntry {
+                index: index as u64,
+                address: deps.api.human_address(&addr)?,
+                total_deposited: record.total_deposited,
+                rounds_played: record.rounds_played,
+                prizes_won: record.prizes_won,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(LeaderboardResponse { entries })
+}
+
+// Get the round number, its phase and the projected phase changes
+fn query_round<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<RoundResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let pool = current_pool(&deps.storage)?;
+    let mut schedule = vec![];
+    if let Some(pool) = pool.as_ref() {
+        // Phase changes are triggered by the owner, so these are the earliest possible points.
+        if let Some((transition, at)) = transitions::next(pool) {
+            schedule.push(ScheduledPhase {
+                phase: transition.to(),
+                at,
+            });
+            if transition == Lock {
+                schedule.push(ScheduledPhase {
+                    phase: PoolStatus::CLOSED,
+                    at: at.saturating_add(state.timing.locked_duration),
+                });
+            }
+        }
+    }
+    Ok(RoundResponse {
+        round: state.pool_count,
+        phase: pool.map(|p| p.status),
+        mode: state.timing.mode,
+        schedule,
+    })
+}
+
+// Get a page of pool summaries, newest first
+fn query_pool_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PoolHistoryResponse> {
+    let state = config_read(&deps.storage).load()?;
+    // Pool ids run from 1 to pool_count.
+    let (start, end) = page_range_rev(start_after, limit, state.pool_count + 1);
+    let pools_bucket = pool_read(&deps.storage);
+    let mut pools = vec![];
+    for pool_id in (start.max(1)..end).rev() {
+        let pool = pools_bucket.load(&pool_key(pool_id))?;
+        pools.push(pool_summary(deps, pool)?);
+    }
+    Ok(PoolHistoryResponse { pools })
+}
+
+fn pool_summary<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool: Pool,
+) -> StdResult<PoolSummary> {
+    let winner = match pool.winner.as_ref().filter(|_| pool.winner_announced) {
+        Some(winner) => Some(deps.api.human_address(winner)?),
+        None => None,
+    };
+    let state = config_read(&deps.storage).load()?;
+    let pool = public_pool(&state, pool);
+    let step = state.public_rounding;
+    Ok(PoolSummary {
+        id: pool.id,
+        name: pool.metadata.name,
+        status: pool.status,
+        created_at: pool.created_at,
+        status_updated_at: pool.status_updated_at,
+        settled_at: pool.settled_at,
+        total_deposits: round_to_step(pool.total_deposits, step),
+        unique_depositors: pool.unique_depositors,
+        winner,
+        has_winner: pool.winner.is_some(),
+        prize_amount: pool.prize_amount,
+    })
+}
+
+// Get the current pool, prize, countdowns, config and stats in one call
+fn query_dashboard<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    time: Option<u64>,
+    height: Option<u64>,
+) -> StdResult<DashboardResponse> {
+    let config = query_config(deps)?;
+    let clock_known = match config.timing.mode {
+        TimingMode::Time => time.is_some(),
+        TimingMode::Height => height.is_some(),
+    };
+    let time_remaining = if clock_known {
+        Some(query_time_remaining(
+            deps,
+            time.unwrap_or_default(),
+            height.unwrap_or_default(),
+        )?)
+    } else {
+        None
+    };
+    let pool = match current_pool(&deps.storage)? {
+        Some(pool) => Some(pool_summary(deps, pool)?),
+        None => None,
+    };
+    Ok(DashboardResponse {
+        pool,
+        prize_pot: query_prize_pot(deps)?,
+        time_remaining,
+        config,
+        stats: query_stats(deps)?,
+    })
+}
+
+// Get a page of the publicly announced winners
+fn query_past_winners<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PastWinnersResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let pools_bucket = pool_read(&deps.storage);
+    let mut winners = vec![];
+    for (index, pool_id) in read_winners(&deps.storage, &state, start_after, limit)? {
+        let pool = pools_bucket.load(&pool_key(pool_id))?;
+        if let Some(winner) = pool.winner.as_ref() {
+            winners.push(PastWinner {
+                index,
+                pool_id,
+                winner: deps.api.human_address(winner)?,
+                prize_amount: pool.prize_amount,
+                settled_at: pool.settled_at,
+            });
+        }
+    }
+    Ok(PastWinnersResponse { winners })
+}
+
+// A pool as public queries see it. In delayed mode its totals are the ones published
+// at its last phase change.
+fn public_pool(state: &State, pool: Pool) -> Pool {
+    if state.delay_public_totals {
+        pool.with_published_totals()
+    } else {
+        pool
+    }
+}
+
+fn public_stats(state: &State) -> Stats {
+    match state.published_stats.as_ref() {
+        Some(stats) if state.delay_public_totals => stats.clone(),
+        _ => state.stats.clone(),
+    }
+}
+
+fn pool_response<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool: Option<Pool>,
+    caller: Option<HumanAddr>,
+) -> StdResult<PoolResponse> {
+    let winner = match pool.as_ref().filter(|p| p.winner_announced) {
+        Some(Pool {
+            winner: Some(winner),
+            ..
+        }) => Some(deps.api.human_address(winner)?),
+        _ => None,
+    };
+    let next = pool
+        .as_ref()
+        .and_then(transitions::next)
+        .map(|(transition, at)| (transition.to(), at));
+    let state = config_read(&deps.storage).load()?;
+    let next_transition = match next {
+        Some((to, allowed_at)) => {
+            let owner = state.owner.clone();
+            let caller_can_trigger = match caller {
+                Some(caller) => deps.api.canonical_address(&caller)? == owner,
+                None => false,
+            };
+            Some(NextTransition {
+                to,
+                allowed_at,
+                caller_can_trigger,
+            })
+        }
+        None => None,
+    };
+    Ok(PoolResponse {
+        pool: pool.map(|p| PoolInfo::from(public_pool(&state, p)).rounded(state.public_rounding)),
+        winner,
+        next_transition,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregator::{
+        InterfaceResponse, LotteryHandleV1, LotteryQueryV1, PrizeResponseV1, RoundPhaseV1,
+        RoundResponseV1,
+    };
+    use crate::oracle::PriceResponse;
+    use crate::state::{
+        legacy_pool_storage, player_history, pool_transitions, read_delegators, save_seen_keys,
+        IbcPayout, LegacyPool, LegacyState, Player, PoolStatus, Validator, CONFIG_KEY, DAYS,
+        IDEMPOTENCY_WINDOW, MAX_LISTED_PLAYERS, MAX_MEMO_LEN, MAX_NAME_LEN, PRNG_SEED_KEY,
+        RECOVERY_DELAY, UNBONDING_GRACE, UNBONDING_PERIOD, VERSION_KEY,
+    };
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::HumanAddr;
+    use cosmwasm_std::{coin, from_binary, from_slice, FullDelegation, ReadonlyStorage, WasmMsg};
+    use cosmwasm_std::{Coin, Empty, QuerierResult, QueryRequest};
+    use cosmwasm_storage::singleton;
+
+    // Everything left to its default; tests override what they exercise with `..init_msg()`.
+    fn init_msg() -> InitMsg {
+        InitMsg {
+            timing: None,
+            validators: None,
+            response_block_size: None,
+            prng_seed: None,
+            public_rounding: None,
+            delay_public_totals: None,
+            transparent: None,
+            generic_errors: None,
+            parent: None,
+            guardian: None,
+        }
+    }
+
+    // A plain native pool with no metadata, tickets or extra denoms.
+    fn create_pool() -> HandleMsg {
+        HandleMsg::CrtePool {
+            metadata: None,
+            nft_tickets: None,
+            extra_denoms: None,
+            ibc_denom: None,
+            padding: None,
+        }
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let msg = init_msg();
+        let env = mock_env("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(&deps, QueryMsg::GetOwner {}).unwrap();
+        let value: OwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(HumanAddr::from("creator"), value.owner);
+    }
+
+    #[test]
+    fn test_create_pool_admin() {
+        let mut deps = mock_dependencies(20, &coins(2, "earth"));
+
+        let msg = init_msg();
+        let env = mock_env("creator", &coins(2, "earth"));
+        init(&mut deps, env, msg).unwrap();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let res = handle(&mut deps, env, create_pool()).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(answer, HandleAnswer::CreatePool { pool_id: 1 });
+
+        // Get the pool result
+        let res = query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let mut expected = Pool::new(1, &Timing::default(), &env.block);
+        expected.transition_count = 1;
+        assert_eq!(value.pool, Some(PoolInfo::from(expected)));
+        assert_eq!(value.winner, None);
+        assert_eq!(
+            value.next_transition,
+            Some(NextTransition {
+                to: PoolStatus::LOCKED,
+                allowed_at: 1000 + DAYS,
+                caller_can_trigger: false,
+            })
+        );
+        let msg = QueryMsg::GetCurrentPool {
+            caller: Some(HumanAddr::from("creator")),
+        };
+        let value: PoolResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert!(value.next_transition.unwrap().caller_can_trigger);
+    }
+
+    #[test]
+    fn test_create_pool_errors() {
+        let mut deps = mock_dependencies(20, &coins(2, "earth"));
+
+        let msg = init_msg();
+        let env = mock_env("creator", &coins(2, "earth"));
+        init(&mut deps, env, msg).unwrap();
+
+        // Only admin can create pool
+        let env = mock_env("voter", &coins(2, "earth"));
+        let res = handle(&mut deps, env, create_pool());
+
+        assert_eq!(res.is_err(), true);
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+    }
+
+    #[test]
+    fn test_init_errors() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        let validator = |address: &str| Validator {
+            address: HumanAddr::from(address),
+            weight: 1,
+        };
+
+        // Validators must be operator addresses.
+        let bad_addresses = [
+            "val1",
+            "secretvaloper1",
+            "secretvaloper1BAD",
+            "secret1qqq",
+            // One character off, so the checksum fails.
+            "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pq",
+        ];
+        for address in &bad_addresses {
+            let bad = InitMsg {
+                validators: Some(vec![validator(address)]),
+                ..msg.clone()
+            };
+            let res = init(&mut deps, mock_env("creator", &[]), bad);
+            let err = ContractError::InvalidValidator {
+                address: address.to_string(),
+            };
+            assert_eq!(res.unwrap_err(), err.into());
+        }
+
+        let bad = InitMsg {
+            timing: Some(Timing {
+                mode: TimingMode::Height,
+                open_duration: 100,
+                locked_duration: 0,
+                ..Timing::default()
+            }),
+            ..msg.clone()
+        };
+        let res = init(&mut deps, mock_env("creator", &[]), bad);
+        assert_eq!(res.unwrap_err(), ContractError::ZeroDuration.into());
+
+        for size in &[0, MAX_RESPONSE_BLOCK_SIZE + 1] {
+            let bad = InitMsg {
+                response_block_size: Some(*size),
+                ..msg.clone()
+            };
+            let res = init(&mut deps, mock_env("creator", &[]), bad);
+            let err = ContractError::InvalidBlockSize {
+                max: MAX_RESPONSE_BLOCK_SIZE,
+            };
+            assert_eq!(res.unwrap_err(), err.into());
+        }
+
+        // Nothing was stored by the failed attempts.
+        assert!(config_read(&deps.storage).may_load().unwrap().is_none());
+        let good = InitMsg {
+            validators: Some(vec![validator(
+                "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm",
+            )]),
+            ..msg
+        };
+        init(&mut deps, mock_env("creator", &[]), good).unwrap();
+    }
+
+    #[test]
+    fn test_lock_pool() {
+        let mut deps = mock_dependencies(20, &coins(2, "scrt"));
+
+        // Initialize the contract
+        let msg = init_msg();
+        let env = mock_env("creator", &coins(2, "scrt"));
+        init(&mut deps, env, msg).unwrap();
+
+        // Create the pool
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        env.block.height = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        // Coins sent with an admin handle are refused rather than stranded.
+        let mut env = mock_env("creator", &coins(2, "scrt"));
+        env.block.time = DAYS * 21 + 1001;
+        env.block.height = DAYS * 21 + 1001;
+        let res = handle(&mut deps, env, HandleMsg::LockPool { padding: None });
+        assert_eq!(res.unwrap_err(), ContractError::FundsNotAccepted.into());
+
+        // Lock the pool.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = DAYS * 21 + 1001;
+        env.block.height = DAYS * 21 + 1001;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+
+        let res = query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        assert_eq!(value.pool.unwrap().status, PoolStatus::LOCKED);
+    }
+
+    #[test]
+    fn test_migrate_legacy_pool() {
+        let mut deps = mock_dependencies(20, &[]);
+        let res = query(&deps, QueryMsg::SimulateMigration {}).unwrap();
+        let value: MigrationPreviewResponse = from_binary(&res).unwrap();
+        assert_eq!(value.layout, "unknown");
+        assert!(value.error.is_some());
+
+        // Write the pre-pool-id layout directly.
+        let owner = deps
+            .api
+            .canonical_address(&HumanAddr::from("creator"))
+            .unwrap();
+        let voter = deps
+            .api
+            .canonical_address(&HumanAddr::from("voter"))
+            .unwrap();
+        singleton(&mut deps.storage, CONFIG_KEY)
+            .save(&LegacyState {
+                count: 0,
+                owner: owner.clone(),
+            })
+            .unwrap();
+        legacy_pool_storage(&mut deps.storage)
+            .save(&LegacyPool {
+                delegated_amt: Uint128(100),
+                delegators: vec![voter.clone(), voter],
+                status: PoolStatus::LOCKED,
+                status_updated_at: 1000,
+            })
+            .unwrap();
+
+        // The dry run reports the conversion and what it can't carry over, and
+        // leaves storage as it was.
+        let preview = |deps: &Extern<_, _, _>| -> MigrationPreviewResponse {
+            from_binary(&query(deps, QueryMsg::SimulateMigration {}).unwrap()).unwrap()
+        };
+        let value = preview(&deps);
+        assert_eq!(value.layout, "legacy");
+        assert_eq!(
+            value.changes,
+            vec![
+                "config will move to the current layout",
+                "pool 1 will be created LOCKED with 1 delegators",
+                "1 duplicate delegator listings will be dropped",
+            ]
+        );
+        assert_eq!(
+            value.unconvertible,
+            vec!["1 delegators have no recorded deposit and will be listed without an entry"]
+        );
+        assert_eq!(value.error, None);
+        assert!(config_read(&deps.storage).may_load().is_err());
+        assert!(legacy_pool_read(&deps.storage)
+            .may_load()
+            .unwrap()
+            .is_some());
+
+        // Only the stored owner can migrate.
+        let env = mock_env("voter", &[]);
+        let res = migrate(&mut deps, env, MigrateMsg { prng_seed: None });
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        assert_eq!(preview(&deps).layout, "legacy");
+
+        // Legacy storage has no seed, and the public block data won't do as one.
+        let res = migrate(
+            &mut deps,
+            mock_env("creator", &[]),
+            MigrateMsg { prng_seed: None },
+        );
+        assert_eq!(res.unwrap_err(), ContractError::SeedRequired.into());
+
+        // Delegators without a recorded deposit can't be carried over.
+        let env = mock_env("creator", &[]);
+        let msg = MigrateMsg {
+            prng_seed: Some(Binary::from(&b"fresh"[..])),
+        };
+        let res = migrate(&mut deps, env.clone(), msg.clone());
+        let err = ContractError::Unconvertible {
+            reasons: value.unconvertible,
+        };
+        assert_eq!(res.unwrap_err(), err.into());
+        assert!(!has_prng_seed(&deps.storage));
+        assert_eq!(preview(&deps).layout, "legacy");
+
+        // Once they're paid out, the pool converts.
+        legacy_pool_storage(&mut deps.storage)
+            .save(&LegacyPool {
+                delegated_amt: Uint128(0),
+                delegators: vec![],
+                status: PoolStatus::LOCKED,
+                status_updated_at: 1000,
+            })
+            .unwrap();
+        let value = preview(&deps);
+        assert!(value.unconvertible.is_empty());
+        let res = migrate(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, vec![log("migrated_pools", 1)]);
+        let mut expected = MockStorage::new();
+        write_prng_seed(&mut expected, b"fresh");
+        let seed = deps.storage.get(PRNG_SEED_KEY);
+        assert_eq!(seed, expected.get(PRNG_SEED_KEY));
+        assert_eq!(storage_version(&deps.storage).unwrap(), STORAGE_VERSION);
+        let value = preview(&deps);
+        assert_eq!(value.layout, "current");
+        assert!(value.changes.is_empty());
+
+        // Owner and the in-flight pool survive the migration.
+        let res = query(&deps, QueryMsg::GetOwner {}).unwrap();
+        let value: OwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(HumanAddr::from("creator"), value.owner);
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.id, 1);
+        assert_eq!(pool.delegated_amt, Uint128(0));
+        assert!(read_delegators(&deps.storage, &pool, None, None)
+            .unwrap()
+            .is_empty());
+        pool.check_totals().unwrap();
+        assert_eq!(pool.is_locked(), true);
+        assert_eq!(legacy_pool_read(&deps.storage).may_load().unwrap(), None);
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(index.current_locked, Some(1));
+        assert_eq!(index.current_open, None);
+
+        // A second migrate leaves the new layout untouched, and is the owner's alone too.
+        let res = migrate(
+            &mut deps,
+            mock_env("voter", &[]),
+            MigrateMsg { prng_seed: None },
+        );
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let env = mock_env("creator", &[]);
+        migrate(&mut deps, env, MigrateMsg { prng_seed: None }).unwrap();
+        assert_eq!(deps.storage.get(PRNG_SEED_KEY), seed);
+        assert_eq!(config_read(&deps.storage).load().unwrap().pool_count, 1);
+        assert_eq!(
+            config_read(&deps.storage).load().unwrap().stats.pools_run,
+            1
+        );
+
+        // A layout this code doesn't know is left alone.
+        singleton(&mut deps.storage, VERSION_KEY)
+            .save(&3u32)
+            .unwrap();
+        let res = migrate(
+            &mut deps,
+            mock_env("creator", &[]),
+            MigrateMsg { prng_seed: None },
+        );
+        let err = ContractError::UnknownStorageVersion { version: 3 };
+        assert_eq!(res.unwrap_err(), err.into());
+    }
+
+    fn load_current_pool<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> Pool {
+        current_pool(&deps.storage).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_deposit_updates_aggregates() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let env = mock_env("creator", &[]);
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let env = mock_env("alice", &coins(50, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let env = mock_env("bob", &coins(10, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(160));
+        assert_eq!(pool.total_weight, Uint128(160));
+        assert_eq!(pool.unique_depositors, 2);
+        assert_eq!(pool.delegator_count, 2);
+
+        // Withdrawing everything drops the depositor from the count.
+        let env = mock_env("bob", &[]);
+        let res = handle(
+            &mut deps,
+            env,
+            HandleMsg::Withdraw {
+                amount: Uint128(10),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("bob"),
+                amount: coins(10, DENOM),
+            })]
+        );
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(150));
+        assert_eq!(pool.unique_depositors, 1);
+
+        // Depositing again reuses the existing entry.
+        let env = mock_env("bob", &coins(5, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(155));
+        assert_eq!(pool.unique_depositors, 2);
+        assert_eq!(pool.delegator_count, 2);
+    }
+
+    #[test]
+    fn test_unique_depositors_across_withdrawals() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let create = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), create).unwrap();
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), deposit).unwrap();
+        assert_eq!(load_current_pool(&deps).unique_depositors, 1);
+
+        // A partial withdrawal leaves the depositor counted.
+        let withdraw = |amount: u128| HandleMsg::Withdraw {
+            amount: Uint128(amount),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), withdraw(40)).unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(60));
+        assert_eq!(pool.unique_depositors, 1);
+
+        // Withdrawing the rest drops them, exactly once.
+        handle(&mut deps, mock_env("alice", &[]), withdraw(60)).unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(0));
+        assert_eq!(pool.unique_depositors, 0);
+        assert!(handle(&mut deps, mock_env("alice", &[]), withdraw(1)).is_err());
+        assert_eq!(load_current_pool(&deps).unique_depositors, 0);
+    }
+
+    #[test]
+    fn test_deposit_errors() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+
+        // No pool yet.
+        let env = mock_env("alice", &coins(100, DENOM));
+        assert!(handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            }
+        )
+        .is_err());
+
+        let env = mock_env("creator", &[]);
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        // Wrong denom and empty deposits are rejected.
+        let env = mock_env("alice", &coins(100, "earth"));
+        assert!(handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            }
+        )
+        .is_err());
+        let env = mock_env("alice", &[]);
+        assert!(handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            }
+        )
+        .is_err());
+
+        // Cannot withdraw more than was deposited.
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let env = mock_env("alice", &[]);
+        let res = handle(
+            &mut deps,
+            env,
+            HandleMsg::Withdraw {
+                amount: Uint128(101),
+                padding: None,
+            },
+        );
+        match res {
+            Err(StdError::Underflow { .. }) => {}
+            _ => panic!("Must return underflow error"),
+        }
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(100));
+    }
+
+    #[test]
+    fn test_zero_amounts_are_rejected() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(&mut deps, env, deposit.clone()).unwrap();
+
+        // Nothing sent, or a zero coin, is not a deposit or a sponsorship.
+        for funds in &[vec![], coins(0, DENOM)] {
+            let env = mock_env("bob", funds);
+            let res = handle(&mut deps, env.clone(), deposit.clone());
+            assert_eq!(res.unwrap_err(), ContractError::NoFunds.into());
+            let res = handle(&mut deps, env, HandleMsg::Sponsor { padding: None });
+            assert_eq!(res.unwrap_err(), ContractError::NoFunds.into());
+        }
+
+        // A zero withdrawal fails whether or not the sender has a deposit, and
+        // leaves the depositor count alone.
+        for sender in &["alice", "bob"] {
+            let msg = HandleMsg::Withdraw {
+                amount: Uint128::zero(),
+                padding: None,
+            };
+            let res = handle(&mut deps, mock_env(*sender, &[]), msg);
+            let err = ContractError::ZeroAmount {
+                what: "withdraw".to_string(),
+            };
+            assert_eq!(res.unwrap_err(), err.into());
+        }
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.unique_depositors, 1);
+        assert_eq!(pool.total_deposits, Uint128(100));
+    }
+
+    #[test]
+    fn test_settled_pool_response() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        let mut pool = load_current_pool(&deps);
+        pool.settle(
+            Some(alice.clone()),
+            Uint128(30),
+            Uint128(25),
+            1000 + 22 * DAYS,
+        )
+        .unwrap();
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+
+        // A closed pool carries its settlement record, but not who won.
+        let res = query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        assert_eq!(value.winner, None);
+        let info = value.pool.unwrap();
+        assert!(info.has_winner);
+        assert_eq!(info.prize_amount, Uint128(25));
+        assert_eq!(info.rewards_realized, Uint128(30));
+        assert_eq!(info.settled_at, Some(1000 + 22 * DAYS));
+        let msg = QueryMsg::GetPoolHistory {
+            start_after: None,
+            limit: None,
+        };
+        let value: PoolHistoryResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.pools[0].winner, None);
+
+        // The winner can find out privately before any announcement.
+        for name in &["alice", "bob"] {
+            let msg = HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*name, &[]), msg).unwrap();
+        }
+        let am_i_the_winner = |name: &str| -> AmITheWinnerResponse {
+            let msg = QueryMsg::AmITheWinner {
+                address: HumanAddr::from(name),
+                key: "key".to_string(),
+                pool_id: 1,
+            };
+            from_binary(&query(&deps, msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            am_i_the_winner("alice"),
+            AmITheWinnerResponse {
+                pool_id: 1,
+                settled: true,
+                won: true,
+                prize_amount: Uint128(25),
+            }
+        );
+        assert!(!am_i_the_winner("bob").won);
+        assert_eq!(am_i_the_winner("bob").prize_amount, Uint128::zero());
+
+        // Announcing makes the winner public.
+        let mut state = config_read(&deps.storage).load().unwrap();
+        let mut pool = load_current_pool(&deps);
+        announce_winner(&mut deps.storage, &mut state, &mut pool).unwrap();
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let res = query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        assert_eq!(value.winner, Some(HumanAddr::from("alice")));
+        assert_eq!(pool.winner, Some(alice));
+    }
+
+    #[test]
+    fn test_lifetime_stats() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let env = mock_env("alice", &[]);
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(100),
+            padding: None,
+        };
+        handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("bob", &coins(20, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        // Run a second pool that alice plays again.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let env = mock_env("creator", &[]);
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("alice", &coins(5, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        // Per-address records span both pools.
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        let record = may_load_player(&deps.storage, &alice).unwrap().unwrap();
+        assert_eq!(
+            record,
+            Player {
+                total_deposited: Uint128(105),
+                rounds_played: 2,
+                prizes_won: 0,
+                total_won: Uint128(0),
+                history_count: 3,
+                listing_index: None,
+            }
+        );
+
+        let res = query(&deps, QueryMsg::GetStats {}).unwrap();
+        let value: StatsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            StatsResponse {
+                pools_run: 2,
+                total_deposited: Uint128(125),
+                total_prizes_paid: Uint128(0),
+                unique_players: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cleanup_pool() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        for name in ["alice", "bob", "carol"].iter() {
+            let env = mock_env(*name, &coins(10, DENOM));
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let env = mock_env("bob", &[]);
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(10),
+            padding: None,
+        };
+        handle(&mut deps, env, msg).unwrap();
+
+        // Cleanup is refused until the pool is settled.
+        let env = mock_env("anyone", &[]);
+        let msg = HandleMsg::CleanupPool {
+            pool_id: 1,
+            limit: Some(2),
+            padding: None,
+        };
+        assert!(handle(&mut deps, env, msg.clone()).is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::ClsePool { padding: None },
+        )
+        .unwrap();
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        // Alice takes her principal back; carol leaves hers in.
+        let claim = HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), claim).unwrap();
+
+        // First call covers alice and bob, second call carol.
+        let env = mock_env("anyone", &[]);
+        let res = handle(&mut deps, env, msg.clone()).unwrap();
+        assert_eq!(res.log[4..], [log("removed", 2), log("done", false)]);
+        let env = mock_env("anyone", &[]);
+        let res = handle(&mut deps, env, msg.clone()).unwrap();
+        assert_eq!(res.log[4..], [log("removed", 0), log("done", true)]);
+        let env = mock_env("anyone", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log[4..], [log("removed", 0), log("done", true)]);
+
+        let pool = load_current_pool(&deps);
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        assert_eq!(may_load_entry(&deps.storage, 1, &bob).unwrap(), None);
+        let remaining: Vec<u64> = read_delegators(&deps.storage, &pool, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(remaining, vec![2]);
+    }
+
+    #[test]
+    fn test_pool_status_index() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(index, PoolIndex::default());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(index.current_open, Some(1));
+
+        // A second pool cannot be created while one is OPEN.
+        let env = mock_env("creator", &[]);
+        assert!(handle(&mut deps, env, create_pool()).is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(index.current_open, None);
+        assert_eq!(index.current_locked, Some(1));
+
+        // Deposits need an OPEN pool.
+        let env = mock_env("alice", &coins(10, DENOM));
+        assert!(handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            }
+        )
+        .is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(
+            index,
+            PoolIndex {
+                current_open: None,
+                current_locked: None,
+                pending_settlement: Some(1),
+                last_settled: None,
+            }
+        );
+
+        // The next pool waits for the last one's draw.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        let create = create_pool();
+        let res = handle(&mut deps, env.clone(), create.clone());
+        let err = ContractError::SettlementPending { pool_id: 1 };
+        assert_eq!(res.unwrap_err(), err.into());
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, create).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(
+            index,
+            PoolIndex {
+                current_open: Some(2),
+                current_locked: None,
+                pending_settlement: None,
+                last_settled: Some(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_deposit_overflow_is_an_error() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let env = mock_env("creator", &[]);
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        let env = mock_env("alice", &coins(u128::MAX, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        // Totals would wrap; the deposit is rejected and nothing changes.
+        let env = mock_env("bob", &coins(1, DENOM));
+        match handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        ) {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.starts_with("Overflow")),
+            _ => panic!("Must return overflow error"),
+        }
+        // Attached coins are summed with the same checks.
+        let mut env = mock_env("bob", &coins(u128::MAX, DENOM));
+        env.message.sent_funds.push(coin(1, DENOM));
+        assert!(handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            }
+        )
+        .is_err());
+
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(u128::MAX));
+        assert_eq!(pool.unique_depositors, 1);
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let create = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), create).unwrap();
+        let deposit = |key: &str| HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: Some(key.to_string()),
+            padding: None,
+        };
+        let mut env = mock_env("alice", &coins(100, DENOM));
+        env.block.time = 1000;
+        handle(&mut deps, env.clone(), deposit("tx-1")).unwrap();
+
+        // The retry is refused; other keys and other senders are not.
+        let res = handle(&mut deps, env.clone(), deposit("tx-1"));
+        assert_eq!(res.unwrap_err(), ContractError::DuplicateRequest.into());
+        handle(&mut deps, env.clone(), deposit("tx-2")).unwrap();
+        let mut bob = mock_env("bob", &coins(100, DENOM));
+        bob.block.time = 1000;
+        handle(&mut deps, bob, deposit("tx-1")).unwrap();
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(300));
+
+        // Keys are forgotten once the window passes.
+        env.block.time += IDEMPOTENCY_WINDOW;
+        handle(&mut deps, env.clone(), deposit("tx-1")).unwrap();
+        let res = handle(&mut deps, env.clone(), deposit(""));
+        assert_eq!(res.unwrap_err(), ContractError::EmptyIdempotencyKey.into());
+        let res = handle(&mut deps, env, deposit(&"k".repeat(65)));
+        let err = ContractError::TooLong {
+            what: "Idempotency key".to_string(),
+            max: 64,
+        };
+        assert_eq!(res.unwrap_err(), err.into());
+    }
+
+    #[test]
+    fn test_deposit_limit() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let create = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), create).unwrap();
+        let limit = DepositLimit {
+            per_block: Some(3),
+            per_address: Some(2),
+        };
+        let msg = HandleMsg::SetDepositLimit {
+            limit: Some(limit.clone()),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), msg.clone()).is_err());
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let res = query(&deps, QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(value.deposit_limit, Some(limit));
+
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let env = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &coins(100, DENOM));
+            env.block.height = height;
+            env
+        };
+        handle(&mut deps, env("alice", 10), deposit.clone()).unwrap();
+        handle(&mut deps, env("alice", 10), deposit.clone()).unwrap();
+        let res = handle(&mut deps, env("alice", 10), deposit.clone());
+        assert_eq!(res.unwrap_err(), ContractError::DepositLimitReached.into());
+        handle(&mut deps, env("bob", 10), deposit.clone()).unwrap();
+        let res = handle(&mut deps, env("carol", 10), deposit.clone());
+        assert_eq!(res.unwrap_err(), ContractError::DepositLimitReached.into());
+
+        // The counts start over with the next block.
+        handle(&mut deps, env("alice", 11), deposit.clone()).unwrap();
+        handle(&mut deps, env("carol", 11), deposit.clone()).unwrap();
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(500));
+
+        // Lifting the limit lets everyone in again.
+        let msg = HandleMsg::SetDepositLimit {
+            limit: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for _ in 0..4 {
+            handle(&mut deps, env("alice", 11), deposit.clone()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_max_depositors() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let create = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), create).unwrap();
+        for max in &[0, MAX_DEPOSITORS + 1] {
+            let msg = HandleMsg::SetMaxDepositors {
+                max: *max,
+                padding: None,
+            };
+            let res = handle(&mut deps, mock_env("creator", &[]), msg);
+            let err = ContractError::InvalidMaxDepositors {
+                max: MAX_DEPOSITORS,
+            };
+            assert_eq!(res.unwrap_err(), err.into());
+        }
+        let msg = HandleMsg::SetMaxDepositors {
+            max: 2,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let alice = mock_env("alice", &coins(100, DENOM));
+        handle(&mut deps, alice.clone(), deposit.clone()).unwrap();
+        let bob = mock_env("bob", &coins(100, DENOM));
+        handle(&mut deps, bob.clone(), deposit.clone()).unwrap();
+        let carol = mock_env("carol", &coins(100, DENOM));
+        let res = handle(&mut deps, carol.clone(), deposit.clone());
+        assert_eq!(res.unwrap_err(), ContractError::PoolFull { max: 2 }.into());
+
+        // Existing entries can still top up, and a withdrawn one keeps its slot.
+        handle(&mut deps, alice, deposit.clone()).unwrap();
+        let withdraw = HandleMsg::Withdraw {
+            amount: Uint128(100),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), withdraw).unwrap();
+        assert!(handle(&mut deps, carol, deposit.clone()).is_err());
+        handle(&mut deps, bob, deposit).unwrap();
+        assert_eq!(load_current_pool(&deps).delegator_count, 2);
+    }
+
+    #[test]
+    fn test_invariants_are_checked() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let create = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), create).unwrap();
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(&mut deps, env, deposit.clone()).unwrap();
+
+        // The running total drifts from the entries. Only debug builds walk the entries.
+        let mut pool = load_current_pool(&deps);
+        #[cfg(debug_assertions)]
+        {
+            let mut drifted = pool.clone();
+            drifted.total_deposits = Uint128(150);
+            drifted.total_weight = Uint128(150);
+            pool_storage(&mut deps.storage)
+                .save(&pool_key(drifted.id), &drifted)
+                .unwrap();
+            let env = mock_env("bob", &coins(10, DENOM));
+            let res = handle(&mut deps, env, deposit);
+            let err = ContractError::InvariantBroken {
+                pool_id: pool.id,
+                what: "totals don't match the entries".to_string(),
+            };
+            assert_eq!(res.unwrap_err(), err.into());
+        }
+
+        // Aggregates that contradict each other are caught without reading entries.
+        pool.total_deposits = Uint128(100);
+        pool.total_weight = Uint128(0);
+        assert!(pool.check_totals().is_err());
+        pool.total_weight = Uint128(100);
+        assert!(pool.check_totals().is_ok());
+        pool.unique_depositors = 2;
+        assert!(pool.check_totals().is_err());
+    }
+
+    #[test]
+    fn test_create_pool_with_metadata() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+
+        let metadata = PoolMetadata {
+            name: Some("Holiday jackpot".to_string()),
+            description: None,
+            image_url: Some("https://example.com/holiday.png".to_string()),
+        };
+        let too_long = PoolMetadata {
+            name: Some("x".repeat(MAX_NAME_LEN + 1)),
+            ..metadata.clone()
+        };
+        let env = mock_env("creator", &[]);
+        let msg = HandleMsg::CrtePool {
+            metadata: Some(too_long),
+            nft_tickets: None,
+            extra_denoms: None,
+            ibc_denom: None,
+            padding: None,
+        };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("creator", &[]);
+        let msg = HandleMsg::CrtePool {
+            metadata: Some(metadata.clone()),
+            nft_tickets: None,
+            extra_denoms: None,
+            ibc_denom: None,
+            padding: None,
+        };
+        handle(&mut deps, env, msg).unwrap();
+        assert_eq!(load_current_pool(&deps).metadata, metadata);
+    }
+
+    #[test]
+    fn test_create_pool_msg_without_metadata() {
+        let msg: HandleMsg = from_binary(&Binary::from(br#"{"crte_pool":{}}"#.to_vec())).unwrap();
+        assert_eq!(msg, create_pool());
+    }
+
+    #[test]
+    fn test_readable_pool_msg_names() {
+        let msg: HandleMsg = from_slice(br#"{"create_pool":{"padding":"  "}}"#).unwrap();
+        let old: HandleMsg = from_slice(br#"{"crte_pool":{"padding":"  "}}"#).unwrap();
+        assert_eq!(msg, old);
+        let msg: HandleMsg = from_slice(br#"{"close_pool":{}}"#).unwrap();
+        assert_eq!(msg, HandleMsg::ClsePool { padding: None });
+        let msg: HandleMsg = from_slice(br#"{"lock_pool":{}}"#).unwrap();
+        assert_eq!(msg, HandleMsg::LockPool { padding: None });
+    }
+
+    #[test]
+    fn test_transition_log() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        init(&mut deps, env, init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+
+        let msg = QueryMsg::GetTransitions {
+            pool_id: 1,
+            start_after: Some(0),
+            limit: None,
+        };
+        let res = query(&deps, msg).unwrap();
+        let value: TransitionsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.transitions,
+            vec![
+                TransitionInfo {
+                    index: 1,
+                    from: Some(PoolStatus::OPEN),
+                    to: PoolStatus::LOCKED,
+                    time: 1000 + DAYS,
+                    sender: HumanAddr::from("creator"),
+                },
+                TransitionInfo {
+                    index: 2,
+                    from: Some(PoolStatus::LOCKED),
+                    to: PoolStatus::CLOSED,
+                    time: 1000 + 22 * DAYS,
+                    sender: HumanAddr::from("creator"),
+                },
+            ]
+        );
+
+        let msg = QueryMsg::GetTransitions {
+            pool_id: 1,
+            start_after: None,
+            limit: Some(1),
+        };
+        let res = query(&deps, msg).unwrap();
+        let value: TransitionsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.transitions.len(), 1);
+        assert_eq!(value.transitions[0].from, None);
+        assert_eq!(value.transitions[0].to, PoolStatus::OPEN);
+
+        // Unknown pools are an error rather than an empty log.
+        let msg = QueryMsg::GetTransitions {
+            pool_id: 2,
+            start_after: None,
+            limit: None,
+        };
+        assert!(query(&deps, msg).is_err());
+    }
+
+    #[test]
+    fn test_height_timing_mode() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            timing: Some(Timing {
+                mode: TimingMode::Height,
+                open_duration: 100,
+                locked_duration: 1000,
+                ..Timing::default()
+            }),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.height = 500;
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        // Plenty of wall-clock time has passed, but not enough blocks.
+        let mut env = mock_env("creator", &[]);
+        env.block.height = 599;
+        env.block.time += 30 * DAYS;
+        assert!(handle(&mut deps, env, HandleMsg::LockPool { padding: None }).is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.height = 600;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.height = 1599;
+        assert!(handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).is_err());
+        let mut env = mock_env("creator", &[]);
+        env.block.height = 1600;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+    }
+
+    #[test]
+    fn test_my_deposit_requires_viewing_key() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let env = mock_env("alice", &coins(300, DENOM));
+        let msg = HandleMsg::Deposit {
+            memo: Some("x".repeat(MAX_MEMO_LEN + 1)),
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        assert!(handle(&mut deps, env.clone(), msg).is_err());
+        let msg = HandleMsg::Deposit {
+            memo: Some("for the office pool".to_string()),
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("bob", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let query_msg = |key: &str| QueryMsg::GetMyDeposit {
+            address: HumanAddr::from("alice"),
+            key: key.to_string(),
+        };
+        // No key set yet.
+        assert!(query(&deps, query_msg("secret")).is_err());
+
+        let msg = HandleMsg::SetViewingKey {
+            key: "secret".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert!(query(&deps, query_msg("wrong")).is_err());
+
+        // A generated key replaces the one set by hand.
+        let msg = HandleMsg::CreateViewingKey {
+            entropy: "dice roll".to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        let data = res.data.unwrap();
+        assert_eq!(data.len(), RESPONSE_BLOCK_SIZE as usize);
+        let key = match from_binary(&data).unwrap() {
+            HandleAnswer::CreateViewingKey { key } => key,
+            other => panic!("unexpected answer {:?}", other),
+        };
+        assert!(query(&deps, query_msg("secret")).is_err());
+        assert!(query(&deps, query_msg(&key)).is_ok());
+        let msg = HandleMsg::SetViewingKey {
+            key: "secret".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+
+        let res = query(&deps, query_msg("secret")).unwrap();
+        let value: MyDepositResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            MyDepositResponse {
+                pool_id: Some(1),
+                balance: Uint128(300),
+                weight: Uint128(300),
+                odds: Decimal::percent(75),
+                memo: Some("for the office pool".to_string()),
+                other_balances: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_private_responses_are_padded() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for (name, amount) in &[("alice", 3), ("bob", 1_000_000_000_000)] {
+            let env = mock_env(*name, &coins(*amount, DENOM));
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                },
+            )
+            .unwrap();
+            let msg = HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*name, &[]), msg).unwrap();
+        }
+        let my_deposit = |name: &str| {
+            let msg = QueryMsg::GetMyDeposit {
+                address: HumanAddr::from(name),
+                key: "key".to_string(),
+            };
+            query(&deps, msg).unwrap()
+        };
+        // Balances of different lengths still produce same-size responses.
+        let small = my_deposit("alice");
+        let large = my_deposit("bob");
+        assert_eq!(small.len(), RESPONSE_BLOCK_SIZE as usize);
+        assert_eq!(large.len(), small.len());
+        let value: MyDepositResponse = from_binary(&large).unwrap();
+        assert_eq!(value.balance, Uint128(1_000_000_000_000));
+    }
+
+    #[test]
+    fn test_query_config() {
+        let mut deps = mock_dependencies(20, &[]);
+        let timing = Timing {
+            mode: TimingMode::Height,
+            open_duration: 100,
+            locked_duration: 1000,
+            ..Timing::default()
+        };
+        let msg = InitMsg {
+            timing: Some(timing.clone()),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            ConfigResponse {
+                owner: HumanAddr::from("creator"),
+                denom: DENOM.to_string(),
+                timing,
+                default_limit: DEFAULT_LIMIT,
+                max_limit: MAX_LIMIT,
+                response_block_size: RESPONSE_BLOCK_SIZE,
+                public_rounding: Uint128(1),
+                delay_public_totals: false,
+                transparent: false,
+                generic_errors: false,
+                tokens: vec![],
+                ticket: None,
+                nft_contract: None,
+                emission: None,
+                hooks: vec![],
+                swap_router: None,
+                oracle: None,
+                wrapped_native: None,
+                factory: None,
+                parent: None,
+                ibc_gateway: None,
+                treasury: None,
+                cover_slashes: false,
+                deposit_limit: None,
+                max_depositors: MAX_DEPOSITORS,
+                max_unclaimed_prizes: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_query_pool_by_id() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        // The first pool is still reachable after the second one opened.
+        let res = query(
+            &deps,
+            QueryMsg::GetPool {
+                pool_id: 1,
+                caller: None,
+            },
+        )
+        .unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        assert_eq!(value.pool.unwrap().status, PoolStatus::CLOSED);
+        let res = query(
+            &deps,
+            QueryMsg::GetPool {
+                pool_id: 2,
+                caller: None,
+            },
+        )
+        .unwrap();
+        let value: PoolResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            from_binary(&query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap()).unwrap()
+        );
+
+        assert!(query(
+            &deps,
+            QueryMsg::GetPool {
+                pool_id: 3,
+                caller: None
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_pool_history() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let msg = QueryMsg::GetPoolHistory {
+            start_after: None,
+            limit: None,
+        };
+        let value: PoolHistoryResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.pools, vec![]);
+
+        let mut time = 1000;
+        for _ in 0..3 {
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time;
+            handle(&mut deps, env, create_pool()).unwrap();
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time + DAYS;
+            handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time + 22 * DAYS;
+            handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+            time += 30 * DAYS;
+        }
+
+        let msg = QueryMsg::GetPoolHistory {
+            start_after: None,
+            limit: Some(2),
+        };
+        let value: PoolHistoryResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        let ids: Vec<u64> = value.pools.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert_eq!(value.pools[1].created_at, 1000 + 30 * DAYS);
+        assert_eq!(value.pools[1].status, PoolStatus::CLOSED);
+
+        let msg = QueryMsg::GetPoolHistory {
+            start_after: Some(2),
+            limit: Some(2),
+        };
+        let value: PoolHistoryResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        let ids: Vec<u64> = value.pools.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![1]);
+        // The cursor's old name is still accepted.
+        let msg: QueryMsg =
+            from_slice(br#"{"get_pool_history":{"start_before":2,"limit":2}}"#).unwrap();
+        assert_eq!(
+            msg,
+            QueryMsg::GetPoolHistory {
+                start_after: Some(2),
+                limit: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_past_winners_lists_only_announced() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut time = 1000;
+        for name in &["alice", "bob", "carol"] {
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time;
+            handle(&mut deps, env, create_pool()).unwrap();
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time + DAYS;
+            handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time + 22 * DAYS;
+            handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+
+            let winner = deps.api.canonical_address(&HumanAddr::from(*name)).unwrap();
+            let mut pool = load_current_pool(&deps);
+            pool.settle(Some(winner), Uint128(10), Uint128(10), time + 22 * DAYS)
+                .unwrap();
+            // bob keeps the win private.
+            if *name != "bob" {
+                let mut state = config_read(&deps.storage).load().unwrap();
+                announce_winner(&mut deps.storage, &mut state, &mut pool).unwrap();
+                config(&mut deps.storage).save(&state).unwrap();
+            }
+            pool_storage(&mut deps.storage)
+                .save(&pool_key(pool.id), &pool)
+                .unwrap();
+            time += 30 * DAYS;
+        }
+
+        let msg = QueryMsg::GetPastWinners {
+            start_after: None,
+            limit: None,
+        };
+        let value: PastWinnersResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        let winners: Vec<(u64, HumanAddr)> = value
+            .winners
+            .into_iter()
+            .map(|w| (w.pool_id, w.winner))
+            .collect();
+        assert_eq!(
+            winners,
+            vec![(1, HumanAddr::from("alice")), (3, HumanAddr::from("carol")),]
+        );
+
+        let msg = QueryMsg::GetPastWinners {
+            start_after: Some(0),
+            limit: Some(1),
+        };
+        let value: PastWinnersResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.winners.len(), 1);
+        assert_eq!(value.winners[0].pool_id, 3);
+        assert_eq!(value.winners[0].prize_amount, Uint128(10));
+    }
+
+    #[test]
+    fn test_query_odds() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let env = mock_env("alice", &coins(10, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let env = mock_env("bob", &coins(3390, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetOdds {
+            address: HumanAddr::from("alice"),
+            key: "key".to_string(),
+        };
+        let value: OddsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            OddsResponse {
+                pool_id: Some(1),
+                weight: Uint128(10),
+                total_weight: Uint128(3400),
+                odds: Decimal::from_ratio(10u128, 3400u128),
+                one_in: Some(Uint128(340)),
+            }
+        );
+
+        let msg = QueryMsg::GetOdds {
+            address: HumanAddr::from("bob"),
+            key: "key".to_string(),
+        };
+        assert!(query(&deps, msg).is_err());
+    }
+
+    #[test]
+    fn test_time_remaining() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        let msg = QueryMsg::GetTimeRemaining {
+            time: 1000 + DAYS - 60,
+            height: 1,
+        };
+        let value: TimeRemainingResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            TimeRemainingResponse {
+                pool_id: Some(1),
+                mode: TimingMode::Time,
+                until_lock: Some(60),
+                until_close: None,
+                until_settle: None,
+            }
+        );
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 2 * DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let msg = QueryMsg::GetTimeRemaining {
+            time: 1000 + 3 * DAYS,
+            height: 1,
+        };
+        let value: TimeRemainingResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.until_lock, Some(0));
+        assert_eq!(value.until_close, Some(20 * DAYS));
+    }
+
+    #[test]
+    fn test_total_value_locked() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+
+        let res = query(&deps, QueryMsg::GetTotalValueLocked {}).unwrap();
+        let value: TotalValueLockedResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            TotalValueLockedResponse {
+                open: Uint128(0),
+                locked: Uint128(0),
+                closed: Uint128(100),
+                settled: Uint128(0),
+                total: Uint128(100),
+            }
+        );
+
+        // Drawn, but nobody has claimed their principal back yet.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("bob", &coins(40, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let res = query(&deps, QueryMsg::GetTotalValueLocked {}).unwrap();
+        let value: TotalValueLockedResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            TotalValueLockedResponse {
+                open: Uint128(40),
+                locked: Uint128(0),
+                closed: Uint128(0),
+                settled: Uint128(100),
+                total: Uint128(140),
+            }
+        );
+    }
+
+    #[test]
+    fn test_delegator_count() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let msg = QueryMsg::GetDelegatorCount { pool_id: None };
+        let value: DelegatorCountResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.pool_id, None);
+
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for name in &["alice", "bob"] {
+            let env = mock_env(*name, &coins(10, DENOM));
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(10),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetDelegatorCount { pool_id: Some(1) };
+        let value: DelegatorCountResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            DelegatorCountResponse {
+                pool_id: Some(1),
+                delegator_count: 2,
+                unique_depositors: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_prize_pot_composition() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        // The first pool ends without a winner.
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(7);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        assert_eq!(
+            config_read(&deps.storage).load().unwrap().carryover,
+            Uint128(7)
+        );
+
+        let env = mock_env("creator", &coins(50, DENOM));
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("sponsor", &coins(20, DENOM));
+        handle(&mut deps, env, HandleMsg::Sponsor { padding: None }).unwrap();
+        let env = mock_env("sponsor", &[]);
+        assert!(handle(&mut deps, env, HandleMsg::Sponsor { padding: None }).is_err());
+
+        let res = query(&deps, QueryMsg::GetCurrentPrizePot {}).unwrap();
+        let value: PrizePotResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            PrizePotResponse {
+                pool_id: Some(2),
+                seed: Uint128(50),
+                sponsored: Uint128(20),
+                carryover: Uint128(7),
+                rewards: Uint128(0),
+                total: Uint128(77),
+            }
+        );
+        // Sponsorship is not principal.
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(0));
+
+        let msg = QueryMsg::GetRewardBreakdown { pool_id: None };
+        let value: RewardBreakdownResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.pool_id, 1);
+        assert_eq!(value.winner, Uint128(0));
+        assert_eq!(value.carryover, Uint128(7));
+        assert_eq!(value.reserve, Uint128(0));
+        let msg = QueryMsg::GetRewardBreakdown { pool_id: Some(2) };
+        assert!(query(&deps, msg).is_err());
+
+        // The winner takes the rewards and all three on top.
+        let start = mock_env("creator", &[]).block.time;
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(10);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        assert_eq!(
+            config_read(&deps.storage).load().unwrap().settled_prizes,
+            Uint128(87)
+        );
+        let claim = HandleMsg::ClaimPrize {
+            pool_id: 2,
+            ibc_payout: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), claim).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(87, DENOM),
+            })]
+        );
+        let msg = QueryMsg::GetRewardBreakdown { pool_id: Some(2) };
+        let value: RewardBreakdownResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.winner, Uint128(87));
+        assert_eq!(value.reserve, Uint128(0));
+    }
+
+    #[test]
+    fn test_prize_pot_while_locked() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            validators: Some(vec![Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            }]),
+            public_rounding: Some(Uint128(10)),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &coins(24, DENOM));
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        deps.querier.update_staking(
+            DENOM,
+            &[],
+            &[FullDelegation {
+                delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                validator: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                amount: coin(100, DENOM),
+                can_redelegate: coin(0, DENOM),
+                accumulated_rewards: vec![coin(12, DENOM), coin(5, "ulsd")],
+            }],
+        );
+
+        // Rewards accrue while the stake is out, before any are realized.
+        let res = query(&deps, QueryMsg::GetCurrentPrizePot {}).unwrap();
+        let value: PrizePotResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            PrizePotResponse {
+                pool_id: Some(1),
+                seed: Uint128(20),
+                sponsored: Uint128(0),
+                carryover: Uint128(0),
+                rewards: Uint128(10),
+                total: Uint128(40),
+            }
+        );
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let mut env = mock_env("creator", &coins(50, DENOM));
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("alice", &coins(10, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let msg = QueryMsg::Dashboard {
+            time: Some(1000),
+            height: None,
+        };
+        let value: DashboardResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        let pool = value.pool.unwrap();
+        assert_eq!(pool.id, 1);
+        assert_eq!(pool.total_deposits, Uint128(10));
+        assert_eq!(value.prize_pot.seed, Uint128(50));
+        assert_eq!(value.time_remaining.unwrap().until_lock, Some(DAYS));
+        assert_eq!(value.config.owner, HumanAddr::from("creator"));
+        assert_eq!(value.stats.unique_players, 1);
+
+        // Without the current time there is nothing to count down from.
+        let value: DashboardResponse = from_binary(
+            &query(
+                &deps,
+                from_binary(&Binary::from(br#"{"dashboard":{}}"#.to_vec())).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(value.time_remaining, None);
+    }
+
+    #[test]
+    fn test_my_history() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("creator", &[]), init_msg()).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("alice", &coins(30, DENOM));
+        env.block.time = 2000;
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let mut env = mock_env("alice", &[]);
+        env.block.time = 2100;
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(5),
+            padding: None,
+        };
+        handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("alice", &coins(1, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetMyHistory {
+            address: HumanAddr::from("alice"),
+            key: "key".to_string(),
+            start_after: Some(2),
+            limit: None,
+        };
+        let value: HistoryResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value.history,
+            vec![
+                HistoryItem {
+                    index: 1,
+                    action: Action::Withdraw,
+                    pool_id: 1,
+                    amount: Uint128(5),
+                    time: 2100,
+                },
+                HistoryItem {
+                    index: 0,
+                    action: Action::Deposit,
+                    pool_id: 1,
+                    amount: Uint128(30),
+                    time: 2000,
+                },
+            ]
+        );
+
+        let msg = QueryMsg::GetMyHistory {
+            address: HumanAddr::from("alice"),
+            key: "wrong".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        assert!(query(&deps, msg).is_err());
+    }
+
+    #[test]
+    fn test_staking_and_unbonding_status() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            validators: Some(vec![
+                Validator {
+                    address: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm",
+                    ),
+                    weight: 3,
+                },
+                Validator {
+                    address: HumanAddr::from(
+                        "secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem",
+                    ),
+                    weight: 1,
+                },
+            ]),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("alice", &coins(400, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        let res = handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Staking(StakingMsg::Delegate {
+                    validator: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"
+                    ),
+                    amount: coin(300, DENOM),
+                }),
+                CosmosMsg::Staking(StakingMsg::Delegate {
+                    validator: HumanAddr::from(
+                        "secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem"
+                    ),
+                    amount: coin(100, DENOM),
+                }),
+            ]
+        );
+        let res = query(&deps, QueryMsg::GetUnbondingStatus {}).unwrap();
+        let value: UnbondingStatusResponse = from_binary(&res).unwrap();
+        assert_eq!(value.pools, vec![]);
+        let res = query(&deps, QueryMsg::GetValidatorSet {}).unwrap();
+        let value: ValidatorSetResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.validators,
+            vec![
+                ValidatorInfo {
+                    address: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"
+                    ),
+                    weight: 3,
+                    delegated: Uint128(300),
+                },
+                ValidatorInfo {
+                    address: HumanAddr::from(
+                        "secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem"
+                    ),
+                    weight: 1,
+                    delegated: Uint128(100),
+                },
+            ]
+        );
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        let res = handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[1],
+            CosmosMsg::Staking(StakingMsg::Undelegate {
+                validator: HumanAddr::from("secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem"),
+                amount: coin(100, DENOM),
+            })
+        );
+
+        let res = query(&deps, QueryMsg::GetUnbondingStatus {}).unwrap();
+        let value: UnbondingStatusResponse = from_binary(&res).unwrap();
+        assert_eq!(value.pools.len(), 1);
+        assert_eq!(value.pools[0].pool_id, 1);
+        assert_eq!(value.pools[0].amount, Uint128(400));
+        assert_eq!(value.pools[0].matures_at, 1000 + 43 * DAYS);
+        // Nothing stays staked once the pool closes.
+        let res = query(&deps, QueryMsg::GetValidatorSet {}).unwrap();
+        let value: ValidatorSetResponse = from_binary(&res).unwrap();
+        assert_eq!(value.validators[0].delegated, Uint128::zero());
+    }
+
+    #[test]
+    fn test_query_round() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let value: RoundResponse =
+            from_binary(&query(&deps, QueryMsg::GetRound {}).unwrap()).unwrap();
+        assert_eq!(value.round, 0);
+        assert_eq!(value.phase, None);
+        assert_eq!(value.schedule, vec![]);
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let value: RoundResponse =
+            from_binary(&query(&deps, QueryMsg::GetRound {}).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            RoundResponse {
+                round: 1,
+                phase: Some(PoolStatus::OPEN),
+                mode: TimingMode::Time,
+                schedule: vec![
+                    ScheduledPhase {
+                        phase: PoolStatus::LOCKED,
+                        at: 1000 + DAYS,
+                    },
+                    ScheduledPhase {
+                        phase: PoolStatus::CLOSED,
+                        at: 1000 + 22 * DAYS,
+                    },
+                ],
+            }
+        );
+
+        // Locking late pushes the close back.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 2 * DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let value: RoundResponse =
+            from_binary(&query(&deps, QueryMsg::GetRound {}).unwrap()).unwrap();
+        assert_eq!(
+            value.schedule,
+            vec![ScheduledPhase {
+                phase: PoolStatus::CLOSED,
+                at: 1000 + 23 * DAYS,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_leaderboard_is_opt_in() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for (name, amount) in &[("alice", 10), ("bob", 30), ("carol", 20)] {
+            let env = mock_env(*name, &coins(*amount, DENOM));
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let msg = HandleMsg::SetPublicListing {
+            public: true,
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("dave", &[]), msg).is_err());
+        for name in &["alice", "bob", "carol"] {
+            let msg = HandleMsg::SetPublicListing {
+                public: true,
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*name, &[]), msg).unwrap();
+        }
+        // alice leaves and carol, listed last, moves into the freed slot.
+        let msg = HandleMsg::SetPublicListing {
+            public: false,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetLeaderboard {
+            order_by: LeaderboardOrder::TotalDeposited,
+            start_after: None,
+            limit: None,
+        };
+        let value: LeaderboardResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        let ranked: Vec<HumanAddr> = value.entries.into_iter().map(|e| e.address).collect();
+        assert_eq!(
+            ranked,
+            vec![HumanAddr::from("bob"), HumanAddr::from("carol")]
+        );
+
+        // Leaving again is a no-op, and bob can still leave after the swap.
+        let msg = HandleMsg::SetPublicListing {
+            public: false,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        let msg = HandleMsg::SetPublicListing {
+            public: false,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+        let msg = QueryMsg::GetLeaderboard {
+            order_by: LeaderboardOrder::RoundsPlayed,
+            start_after: None,
+            limit: Some(1),
+        };
+        let value: LeaderboardResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.entries.len(), 1);
+        assert_eq!(value.entries[0].address, HumanAddr::from("carol"));
+        let msg = QueryMsg::GetLeaderboard {
+            order_by: LeaderboardOrder::RoundsPlayed,
+            start_after: Some(0),
+            limit: Some(1),
+        };
+        let value: LeaderboardResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.entries, vec![]);
+    }
+
+    #[test]
+    fn test_full_leaderboard_drops_the_smallest_depositor() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let join = |deps: &mut Extern<_, _, _>, name: &str, amount: u128| {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            handle(deps, mock_env(name, &coins(amount, DENOM)), msg).unwrap();
+            let msg = HandleMsg::SetPublicListing {
+                public: true,
+                padding: None,
+            };
+            handle(deps, mock_env(name, &[]), msg)
+        };
+        // player0 deposited the least.
+        for i in 0..MAX_LISTED_PLAYERS {
+            let name = format!("player{}", i);
+            join(&mut deps, &name, 10 + u128::from(i)).unwrap();
+        }
+        let res = join(&mut deps, "minnow", 5);
+        assert_eq!(res.unwrap_err(), ContractError::LeaderboardFull.into());
+
+        join(&mut deps, "whale", 500).unwrap();
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(state.listed_players, MAX_LISTED_PLAYERS);
+        let player0 = deps
+            .api
+            .canonical_address(&HumanAddr::from("player0"))
+            .unwrap();
+        let record = may_load_player(&deps.storage, &player0).unwrap().unwrap();
+        assert_eq!(record.listing_index, None);
+        let msg = QueryMsg::GetLeaderboard {
+            order_by: LeaderboardOrder::TotalDeposited,
+            start_after: None,
+            limit: Some(1),
+        };
+        let value: LeaderboardResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.entries[0].address, HumanAddr::from("whale"));
+        // The dropped player can leave without touching the whale's slot.
+        let msg = HandleMsg::SetPublicListing {
+            public: false,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("player0", &[]), msg).unwrap();
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(state.listed_players, MAX_LISTED_PLAYERS);
+    }
+
+    #[test]
+    fn test_query_statistics() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.stats.pools_run = 3;
+        state.stats.prizes_awarded = 2;
+        state.stats.total_prizes_paid = Uint128(25);
+        state.stats.total_rewards = Uint128(40);
+        config(&mut deps.storage).save(&state).unwrap();
+
+        let res = query(&deps, QueryMsg::GetStatistics {}).unwrap();
+        let value: StatisticsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            StatisticsResponse {
+                pools_run: 3,
+                prizes_awarded: 2,
+                total_prizes_paid: Uint128(25),
+                average_prize: Uint128(12),
+                total_rewards: Uint128(40),
+                unique_players: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+
+        let msg = QueryMsg::ChangesSince {
+            start_after: None,
+            limit: None,
+        };
+        let value: ChangesResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.event_count, 3);
+        let kinds: Vec<EventKind> = value.changes.into_iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::PoolCreated,
+                EventKind::PoolLocked,
+                EventKind::PoolClosed,
+            ]
+        );
+
+        let msg = QueryMsg::ChangesSince {
+            start_after: Some(1),
+            limit: None,
+        };
+        let value: ChangesResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value.changes,
+            vec![Change {
+                seq: 2,
+                kind: EventKind::PoolClosed,
+                pool_id: 1,
+                time: 1000 + 22 * DAYS,
+            }]
+        );
+        let msg = QueryMsg::ChangesSince {
+            start_after: Some(2),
+            limit: None,
+        };
+        let value: ChangesResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.changes, vec![]);
+    }
+
+    #[test]
+    fn test_handlers_log_action_attributes() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let env = mock_env("creator", &coins(5, DENOM));
+        let res = handle(&mut deps, env, create_pool()).unwrap();
+        assert_eq!(
+            res.log[..5],
+            [
+                log("action", "create_pool"),
+                log("sender", "creator"),
+                log("pool_id", 1),
+                log("status", "OPEN"),
+                log("amount", 5),
+            ]
+        );
+        // Lifecycle milestones follow the action attributes.
+        assert_eq!(res.log[5], log("event", "pool_created"));
+        assert_eq!(res.log[7], log("seed_amount", 5));
+
+        let env = mock_env("alice", &coins(10, DENOM));
+        let res = handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.log[0], log("action", "deposit"));
+        assert_eq!(res.log[4], log("amount", 10));
+
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        // The key itself is never logged.
+        assert_eq!(
+            res.log,
+            vec![log("action", "set_viewing_key"), log("sender", "alice")]
+        );
+    }
+
+    #[test]
+    fn test_batch_query() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let msg = QueryMsg::Batch {
+            queries: vec![
+                QueryMsg::GetOwner {},
+                QueryMsg::GetCurrentPool { caller: None },
+                QueryMsg::GetPool {
+                    pool_id: 9,
+                    caller: None,
+                },
+            ],
+        };
+        let res: BatchResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.results.len(), 3);
+        match &res.results[0].ok {
+            Some(BatchAnswer::GetOwner(owner)) => {
+                assert_eq!(owner.owner, HumanAddr::from("creator"))
+            }
+            other => panic!("unexpected answer {:?}", other),
+        }
+        match &res.results[1].ok {
+            Some(BatchAnswer::GetCurrentPool(pool)) => {
+                assert_eq!(pool.pool.as_ref().unwrap().id, 1)
+            }
+            other => panic!("unexpected answer {:?}", other),
+        }
+        // A failing sub-query is reported in place.
+        assert_eq!(res.results[2].ok, None);
+        assert!(res.results[2].error.is_some());
+
+        let nested = QueryMsg::Batch {
+            queries: vec![QueryMsg::Batch { queries: vec![] }],
+        };
+        assert!(query(&deps, nested).is_err());
+        let keyed = QueryMsg::Batch {
+            queries: vec![
+                QueryMsg::GetOwner {},
+                QueryMsg::GetOdds {
+                    address: HumanAddr::from("alice"),
+                    key: "wrong".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            query(&deps, keyed).unwrap_err(),
+            ContractError::AuthenticatedInBatch.into()
+        );
+        let aggregator = QueryMsg::Batch {
+            queries: vec![QueryMsg::LotteryV1(LotteryQueryV1::Interface {})],
+        };
+        let res: BatchResponse = from_binary(&query(&deps, aggregator).unwrap()).unwrap();
+        assert_eq!(
+            res.results[0].error,
+            Some(StdError::from(ContractError::NotBatchable).to_string())
+        );
+        let too_many = QueryMsg::Batch {
+            queries: vec![QueryMsg::GetOwner {}; MAX_LIMIT as usize + 1],
+        };
+        assert!(query(&deps, too_many).is_err());
+    }
+
+    #[test]
+    fn test_fee_changes_apply_to_the_next_pool() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+
+        let set_fee = |rate| HandleMsg::SetFee {
+            rate,
+            recipient: Some(HumanAddr::from("treasury")),
+            padding: None,
+        };
+        assert!(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            set_fee(Decimal::percent(5))
+        )
+        .is_err());
+        assert!(handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_fee(Decimal::permille(201))
+        )
+        .is_err());
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_fee(Decimal::percent(5)),
+        )
+        .unwrap();
+
+        let value: FeeInfoResponse =
+            from_binary(&query(&deps, QueryMsg::GetFeeInfo {}).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            FeeInfoResponse {
+                rate: Decimal::zero(),
+                max_rate: Decimal::percent(20),
+                recipient: HumanAddr::from("creator"),
+                accrued: Uint128::zero(),
+                pending: Some(PendingFee {
+                    rate: Decimal::percent(5),
+                    recipient: HumanAddr::from("treasury"),
+                    effective_from_pool: 2,
+                }),
+            }
+        );
+
+        for (msg, time) in &[
+            (HandleMsg::LockPool { padding: None }, 1000 + DAYS),
+            (HandleMsg::ClsePool { padding: None }, 1000 + 22 * DAYS),
+            (HandleMsg::Draw { padding: None }, 1000 + 22 * DAYS),
+            (create_pool(), 1000 + 22 * DAYS),
+        ] {
+            let mut env = mock_env("creator", &[]);
+            env.block.time = *time;
+            handle(&mut deps, env, msg.clone()).unwrap();
+        }
+        let value: FeeInfoResponse =
+            from_binary(&query(&deps, QueryMsg::GetFeeInfo {}).unwrap()).unwrap();
+        assert_eq!(value.rate, Decimal::percent(5));
+        assert_eq!(value.recipient, HumanAddr::from("treasury"));
+        assert_eq!(value.pending, None);
+        assert_eq!(load_current_pool(&deps).fee_rate, Decimal::percent(5));
+    }
+
+    #[test]
+    fn test_guardian_recovery() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            guardian: Some(HumanAddr::from("guardian")),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let start = HandleMsg::StartRecovery {
+            new_owner: HumanAddr::from("rescue"),
+            padding: None,
+        };
+        let finish = HandleMsg::FinishRecovery { padding: None };
+        let cancel = HandleMsg::CancelRecovery { padding: None };
+
+        // Only the guardian starts or completes a recovery.
+        let res = handle(&mut deps, at("creator", 1000), start.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(&mut deps, at("guardian", 1000), finish.clone());
+        assert_eq!(res.unwrap_err(), ContractError::NoRecovery.into());
+        handle(&mut deps, at("guardian", 1000), start.clone()).unwrap();
+        let res = handle(&mut deps, at("guardian", 1000), start.clone());
+        assert_eq!(res.unwrap_err(), ContractError::RecoveryPending.into());
+        let value: RecoveryResponse =
+            from_binary(&query(&deps, QueryMsg::GetRecovery {}).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            RecoveryResponse {
+                guardian: Some(HumanAddr::from("guardian")),
+                pending: Some(PendingRecovery {
+                    new_owner: HumanAddr::from("rescue"),
+                    executable_at: 1000 + RECOVERY_DELAY,
+                }),
+            }
+        );
+
+        // Only the guardian can call it off; an owner with a stolen key can't veto it.
+        let res = handle(&mut deps, at("alice", 1000), cancel.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(&mut deps, at("creator", 1000), cancel.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, at("guardian", 2000), cancel).unwrap();
+        let value: RecoveryResponse =
+            from_binary(&query(&deps, QueryMsg::GetRecovery {}).unwrap()).unwrap();
+        assert_eq!(value.pending, None);
+
+        // Otherwise the guardian hands the contract over once the delay is up.
+        handle(&mut deps, at("guardian", 3000), start).unwrap();
+        let res = handle(
+            &mut deps,
+            at("guardian", 3000 + RECOVERY_DELAY - 1),
+            finish.clone(),
+        );
+        let err = ContractError::RecoveryNotReady {
+            remaining: 1,
+            unit: "seconds".to_string(),
+        };
+        assert_eq!(res.unwrap_err(), err.into());
+        handle(&mut deps, at("guardian", 3000 + RECOVERY_DELAY), finish).unwrap();
+        let value: OwnerResponse =
+            from_binary(&query(&deps, QueryMsg::GetOwner {}).unwrap()).unwrap();
+        assert_eq!(value.owner, HumanAddr::from("rescue"));
+        let create = create_pool();
+        let res = handle(&mut deps, mock_env("creator", &[]), create.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("rescue", &[]), create).unwrap();
+    }
+
+    #[test]
+    fn test_export_state() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for name in &["alice", "bob", "creator"] {
+            let msg = HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*name, &[]), msg).unwrap();
+        }
+        for name in &["alice", "bob"] {
+            let env = mock_env(*name, &coins(10, DENOM));
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let export = |address: &str, cursor: Option<ExportCursor>| {
+            let msg = QueryMsg::ExportState {
+                address: HumanAddr::from(address),
+                key: "key".to_string(),
+                cursor,
+                limit: Some(1),
+            };
+            query(&deps, msg)
+        };
+        // Players can't export, even with a valid key.
+        assert!(export("alice", None).is_err());
+
+        let page: ExportResponse = from_binary(&export("creator", None).unwrap()).unwrap();
+        assert_eq!(page.config.unwrap().pool_count, 1);
+        assert_eq!(page.pool_index.unwrap().current_open, Some(1));
+        let mut cursor = page.next;
+        let mut addresses = vec![];
+        while cursor.is_some() {
+            let page: ExportResponse = from_binary(&export("creator", cursor).unwrap()).unwrap();
+            if let Some(pool) = page.pool {
+                assert_eq!(pool.id, 1);
+            }
+            for item in page.entries {
+                assert_eq!(item.entry.amount, Uint128(10));
+                addresses.push(item.address);
+            }
+            cursor = page.next;
+        }
+        assert_eq!(
+            addresses,
+            vec![HumanAddr::from("alice"), HumanAddr::from("bob")]
+        );
+    }
+
+    // Write an export page back into fresh storage, the way a migration tool would.
+    // `cursor` is the one the page was fetched with.
+    fn import_export_page(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        cursor: &Option<ExportCursor>,
+        page: ExportResponse,
+    ) {
+        let storage = &mut deps.storage;
+        let api = deps.api;
+        let canonical = |address: &HumanAddr| api.canonical_address(address).unwrap();
+        if let Some(state) = page.config {
+            config(storage).save(&state).unwrap();
+        }
+        if let Some(index) = page.pool_index {
+            pool_index(storage).save(&index).unwrap();
+        }
+        if let Some(pool) = page.pool {
+            pool_storage(storage)
+                .save(&pool_key(pool.id), &pool)
+                .unwrap();
+            for (index, transition) in page.transitions.iter().enumerate() {
+                pool_transitions(storage, pool.id)
+                    .save(&(index as u64).to_be_bytes(), transition)
+                    .unwrap();
+            }
+            for item in page.entries {
+                let address = canonical(&item.address);
+                pool_delegators(storage, pool.id)
+                    .save(&item.entry.index.to_be_bytes(), &address)
+                    .unwrap();
+                save_entry(storage, pool.id, &address, &item.entry).unwrap();
+                if let Some(memo) = item.memo {
+                    save_memo(storage, pool.id, &address, &memo).unwrap();
+                }
+                save_entry_balances(storage, pool.id, &address, &item.balances).unwrap();
+                if item.emission_claimed {
+                    mark_emission_claimed(storage, pool.id, &address);
+                }
+            }
+        }
+        if let Some(ExportCursor::Referred { pool_id, .. }) = cursor {
+            for item in page.referred {
+                add_referred(storage, *pool_id, item.code_hash.as_slice(), item.amount).unwrap();
+                if item.claimed {
+                    mark_referral_claimed(storage, *pool_id, item.code_hash.as_slice());
+                }
+            }
+        }
+        for item in page.players {
+            let address = canonical(&item.address);
+            save_player(storage, &address, &item.player).unwrap();
+            save_seen_keys(storage, &address, &item.idempotency_keys).unwrap();
+            if let Some(callback) = item.winner_callback {
+                winner_callbacks(storage)
+                    .save(address.as_slice(), &callback)
+                    .unwrap();
+            }
+        }
+        if let Some(history) = page.history {
+            let address = canonical(&history.address);
+            for (offset, entry) in history.entries.iter().enumerate() {
+                let index = history.start + offset as u64;
+                player_history(storage, &address)
+                    .save(&index.to_be_bytes(), entry)
+                    .unwrap();
+            }
+        }
+        for item in page.referrals {
+            let referral = Referral {
+                referrer: canonical(&item.referrer),
+                deposits: item.deposits,
+                referred_amount: item.referred_amount,
+            };
+            save_referral(storage, item.code_hash.as_slice(), &referral).unwrap();
+        }
+        // The rest are index-keyed lists without gaps.
+        let start = match cursor {
+            Some(ExportCursor::Children { start_after })
+            | Some(ExportCursor::Winners { start_after })
+            | Some(ExportCursor::Events { start_after }) => start_after.map_or(0, |i| i + 1),
+            _ => 0,
+        };
+        for (offset, child) in page.children.iter().enumerate() {
+            let index = start + offset as u64;
+            children(storage).save(&index.to_be_bytes(), child).unwrap();
+        }
+        for (offset, pool_id) in page.winners.iter().enumerate() {
+            let index = start + offset as u64;
+            crate::state::winners(storage)
+                .save(&index.to_be_bytes(), pool_id)
+                .unwrap();
+        }
+        for (offset, event) in page.events.iter().enumerate() {
+            let index = start + offset as u64;
+            crate::state::events(storage)
+                .save(&index.to_be_bytes(), event)
+                .unwrap();
+        }
+    }
+
+    // Every page of an export, with the cursor it was fetched with.
+    fn export_all(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+    ) -> Vec<(Option<ExportCursor>, ExportResponse)> {
+        let mut pages = vec![];
+        let mut cursor = None;
+        loop {
+            let msg = QueryMsg::ExportState {
+                address: HumanAddr::from("creator"),
+                key: "key".to_string(),
+                cursor: cursor.clone(),
+                limit: Some(1),
+            };
+            let page: ExportResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            let next = page.next.clone();
+            pages.push((cursor, page));
+            if next.is_none() {
+                return pages;
+            }
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            transparent: Some(true),
+            guardian: Some(HumanAddr::from("guardian")),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::RegisterReferralCode {
+            code: "ref".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("rita", &[]), msg).unwrap();
+        let msg = HandleMsg::SetWinnerCallback {
+            callback: Some(WinnerCallback {
+                code_hash: "vault_hash".to_string(),
+                attach_prize: false,
+            }),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        for sender in &["alice", "bob", "carol"] {
+            let msg = HandleMsg::Deposit {
+                memo: Some(format!("from {}", sender)),
+                referral: Some("ref".to_string()),
+                idempotency_key: Some("once".to_string()),
+                padding: None,
+            };
+            let mut env = mock_env(*sender, &coins(100, DENOM));
+            env.block.time = 1000;
+            handle(&mut deps, env, msg).unwrap();
+        }
+        for (msg, time) in vec![
+            (HandleMsg::LockPool { padding: None }, 1000 + DAYS),
+            (HandleMsg::ClsePool { padding: None }, 1000 + 22 * DAYS),
+            (HandleMsg::Draw { padding: None }, 1000 + 23 * DAYS),
+        ] {
+            let mut env = mock_env("creator", &[]);
+            env.block.time = time;
+            handle(&mut deps, env, msg).unwrap();
+        }
+        // Emission claims, child rounds and recoveries take more setup than they're worth
+        // here; what matters is that they come back out.
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        mark_emission_claimed(&mut deps.storage, 1, &alice);
+        mark_referral_claimed(&mut deps.storage, 1, &referral_code_hash("ref"));
+        let child = Child {
+            label: "round-2".to_string(),
+            address: Some(HumanAddr::from("child")),
+            created_at: 1000,
+            code_hash: "lottery_hash".to_string(),
+        };
+        children(&mut deps.storage)
+            .save(&0u64.to_be_bytes(), &child)
+            .unwrap();
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.child_count = 1;
+        state.recovery = Some(Recovery {
+            new_owner: alice.clone(),
+            executable_at: 5000,
+        });
+        config(&mut deps.storage).save(&state).unwrap();
+
+        let pages = export_all(&deps);
+        let config_page = &pages[0].1;
+        assert_eq!(
+            config_page.config.as_ref().unwrap().recovery,
+            state.recovery
+        );
+        let entries: Vec<&ExportEntry> = pages.iter().flat_map(|(_, p)| &p.entries).collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].memo, Some("from alice".to_string()));
+        assert!(entries[0].emission_claimed);
+        assert!(!entries[1].emission_claimed);
+        let referred: Vec<&ExportReferred> = pages.iter().flat_map(|(_, p)| &p.referred).collect();
+        assert_eq!(referred.len(), 1);
+        assert_eq!(referred[0].amount, Uint128(300));
+        assert!(referred[0].claimed);
+        let players: Vec<&ExportPlayer> = pages.iter().flat_map(|(_, p)| &p.players).collect();
+        assert_eq!(players.len(), 3);
+        assert_eq!(players[0].idempotency_keys.len(), 1);
+        assert!(players[1].winner_callback.is_some());
+        let histories = pages.iter().filter_map(|(_, p)| p.history.as_ref());
+        assert_eq!(histories.map(|h| h.entries.len()).sum::<usize>(), 3);
+        let referrals: Vec<&ExportReferral> =
+            pages.iter().flat_map(|(_, p)| &p.referrals).collect();
+        assert_eq!(referrals.len(), 1);
+        assert_eq!(referrals[0].referrer, HumanAddr::from("rita"));
+        let exported_children: Vec<&Child> = pages.iter().flat_map(|(_, p)| &p.children).collect();
+        assert_eq!(exported_children, vec![&child]);
+        assert_eq!(pages.iter().map(|(_, p)| p.winners.len()).sum::<usize>(), 1);
+        let event_count = pages.iter().map(|(_, p)| p.events.len()).sum::<usize>();
+        assert_eq!(event_count as u64, state.event_count);
+
+        // Importing everything and exporting again gives the same pages.
+        let mut copy = mock_dependencies(20, &[]);
+        for (cursor, page) in pages.clone() {
+            import_export_page(&mut copy, &cursor, page);
+        }
+        let creator = copy
+            .api
+            .canonical_address(&HumanAddr::from("creator"))
+            .unwrap();
+        write_viewing_key(&mut copy.storage, &creator, "key");
+        assert_eq!(export_all(&copy), pages);
+    }
+
+    #[test]
+    fn test_health_check() {
+        let mut deps = mock_dependencies(20, &coins(100, DENOM));
+        let msg = InitMsg {
+            validators: Some(vec![Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            }]),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let env = mock_env("alice", &coins(100, DENOM));
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let health = |deps: &Extern<_, _, _>| -> HealthResponse {
+            from_binary(&query(deps, QueryMsg::HealthCheck {}).unwrap()).unwrap()
+        };
+        let value = health(&deps);
+        assert!(value.healthy);
+        assert_eq!(
+            value.checks[1],
+            InvariantCheck {
+                name: "liquid_balance".to_string(),
+                expected: Uint128(100),
+                actual: Uint128(100),
+                passed: true,
+            }
+        );
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        // The staking module doesn't report the delegation yet.
+        let value = health(&deps);
+        assert!(!value.healthy);
+        assert_eq!(value.checks[0].name, "delegations");
+        assert_eq!(value.checks[0].expected, Uint128(100));
+        assert_eq!(value.checks[0].actual, Uint128(0));
+
+        deps.querier.update_staking(
+            DENOM,
+            &[],
+            &[FullDelegation {
+                delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                validator: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                amount: coin(100, DENOM),
+                can_redelegate: coin(0, DENOM),
+                accumulated_rewards: vec![],
+            }],
+        );
+        assert!(health(&deps).healthy);
+    }
+
+    #[test]
+    fn test_health_check_without_validators() {
+        // Nothing is staked by default, so a LOCKED pool's deposits stay in the contract.
+        let mut deps = mock_dependencies(20, &coins(100, DENOM));
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let health = |deps: &Extern<_, _, _>| -> HealthResponse {
+            from_binary(&query(deps, QueryMsg::HealthCheck {}).unwrap()).unwrap()
+        };
+        let value = health(&deps);
+        assert!(value.healthy);
+        assert_eq!(
+            value.checks,
+            vec![
+                InvariantCheck {
+                    name: "delegations".to_string(),
+                    expected: Uint128(0),
+                    actual: Uint128(0),
+                    passed: true,
+                },
+                InvariantCheck {
+                    name: "liquid_balance".to_string(),
+                    expected: Uint128(100),
+                    actual: Uint128(100),
+                    passed: true,
+                },
+            ]
+        );
+        // The deposits are owed, so they can't pay for anything else.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(60, DENOM));
+        assert!(!health(&deps).healthy);
+    }
+
+    #[test]
+    fn test_health_check_counts_settled_pools() {
+        let mut deps = closed_pool(b"seed");
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(30);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let liquid_balance = |deps: &Extern<_, _, _>| -> InvariantCheck {
+            let value: HealthResponse =
+                from_binary(&query(deps, QueryMsg::HealthCheck {}).unwrap()).unwrap();
+            let check = value
+                .checks
+                .into_iter()
+                .find(|c| c.name == "liquid_balance");
+            check.unwrap()
+        };
+
+        // The settled pool owes its principal and an unclaimed prize of 30.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DENOM));
+        assert_eq!(
+            liquid_balance(&deps),
+            InvariantCheck {
+                name: "liquid_balance".to_string(),
+                expected: Uint128(330),
+                actual: Uint128(300),
+                passed: false,
+            }
+        );
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(330, DENOM));
+        assert!(liquid_balance(&deps).passed);
+
+        // Paid-out principal is no longer owed.
+        let msg = HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(230, DENOM));
+        let check = liquid_balance(&deps);
+        assert_eq!(check.expected, Uint128(230));
+        assert!(check.passed);
+    }
+
+    #[test]
+    fn test_handle_msg_padding_is_ignored() {
+        let msg: HandleMsg = from_slice(br#"{"deposit":{"padding":"          "}}"#).unwrap();
+        assert_eq!(
+            msg,
+            HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: Some("          ".to_string())
+            }
+        );
+        let msg: HandleMsg = from_slice(br#"{"withdraw":{"amount":"5"}}"#).unwrap();
+        assert_eq!(
+            msg,
+            HandleMsg::Withdraw {
+                amount: Uint128(5),
+                padding: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_public_totals_are_rounded() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            public_rounding: Some(Uint128(1000)),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        for (sender, amount) in &[("alice", 1400), ("bob", 300)] {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*sender, &coins(*amount, DENOM)), msg).unwrap();
+        }
+
+        // 1700 rounds to 2000; bob's 300 can't be told apart from no deposit at all.
+        let res = query(&deps, QueryMsg::GetTotalValueLocked {}).unwrap();
+        let value: TotalValueLockedResponse = from_binary(&res).unwrap();
+        assert_eq!(value.open, Uint128(2000));
+        assert_eq!(value.total, Uint128(2000));
+        let res = query(&deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+        let pool = from_binary::<PoolResponse>(&res).unwrap().pool.unwrap();
+        assert_eq!(pool.total_deposits, Uint128(2000));
+        assert_eq!(pool.total_weight, Uint128(2000));
+        let res = query(&deps, QueryMsg::GetStats {}).unwrap();
+        let value: StatsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.total_deposited, Uint128(2000));
+
+        // Authenticated queries show the caller's own amounts exactly, but the pool
+        // total behind their odds only as rounded.
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+        let msg = QueryMsg::GetOdds {
+            address: HumanAddr::from("bob"),
+            key: "key".to_string(),
+        };
+        let value: OddsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.weight, Uint128(300));
+        assert_eq!(value.total_weight, Uint128(2000));
+        assert_eq!(value.odds, Decimal::percent(15));
+        assert_eq!(value.one_in, Some(Uint128(7)));
+        let msg = QueryMsg::GetMyDeposit {
+            address: HumanAddr::from("bob"),
+            key: "key".to_string(),
+        };
+        let value: MyDepositResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.balance, Uint128(300));
+        assert_eq!(value.odds, Decimal::percent(15));
+
+        // Private queries still report exact amounts.
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(1700));
+    }
+
+    #[test]
+    fn test_delayed_public_totals() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            delay_public_totals: Some(true),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(300, DENOM)), msg).unwrap();
+
+        let public_totals = |deps: &Extern<_, _, _>| {
+            let res = query(deps, QueryMsg::GetCurrentPool { caller: None }).unwrap();
+            let pool = from_binary::<PoolResponse>(&res).unwrap().pool.unwrap();
+            let res = query(deps, QueryMsg::GetStats {}).unwrap();
+            let stats: StatsResponse = from_binary(&res).unwrap();
+            (
+                pool.total_deposits,
+                pool.unique_depositors,
+                stats.total_deposited,
+            )
+        };
+        // The deposit doesn't show until the pool changes phase, not even in the odds.
+        let msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        let odds = |deps: &Extern<_, _, _>| {
+            let msg = QueryMsg::GetOdds {
+                address: HumanAddr::from("alice"),
+                key: "key".to_string(),
+            };
+            let value: OddsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            (value.total_weight, value.odds)
+        };
+        assert_eq!(public_totals(&deps), (Uint128(0), 0, Uint128(0)));
+        assert_eq!(odds(&deps), (Uint128(0), Decimal::one()));
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(300));
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        assert_eq!(public_totals(&deps), (Uint128(300), 1, Uint128(300)));
+        assert_eq!(odds(&deps), (Uint128(300), Decimal::one()));
+    }
+
+    #[test]
+    fn test_rotate_seed() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            prng_seed: Some(Binary::from(b"weak".to_vec())),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let rotate = |entropy: &str| HandleMsg::RotateSeed {
+            entropy: entropy.to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), rotate("x"));
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+
+        let seed_before = deps.storage.get(PRNG_SEED_KEY).unwrap();
+        handle(&mut deps, mock_env("creator", &[]), rotate("fresh")).unwrap();
+        let seed_after = deps.storage.get(PRNG_SEED_KEY).unwrap();
+        assert_ne!(seed_before, seed_after);
+
+        // Still allowed once a round is locked; its draw is sealed, see below.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        handle(&mut deps, mock_env("creator", &[]), rotate("open")).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let seed_locked = deps.storage.get(PRNG_SEED_KEY).unwrap();
+        handle(&mut deps, mock_env("creator", &[]), rotate("late")).unwrap();
+        assert_ne!(deps.storage.get(PRNG_SEED_KEY).unwrap(), seed_locked);
+    }
+
+    // Alice, bob and carol deposit 100 each into a pool that is then locked and closed.
+    fn closed_pool(seed: &[u8]) -> Extern<MockStorage, MockApi, MockQuerier> {
+        closed_pool_with(seed, |_| {})
+    }
+
+    // `closed_pool`, running `while_locked` between the lock and the close.
+    fn closed_pool_with(
+        seed: &[u8],
+        while_locked: impl FnOnce(&mut Extern<MockStorage, MockApi, MockQuerier>),
+    ) -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            prng_seed: Some(Binary::from(seed)),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        for sender in &["alice", "bob", "carol"] {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*sender, &coins(100, DENOM)), msg).unwrap();
+        }
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        while_locked(&mut deps);
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        deps
+    }
+
+    #[test]
+    fn test_draw_does_not_depend_on_the_message() {
+        // Same contract state, drawn by differently shaped messages in different blocks.
+        let mut winners = vec![];
+        for (height, padding) in &[(100, None), (9_999, Some("abcdefgh".to_string()))] {
+            let mut deps = closed_pool(b"seed");
+            let mut env = mock_env("creator", &[]);
+            env.block.height = *height;
+            env.block.time = 1000 + 23 * DAYS + height;
+            let msg = HandleMsg::Draw {
+                padding: padding.clone(),
+            };
+            handle(&mut deps, env, msg).unwrap();
+            winners.push(load_current_pool(&deps).winner.unwrap());
+        }
+        assert_eq!(winners[0], winners[1]);
+
+        // The sealed seed is what decides.
+        let mut seen = vec![];
+        for seed in 0u8..16 {
+            let mut deps = closed_pool(&[seed]);
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000 + 23 * DAYS;
+            handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+            let winner = load_current_pool(&deps).winner.unwrap();
+            if !seen.contains(&winner) {
+                seen.push(winner);
+            }
+        }
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn test_draw_uses_the_seed_sealed_at_lock() {
+        let draw = |mut deps: Extern<MockStorage, MockApi, MockQuerier>| {
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000 + 23 * DAYS;
+            handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+            load_current_pool(&deps).draw.unwrap().target
+        };
+        let expected = draw(closed_pool(b"seed"));
+
+        // Viewing keys created after the lock move the live seed, not the sealed one.
+        let deps = closed_pool_with(b"seed", |deps| {
+            for sender in &["alice", "dave", "erin"] {
+                let msg = HandleMsg::CreateViewingKey {
+                    entropy: sender.to_string(),
+                    padding: None,
+                };
+                handle(deps, mock_env(*sender, &[]), msg).unwrap();
+            }
+        });
+        assert_eq!(draw(deps), expected);
+
+        // Neither does an owner rotating the seed mid-round.
+        let deps = closed_pool_with(b"seed", |deps| {
+            let msg = HandleMsg::RotateSeed {
+                entropy: "mid-round".to_string(),
+                padding: None,
+            };
+            handle(deps, mock_env("creator", &[]), msg).unwrap();
+        });
+        assert_eq!(draw(deps), expected);
+    }
+
+    #[test]
+    fn test_draw_settles_the_closed_pool() {
+        let mut deps = closed_pool(b"seed");
+        let draw = HandleMsg::Draw { padding: None };
+        let res = handle(&mut deps, mock_env("alice", &[]), draw.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        let res = handle(&mut deps, env.clone(), draw.clone()).unwrap();
+        assert!(res.log.contains(&log("event", "winner_drawn")));
+        let pool = load_current_pool(&deps);
+        assert!(pool.is_settled());
+        assert_eq!(pool.settled_at, Some(1000 + 23 * DAYS));
+        assert!(handle(&mut deps, env, draw).is_err());
+    }
+
+    #[test]
+    fn test_prepare_draw_resumes() {
+        let progress = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let msg = QueryMsg::GetSettlementProgress { pool_id: None };
+            let res: SettlementProgressResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            (res.stage, res.draw_cursor, res.cleanup_cursor)
+        };
+        let mut expected = closed_pool(b"seed");
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut expected, env, HandleMsg::Draw { padding: None }).unwrap();
+
+        let mut deps = closed_pool(b"seed");
+        assert_eq!(progress(&deps), (SettlementStage::Pending, 0, 0));
+        // Anyone can move the scan along, a slot at a time.
+        let prepare = HandleMsg::PrepareDraw {
+            limit: Some(1),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), prepare.clone()).unwrap();
+        assert_eq!(res.log.last(), Some(&log("done", false)));
+        assert_eq!(progress(&deps), (SettlementStage::PreparingDraw, 1, 0));
+        handle(&mut deps, mock_env("bob", &[]), prepare.clone()).unwrap();
+        let res = handle(&mut deps, mock_env("bob", &[]), prepare.clone()).unwrap();
+        assert_eq!(res.log.last(), Some(&log("done", true)));
+        assert_eq!(progress(&deps), (SettlementStage::ReadyToSettle, 3, 0));
+        // Calling again once done changes nothing.
+        handle(&mut deps, mock_env("carol", &[]), prepare.clone()).unwrap();
+        assert_eq!(progress(&deps), (SettlementStage::ReadyToSettle, 3, 0));
+
+        // The draw picks up the finished scan and lands on the same winner.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        assert_eq!(
+            load_current_pool(&deps).winner,
+            load_current_pool(&expected).winner
+        );
+        let msg = QueryMsg::GetSettlementProgress { pool_id: Some(1) };
+        let res: SettlementProgressResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.stage, SettlementStage::CleaningUp);
+        let res = handle(&mut deps, mock_env("alice", &[]), prepare);
+        assert_eq!(res.unwrap_err(), ContractError::NoPendingSettlement.into());
+
+        let msg = HandleMsg::CleanupPool {
+            pool_id: 1,
+            limit: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        let msg = QueryMsg::GetSettlementProgress { pool_id: Some(1) };
+        let res: SettlementProgressResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!((res.stage, res.cleanup_cursor), (SettlementStage::Done, 3));
+    }
+
+    #[test]
+    fn test_create_waits_for_unclaimed_prizes() {
+        let mut deps = closed_pool(b"seed");
+        let msg = HandleMsg::SetMaxUnclaimedPrizes {
+            max: Some(0),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), msg.clone()).is_err());
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        let create = create_pool();
+        let res = handle(&mut deps, env.clone(), create.clone());
+        let err = ContractError::TooManyUnclaimedPrizes { max: 0 };
+        assert_eq!(res.unwrap_err(), err.into());
+
+        // Paying the prize out clears the way.
+        let winner = load_current_pool(&deps).winner.unwrap();
+        let winner = deps.api.human_address(&winner).unwrap();
+        let claim = HandleMsg::ClaimPrize {
+            pool_id: 1,
+            ibc_payout: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env(winner, &[]), claim).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        let prize = load_current_pool(&deps).prize_amount;
+        assert_eq!(
+            answer,
+            HandleAnswer::ClaimPrize {
+                pool_id: 1,
+                amount: prize,
+                denom: DENOM.to_string(),
+            }
+        );
+        assert_eq!(
+            config_read(&deps.storage).load().unwrap().unclaimed_prizes,
+            0
+        );
+        handle(&mut deps, env, create).unwrap();
+    }
+
+    #[test]
+    fn test_close_waits_for_the_earlier_settlement() {
+        let mut deps = closed_pool(b"seed");
+        let start = 1000 + 22 * DAYS;
+        let create = create_pool();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start;
+        let res = handle(&mut deps, env.clone(), create.clone());
+        let err = ContractError::SettlementPending { pool_id: 1 };
+        assert_eq!(res.unwrap_err(), err.into());
+
+        // Earlier versions opened the next pool regardless.
+        let set_pending = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, pending| {
+            let mut index = pool_index_read(&deps.storage).load().unwrap();
+            index.pending_settlement = pending;
+            pool_index(&mut deps.storage).save(&index).unwrap();
+        };
+        set_pending(&mut deps, None);
+        handle(&mut deps, env, create).unwrap();
+        set_pending(&mut deps, Some(1));
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+
+        // Pool 2 can't close over pool 1's pending settlement.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 22 * DAYS;
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::ClsePool { padding: None },
+        );
+        let err = ContractError::SettlementPending { pool_id: 1 };
+        assert_eq!(res.unwrap_err(), err.into());
+        assert_eq!(load_current_pool(&deps).status, PoolStatus::LOCKED);
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let index = pool_index_read(&deps.storage).load().unwrap();
+        assert_eq!(index.pending_settlement, Some(2));
+    }
+
+    #[test]
+    fn test_slashed_settlement() {
+        // Alice and Bob stake 200, which earns 12 but comes back 10 short.
+        let slashed_pool = |cover: bool| {
+            let mut deps = mock_dependencies(20, &[]);
+            let msg = InitMsg {
+                validators: Some(vec![Validator {
+                    address: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm",
+                    ),
+                    weight: 1,
+                }]),
+                ..init_msg()
+            };
+            init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+            let msg = HandleMsg::SetSlashCover {
+                enabled: cover,
+                padding: None,
+            };
+            handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000;
+            let msg = create_pool();
+            handle(&mut deps, env, msg).unwrap();
+            for sender in &["alice", "bob"] {
+                let msg = HandleMsg::Deposit {
+                    memo: None,
+                    referral: None,
+                    idempotency_key: None,
+                    padding: None,
+                };
+                handle(&mut deps, mock_env(*sender, &coins(100, DENOM)), msg).unwrap();
+            }
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000 + DAYS;
+            handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+            deps.querier.update_staking(
+                DENOM,
+                &[],
+                &[FullDelegation {
+                    delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    validator: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm",
+                    ),
+                    amount: coin(200, DENOM),
+                    can_redelegate: coin(0, DENOM),
+                    accumulated_rewards: vec![coin(12, DENOM)],
+                }],
+            );
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000 + 22 * DAYS;
+            handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+            deps.querier.update_staking(DENOM, &[], &[]);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(190 + 12, DENOM));
+            // The rest could still be on its way at maturity.
+            let mut env = mock_env("creator", &[]);
+            env.block.time = 1000 + 22 * DAYS + UNBONDING_PERIOD;
+            let res = handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None });
+            assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+            env.block.time += UNBONDING_GRACE;
+            handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+            deps
+        };
+        let alice = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let addr = deps
+                .api
+                .canonical_address(&HumanAddr::from("alice"))
+                .unwrap();
+            query_my_deposit(deps, &addr).unwrap().balance
+        };
+
+        // Uncovered, depositors share the loss and the prize is untouched.
+        let deps = slashed_pool(false);
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.slashed_amount, Uint128(10));
+        assert_eq!(pool.slash_covered, Uint128(0));
+        assert_eq!(pool.prize_amount, Uint128(12));
+        assert_eq!(alice(&deps), Uint128(95));
+
+        // Covered, the rewards make up the loss first and the prize gets the rest.
+        let deps = slashed_pool(true);
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.slashed_amount, Uint128(0));
+        assert_eq!(pool.slash_covered, Uint128(10));
+        assert_eq!(pool.prize_amount, Uint128(2));
+        assert_eq!(alice(&deps), Uint128(100));
+        let res = query(&deps, QueryMsg::GetRewardBreakdown { pool_id: Some(1) }).unwrap();
+        let value: RewardBreakdownResponse = from_binary(&res).unwrap();
+        assert_eq!(value.rewards_realized, Uint128(2));
+        assert_eq!(value.slash_covered, Uint128(10));
+        assert_eq!(value.slashed, Uint128(0));
+    }
+
+    #[test]
+    fn test_claim_principal() {
+        let mut deps = closed_pool(b"seed");
+        let claim = || HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::PoolNotSettled.into());
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+
+        // Settled, but the principal isn't back in the contract yet.
+        let res = handle(&mut deps, mock_env("alice", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DENOM));
+        let res = handle(&mut deps, mock_env("alice", &[]), claim()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(100, DENOM),
+            })]
+        );
+        assert_eq!(load_current_pool(&deps).principal_claimed, Uint128(100));
+        let res = handle(&mut deps, mock_env("alice", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::NothingToClaim.into());
+        let res = handle(&mut deps, mock_env("dave", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::NothingToClaim.into());
+    }
+
+    #[test]
+    fn test_draw_waits_for_unbonding() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            validators: Some(vec![Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            }]),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        deps.querier.update_staking(
+            DENOM,
+            &[],
+            &[FullDelegation {
+                delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                validator: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                amount: coin(100, DENOM),
+                can_redelegate: coin(0, DENOM),
+                accumulated_rewards: vec![coin(12, DENOM)],
+            }],
+        );
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        assert_eq!(load_current_pool(&deps).harvested_rewards, Uint128(12));
+        // The undelegated stake leaves the delegation list right away.
+        deps.querier.update_staking(DENOM, &[], &[]);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(12, DENOM));
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS + UNBONDING_PERIOD - 1;
+        let draw = HandleMsg::Draw { padding: None };
+        assert!(handle(&mut deps, env.clone(), draw.clone()).is_err());
+        // No new round until the last one's stake is back either.
+        let create = create_pool();
+        let res = handle(&mut deps, env.clone(), create);
+        assert_eq!(res.unwrap_err(), ContractError::StillUnbonding.into());
+        env.block.time += 1;
+        // Past maturity, but only the harvested rewards are in the balance.
+        let res = handle(&mut deps, env.clone(), draw.clone());
+        assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+        // Not even those.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(11, DENOM));
+        let res = handle(&mut deps, env.clone(), draw.clone());
+        assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+        // More than a slash can take is still missing.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(12 + 94, DENOM));
+        let res = handle(&mut deps, env.clone(), draw.clone());
+        assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(112, DENOM));
+        handle(&mut deps, env, draw).unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(
+            pool.winner,
+            Some(
+                deps.api
+                    .canonical_address(&HumanAddr::from("alice"))
+                    .unwrap()
+            )
+        );
+        assert_eq!(pool.rewards_realized, Uint128(12));
+        assert_eq!(pool.prize_amount, Uint128(12));
+    }
+
+    #[test]
+    fn test_early_draw_pays_full_principal() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            validators: Some(vec![Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            }]),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        handle(&mut deps, env, create_pool()).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        deps.querier.update_staking(
+            DENOM,
+            &[],
+            &[FullDelegation {
+                delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                validator: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                amount: coin(100, DENOM),
+                can_redelegate: coin(0, DENOM),
+                accumulated_rewards: vec![coin(12, DENOM)],
+            }],
+        );
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        deps.querier.update_staking(DENOM, &[], &[]);
+
+        // Tried at maturity, before the stake arrived. A balance that looks like a
+        // small slash isn't settled on either.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS + UNBONDING_PERIOD;
+        let draw = HandleMsg::Draw { padding: None };
+        for balance in &[12, 12 + 96] {
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(*balance, DENOM));
+            let res = handle(&mut deps, env.clone(), draw.clone());
+            assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+        }
+        let pool = load_current_pool(&deps);
+        assert!(!pool.is_settled());
+        assert_eq!(pool.slashed_amount, Uint128(0));
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(112, DENOM));
+        env.block.time += 10;
+        handle(&mut deps, env, draw).unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.slashed_amount, Uint128(0));
+        let claim = HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), claim).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(100, DENOM),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_transparent_mode() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            public_rounding: Some(Uint128(1000)),
+            transparent: Some(true),
+            ..init_msg()
+        };
+        assert!(init(&mut deps, mock_env("creator", &[]), msg).is_err());
+        let msg = InitMsg {
+            transparent: Some(true),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+
+        let msg = QueryMsg::GetDelegators {
+            pool_id: None,
+            start_after: None,
+            limit: None,
+        };
+        let value: DelegatorsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value.delegators,
+            vec![DelegatorInfo {
+                index: 0,
+                address: HumanAddr::from("alice"),
+                amount: Uint128(100),
+                weight: Uint128(100),
+            }]
+        );
+        // Balances need no viewing key.
+        let msg = QueryMsg::GetMyDeposit {
+            address: HumanAddr::from("alice"),
+            key: String::new(),
+        };
+        let value: MyDepositResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.balance, Uint128(100));
+        // The owner's export still does.
+        let msg = QueryMsg::ExportState {
+            address: HumanAddr::from("creator"),
+            key: String::new(),
+            cursor: None,
+            limit: None,
+        };
+        assert_eq!(query(&deps, msg).unwrap_err(), StdError::unauthorized());
+
+        // Winners are announced as they are drawn.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let msg = QueryMsg::GetPastWinners {
+            start_after: None,
+            limit: None,
+        };
+        let value: PastWinnersResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.winners[0].winner, HumanAddr::from("alice"));
+    }
+
+    #[test]
+    fn test_delegators_are_private_by_default() {
+        let deps = closed_pool(b"seed");
+        let msg = QueryMsg::GetDelegators {
+            pool_id: Some(1),
+            start_after: None,
+            limit: None,
+        };
+        assert!(query(&deps, msg).is_err());
+    }
+
+    #[test]
+    fn test_announce_win() {
+        let mut deps = closed_pool(b"seed");
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let winner = load_current_pool(&deps).winner.unwrap();
+        let winner = deps.api.human_address(&winner).unwrap();
+        let loser = ["alice", "bob", "carol"]
+            .iter()
+            .map(|name| HumanAddr::from(*name))
+            .find(|name| *name != winner)
+            .unwrap();
+        let history = |deps: &Extern<_, _, _>| -> PoolHistoryResponse {
+            let msg = QueryMsg::GetPoolHistory {
+                start_after: None,
+                limit: None,
+            };
+            from_binary(&query(deps, msg).unwrap()).unwrap()
+        };
+        // The public record shows a prize went out, but not to whom.
+        let summary = &history(&deps).pools[0];
+        assert!(summary.has_winner);
+        assert_eq!(summary.winner, None);
+
+        let announce = HandleMsg::AnnounceWin {
+            pool_id: 1,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env(loser, &[]), announce.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env(winner.clone(), &[]), announce.clone()).unwrap();
+        assert_eq!(history(&deps).pools[0].winner, Some(winner.clone()));
+        let msg = QueryMsg::GetPastWinners {
+            start_after: None,
+            limit: None,
+        };
+        let value: PastWinnersResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.winners[0].winner, winner);
+        // Once is enough.
+        assert!(handle(&mut deps, mock_env(winner, &[]), announce).is_err());
+    }
+
+    #[test]
+    fn test_referral_codes() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let register = |code: &str| HandleMsg::RegisterReferralCode {
+            code: code.to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[]), register("ALICE")).unwrap();
+        assert!(handle(&mut deps, mock_env("bob", &[]), register("ALICE")).is_err());
+        assert!(handle(&mut deps, mock_env("bob", &[]), register("")).is_err());
+
+        let deposit = |code: &str| HandleMsg::Deposit {
+            memo: None,
+            referral: Some(code.to_string()),
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(
+            &mut deps,
+            mock_env("bob", &coins(100, DENOM)),
+            deposit("NOPE"),
+        );
+        assert!(res.is_err());
+        let res = handle(
+            &mut deps,
+            mock_env("alice", &coins(100, DENOM)),
+            deposit("ALICE"),
+        );
+        assert!(res.is_err());
+        handle(
+            &mut deps,
+            mock_env("bob", &coins(100, DENOM)),
+            deposit("ALICE"),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("carol", &coins(50, DENOM)),
+            deposit("ALICE"),
+        )
+        .unwrap();
+
+        let msg = QueryMsg::GetReferral {
+            code: "ALICE".to_string(),
+        };
+        let value: ReferralResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            ReferralResponse {
+                code_hash: Binary::from(referral_code_hash("ALICE")),
+                deposits: 2,
+                referred_amount: Uint128(150),
+            }
+        );
+        // The rejected deposits wrote nothing.
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(150));
+    }
+
+    #[test]
+    fn test_referral_rewards() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            public_rounding: Some(Uint128(1000)),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let set_share = |share| HandleMsg::SetReferralShare {
+            share,
+            padding: None,
+        };
+        let res = handle(
+            &mut deps,
+            mock_env("rita", &[]),
+            set_share(Decimal::percent(50)),
+        );
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_share(Decimal::percent(101)),
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::InvalidRate {
+                rate: Decimal::percent(101)
+            }
+            .into()
+        );
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_share(Decimal::percent(50)),
+        )
+        .unwrap();
+        let msg = HandleMsg::RegisterReferralCode {
+            code: "ref".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("rita", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env, msg).unwrap();
+        for (sender, amount, referral) in &[("alice", 100, Some("ref")), ("bob", 300, None)] {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: referral.map(String::from),
+                idempotency_key: None,
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*sender, &coins(*amount, DENOM)), msg).unwrap();
+        }
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 22 * DAYS;
+        handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        let mut pool = load_current_pool(&deps);
+        pool.fee_rate = Decimal::percent(10);
+        pool.harvested_rewards = Uint128(1000);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let claim = || HandleMsg::ClaimReferralRewards {
+            code: "ref".to_string(),
+            pool_ids: vec![pool.id],
+            padding: None,
+        };
+        // Nothing is owed before the draw.
+        let res = handle(&mut deps, mock_env("rita", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::NothingToClaim.into());
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+
+        // Half the fee of 100, for the quarter of the pool the code brought in.
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.referral_amount, Uint128(12));
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(state.fees.accrued, Uint128(88));
+        assert_eq!(state.referral_rewards, Uint128(12));
+
+        let res = handle(&mut deps, mock_env("bob", &[]), claim());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(&mut deps, mock_env("rita", &[]), claim()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("rita"),
+                amount: coins(12, DENOM),
+            })]
+        );
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(state.referral_rewards, Uint128::zero());
+        let res = handle(&mut deps, mock_env("rita", &[]), claim());
+        assert_eq!(res.unwrap_err(), ContractError::NothingToClaim.into());
+
+        // With rounded public totals, a single referred deposit doesn't show.
+        let msg = QueryMsg::GetReferral {
+            code: "ref".to_string(),
+        };
+        let value: ReferralResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(value.deposits, 0);
+    }
+
+    #[test]
+    fn test_generic_errors() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            generic_errors: Some(true),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let generic = StdError::generic_err(GENERIC_ERROR);
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        // No pool is OPEN.
+        let res = handle(&mut deps, mock_env("alice", &coins(100, DENOM)), deposit);
+        assert_eq!(res.unwrap_err(), generic);
+        let msg = QueryMsg::GetMyDeposit {
+            address: HumanAddr::from("alice"),
+            key: "wrong".to_string(),
+        };
+        assert_eq!(query(&deps, msg).unwrap_err(), generic);
+        // Owner messages keep their errors.
+        let res = handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            HandleMsg::LockPool { padding: None },
+        );
+        assert_ne!(res.unwrap_err(), generic);
+    }
+
+    #[test]
+    fn test_snip20_deposits() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let receive = HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128(100),
+            msg: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("sscrt", &[]), receive.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+
+        let register = HandleMsg::RegisterToken {
+            address: HumanAddr::from("sscrt"),
+            code_hash: "sscrt_hash".to_string(),
+            padding: None,
+        };
+        let mut env = mock_env("creator", &[]);
+        env.contract_code_hash = "lottery_hash".to_string();
+        let res = handle(&mut deps, env, register.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("sscrt"),
+                callback_code_hash: "sscrt_hash".to_string(),
+                msg: Binary::from(
+                    br#"{"register_receive":{"code_hash":"lottery_hash","padding":null}}"#.to_vec()
+                ),
+                send: vec![],
+            })]
+        );
+        assert!(handle(&mut deps, mock_env("creator", &[]), register).is_err());
+
+        // Deposits are unwrapped on receipt, so only native wrappers can be used.
+        let res = handle(&mut deps, mock_env("sscrt", &[]), receive).unwrap();
+        let redeem = Snip20HandleMsg::Redeem {
+            amount: Uint128(100),
+            denom: Some(DENOM.to_string()),
+            padding: None,
+        };
+        let redeem = redeem
+            .to_cosmos_msg(HumanAddr::from("sscrt"), "sscrt_hash".to_string())
+            .unwrap();
+        assert_eq!(res.messages, vec![redeem]);
+        let deposit = ReceiveMsg::Deposit {
+            memo: Some("via sscrt".to_string()),
+            referral: None,
+        };
+        let receive = HandleMsg::Receive {
+            sender: HumanAddr::from("router"),
+            from: HumanAddr::from("bob"),
+            amount: Uint128(50),
+            msg: Some(to_binary(&deposit).unwrap()),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("sscrt", &[]), receive).unwrap();
+        // Tokens are credited to their owner, not to whoever routed them.
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        let entry = may_load_entry(&deps.storage, 1, &bob).unwrap().unwrap();
+        assert_eq!(entry.amount, Uint128(50));
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(150));
+
+        let deregister = HandleMsg::DeregisterToken {
+            address: HumanAddr::from("sscrt"),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), deregister.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("creator", &[]), deregister.clone()).unwrap();
+        assert!(handle(&mut deps, mock_env("creator", &[]), deregister).is_err());
+        let receive = HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128(10),
+            msg: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("sscrt", &[]), receive);
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+    }
+
+    #[test]
+    fn test_ticket_token() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::SetTicketToken {
+            address: HumanAddr::from("ticket"),
+            code_hash: "ticket_hash".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg.clone()).unwrap();
+        assert!(handle(&mut deps, mock_env("creator", &[]), msg).is_err());
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        let mint = Snip20HandleMsg::Mint {
+            recipient: HumanAddr::from("alice"),
+            amount: Uint128(100),
+            padding: None,
+        };
+        let mint = mint
+            .to_cosmos_msg(HumanAddr::from("ticket"), "ticket_hash".to_string())
+            .unwrap();
+        assert_eq!(res.messages, vec![mint]);
+
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(40),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), msg).is_err());
+        // Tickets can't be deposited, and only tickets withdraw.
+        let receive = |msg: &ReceiveMsg| HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128(40),
+            msg: Some(to_binary(msg).unwrap()),
+            padding: None,
+        };
+        let deposit = ReceiveMsg::Deposit {
+            memo: None,
+            referral: None,
+        };
+        assert!(handle(&mut deps, mock_env("ticket", &[]), receive(&deposit)).is_err());
+        let withdraw = ReceiveMsg::Withdraw { pool_id: None };
+        let res = handle(&mut deps, mock_env("other", &[]), receive(&withdraw));
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        // Bob holds tickets Alice sent him, but no deposit of his own in the open pool.
+        let transferred = HandleMsg::Receive {
+            sender: HumanAddr::from("bob"),
+            from: HumanAddr::from("bob"),
+            amount: Uint128(40),
+            msg: Some(to_binary(&withdraw).unwrap()),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("ticket", &[]), transferred);
+        assert_eq!(res.unwrap_err(), ContractError::TicketsNotDeposited.into());
+
+        let res = handle(&mut deps, mock_env("ticket", &[]), receive(&withdraw)).unwrap();
+        let burn = Snip20HandleMsg::Burn {
+            amount: Uint128(40),
+            padding: None,
+        };
+        let burn = burn
+            .to_cosmos_msg(HumanAddr::from("ticket"), "ticket_hash".to_string())
+            .unwrap();
+        assert_eq!(res.messages[1], burn);
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(40, DENOM),
+            })
+        );
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(60));
+    }
+
+    #[test]
+    fn test_redeem_transferred_tickets() {
+        let mut deps = closed_pool(b"seed");
+        // As if set before the pool opened; the setting is refused once it has.
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.ticket = Some(Token {
+            address: HumanAddr::from("ticket"),
+            code_hash: "ticket_hash".to_string(),
+        });
+        config(&mut deps.storage).save(&state).unwrap();
+        // Dave never deposited; he holds tickets from the round's depositors.
+        let redeem = |amount: u128| HandleMsg::Receive {
+            sender: HumanAddr::from("dave"),
+            from: HumanAddr::from("dave"),
+            amount: Uint128(amount),
+            msg: Some(to_binary(&ReceiveMsg::Withdraw { pool_id: Some(1) }).unwrap()),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("ticket", &[]), redeem(100));
+        assert_eq!(res.unwrap_err(), ContractError::PoolNotSettled.into());
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let res = handle(&mut deps, mock_env("ticket", &[]), redeem(100));
+        assert_eq!(res.unwrap_err(), ContractError::UnbondedFundsMissing.into());
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DENOM));
+        let res = handle(&mut deps, mock_env("ticket", &[]), redeem(100)).unwrap();
+        let burn = Snip20HandleMsg::Burn {
+            amount: Uint128(100),
+            padding: None,
+        };
+        let burn = burn
+            .to_cosmos_msg(HumanAddr::from("ticket"), "ticket_hash".to_string())
+            .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("dave"),
+                    amount: coins(100, DENOM),
+                }),
+                burn,
+            ]
+        );
+        // The tickets carry the principal, so depositors can't claim it a second time.
+        let claim = HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), claim);
+        assert_eq!(res.unwrap_err(), ContractError::NothingToClaim.into());
+        let res = handle(&mut deps, mock_env("ticket", &[]), redeem(250));
+        let err = ContractError::TicketsExceedPrincipal { pool_id: 1 };
+        assert_eq!(res.unwrap_err(), err.into());
+        assert_eq!(load_current_pool(&deps).principal_claimed, Uint128(100));
+
+        // Entries hold no principal of their own, so cleanup frees them all.
+        let msg = HandleMsg::CleanupPool {
+            pool_id: 1,
+            limit: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("anyone", &[]), msg).unwrap();
+        assert_eq!(res.log[4..], [log("removed", 3), log("done", true)]);
+    }
+
+    #[test]
+    fn test_ticket_rate() {
+        let mut deps = closed_pool(b"seed");
+        let rate = |deps: &Extern<_, _, _>, pool_id| -> TicketRateResponse {
+            from_binary(&query(deps, QueryMsg::GetTicketRate { pool_id }).unwrap()).unwrap()
+        };
+        let value = rate(&deps, None);
+        assert_eq!(value.ticket, None);
+        assert_eq!(value.unbonding_period, UNBONDING_PERIOD);
+        assert_eq!(value.pools.len(), 1);
+        assert_eq!(value.pools[0].pool_id, 1);
+        assert_eq!(value.pools[0].rate, Decimal::zero());
+        assert!(!value.pools[0].redeemable);
+        assert_eq!(value.pools[0].closes_at, Some(1000 + 22 * DAYS));
+
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.ticket = Some(Token {
+            address: HumanAddr::from("ticket"),
+            code_hash: "ticket_hash".to_string(),
+        });
+        config(&mut deps.storage).save(&state).unwrap();
+        let value = rate(&deps, None);
+        assert_eq!(value.pools[0].rate, Decimal::one());
+        assert!(!value.pools[0].redeemable);
+
+        // The settled pool is only listed when asked for, next to the OPEN one.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env.clone(), HandleMsg::Draw { padding: None }).unwrap();
+        handle(&mut deps, env, create_pool()).unwrap();
+        let value = rate(&deps, None);
+        assert_eq!(value.pools.len(), 1);
+        assert_eq!(value.pools[0].pool_id, 2);
+        assert_eq!(value.pools[0].rate, Decimal::one());
+        assert!(value.pools[0].redeemable);
+        let value = rate(&deps, Some(1));
+        assert_eq!(value.pools[0].pool_id, 1);
+        assert_eq!(value.pools[0].rate, Decimal::one());
+        assert!(value.pools[0].redeemable);
+
+        // A slash is shared by every ticket of the pool.
+        let mut pool = pool_read(&deps.storage).load(&pool_key(1)).unwrap();
+        pool.slashed_amount = Uint128(30);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(1), &pool)
+            .unwrap();
+        let value = rate(&deps, Some(1));
+        assert_eq!(value.pools[0].rate, Decimal::percent(90));
+        assert_eq!(pool.principal_for(Uint128(100)).unwrap(), Uint128(90));
+    }
+
+    #[test]
+    fn test_nft_tickets() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let nft_pool = HandleMsg::CrtePool {
+            metadata: None,
+            nft_tickets: Some(true),
+            extra_denoms: None,
+            ibc_denom: None,
+            padding: None,
+        };
+        assert!(handle(&mut deps, env.clone(), nft_pool.clone()).is_err());
+        let msg = HandleMsg::SetNftContract {
+            address: HumanAddr::from("nft"),
+            code_hash: "nft_hash".to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, env.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        handle(&mut deps, env, nft_pool).unwrap();
+
+        // One ticket per entry, no top-ups and no withdrawals.
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        for (i, sender) in ["alice", "bob"].iter().enumerate() {
+            let env = mock_env(*sender, &coins(100, DENOM));
+            let res = handle(&mut deps, env, deposit.clone()).unwrap();
+            let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+            assert_eq!(
+                answer,
+                HandleAnswer::Deposit {
+                    pool_id: 1,
+                    amount: Uint128(100),
+                    ticket_id: Some(nft_token_id(1, i as u64)),
+                }
+            );
+            match &res.messages[0] {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => {
+                    assert_eq!(contract_addr, &HumanAddr::from("nft"))
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+        }
+        let env = mock_env("alice", &coins(100, DENOM));
+        assert!(handle(&mut deps, env, deposit).is_err());
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(100),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), msg).is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::LockPool { padding: None },
+        )
+        .unwrap();
+        env.block.time = 1000 + 22 * DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::ClsePool { padding: None },
+        )
+        .unwrap();
+        env.block.time = 1000 + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let winner = load_current_pool(&deps).winner.unwrap();
+        let index = load_entry(&deps.storage, 1, &winner).unwrap().index;
+
+        // Cleanup leaves the winning entry alone even once its principal is claimed.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DENOM));
+        let winner_addr = deps.api.human_address(&winner).unwrap();
+        let msg = HandleMsg::ClaimPrincipal {
+            pool_id: 1,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env(winner_addr, &[]), msg).unwrap();
+        let msg = HandleMsg::CleanupPool {
+            pool_id: 1,
+            limit: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("carol", &[]), msg).unwrap();
+        assert!(may_load_entry(&deps.storage, 1, &winner).unwrap().is_some());
+
+        let claim = |token_id: String| HandleMsg::ReceiveNft {
+            sender: HumanAddr::from("dave"),
+            from: HumanAddr::from("dave"),
+            token_id,
+            msg: None,
+            padding: None,
+        };
+        let winning = format!("1:{}", index);
+        let losing = format!("1:{}", 1 - index);
+        let res = handle(&mut deps, mock_env("dave", &[]), claim(winning.clone()));
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        assert!(handle(&mut deps, mock_env("nft", &[]), claim(losing)).is_err());
+        // Whoever holds the winning ticket gets the prize.
+        let res = handle(&mut deps, mock_env("nft", &[]), claim(winning.clone())).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send { to_address, .. }) => {
+                assert_eq!(to_address, &HumanAddr::from("dave"))
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(load_current_pool(&deps).prize_claimed);
+        assert!(handle(&mut deps, mock_env("nft", &[]), claim(winning)).is_err());
+    }
+
+    #[test]
+    fn test_emissions() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let set_emission = |address: &str| HandleMsg::SetEmission {
+            address: HumanAddr::from(address),
+            code_hash: "reward_hash".to_string(),
+            per_round: Uint128(1000),
+            halving_interval: 0,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), set_emission("reward"));
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("creator", &[]), set_emission("reward")).unwrap();
+        assert!(handle(&mut deps, mock_env("creator", &[]), set_emission("other")).is_err());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env.clone(), msg).unwrap();
+        for (sender, amount) in &[("alice", 100), ("bob", 300)] {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*sender, &coins(*amount, DENOM)), msg).unwrap();
+        }
+        let claim = HandleMsg::ClaimEmissions {
+            pool_ids: vec![1],
+            idempotency_key: None,
+            padding: None,
+        };
+        // Nothing until the round is settled.
+        assert!(handle(&mut deps, mock_env("alice", &[]), claim.clone()).is_err());
+        env.block.time = 1000 + DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::LockPool { padding: None },
+        )
+        .unwrap();
+        env.block.time = 1000 + 22 * DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::ClsePool { padding: None },
+        )
+        .unwrap();
+        env.block.time = 1000 + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        assert_eq!(load_current_pool(&deps).emission_amount, Uint128(1000));
+
+        for (sender, share) in &[("alice", 250), ("bob", 750)] {
+            let res = handle(&mut deps, mock_env(*sender, &[]), claim.clone()).unwrap();
+            let expected = Snip20HandleMsg::Mint {
+                recipient: HumanAddr::from(*sender),
+                amount: Uint128(*share),
+                padding: None,
+            }
+            .to_cosmos_msg(HumanAddr::from("reward"), "reward_hash".to_string())
+            .unwrap();
+            assert_eq!(res.messages, vec![expected]);
+        }
+        assert!(handle(&mut deps, mock_env("alice", &[]), claim.clone()).is_err());
+        assert!(handle(&mut deps, mock_env("carol", &[]), claim).is_err());
+    }
+
+    #[test]
+    fn test_ibc_denom_pool() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let atom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+        let create = |trace: &str| HandleMsg::CrtePool {
+            metadata: None,
+            nft_tickets: None,
+            extra_denoms: None,
+            ibc_denom: Some(IbcDenom {
+                denom: atom.to_string(),
+                trace: trace.to_string(),
+            }),
+            padding: None,
+        };
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let res = handle(&mut deps, env.clone(), create("transfer/channel-9/uatom"));
+        assert!(res.is_err());
+        handle(&mut deps, env.clone(), create("transfer/channel-0/uatom")).unwrap();
+        assert_eq!(load_current_pool(&deps).denom(), atom);
+
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(
+            &mut deps,
+            mock_env("alice", &coins(100, DENOM)),
+            deposit.clone(),
+        );
+        assert!(res.is_err());
+        handle(&mut deps, mock_env("alice", &coins(100, atom)), deposit).unwrap();
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(40),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(40, atom),
+            })]
+        );
+        // Vouchers stay in the contract.
+        env.block.time = 1000 + DAYS;
+        let res = handle(&mut deps, env, HandleMsg::LockPool { padding: None }).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(load_current_pool(&deps).delegations.is_empty());
+    }
+
+    #[test]
+    fn test_hooks() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let register = |code_hash: &str| HandleMsg::RegisterHook {
+            code_hash: code_hash.to_string(),
+            padding: None,
+        };
+        // Registrations don't take the places of approved hooks.
+        for i in 0..=MAX_HOOKS {
+            let env = mock_env(HumanAddr(format!("spam{}", i)), &[]);
+            handle(&mut deps, env, register("spam_hash")).unwrap();
+        }
+        handle(&mut deps, mock_env("bot", &[]), register("bot_hash")).unwrap();
+        assert!(handle(&mut deps, mock_env("bot", &[]), register("bot_hash")).is_err());
+        let approve = HandleMsg::ApproveHook {
+            address: HumanAddr::from("bot"),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("bot", &[]), approve.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+
+        // Pending hooks are not called.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let create = create_pool();
+        let res = handle(&mut deps, env.clone(), create).unwrap();
+        assert!(res.messages.is_empty());
+
+        handle(&mut deps, mock_env("creator", &[]), approve).unwrap();
+        env.block.time = 1000 + DAYS;
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::LockPool { padding: None },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("bot"),
+                callback_code_hash: "bot_hash".to_string(),
+                msg: Binary::from(
+                    br#"{"lottery_hook":{"event":"pool_locked","pool_id":1}}"#.to_vec()
+                ),
+                send: vec![],
+            })]
+        );
+
+        // A hook can take itself off the list.
+        let remove = HandleMsg::RemoveHook {
+            address: HumanAddr::from("bot"),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), remove.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("bot", &[]), remove).unwrap();
+        env.block.time = 1000 + 22 * DAYS;
+        let res = handle(&mut deps, env, HandleMsg::ClsePool { padding: None }).unwrap();
+        assert!(res.messages.is_empty());
+
+        // Only approved hooks count against the cap.
+        for i in 0..=MAX_HOOKS {
+            let approve = HandleMsg::ApproveHook {
+                address: HumanAddr(format!("spam{}", i)),
+                padding: None,
+            };
+            let res = handle(&mut deps, mock_env("creator", &[]), approve);
+            if i < MAX_HOOKS {
+                res.unwrap();
+            } else {
+                let err = ContractError::CapExceeded {
+                    what: "hooks".to_string(),
+                    max: MAX_HOOKS as u64,
+                };
+                assert_eq!(res.unwrap_err(), err.into());
+            }
+        }
+    }
+
+    #[test]
+    fn test_winner_callback() {
+        let mut deps = closed_pool(b"seed");
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(100);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        for sender in &["alice", "bob", "carol"] {
+            let msg = HandleMsg::SetWinnerCallback {
+                callback: Some(WinnerCallback {
+                    code_hash: "vault_hash".to_string(),
+                    attach_prize: true,
+                }),
+                padding: None,
+            };
+            handle(&mut deps, mock_env(*sender, &[]), msg).unwrap();
+        }
+        let msg = HandleMsg::SetWinnerCallback {
+            callback: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("carol", &[]), msg).unwrap();
+        let carol = deps
+            .api
+            .canonical_address(&HumanAddr::from("carol"))
+            .unwrap();
+        let callbacks = winner_callbacks_read(&deps.storage);
+        assert!(callbacks.may_load(carol.as_slice()).unwrap().is_none());
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 43 * DAYS;
+        // Settlement doesn't call the winner, so a failing callback can't hold it up.
+        let res = handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        assert!(res.messages.is_empty());
+        let pool = load_current_pool(&deps);
+        assert!(!pool.prize_claimed);
+        let winner = deps.api.human_address(&pool.winner.unwrap()).unwrap();
+        let claim = HandleMsg::ClaimPrize {
+            pool_id: pool.id,
+            ibc_payout: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env(winner.clone(), &[]), claim).unwrap();
+        let notification = WinnerMsg::WinnerNotification {
+            pool_id: pool.id,
+            prize_amount: pool.prize_amount,
+            denom: DENOM.to_string(),
+            prize_attached: true,
+        };
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: winner,
+                callback_code_hash: "vault_hash".to_string(),
+                msg: to_binary(&notification).unwrap(),
+                send: coins(100, DENOM),
+            })]
+        );
+        assert!(load_current_pool(&deps).prize_claimed);
+    }
+
+    #[test]
+    fn test_convert_rewards() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            validators: Some(vec![Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            }]),
+            ..init_msg()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let msg = create_pool();
+        handle(&mut deps, env.clone(), msg).unwrap();
+        let msg = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &coins(100, DENOM)), msg).unwrap();
+        env.block.time = 1000 + DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::LockPool { padding: None },
+        )
+        .unwrap();
+        deps.querier.update_staking(
+            DENOM,
+            &[],
+            &[FullDelegation {
+                delegator: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                validator: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                amount: coin(100, DENOM),
+                can_redelegate: coin(0, DENOM),
+                accumulated_rewards: vec![coin(12, DENOM), coin(5, "ulsd")],
+            }],
+        );
+        env.block.time = 1000 + 22 * DAYS;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::ClsePool { padding: None },
+        )
+        .unwrap();
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.harvested_rewards, Uint128(12));
+        assert_eq!(pool.unconverted_rewards, vec![coin(5, "ulsd")]);
+
+        env.block.time = 1000 + 22 * DAYS + UNBONDING_PERIOD;
+        let convert = HandleMsg::ConvertRewards {
+            min_return: Uint128(4),
+            padding: None,
+        };
+        assert!(handle(&mut deps, env.clone(), convert.clone()).is_err());
+        let msg = HandleMsg::SetSwapRouter {
+            address: HumanAddr::from("router"),
+            code_hash: "router_hash".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, env.clone(), msg).unwrap();
+        let res = handle(&mut deps, env.clone(), convert.clone()).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                send,
+                ..
+            }) => {
+                assert_eq!(contract_addr, &HumanAddr::from("router"));
+                assert_eq!(send, &vec![coin(5, "ulsd")]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(handle(&mut deps, env.clone(), convert).is_err());
+        let draw = HandleMsg::Draw { padding: None };
+        assert!(handle(&mut deps, env.clone(), draw.clone()).is_err());
+
+        let proceeds = HandleMsg::SwapProceeds {
+            pool_id: 1,
+            padding: None,
+        };
+        let res = handle(
+            &mut deps,
+            mock_env("alice", &coins(6, DENOM)),
+            proceeds.clone(),
+        );
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("router", &coins(6, DENOM)), proceeds).unwrap();
+        // The stake has come back along with the rewards.
+        deps.querier.update_staking(DENOM, &[], &[]);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(118, DENOM));
+        handle(&mut deps, env, draw).unwrap();
+        let pool = load_current_pool(&deps);
+        assert!(pool.unconverted_rewards.is_empty());
+        assert_eq!(pool.rewards_realized, Uint128(18));
+    }
+
+    #[test]
+    fn test_reset_conversion() {
+        let mut deps = closed_pool(b"seed");
+        let mut pool = load_current_pool(&deps);
+        pool.unconverted_rewards = vec![coin(5, "ulsd")];
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let msg = HandleMsg::SetSwapRouter {
+            address: HumanAddr::from("router"),
+            code_hash: "router_hash".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let reset = HandleMsg::ResetConversion { padding: None };
+        let res = handle(&mut deps, mock_env("creator", &[]), reset.clone());
+        assert_eq!(res.unwrap_err(), ContractError::NoConversion.into());
+        let convert = HandleMsg::ConvertRewards {
+            min_return: Uint128(4),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), convert).unwrap();
+
+        // The router never pays back, which holds up the draw until the owner resets.
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        let draw = HandleMsg::Draw { padding: None };
+        let res = handle(&mut deps, env.clone(), draw.clone());
+        assert_eq!(res.unwrap_err(), ContractError::RewardsConverting.into());
+        let res = handle(&mut deps, mock_env("alice", &[]), reset.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("creator", &[]), reset).unwrap();
+        let pool = load_current_pool(&deps);
+        assert!(!pool.converting);
+        assert!(pool.unconverted_rewards.is_empty());
+        handle(&mut deps, env, draw).unwrap();
+        assert!(load_current_pool(&deps).is_settled());
+
+        // A late payment no longer has a conversion to land in.
+        let proceeds = HandleMsg::SwapProceeds {
+            pool_id: 1,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("router", &coins(6, DENOM)), proceeds);
+        assert_eq!(res.unwrap_err(), ContractError::NoConversion.into());
+    }
+
+    // Answers oracle queries with a fixed price and everything else like `MockQuerier`.
+    struct PricedQuerier {
+        base: MockQuerier,
+        price: PriceResponse,
+    }
+
+    impl Querier for PricedQuerier {
+        fn raw_query(&self, request: &[u8]) -> QuerierResult {
+            match from_slice::<QueryRequest<Empty>>(request) {
+                Ok(QueryRequest::Wasm(_)) => Ok(to_binary(&self.price)),
+                _ => self.base.raw_query(request),
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_denom_pool() {
+        let mock = mock_dependencies(20, &[]);
+        let mut deps = Extern {
+            storage: mock.storage,
+            api: mock.api,
+            querier: PricedQuerier {
+                base: mock.querier,
+                price: PriceResponse {
+                    rate: Decimal::percent(200),
+                    last_updated: 900,
+                },
+            },
+        };
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000;
+        let create = HandleMsg::CrtePool {
+            metadata: None,
+            nft_tickets: None,
+            extra_denoms: Some(vec!["uatom".to_string()]),
+            ibc_denom: None,
+            padding: None,
+        };
+        assert!(handle(&mut deps, env.clone(), create.clone()).is_err());
+        let msg = HandleMsg::SetOracle {
+            oracle: Some(Oracle {
+                contract: Token {
+                    address: HumanAddr::from("oracle"),
+                    code_hash: "oracle_hash".to_string(),
+                },
+                quote: DENOM.to_string(),
+                max_age: 600,
+            }),
+            padding: None,
+        };
+        handle(&mut deps, env.clone(), msg).unwrap();
+        handle(&mut deps, env, create).unwrap();
+
+        // Entries are weighted by value: one uatom is worth two uscrt.
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        let mut env = mock_env("alice", &coins(100, DENOM));
+        env.block.time = 1000;
+        handle(&mut deps, env, deposit.clone()).unwrap();
+        let mut env = mock_env("bob", &coins(50, "uatom"));
+        env.block.time = 1000;
+        handle(&mut deps, env, deposit.clone()).unwrap();
+        let mut env = mock_env("carol", &[coin(10, DENOM), coin(10, "uatom")]);
+        env.block.time = 1000;
+        assert!(handle(&mut deps, env, deposit.clone()).is_err());
+        let mut env = mock_env("carol", &coins(10, "uosmo"));
+        env.block.time = 1000;
+        assert!(handle(&mut deps, env, deposit.clone()).is_err());
+        // Stale prices are refused.
+        let mut env = mock_env("carol", &coins(10, "uatom"));
+        env.block.time = 1501;
+        assert!(handle(&mut deps, env, deposit).is_err());
+
+        let pool = load_current_pool(&deps);
+        assert_eq!(pool.total_deposits, Uint128(100));
+        assert_eq!(pool.total_weight, Uint128(200));
+        assert_eq!(pool.denom_balances, vec![coin(50, "uatom")]);
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        let entry = load_entry(&deps.storage, pool.id, &bob).unwrap();
+        assert_eq!(entry.amount, Uint128(0));
+        assert_eq!(entry.weight, Uint128(100));
+        assert_eq!(
+            load_entry_balances(&deps.storage, pool.id, &bob).unwrap(),
+            vec![coin(50, "uatom")]
+        );
+        let msg = HandleMsg::Withdraw {
+            amount: Uint128(10),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), msg).is_err());
+    }
+
+    #[test]
+    fn test_draw_with_oracle_down() {
+        // The oracle only values the prize for display, so it can't hold up the draw.
+        let mut deps = closed_pool(b"seed");
+        let msg = HandleMsg::SetOracle {
+            oracle: Some(Oracle {
+                contract: Token {
+                    address: HumanAddr::from("oracle"),
+                    code_hash: "oracle_hash".to_string(),
+                },
+                quote: "uusd".to_string(),
+                max_age: 600,
+            }),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 23 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let pool = load_current_pool(&deps);
+        assert!(pool.is_settled());
+        assert_eq!(pool.prize_value, None);
+    }
+
+    #[test]
+    fn test_wrapped_native() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::SetWrappedNative {
+            address: HumanAddr::from("sscrt"),
+            code_hash: "sscrt_hash".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = create_pool();
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        // sSCRT deposits are unwrapped so they can be staked.
+        let msg = HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128(100),
+            msg: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("sscrt", &[]), msg).unwrap();
+        let redeem = Snip20HandleMsg::Redeem {
+            amount: Uint128(100),
+            denom: Some(DENOM.to_string()),
+            padding: None,
+        };
+        let sscrt = |msg: Snip20HandleMsg, send: Vec<Coin>| {
+            msg.to_cosmos_msg_with_funds(HumanAddr::from("sscrt"), "sscrt_hash".to_string(), send)
+                .unwrap()
+        };
+        assert_eq!(res.messages, vec![sscrt(redeem, vec![])]);
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(100));
+
+        let msg = HandleMsg::WithdrawWrapped {
+            amount: Uint128(40),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        let transfer = Snip20HandleMsg::Transfer {
+            recipient: HumanAddr::from("alice"),
+            amount: Uint128(40),
+            padding: None,
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                sscrt(Snip20HandleMsg::Deposit { padding: None }, coins(40, DENOM)),
+                sscrt(transfer, vec![]),
+            ]
+        );
+        assert_eq!(load_current_pool(&deps).total_deposits, Uint128(60));
+    }
+
+    #[test]
+    fn test_factory_rounds() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg.clone()).unwrap();
+        let spawn = HandleMsg::SpawnRound {
+            label: "weekly".to_string(),
+            init: msg.clone(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("creator", &[]), spawn.clone());
+        assert_eq!(res.unwrap_err(), ContractError::FactoryOff.into());
+        let set = HandleMsg::SetFactory {
+            code_id: 7,
+            code_hash: "lottery_hash".to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("anyone", &[]), set.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        handle(&mut deps, mock_env("creator", &[]), set).unwrap();
+
+        let res = handle(&mut deps, mock_env("creator", &[]), spawn).unwrap();
+        let mut child_init = msg;
+        child_init.parent = Some(ParentInfo {
+            address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            code_hash: mock_env("creator", &[]).contract_code_hash,
+            index: 0,
+        });
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id: 7,
+                callback_code_hash: "lottery_hash".to_string(),
+                msg: to_binary(&child_init).unwrap(),
+                send: vec![],
+                label: "weekly".to_string(),
+            })]
+        );
+
+        // The child registers itself with its parent on init.
+        let mut child = mock_dependencies(20, &[]);
+        let res = init(&mut child, mock_env("factory", &[]), child_init).unwrap();
+        let register = HandleMsg::RegisterChild {
+            index: 0,
+            padding: None,
+        };
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                callback_code_hash: mock_env("creator", &[]).contract_code_hash,
+                msg: to_binary(&register).unwrap(),
+                send: vec![],
+            })]
+        );
+        handle(&mut deps, mock_env("child", &[]), register.clone()).unwrap();
+        let res = handle(&mut deps, mock_env("impostor", &[]), register);
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+
+        let res = query(
+            &deps,
+            QueryMsg::GetChildren {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: ChildrenResponse = from_binary(&res).unwrap();
+        assert_eq!(value.children.len(), 1);
+        assert_eq!(value.children[0].address, Some(HumanAddr::from("child")));
+
+        let relay = HandleMsg::ExecuteChild {
+            index: 0,
+            msg: to_binary(&HandleMsg::LockPool { padding: None }).unwrap(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("creator", &[]), relay.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("child"),
+                callback_code_hash: "lottery_hash".to_string(),
+                msg: Binary::from(br#"{"lock_pool":{"padding":null}}"#.to_vec()),
+                send: vec![],
+            })]
+        );
+
+        // Children keep running the code they were spawned with.
+        let set = HandleMsg::SetFactory {
+            code_id: 8,
+            code_hash: "lottery_v2_hash".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), set).unwrap();
+        let res = handle(&mut deps, mock_env("creator", &[]), relay).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                callback_code_hash, ..
+            }) => assert_eq!(callback_code_hash, "lottery_hash"),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lottery_v1_interface() {
+        let mut deps = closed_pool(b"seed");
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(100);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let res = query(&deps, QueryMsg::LotteryV1(LotteryQueryV1::Interface {})).unwrap();
+        let value: InterfaceResponse = from_binary(&res).unwrap();
+        assert_eq!(value.versions, vec![1]);
+        let round = QueryMsg::LotteryV1(LotteryQueryV1::CurrentRound {});
+        let value: RoundResponseV1 = from_binary(&query(&deps, round.clone()).unwrap()).unwrap();
+        assert_eq!(value.phase, Some(RoundPhaseV1::Closed));
+        assert_eq!(value.total_deposits, Uint128(300));
+        assert!(!value.accepts_deposits);
+        // Rounded like every other public total.
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.public_rounding = Uint128(200);
+        config(&mut deps.storage).save(&state).unwrap();
+        let value: RoundResponseV1 = from_binary(&query(&deps, round.clone()).unwrap()).unwrap();
+        assert_eq!(value.total_deposits, Uint128(400));
+        state.public_rounding = Uint128(1);
+        config(&mut deps.storage).save(&state).unwrap();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let value: RoundResponseV1 = from_binary(&query(&deps, round).unwrap()).unwrap();
+        assert_eq!(value.phase, Some(RoundPhaseV1::Drawn));
+        let prize = QueryMsg::LotteryV1(LotteryQueryV1::Prize { pool_id: Some(1) });
+        let value: PrizeResponseV1 = from_binary(&query(&deps, prize.clone()).unwrap()).unwrap();
+        assert_eq!(value.amount, Uint128(100));
+        assert!(value.drawn && !value.claimed);
+
+        let pool = load_current_pool(&deps);
+        let winner = deps.api.human_address(&pool.winner.unwrap()).unwrap();
+        let loser = ["alice", "bob", "carol"]
+            .iter()
+            .find(|s| HumanAddr::from(**s) != winner)
+            .unwrap();
+        let claim = HandleMsg::LotteryV1(LotteryHandleV1::Claim {
+            pool_id: 1,
+            padding: None,
+        });
+        let res = handle(&mut deps, mock_env(*loser, &[]), claim.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(&mut deps, mock_env(winner.clone(), &[]), claim.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: winner.clone(),
+                amount: coins(100, DENOM),
+            })]
+        );
+        assert!(handle(&mut deps, mock_env(winner, &[]), claim).is_err());
+        let value: PrizeResponseV1 = from_binary(&query(&deps, prize).unwrap()).unwrap();
+        assert!(value.claimed);
+    }
+
+    #[test]
+    fn test_ibc_prize_claim() {
+        let mut deps = closed_pool(b"seed");
+        let mut pool = load_current_pool(&deps);
+        pool.harvested_rewards = Uint128(100);
+        pool_storage(&mut deps.storage)
+            .save(&pool_key(pool.id), &pool)
+            .unwrap();
+        let mut env = mock_env("creator", &[]);
+        env.block.time = 1000 + 43 * DAYS;
+        handle(&mut deps, env, HandleMsg::Draw { padding: None }).unwrap();
+        let pool = load_current_pool(&deps);
+        let winner = deps.api.human_address(&pool.winner.unwrap()).unwrap();
+        let payout = IbcPayout {
+            channel: "channel-0".to_string(),
+            recipient: "cosmos1winner".to_string(),
+        };
+        let claim = HandleMsg::ClaimPrize {
+            pool_id: 1,
+            ibc_payout: Some(payout.clone()),
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env(winner.clone(), &[]), claim.clone());
+        assert_eq!(
+            res.unwrap_err(),
+            StdError::generic_err("No IBC gateway is set.")
+        );
+        let gateway = Token {
+            address: HumanAddr::from("gateway"),
+            code_hash: "gateway_hash".to_string(),
+        };
+        let msg = HandleMsg::SetIbcGateway {
+            gateway: Some(gateway),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let bad = HandleMsg::ClaimPrize {
+            pool_id: 1,
+            ibc_payout: Some(IbcPayout {
+                channel: "transfer".to_string(),
+                recipient: "cosmos1winner".to_string(),
+            }),
+            idempotency_key: None,
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env(winner.clone(), &[]), bad);
+        assert_eq!(
+            res.unwrap_err(),
+            StdError::generic_err("Invalid channel id")
+        );
+
+        let res = handle(&mut deps, mock_env(winner, &[]), claim).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("gateway"),
+                callback_code_hash: "gateway_hash".to_string(),
+                msg: Binary::from(
+                    br#"{"transfer":{"channel":"channel-0","remote_address":"cosmos1winner","timeout":null}}"#
+                        .to_vec()
+                ),
+                send: coins(100, DENOM),
+            })]
+        );
+        let pool = load_current_pool(&deps);
+        assert!(pool.prize_claimed);
+        assert_eq!(pool.ibc_payout, Some(payout));
+    }
+
+    #[test]
+    fn test_responses_encode_numbers_as_strings() {
+        let deps = closed_pool(b"seed");
+        let res = query(
+            &deps,
+            QueryMsg::GetPool {
+                pool_id: 1,
+                caller: None,
+            },
+        )
+        .unwrap();
+        let json = String::from_utf8(res.0).unwrap();
+        assert!(json.contains(r#""created_at":"1000""#));
+        assert!(json.contains(r#""total_deposits":"300""#));
+        assert!(json.contains(r#""settled_at":null"#));
+        let value: PoolResponse = from_slice(json.as_bytes()).unwrap();
+        assert_eq!(value.pool.unwrap().created_at, 1000);
+
+        let msg = QueryMsg::Batch {
+            queries: vec![QueryMsg::GetOwner {}],
+        };
+        let json = String::from_utf8(query(&deps, msg).unwrap().0).unwrap();
+        assert!(json.contains(r#""ok":{"get_owner":{"owner":"creator"}}"#));
+    }
+
+    #[test]
+    fn test_treasury() {
+        let mut deps = mock_dependencies(20, &[coin(50, "uairdrop"), coin(10, DENOM)]);
+        let msg = init_msg();
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let collect = HandleMsg::CollectFees { padding: None };
+        let res = handle(&mut deps, mock_env("creator", &[]), collect.clone());
+        assert_eq!(res.unwrap_err(), ContractError::NoFeesToCollect.into());
+
+        // Without a treasury, fees go to the fee recipient.
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.fees.accrued = Uint128(7);
+        config(&mut deps.storage).save(&state).unwrap();
+        let res = handle(&mut deps, mock_env("creator", &[]), collect.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("creator"),
+                amount: coins(7, DENOM),
+            })]
+        );
+
+        let msg = HandleMsg::SetTreasury {
+            treasury: Some(Token {
+                address: HumanAddr::from("treasury"),
+                code_hash: "treasury_hash".to_string(),
+            }),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let mut state = config_read(&deps.storage).load().unwrap();
+        state.fees.accrued = Uint128(5);
+        config(&mut deps.storage).save(&state).unwrap();
+        let res = handle(&mut deps, mock_env("anyone", &[]), collect.clone());
+        assert_eq!(res.unwrap_err(), StdError::unauthorized());
+        let res = handle(&mut deps, mock_env("creator", &[]), collect).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("treasury"),
+                callback_code_hash: "treasury_hash".to_string(),
+                msg: Binary::from(br#"{"deposit":{"purpose":"fees"}}"#.to_vec()),
+                send: coins(5, DENOM),
+            })]
+        );
+        assert!(config_read(&deps.storage)
+            .load()
+            .unwrap()
+            .fees
+            .accrued
+            .is_zero());
+
+        let sweep = |denom: &str| HandleMsg::SweepToTreasury {
+            denom: denom.to_string(),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("creator", &[]), sweep(DENOM));
+        assert!(res.is_err());
+        let res = handle(&mut deps, mock_env("creator", &[]), sweep("uairdrop")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("treasury"),
+                callback_code_hash: "treasury_hash".to_string(),
+                msg: Binary::from(br#"{"deposit":{"purpose":"sweep"}}"#.to_vec()),
+                send: vec![coin(50, "uairdrop")],
+            })]
+        );
     }
 }
@@ -0,0 +1,413 @@
+use cosmwasm_std::{Decimal, StdError, Uint128};
+use std::fmt;
+
+// What can go wrong in a handler, by kind. Handlers return it and `handle` turns it
+// into a `StdError` on the way out: `Unauthorized` stays `StdError::Unauthorized`,
+// `Std` passes through, and everything else becomes a generic error carrying the
+// message below, so clients can match on that text.
+#[derive(Debug, PartialEq)]
+pub enum ContractError {
+    Std(StdError),
+    Unauthorized,
+
+    // Arithmetic on amounts and totals.
+    Overflow {
+        lhs: Uint128,
+        op: char,
+        rhs: Uint128,
+    },
+    Underflow {
+        minuend: Uint128,
+        subtrahend: Uint128,
+    },
+    DivideByZero,
+    InvalidRate {
+        rate: Decimal,
+    },
+
+    // Round lifecycle.
+    CannotCreate,
+    PoolNotOpen,
+    PoolNotLocked,
+    PoolNotClosed,
+    PoolNotSettled,
+    AlreadySettled,
+    CannotLock,
+    NoPendingSettlement,
+    SettlementPending {
+        pool_id: u64,
+    },
+    NoSettledPool,
+    NoPoolToSponsor,
+    StillUnbonding,
+    UnbondedFundsMissing,
+    RewardsConverting,
+    TooEarly {
+        status: String,
+        duration: u64,
+        unit: String,
+        remaining: u64,
+    },
+
+    // Funds.
+    NoFunds,
+    ZeroAmount {
+        what: String,
+    },
+    WrongDenom {
+        expected: String,
+    },
+    DenomNotAccepted {
+        denom: String,
+    },
+    IbcDenomOnly,
+    FundsNotAccepted,
+    WorthNothing,
+    OnlyNativeWrapped,
+    HeldForPools {
+        denom: String,
+    },
+    NothingToSweep {
+        denom: String,
+    },
+    NothingToClaim,
+    NothingToConvert,
+    NoConversion,
+    NoFeesToCollect,
+    PrizeExceedsRewards,
+    WeightsMismatch,
+
+    // Caps.
+    CapExceeded {
+        what: String,
+        max: u64,
+    },
+    FeeTooHigh {
+        max: Decimal,
+    },
+    TooLong {
+        what: String,
+        max: usize,
+    },
+    ReferralCodeLength {
+        max: usize,
+    },
+    LeaderboardFull,
+
+    // Prizes.
+    AlreadyClaimed,
+    NotATicket,
+    NotDrawn,
+    NoWinner,
+    TicketDidNotWin,
+    ClaimWithTicket,
+    AlreadyAnnounced,
+
+    // Configuration.
+    NotSet {
+        what: String,
+    },
+    NoEmission,
+    FactoryOff,
+    OracleRequired,
+    StalePrice {
+        denom: String,
+    },
+    TransparentRounding,
+    NotMigrated,
+    TicketAlreadySet,
+    DepositTokenAsTicket,
+    WrappedNativeAsTicket,
+    NftContractLocked,
+    EmissionTokenFixed,
+    TokenAlreadyRegistered,
+    TokenNotRegistered,
+    TokenNotUsable,
+    HookAlreadyRegistered,
+    InvalidExtraDenom {
+        denom: String,
+    },
+    ZeroValidatorWeight,
+    InvalidValidator {
+        address: String,
+    },
+    ZeroDuration,
+    InvalidBlockSize {
+        max: u32,
+    },
+    InvalidMaxDepositors {
+        max: u32,
+    },
+    DuplicateValidator {
+        address: String,
+    },
+    UnknownStorageVersion {
+        version: u32,
+    },
+    Unconvertible {
+        reasons: Vec<String>,
+    },
+    SeedRequired,
+
+    // Entries.
+    DuplicateEntry,
+    DuplicateRequest,
+    EmptyIdempotencyKey,
+    DepositLimitReached,
+    PoolFull {
+        max: u32,
+    },
+    TooManyUnclaimedPrizes {
+        max: u64,
+    },
+    WithdrawWithTickets,
+    TicketsNotDeposited,
+    TicketsExceedPrincipal {
+        pool_id: u64,
+    },
+    OneNftDeposit,
+    NftWithdrawal,
+    MultiDenomWithdrawal,
+    UnknownReferralCode,
+    OwnReferralCode,
+    ReferralCodeTaken,
+    OnlyPlayersListed,
+
+    // IBC.
+    InvalidPort,
+    InvalidDenomTrace,
+    DenomTraceMismatch,
+    InvalidChannel,
+    InvalidRecipient,
+
+    // Owner recovery.
+    RecoveryPending,
+    NoRecovery,
+    RecoveryNotReady {
+        remaining: u64,
+        unit: String,
+    },
+
+    // A bug, not a user error; the transaction is rolled back.
+    InvariantBroken {
+        pool_id: u64,
+        what: String,
+    },
+
+    // Queries.
+    NestedBatch,
+    AuthenticatedInBatch,
+    NotBatchable,
+    DelegatorsPrivate,
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ContractError::*;
+        match self {
+            Std(err) => write!(f, "{}", err),
+            Unauthorized => write!(f, "Unauthorized"),
+            Overflow { lhs, op, rhs } => write!(f, "Overflow: {} {} {}", lhs, op, rhs),
+            Underflow {
+                minuend,
+                subtrahend,
+            } => write!(f, "Cannot subtract {} from {}", subtrahend, minuend),
+            DivideByZero => write!(f, "Division by zero"),
+            InvalidRate { rate } => write!(f, "Rate {} is above 1", rate),
+            CannotCreate => write!(f, "Cannot create"),
+            PoolNotOpen => write!(f, "Pool is not OPEN."),
+            PoolNotLocked => write!(f, "Pool is not LOCKED."),
+            PoolNotClosed => write!(f, "Pool must be CLOSED to be settled."),
+            PoolNotSettled => write!(f, "Pool is not settled."),
+            AlreadySettled => write!(f, "Pool is already settled."),
+            CannotLock => write!(f, "Pool must be in OPEN status to be locked."),
+            NoPendingSettlement => write!(f, "No pool is awaiting settlement."),
+            SettlementPending { pool_id } => write!(f, "Pool {} must be settled first.", pool_id),
+            NoSettledPool => write!(f, "No pool has been settled yet."),
+            NoPoolToSponsor => write!(f, "No pool to sponsor."),
+            StillUnbonding => write!(f, "Pool stake is still unbonding."),
+            UnbondedFundsMissing => write!(f, "Unbonded funds have not arrived yet."),
+            RewardsConverting => write!(f, "Rewards are being converted."),
+            TooEarly {
+                status,
+                duration,
+                unit,
+                remaining,
+            } => write!(
+                f,
+                "Pool has to be {} for {} {}; {} to go",
+                status, duration, unit, remaining
+            ),
+            NoFunds => write!(f, "No funds were sent"),
+            ZeroAmount { what } => write!(f, "Cannot {} a zero amount", what),
+            WrongDenom { expected } => write!(f, "Only {} can be deposited", expected),
+            DenomNotAccepted { denom } => write!(f, "This pool does not take {}", denom),
+            IbcDenomOnly => write!(f, "This pool only takes its IBC denom."),
+            FundsNotAccepted => write!(f, "This handle takes no funds."),
+            WorthNothing => write!(f, "Deposit is worth nothing."),
+            OnlyNativeWrapped => write!(f, "Only native deposits can be wrapped."),
+            HeldForPools { denom } => write!(f, "{} is held for the pools", denom),
+            NothingToSweep { denom } => write!(f, "No {} to sweep", denom),
+            NothingToClaim => write!(f, "Nothing to claim."),
+            NothingToConvert => write!(f, "Nothing to convert."),
+            NoConversion => write!(f, "No conversion is in flight."),
+            NoFeesToCollect => write!(f, "No fees to collect."),
+            PrizeExceedsRewards => write!(f, "Prize exceeds realized rewards."),
+            WeightsMismatch => write!(f, "Entry weights don't add up to the pool total."),
+            CapExceeded { what, max } => write!(f, "At most {} {} are allowed.", max, what),
+            FeeTooHigh { max } => write!(f, "Fee rate cannot exceed {}", max),
+            TooLong { what, max } => write!(f, "{} cannot be longer than {} bytes", what, max),
+            ReferralCodeLength { max } => {
+                write!(f, "Referral code must be 1 to {} bytes", max)
+            }
+            LeaderboardFull => write!(f, "Leaderboard is full of larger depositors"),
+            AlreadyClaimed => write!(f, "The prize was already claimed."),
+            NotATicket => write!(f, "Not a lottery ticket."),
+            NotDrawn => write!(f, "This round has not been drawn."),
+            NoWinner => write!(f, "This round has no winner."),
+            TicketDidNotWin => write!(f, "This ticket did not win."),
+            ClaimWithTicket => write!(f, "Send the winning ticket to claim."),
+            AlreadyAnnounced => write!(f, "Winner is already announced."),
+            NotSet { what } => write!(f, "No {} is set.", what),
+            NoEmission => write!(f, "No emission is configured."),
+            FactoryOff => write!(f, "Factory mode is off."),
+            OracleRequired => write!(f, "Pools with extra denoms need an oracle."),
+            StalePrice { denom } => write!(f, "The {} price is stale", denom),
+            TransparentRounding => write!(
+                f,
+                "Transparent mode reports exact totals; drop public_rounding and delay_public_totals."
+            ),
+            NotMigrated => write!(f, "Contract address is not recorded; migrate first."),
+            TicketAlreadySet => write!(
+                f,
+                "The ticket token can only be set once, before the first pool."
+            ),
+            DepositTokenAsTicket => write!(f, "A deposit token can't be the ticket."),
+            WrappedNativeAsTicket => write!(f, "The ticket can't be the wrapped native token."),
+            NftContractLocked => write!(
+                f,
+                "The NFT contract can't change while a round is running."
+            ),
+            EmissionTokenFixed => write!(f, "The emission token can't change once set."),
+            TokenAlreadyRegistered => write!(f, "Token is already registered."),
+            TokenNotRegistered => write!(f, "Token is not registered."),
+            TokenNotUsable => write!(f, "This token can't be used for that."),
+            HookAlreadyRegistered => write!(f, "Hook is already registered."),
+            InvalidExtraDenom { denom } => write!(f, "Invalid extra denom {:?}", denom),
+            ZeroValidatorWeight => write!(f, "Validator weight must be positive"),
+            InvalidValidator { address } => write!(f, "Invalid validator address {}", address),
+            ZeroDuration => write!(f, "Status durations must be positive"),
+            InvalidBlockSize { max } => {
+                write!(f, "Response block size must be 1 to {} bytes", max)
+            }
+            InvalidMaxDepositors { max } => {
+                write!(f, "Max depositors must be 1 to {}", max)
+            }
+            DuplicateValidator { address } => write!(f, "Duplicate validator {}", address),
+            UnknownStorageVersion { version } => {
+                write!(f, "Unknown storage version {}", version)
+            }
+            Unconvertible { reasons } => {
+                write!(f, "Storage can't be migrated: {}", reasons.join("; "))
+            }
+            SeedRequired => write!(f, "A fresh prng_seed is required."),
+            DuplicateEntry => write!(f, "Address already has an entry in this pool."),
+            DuplicateRequest => write!(f, "This idempotency key was already used."),
+            EmptyIdempotencyKey => write!(f, "Idempotency key cannot be empty."),
+            PoolFull { max } => write!(f, "Pool is full at {} depositors.", max),
+            TooManyUnclaimedPrizes { max } => {
+                write!(f, "More than {} prizes are still unclaimed.", max)
+            }
+            DepositLimitReached => {
+                write!(f, "Too many deposits this block; try again in the next one.")
+            }
+            WithdrawWithTickets => write!(f, "Send tickets back to the lottery to withdraw."),
+            TicketsNotDeposited => write!(
+                f,
+                "Tickets withdraw only their holder's own deposit until the round settles."
+            ),
+            TicketsExceedPrincipal { pool_id } => write!(
+                f,
+                "Pool {} has less principal left than those tickets are worth.",
+                pool_id
+            ),
+            OneNftDeposit => write!(f, "NFT ticket rounds take one deposit per address."),
+            NftWithdrawal => write!(f, "Deposits in NFT ticket rounds can't be withdrawn."),
+            MultiDenomWithdrawal => {
+                write!(f, "Deposits in multi-denom rounds can't be withdrawn.")
+            }
+            UnknownReferralCode => write!(f, "Unknown referral code."),
+            OwnReferralCode => write!(f, "Cannot use your own referral code."),
+            ReferralCodeTaken => write!(f, "Referral code is taken."),
+            OnlyPlayersListed => write!(f, "Only players can be listed"),
+            InvalidPort => write!(f, "Invalid port in denom trace"),
+            InvalidDenomTrace => write!(
+                f,
+                "Denom trace must be port/channel hops followed by a base denom"
+            ),
+            DenomTraceMismatch => write!(f, "Denom does not match its trace"),
+            InvalidChannel => write!(f, "Invalid channel id"),
+            InvalidRecipient => write!(f, "Invalid recipient address"),
+            RecoveryPending => write!(f, "A recovery is already pending."),
+            NoRecovery => write!(f, "No recovery is pending."),
+            RecoveryNotReady { remaining, unit } => {
+                write!(f, "Recovery can complete in {} {}.", remaining, unit)
+            }
+            InvariantBroken { pool_id, what } => {
+                write!(f, "Pool {} is inconsistent: {}", pool_id, what)
+            }
+            NestedBatch => write!(f, "Batches cannot be nested"),
+            AuthenticatedInBatch => write!(f, "Queries with a viewing key cannot be batched."),
+            NotBatchable => write!(f, "This query cannot be batched."),
+            DelegatorsPrivate => write!(f, "Delegators are private on this contract."),
+        }
+    }
+}
+
+impl From<StdError> for ContractError {
+    fn from(err: StdError) -> Self {
+        ContractError::Std(err)
+    }
+}
+
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std(err) => err,
+            ContractError::Unauthorized => StdError::unauthorized(),
+            ContractError::Underflow {
+                minuend,
+                subtrahend,
+            } => StdError::underflow(minuend, subtrahend),
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_std_error() {
+        assert_eq!(
+            StdError::from(ContractError::Unauthorized),
+            StdError::unauthorized()
+        );
+        assert_eq!(
+            StdError::from(ContractError::Std(StdError::not_found("Pool"))),
+            StdError::not_found("Pool")
+        );
+        assert_eq!(
+            StdError::from(ContractError::WrongDenom {
+                expected: "uscrt".to_string()
+            }),
+            StdError::generic_err("Only uscrt can be deposited")
+        );
+        let err = ContractError::CapExceeded {
+            what: "hooks".to_string(),
+            max: 8,
+        };
+        assert_eq!(err.to_string(), "At most 8 hooks are allowed.");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::state::Pool;
+use cosmwasm_std::{log, LogAttribute, Uint128};
+
+// Log schema for pool lifecycle milestones. Each milestone is appended to the
+// handler's action attributes (which already carry `pool_id`) and starts with
+// `event` and `event_version`. The keys below are fixed for a given version;
+// any rename or removal must bump `EVENT_SCHEMA_VERSION`.
+//
+//   pool_created      seed_amount, carryover_amount, locks_at
+//   pool_locked       delegated_amount, closes_at
+//   rewards_harvested undelegated_amount, unbonds_at
+//   winner_drawn      prize_amount, rewards_realized, settled_at
+//   prize_claimed     claimed_amount
+//
+// Timestamps are seconds or block heights depending on the pool timing mode;
+// a timestamp that is not known yet is logged as an empty string.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+fn milestone(event: &str) -> Vec<LogAttribute> {
+    vec![
+        log("event", event),
+        log("event_version", EVENT_SCHEMA_VERSION),
+    ]
+}
+
+fn optional(value: Option<u64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+pub fn pool_created(pool: &Pool) -> Vec<LogAttribute> {
+    let mut attrs = milestone("pool_created");
+    attrs.push(log("seed_amount", pool.seed_amount));
+    attrs.push(log("carryover_amount", pool.carryover_amount));
+    attrs.push(log("locks_at", optional(pool.locks_at)));
+    attrs
+}
+
+pub fn pool_locked(pool: &Pool) -> Vec<LogAttribute> {
+    let mut attrs = milestone("pool_locked");
+    attrs.push(log("delegated_amount", pool.delegated_amt));
+    attrs.push(log("closes_at", optional(pool.closes_at)));
+    attrs
+}
+
+// Undelegating withdraws the accrued rewards to the contract, so closing a
+// pool is where its rewards are harvested.
+pub fn rewards_harvested(pool: &Pool) -> Vec<LogAttribute> {
+    let mut attrs = milestone("rewards_harvested");
+    attrs.push(log("undelegated_amount", pool.unbonding_amount));
+    attrs.push(log("unbonds_at", optional(pool.unbonds_at)));
+    attrs
+}
+
+// The winner's address is deliberately left out.
+pub fn winner_drawn(pool: &Pool) -> Vec<LogAttribute> {
+    let mut attrs = milestone("winner_drawn");
+    attrs.push(log("prize_amount", pool.prize_amount));
+    attrs.push(log("rewards_realized", pool.rewards_realized));
+    attrs.push(log("settled_at", optional(pool.settled_at)));
+    attrs
+}
+
+pub fn prize_claimed(amount: Uint128) -> Vec<LogAttribute> {
+    let mut attrs = milestone("prize_claimed");
+    attrs.push(log("claimed_amount", amount));
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Timing;
+    use cosmwasm_std::testing::mock_env;
+
+    fn keys(attrs: &[LogAttribute]) -> Vec<&str> {
+        attrs.iter().map(|a| a.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_milestone_keys_are_fixed() {
+        let env = mock_env("owner", &[]);
+        let pool = Pool::new(1, &Timing::default(), &env.block);
+        let attrs = pool_created(&pool);
+        assert_eq!(attrs[0], log("event", "pool_created"));
+        assert_eq!(attrs[1], log("event_version", 1));
+        assert_eq!(
+            keys(&attrs[2..]),
+            vec!["seed_amount", "carryover_amount", "locks_at"]
+        );
+        assert_eq!(
+            keys(&pool_locked(&pool)[2..]),
+            vec!["delegated_amount", "closes_at"]
+        );
+        // Unknown timestamps keep their key with an empty value.
+        let attrs = rewards_harvested(&pool);
+        assert_eq!(
+            attrs[2..],
+            [log("undelegated_amount", 0), log("unbonds_at", "")]
+        );
+        assert_eq!(
+            keys(&winner_drawn(&pool)[2..]),
+            vec!["prize_amount", "rewards_realized", "settled_at"]
+        );
+        assert_eq!(prize_claimed(Uint128(5))[2..], [log("claimed_amount", 5)]);
+    }
+}
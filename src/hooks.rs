@@ -0,0 +1,61 @@
+use crate::state::Token;
+use cosmwasm_std::{to_binary, CosmosMsg, StdResult, Uint128, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Most hooks a lottery notifies. Every hook adds a message to each lifecycle
+// transaction, and a failing hook fails it, so the owner keeps the list short.
+// Registrations waiting for approval don't count, see `pending_hooks`.
+pub const MAX_HOOKS: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    PoolOpened,
+    PoolLocked,
+    PoolSettled,
+}
+
+// What approved hook contracts receive, as `{"lottery_hook": {...}}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookMsg {
+    LotteryHook { event: HookEvent, pool_id: u64 },
+}
+
+// Sent to a winner that registered a `WinnerCallback`. Funds are attached only
+// if it asked for the prize along with it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WinnerMsg {
+    WinnerNotification {
+        pool_id: u64,
+        prize_amount: Uint128,
+        denom: String,
+        prize_attached: bool,
+    },
+}
+
+// A contract that asked to be notified. Only approved hooks are called.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Hook {
+    pub contract: Token,
+    pub approved: bool,
+}
+
+// One notification per approved hook.
+pub fn hook_messages(hooks: &[Hook], event: HookEvent, pool_id: u64) -> StdResult<Vec<CosmosMsg>> {
+    let msg = to_binary(&HookMsg::LotteryHook { event, pool_id })?;
+    Ok(hooks
+        .iter()
+        .filter(|hook| hook.approved)
+        .map(|hook| {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: hook.contract.address.clone(),
+                callback_code_hash: hook.contract.code_hash.clone(),
+                msg: msg.clone(),
+                send: vec![],
+            })
+        })
+        .collect())
+}
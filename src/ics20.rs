@@ -0,0 +1,33 @@
+use cosmwasm_std::{to_binary, Coin, CosmosMsg, HumanAddr, StdResult, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// The handle of the ICS-20 gateway that the lottery calls. This version of
+// cosmwasm has no IBC messages, so transfers out go through a gateway contract
+// that sends the attached coins over `channel`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ics20HandleMsg {
+    Transfer {
+        channel: String,
+        remote_address: String,
+        // Seconds; the gateway's default when None.
+        timeout: Option<u64>,
+    },
+}
+
+impl Ics20HandleMsg {
+    pub fn to_cosmos_msg(
+        &self,
+        gateway: HumanAddr,
+        gateway_code_hash: String,
+        send: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: gateway,
+            callback_code_hash: gateway_code_hash,
+            msg: to_binary(self)?,
+            send,
+        }))
+    }
+}
@@ -0,0 +1,120 @@
+use crate::error::ContractError;
+use cosmwasm_std::Uint128;
+
+// Checked arithmetic for pool totals and balances. Amounts never wrap or
+// panic; overflow and underflow abort the transaction with an error instead.
+
+pub fn add(a: Uint128, b: Uint128) -> Result<Uint128, ContractError> {
+    a.u128()
+        .checked_add(b.u128())
+        .map(Uint128)
+        .ok_or(ContractError::Overflow {
+            lhs: a,
+            op: '+',
+            rhs: b,
+        })
+}
+
+pub fn sub(a: Uint128, b: Uint128) -> Result<Uint128, ContractError> {
+    a.u128()
+        .checked_sub(b.u128())
+        .map(Uint128)
+        .ok_or(ContractError::Underflow {
+            minuend: a,
+            subtrahend: b,
+        })
+}
+
+// `sub` for counters such as the number of depositors.
+pub fn sub_count(a: u64, b: u64) -> Result<u64, ContractError> {
+    a.checked_sub(b).ok_or(ContractError::Underflow {
+        minuend: Uint128(u128::from(a)),
+        subtrahend: Uint128(u128::from(b)),
+    })
+}
+
+// `a * numerator / denominator`, rounded down. Unlike `Uint128::multiply_ratio`
+// the intermediate product is checked.
+pub fn mul_ratio(a: Uint128, numerator: u128, denominator: u128) -> Result<Uint128, ContractError> {
+    if denominator == 0 {
+        return Err(ContractError::DivideByZero);
+    }
+    a.u128()
+        .checked_mul(numerator)
+        .map(|product| Uint128(product / denominator))
+        .ok_or(ContractError::Overflow {
+            lhs: a,
+            op: '*',
+            rhs: Uint128(numerator),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_boundaries() {
+        assert_eq!(add(Uint128(1), Uint128(2)).unwrap(), Uint128(3));
+        assert_eq!(
+            add(Uint128(u128::MAX - 1), Uint128(1)).unwrap(),
+            Uint128(u128::MAX)
+        );
+        let err = add(Uint128(u128::MAX), Uint128(1)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Overflow {
+                lhs: Uint128(u128::MAX),
+                op: '+',
+                rhs: Uint128(1),
+            }
+        );
+        assert_eq!(err.to_string(), format!("Overflow: {} + 1", u128::MAX));
+    }
+
+    #[test]
+    fn test_sub_boundaries() {
+        assert_eq!(sub(Uint128(3), Uint128(3)).unwrap(), Uint128(0));
+        assert_eq!(
+            sub(Uint128(0), Uint128(1)).unwrap_err(),
+            ContractError::Underflow {
+                minuend: Uint128(0),
+                subtrahend: Uint128(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_sub_count_boundaries() {
+        assert_eq!(sub_count(1, 1).unwrap(), 0);
+        assert_eq!(
+            sub_count(0, 1).unwrap_err(),
+            ContractError::Underflow {
+                minuend: Uint128(0),
+                subtrahend: Uint128(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_mul_ratio_boundaries() {
+        assert_eq!(mul_ratio(Uint128(1_000), 250, 10_000).unwrap(), Uint128(25));
+        assert_eq!(mul_ratio(Uint128(7), 1, 2).unwrap(), Uint128(3));
+        assert_eq!(
+            mul_ratio(Uint128(u128::MAX), 1, 1).unwrap(),
+            Uint128(u128::MAX)
+        );
+        assert_eq!(
+            mul_ratio(Uint128(u128::MAX), 2, 2).unwrap_err(),
+            ContractError::Overflow {
+                lhs: Uint128(u128::MAX),
+                op: '*',
+                rhs: Uint128(2),
+            }
+        );
+        assert_eq!(
+            mul_ratio(Uint128(1), 1, 0).unwrap_err(),
+            ContractError::DivideByZero
+        );
+    }
+}
@@ -1,33 +1,1401 @@
-use crate::state::Pool;
-use cosmwasm_std::HumanAddr;
+use crate::aggregator::{LotteryHandleV1, LotteryQueryV1};
+use crate::hooks::Hook;
+use crate::oracle::Oracle;
+use crate::state::{
+    round_to_step, Action, Child, Delegation, DepositLimit, Emission, Entry, Event, EventKind,
+    Factory, HistoryEntry, IbcDenom, IbcPayout, Player, Pool, PoolIndex, PoolMetadata, PoolStatus,
+    SeenKey, State, Timing, TimingMode, Token, Transition, Validator, WinnerCallback,
+};
+use cosmwasm_std::{Binary, Coin, Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InitMsg {}
+pub struct InitMsg {
+    // Defaults to wall-clock timing with 1 day OPEN and 21 days LOCKED.
+    pub timing: Option<Timing>,
+    // Validators LOCKED pools are staked with, by weight. None keeps funds in the contract.
+    pub validators: Option<Vec<Validator>>,
+    // Seeds generated viewing keys. Derived from the init block when omitted.
+    pub prng_seed: Option<Binary>,
+    // Private query responses are padded to a multiple of this many bytes. Defaults to 256.
+    pub response_block_size: Option<u32>,
+    // Public totals are rounded to a multiple of this, so diffing them doesn't reveal
+    // a single deposit. Defaults to 1, which reports them exactly.
+    pub public_rounding: Option<Uint128>,
+    // Public totals only change when a pool is created, locked or closed, so they can't
+    // be matched to individual transactions. Off by default.
+    pub delay_public_totals: Option<bool>,
+    // Make delegator lists, balances and winners public. Viewing keys are then not
+    // checked, and every winner is announced. Off by default, and can't be combined
+    // with `public_rounding` or `delay_public_totals`.
+    pub transparent: Option<bool>,
+    // Replace the errors of deposits, withdrawals, win announcements and private
+    // queries with one generic message, so probing them reveals nothing about pool
+    // status, deadlines or keys. Off by default.
+    pub generic_errors: Option<bool>,
+    // Set by a factory spawning this lottery as a child round. Init tells it our address
+    // through `HandleMsg::RegisterChild`.
+    pub parent: Option<ParentInfo>,
+    // Can replace a lost owner key after `RECOVERY_DELAY`, see `HandleMsg::StartRecovery`.
+    // None leaves the owner key as the only way in.
+    pub guardian: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ParentInfo {
+    pub address: HumanAddr,
+    pub code_hash: String,
+    pub index: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    // Fresh entropy for the viewing key and draw seed. Required when migrating the
+    // legacy layout, which never stored a seed; otherwise it is folded into the seed
+    // already there, which is never replaced.
+    pub prng_seed: Option<Binary>,
+}
 
+// Response data of handles that return something to the sender, so calling
+// contracts don't have to parse logs.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    CreateViewingKey {
+        key: String,
+    },
+    CreatePool {
+        #[serde(with = "u64_string")]
+        #[schemars(with = "String")]
+        pool_id: u64,
+    },
+    // `ticket_id` is the token id of the ticket minted in NFT ticket rounds.
+    Deposit {
+        #[serde(with = "u64_string")]
+        #[schemars(with = "String")]
+        pool_id: u64,
+        amount: Uint128,
+        ticket_id: Option<String>,
+    },
+    ClaimPrize {
+        #[serde(with = "u64_string")]
+        #[schemars(with = "String")]
+        pool_id: u64,
+        amount: Uint128,
+        denom: String,
+    },
+    ClaimPrincipal {
+        #[serde(with = "u64_string")]
+        #[schemars(with = "String")]
+        pool_id: u64,
+        amount: Uint128,
+        denom: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// Every variant takes an optional `padding`, which is ignored. Wallets fill it so
+// that all messages encrypt to the same length, whatever they do.
 pub enum HandleMsg {
-    CrtePool {},
-    LockPool {},
-    ClsePool {},
+    // Funds attached by the owner seed the prize of the new pool. `nft_tickets` mints a
+    // SNIP-721 per entry instead, see `SetNftContract`. `ibc_denom` denominates the
+    // pool in an IBC voucher instead of the native denom. `extra_denoms` are accepted as
+    // well and need an oracle, see `Pool::extra_denoms`.
+    // Also accepted as `create_pool`, like `close_pool` below. The short names are
+    // kept for existing clients; new variants spell their names out.
+    #[serde(alias = "create_pool")]
+    CrtePool {
+        metadata: Option<PoolMetadata>,
+        nft_tickets: Option<bool>,
+        ibc_denom: Option<IbcDenom>,
+        extra_denoms: Option<Vec<String>>,
+        padding: Option<String>,
+    },
+    LockPool {
+        padding: Option<String>,
+    },
+    #[serde(alias = "close_pool")]
+    ClsePool {
+        padding: Option<String>,
+    },
+    // `memo` replaces the note kept on the sender's entry; see `save_memo`.
+    // `referral` credits the deposit to a registered referral code. Tickets, if
+    // enabled, are minted to the sender. A message repeating an `idempotency_key`
+    // the sender used in the last 10 minutes fails; the same goes for the claims.
+    Deposit {
+        memo: Option<String>,
+        referral: Option<String>,
+        idempotency_key: Option<String>,
+        padding: Option<String>,
+    },
+    // Not available once tickets are enabled; send them back instead, see `ReceiveMsg`.
+    Withdraw {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    CleanupPool {
+        pool_id: u64,
+        limit: Option<u32>,
+        padding: Option<String>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    // Generate a viewing key, returned in the response data as `HandleAnswer`.
+    CreateViewingKey {
+        entropy: String,
+        padding: Option<String>,
+    },
+    // Add the attached funds to the prize of the OPEN or LOCKED pool.
+    Sponsor {
+        padding: Option<String>,
+    },
+    // Opt into or out of the public leaderboard. Once it is full, opting in takes the
+    // place of the smallest listed depositor, if the sender deposited more.
+    SetPublicListing {
+        public: bool,
+        padding: Option<String>,
+    },
+    // Owner only. Takes effect when the next pool is created.
+    SetFee {
+        rate: Decimal,
+        recipient: Option<HumanAddr>,
+        padding: Option<String>,
+    },
+    // Owner only. Mix fresh entropy into the internal seed. Rounds already locked keep
+    // the seed they sealed, so it only affects later rounds and viewing keys.
+    RotateSeed {
+        entropy: String,
+        padding: Option<String>,
+    },
+    // Owner only. Draw the winner of the CLOSED pool once its stake has unbonded.
+    // Picks up a scan started by `PrepareDraw`.
+    Draw {
+        padding: Option<String>,
+    },
+    // Anyone. Scan up to `limit` more of the CLOSED pool's delegators for the winner,
+    // so `Draw` stays within gas limits however many there are. The winner is fixed
+    // by the first call; see `GetSettlementProgress`.
+    PrepareDraw {
+        limit: Option<u32>,
+        padding: Option<String>,
+    },
+    // Winner only. List the sender's win publicly; see `GetPastWinners`.
+    AnnounceWin {
+        pool_id: u64,
+        padding: Option<String>,
+    },
+    // Winner only. Pay out the prize of a drawn round, through the winner's callback if
+    // it set one. With `ibc_payout` it goes to an address on another chain through the
+    // IBC gateway instead.
+    ClaimPrize {
+        pool_id: u64,
+        ibc_payout: Option<IbcPayout>,
+        idempotency_key: Option<String>,
+        padding: Option<String>,
+    },
+    // Depositors only. Pay back the sender's principal in a settled round, less their
+    // share of any slash, once the unbonded stake has reached the contract.
+    ClaimPrincipal {
+        pool_id: u64,
+        idempotency_key: Option<String>,
+        padding: Option<String>,
+    },
+    // Owner only. The contract fees and swept funds are sent to, see `treasury`.
+    SetTreasury {
+        treasury: Option<Token>,
+        padding: Option<String>,
+    },
+    // Owner only. Whether slashes are covered from rewards, see `cover_slashes`.
+    SetSlashCover {
+        enabled: bool,
+        padding: Option<String>,
+    },
+    // Owner only. Cap deposits accepted per block, see `DepositLimit`. None lifts the cap.
+    SetDepositLimit {
+        limit: Option<DepositLimit>,
+        padding: Option<String>,
+    },
+    // Owner only. Cap the addresses each pool accepts, from the next deposit on.
+    SetMaxDepositors {
+        max: u32,
+        padding: Option<String>,
+    },
+    // Owner only. Refuse new pools while more prizes than `max` are unclaimed. None
+    // lifts the limit.
+    SetMaxUnclaimedPrizes {
+        max: Option<u64>,
+        padding: Option<String>,
+    },
+    // Guardian only. Propose `new_owner` as the owner. It can be completed with
+    // `FinishRecovery` once the recovery delay has passed on the contract's clock, and
+    // cancelled until then.
+    StartRecovery {
+        new_owner: HumanAddr,
+        padding: Option<String>,
+    },
+    // Guardian only. Drop the pending recovery.
+    CancelRecovery {
+        padding: Option<String>,
+    },
+    // Guardian only. Hand the contract to the proposed owner.
+    FinishRecovery {
+        padding: Option<String>,
+    },
+    // Owner only. Pay out the accrued fees to the treasury, or to the fee recipient
+    // while there is none.
+    CollectFees {
+        padding: Option<String>,
+    },
+    // Owner only. Send the whole balance of a denom no pool uses to the treasury,
+    // such as an airdrop.
+    SweepToTreasury {
+        denom: String,
+        padding: Option<String>,
+    },
+    // Owner only. The ICS-20 gateway that carries `ClaimPrize` payouts to other chains.
+    SetIbcGateway {
+        gateway: Option<Token>,
+        padding: Option<String>,
+    },
+    // Claim a referral code for the sender. Only its hash is stored.
+    RegisterReferralCode {
+        code: String,
+        padding: Option<String>,
+    },
+    // Owner only. Part of each round's protocol fee set aside for referrers, at most one.
+    SetReferralShare {
+        share: Decimal,
+        padding: Option<String>,
+    },
+    // Referrers only. Pay out what the sender's code earned in each listed settled pool,
+    // at most `MAX_LIMIT` of them.
+    ClaimReferralRewards {
+        code: String,
+        pool_ids: Vec<u64>,
+        padding: Option<String>,
+    },
+    // Owner only. Accept deposits of a SNIP-20 token wrapping the native denom 1:1, and
+    // register with it for `Receive`. Deposits are redeemed for the native denom on
+    // receipt, which fails for any other token.
+    RegisterToken {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Owner only. Stop accepting deposits of a registered token.
+    DeregisterToken {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    // Called by a registered token when `from` sends it tokens. `msg` is a `ReceiveMsg`
+    // and defaults to a plain deposit.
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    // Owner only, once. Mint tickets for every deposit from now on. The lottery must
+    // be a minter of the token.
+    SetTicketToken {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Owner only. The SNIP-721 that NFT ticket rounds mint from. The lottery must be a
+    // minter of it.
+    SetNftContract {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Called by the NFT contract when `from` sends it a ticket. Sending back the winning
+    // ticket of a settled round pays its prize to `from` and burns it.
+    ReceiveNft {
+        sender: HumanAddr,
+        from: HumanAddr,
+        token_id: String,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    // Owner only. Mint `per_round` of a reward token to the depositors of each round
+    // settled from the next pool on, see `Emission`. A `per_round` of 0 stops it. The
+    // token can't change once set, and the lottery must be a minter of it.
+    SetEmission {
+        address: HumanAddr,
+        code_hash: String,
+        per_round: Uint128,
+        halving_interval: u64,
+        padding: Option<String>,
+    },
+    // Mint the sender's share of the emission of each listed settled pool, at most
+    // `MAX_LIMIT` of them.
+    ClaimEmissions {
+        pool_ids: Vec<u64>,
+        idempotency_key: Option<String>,
+        padding: Option<String>,
+    },
+    // Called by a contract that wants a `HookMsg` whenever a pool opens, locks or
+    // settles. It is only notified once the owner approves it.
+    RegisterHook {
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Owner only.
+    ApproveHook {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    // Owner or the hook itself.
+    RemoveHook {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    // For contract depositors: get a `WinnerNotification` when the sender claims a prize,
+    // with the prize attached if `attach_prize`. None removes the callback.
+    SetWinnerCallback {
+        callback: Option<WinnerCallback>,
+        padding: Option<String>,
+    },
+    // Owner only.
+    SetSwapRouter {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Owner only. Send the undrawn pool's rewards in other denoms to the swap router,
+    // to come back in the prize denom through `SwapProceeds`.
+    ConvertRewards {
+        min_return: Uint128,
+        padding: Option<String>,
+    },
+    // Called by the swap router with the proceeds of `ConvertRewards` attached.
+    SwapProceeds {
+        pool_id: u64,
+        padding: Option<String>,
+    },
+    // Owner only. Give up on a conversion the swap router never paid back, so the
+    // undrawn pool can settle. The rewards sent to the router are written off.
+    ResetConversion {
+        padding: Option<String>,
+    },
+    // Owner only. None stops valuing assets.
+    SetOracle {
+        oracle: Option<Oracle>,
+        padding: Option<String>,
+    },
+    // Owner only. Accept deposits of the wrapped native token and register for `Receive`.
+    SetWrappedNative {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Like `Withdraw`, paid out in the wrapped native token.
+    WithdrawWrapped {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    // Owner only. Enable factory mode with the code child lotteries are instantiated from.
+    SetFactory {
+        code_id: u64,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    // Owner only. Instantiate a child lottery for a new round. This contract becomes its
+    // owner; `init.parent` is filled in.
+    SpawnRound {
+        label: String,
+        init: InitMsg,
+        padding: Option<String>,
+    },
+    // Called by a child from its init.
+    RegisterChild {
+        index: u64,
+        padding: Option<String>,
+    },
+    // Owner only. Relay a handle to a child, with any attached funds.
+    ExecuteChild {
+        index: u64,
+        msg: Binary,
+        padding: Option<String>,
+    },
+    // The stable interface for aggregators, see `aggregator`.
+    LotteryV1(LotteryHandleV1),
+}
+
+// What a SNIP-20 `Send` to the lottery does.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Deposit {
+        memo: Option<String>,
+        referral: Option<String>,
+    },
+    // Sent with tickets, which are burned: withdraw that much of the holder's deposit in
+    // the OPEN pool, or with `pool_id` redeem them for a settled pool's principal.
+    // Tickets are fungible, so transferred ones can only be redeemed after settlement.
+    Withdraw {
+        pool_id: Option<u64>,
+    },
 }
 
+// List queries page with `start_after`, the index of the last item the caller
+// has seen (exclusive), and `limit`, which defaults to `DEFAULT_LIMIT` (10) and is
+// capped at `MAX_LIMIT` (30). Newest-first lists continue below `start_after`.
+// Pass the last index of one page as `start_after` to get the next.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetOwner {},
-    GetCurrentPool {},
+    // The guardian and any recovery in progress. Public, so a recovery can't go unnoticed.
+    GetRecovery {},
+    GetConfig {},
+    // `caller` only fills in `NextTransition::caller_can_trigger`.
+    GetCurrentPool {
+        caller: Option<HumanAddr>,
+    },
+    GetPool {
+        pool_id: u64,
+        caller: Option<HumanAddr>,
+    },
+    // Queries carry no block info, so the caller passes the current block time and height.
+    GetTimeRemaining {
+        time: u64,
+        height: u64,
+    },
+    // Newest first, indexed by pool id.
+    GetPoolHistory {
+        #[serde(alias = "start_before")]
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Only winners who opted into public listing appear here.
+    GetPastWinners {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetStats {},
+    GetStatistics {},
+    // Oldest first, indexed by event sequence number.
+    ChangesSince {
+        #[serde(alias = "since")]
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetTotalValueLocked {},
+    // Factory mode: the spawned children, oldest first.
+    GetChildren {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Factory mode: `GetTotalValueLocked` summed over a page of children.
+    GetChildrenTotalValueLocked {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Only addresses that opted into public listing are ranked. Indexed by rank, top first.
+    GetLeaderboard {
+        order_by: LeaderboardOrder,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetRound {},
+    GetUnbondingStatus {},
+    // How far the CLOSED pool, or `pool_id`, has got towards settlement and cleanup.
+    // Defaults to the pool waiting for its draw.
+    GetSettlementProgress {
+        pool_id: Option<u64>,
+    },
+    // What a ticket token redeems for, and when, in each live pool, or in `pool_id`.
+    GetTicketRate {
+        pool_id: Option<u64>,
+    },
+    // Configured validators and what the LOCKED pool has staked with each.
+    GetValidatorSet {},
+    GetFeeInfo {},
+    // Defaults to the most recently settled pool.
+    GetRewardBreakdown {
+        pool_id: Option<u64>,
+    },
+    // Everything a frontend shows on load. Countdowns are included when the
+    // current block value for the configured timing mode is passed.
+    Dashboard {
+        time: Option<u64>,
+        height: Option<u64>,
+    },
+    GetCurrentPrizePot {},
+    // Defaults to the current pool.
+    GetDelegatorCount {
+        pool_id: Option<u64>,
+    },
+    GetTransitions {
+        pool_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Credit of a referral code. Never names the referrer or the referred.
+    GetReferral {
+        code: String,
+    },
+    // Transparent mode only. Defaults to the current pool.
+    GetDelegators {
+        pool_id: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Authenticated with the viewing key set by `address`.
+    GetMyDeposit {
+        address: HumanAddr,
+        key: String,
+    },
+    GetOdds {
+        address: HumanAddr,
+        key: String,
+    },
+    // Newest first.
+    GetMyHistory {
+        address: HumanAddr,
+        key: String,
+        #[serde(alias = "start_before")]
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Tells only the winner that they won, whether or not it has been announced.
+    AmITheWinner {
+        address: HumanAddr,
+        key: String,
+        pool_id: u64,
+    },
+    // Up to `MAX_LIMIT` sub-queries, answered in order. Batches don't nest, and take no
+    // queries with a viewing key or of the aggregator interface. The response is padded
+    // like private ones.
+    Batch {
+        queries: Vec<QueryMsg>,
+    },
+    // Recompute accounting invariants against storage, the bank and the staking module.
+    // The liquid balance must cover fees, live pools, and the principal, prizes and
+    // referral rewards settled pools still owe.
+    HealthCheck {},
+    // Run against the code a migration would install, before migrating to it.
+    SimulateMigration {},
+    // Owner only, authenticated with the owner's viewing key. Start without a cursor
+    // and pass back `next` until it is None. Seeds and viewing keys are not exported.
+    ExportState {
+        address: HumanAddr,
+        key: String,
+        cursor: Option<ExportCursor>,
+        limit: Option<u32>,
+    },
+    // The stable interface for aggregators, see `aggregator`.
+    LotteryV1(LotteryQueryV1),
 }
 
 // We define a custom struct for each query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardOrder {
+    TotalDeposited,
+    RoundsPlayed,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OwnerResponse {
     pub owner: HumanAddr,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RecoveryResponse {
+    pub guardian: Option<HumanAddr>,
+    pub pending: Option<PendingRecovery>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRecovery {
+    pub new_owner: HumanAddr,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub executable_at: u64,
+}
+
+// Effective deployment parameters, so clients need not hardcode them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: HumanAddr,
+    pub denom: String,
+    pub timing: Timing,
+    pub default_limit: u32,
+    pub max_limit: u32,
+    pub response_block_size: u32,
+    pub public_rounding: Uint128,
+    pub delay_public_totals: bool,
+    pub transparent: bool,
+    pub generic_errors: bool,
+    pub tokens: Vec<Token>,
+    pub ticket: Option<Token>,
+    pub nft_contract: Option<Token>,
+    pub emission: Option<Emission>,
+    pub hooks: Vec<Hook>,
+    pub swap_router: Option<Token>,
+    pub oracle: Option<Oracle>,
+    pub wrapped_native: Option<Token>,
+    pub factory: Option<Factory>,
+    pub parent: Option<Token>,
+    pub ibc_gateway: Option<Token>,
+    pub treasury: Option<Token>,
+    pub cover_slashes: bool,
+    pub deposit_limit: Option<DepositLimit>,
+    pub max_depositors: u32,
+    pub max_unclaimed_prizes: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PoolResponse {
+    pub pool: Option<PoolInfo>,
+    // Only set once the winner agreed to be announced.
+    pub winner: Option<HumanAddr>,
+    pub next_transition: Option<NextTransition>,
+}
+
+// Public view of a pool: aggregates only. The winner's address is left out; it
+// appears in `PoolResponse::winner` once announced.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolInfo {
+    pub id: u64,
+    pub metadata: PoolMetadata,
+    pub delegated_amt: Uint128,
+    pub delegations: Vec<Delegation>,
+    pub unbonding_amount: Uint128,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub unbonds_at: Option<u64>,
+    pub delegator_count: u64,
+    pub total_deposits: Uint128,
+    pub unique_depositors: u64,
+    pub total_weight: Uint128,
+    pub seed_amount: Uint128,
+    pub sponsor_amount: Uint128,
+    pub carryover_amount: Uint128,
+    pub status: PoolStatus,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub created_at: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub status_updated_at: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub status_updated_height: u64,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub locks_at: Option<u64>,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub closes_at: Option<u64>,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub settles_at: Option<u64>,
+    pub has_winner: bool,
+    pub prize_amount: Uint128,
+    pub rewards_realized: Uint128,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub settled_at: Option<u64>,
+    pub winner_announced: bool,
+    pub transition_count: u64,
+    pub fee_rate: Decimal,
+    pub fee_amount: Uint128,
+    pub nft_tickets: bool,
+    pub prize_claimed: bool,
+    pub emission_amount: Uint128,
+    pub denom: String,
+    pub denom_trace: Option<String>,
+    pub unconverted_rewards: Vec<Coin>,
+    pub converting: bool,
+    pub prize_value: Option<Uint128>,
+    pub extra_denoms: Vec<String>,
+    pub denom_balances: Vec<Coin>,
+}
+
+impl From<Pool> for PoolInfo {
+    fn from(pool: Pool) -> Self {
+        let denom = pool.denom().to_string();
+        PoolInfo {
+            id: pool.id,
+            metadata: pool.metadata,
+            delegated_amt: pool.delegated_amt,
+            delegations: pool.delegations,
+            unbonding_amount: pool.unbonding_amount,
+            unbonds_at: pool.unbonds_at,
+            delegator_count: pool.delegator_count,
+            total_deposits: pool.total_deposits,
+            unique_depositors: pool.unique_depositors,
+            total_weight: pool.total_weight,
+            seed_amount: pool.seed_amount,
+            sponsor_amount: pool.sponsor_amount,
+            carryover_amount: pool.carryover_amount,
+            status: pool.status,
+            created_at: pool.created_at,
+            status_updated_at: pool.status_updated_at,
+            status_updated_height: pool.status_updated_height,
+            locks_at: pool.locks_at,
+            closes_at: pool.closes_at,
+            settles_at: pool.settles_at,
+            has_winner: pool.winner.is_some(),
+            prize_amount: pool.prize_amount,
+            rewards_realized: pool.rewards_realized,
+            settled_at: pool.settled_at,
+            winner_announced: pool.winner_announced,
+            transition_count: pool.transition_count,
+            fee_rate: pool.fee_rate,
+            fee_amount: pool.fee_amount,
+            nft_tickets: pool.nft_tickets,
+            prize_claimed: pool.prize_claimed,
+            emission_amount: pool.emission_amount,
+            denom,
+            denom_trace: pool.ibc_denom.map(|ibc| ibc.trace),
+            unconverted_rewards: pool.unconverted_rewards,
+            converting: pool.converting,
+            prize_value: pool.prize_value,
+            extra_denoms: pool.extra_denoms,
+            denom_balances: pool.denom_balances,
+        }
+    }
+}
+
+impl PoolInfo {
+    // Round the amounts that move with individual deposits, withdrawals and sponsorships.
+    pub fn rounded(mut self, step: Uint128) -> Self {
+        self.delegated_amt = round_to_step(self.delegated_amt, step);
+        for delegation in self.delegations.iter_mut() {
+            delegation.amount = round_to_step(delegation.amount, step);
+        }
+        self.unbonding_amount = round_to_step(self.unbonding_amount, step);
+        self.total_deposits = round_to_step(self.total_deposits, step);
+        self.total_weight = round_to_step(self.total_weight, step);
+        self.sponsor_amount = round_to_step(self.sponsor_amount, step);
+        self
+    }
+}
+
+// The status change a pool can make next. Transitions are triggered by the owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NextTransition {
+    pub to: PoolStatus,
+    // Earliest point it is allowed, in the units of the timing mode.
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub allowed_at: u64,
+    pub caller_can_trigger: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsResponse {
+    pub pools_run: u64,
+    pub total_deposited: Uint128,
+    pub total_prizes_paid: Uint128,
+    pub unique_players: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransitionInfo {
+    pub index: u64,
+    pub from: Option<PoolStatus>,
+    pub to: PoolStatus,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub time: u64,
+    pub sender: HumanAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransitionsResponse {
+    pub transitions: Vec<TransitionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralResponse {
+    pub code_hash: Binary,
+    // Rounded down to a multiple of `REFERRAL_COUNT_STEP` unless public totals are exact.
+    pub deposits: u64,
+    // Rounded like other public totals.
+    pub referred_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatorInfo {
+    pub index: u64,
+    pub address: HumanAddr,
+    pub amount: Uint128,
+    pub weight: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatorsResponse {
+    pub pool_id: Option<u64>,
+    pub delegators: Vec<DelegatorInfo>,
+}
+
+// Position of one address in the current pool. `odds` is its share of the pool's weight.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MyDepositResponse {
+    pub pool_id: Option<u64>,
+    pub balance: Uint128,
+    pub weight: Uint128,
+    pub odds: Decimal,
+    pub memo: Option<String>,
+    // Deposits in the pool's extra denoms.
+    pub other_balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolSummary {
+    pub id: u64,
+    pub name: Option<String>,
+    pub status: PoolStatus,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub created_at: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub status_updated_at: u64,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub settled_at: Option<u64>,
+    pub total_deposits: Uint128,
+    pub unique_depositors: u64,
+    // Only set once the winner agreed to be announced.
+    pub winner: Option<HumanAddr>,
+    // Whether the prize went to someone, announced or not.
+    pub has_winner: bool,
+    pub prize_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolHistoryResponse {
+    pub pools: Vec<PoolSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PastWinner {
+    pub index: u64,
+    pub pool_id: u64,
+    pub winner: HumanAddr,
+    pub prize_amount: Uint128,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub settled_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PastWinnersResponse {
+    pub winners: Vec<PastWinner>,
+}
+
+// Chance of `address` winning the current pool. `one_in` is the odds as "1 in N",
+// rounded to the nearest N, and None when the address has no weight.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OddsResponse {
+    pub pool_id: Option<u64>,
+    pub weight: Uint128,
+    pub total_weight: Uint128,
+    pub odds: Decimal,
+    pub one_in: Option<Uint128>,
+}
+
+// Remaining seconds or blocks, per `mode`, until each deadline of the current pool.
+// A deadline that is not set yet is None; one that has passed is 0.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TimeRemainingResponse {
+    pub pool_id: Option<u64>,
+    pub mode: TimingMode,
+    pub until_lock: Option<u64>,
+    pub until_close: Option<u64>,
+    pub until_settle: Option<u64>,
+}
+
+// Principal held for unsettled pools, by pool status.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChildInfo {
+    pub index: u64,
+    pub label: String,
+    pub address: Option<HumanAddr>,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChildrenResponse {
+    pub children: Vec<ChildInfo>,
+}
+
+// Funds held for depositors and winners of this contract's pools. `settled` is the
+// principal and prizes settled pools still owe. Child rounds are summed separately by
+// `GetChildrenTotalValueLocked`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalValueLockedResponse {
+    pub open: Uint128,
+    pub locked: Uint128,
+    pub closed: Uint128,
+    // Missing from children running older code.
+    #[serde(default)]
+    pub settled: Uint128,
+    pub total: Uint128,
+}
+
+// `delegator_count` counts every address that ever deposited into the pool;
+// `unique_depositors` only those with a balance left.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatorCountResponse {
+    pub pool_id: Option<u64>,
+    pub delegator_count: u64,
+    pub unique_depositors: u64,
+}
+
+// What the current pool's prize is made of. `rewards` are the staking rewards earned so far,
+// including what a LOCKED pool's delegations have accrued. Every amount is rounded to the
+// public rounding step.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrizePotResponse {
+    pub pool_id: Option<u64>,
+    pub seed: Uint128,
+    pub sponsored: Uint128,
+    pub carryover: Uint128,
+    pub rewards: Uint128,
+    pub total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DashboardResponse {
+    pub pool: Option<PoolSummary>,
+    pub prize_pot: PrizePotResponse,
+    pub time_remaining: Option<TimeRemainingResponse>,
+    pub config: ConfigResponse,
+    pub stats: StatsResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HistoryItem {
+    pub index: u64,
+    pub action: Action,
+    pub pool_id: u64,
+    pub amount: Uint128,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HistoryResponse {
+    pub history: Vec<HistoryItem>,
+}
+
+// Stake of a CLOSED pool on its way back to the contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingInfo {
+    pub pool_id: u64,
+    pub amount: Uint128,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub matures_at: u64,
+    pub undelegations: Vec<Delegation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingStatusResponse {
+    pub pools: Vec<UnbondingInfo>,
+}
+
+// Steps between closing a pool and being done with it. Whatever is batched resumes
+// from its cursor, whoever sends the next batch.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementStage {
+    // Unbonding, or waiting for the draw to start.
+    Pending,
+    // Rewards in other denoms are out to the swap router.
+    ConvertingRewards,
+    // `PrepareDraw` has scanned part of the delegators.
+    PreparingDraw,
+    // The scan is complete; `Draw` only has to pay out.
+    ReadyToSettle,
+    // Settled; `CleanupPool` hasn't reached the last delegator yet.
+    CleaningUp,
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SettlementProgressResponse {
+    pub pool_id: u64,
+    pub stage: SettlementStage,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub unbonds_at: Option<u64>,
+    pub harvested_rewards: Uint128,
+    pub unconverted_rewards: Vec<Coin>,
+    // Delegator slots scanned for the winner and cleaned up, out of `delegator_count`.
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub draw_cursor: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub cleanup_cursor: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub delegator_count: u64,
+}
+
+// Tickets redeem for principal only, less the pool's share of any slash, so `rate` is
+// at most 1 and stays 1 until the pool is settled. It is 0 in pools that mint none.
+// Tickets can be sent back while the pool is OPEN, and redeemed once it is settled.
+// In between the principal is staked until the pool closes, in timing units, and
+// then unbonds for `unbonding_period` seconds, until `unbonds_at` once known.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolTicketRate {
+    pub pool_id: u64,
+    pub rate: Decimal,
+    pub redeemable: bool,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub closes_at: Option<u64>,
+    #[serde(with = "opt_u64_string")]
+    #[schemars(with = "Option<String>")]
+    pub unbonds_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TicketRateResponse {
+    pub ticket: Option<Token>,
+    pub unbonding_period: u64,
+    pub pools: Vec<PoolTicketRate>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorInfo {
+    pub address: HumanAddr,
+    pub weight: u64,
+    pub delegated: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorSetResponse {
+    pub validators: Vec<ValidatorInfo>,
+}
+
+// Earliest point, in the units of the timing mode, the round can enter `phase`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledPhase {
+    pub phase: PoolStatus,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub at: u64,
+}
+
+// `round` is the id of the current pool, 0 before the first one is created.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundResponse {
+    pub round: u64,
+    pub phase: Option<PoolStatus>,
+    pub mode: TimingMode,
+    pub schedule: Vec<ScheduledPhase>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LeaderboardEntry {
+    pub index: u64,
+    pub address: HumanAddr,
+    pub total_deposited: Uint128,
+    pub rounds_played: u64,
+    pub prizes_won: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LeaderboardResponse {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+// Lifetime metrics. `average_prize` is over pools that had a winner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatisticsResponse {
+    pub pools_run: u64,
+    pub prizes_awarded: u64,
+    pub total_prizes_paid: Uint128,
+    pub average_prize: Uint128,
+    pub total_rewards: Uint128,
+    pub unique_players: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Change {
+    pub seq: u64,
+    pub kind: EventKind,
+    pub pool_id: u64,
+    #[serde(with = "u64_string")]
+    #[schemars(with = "String")]
+    pub time: u64,
+}
+
+// `event_count` lets a poller tell whether it has caught up.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChangesResponse {
+    pub changes: Vec<Change>,
+    pub event_count: u64,
+}
+
+// A sub-query's response, under the name of the query that produced it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchAnswer {
+    GetOwner(OwnerResponse),
+    GetRecovery(RecoveryResponse),
+    GetConfig(ConfigResponse),
+    GetCurrentPool(PoolResponse),
+    GetPool(PoolResponse),
+    GetTimeRemaining(TimeRemainingResponse),
+    GetPoolHistory(PoolHistoryResponse),
+    GetPastWinners(PastWinnersResponse),
+    GetStats(StatsResponse),
+    GetStatistics(StatisticsResponse),
+    ChangesSince(ChangesResponse),
+    GetTotalValueLocked(TotalValueLockedResponse),
+    GetChildren(ChildrenResponse),
+    GetChildrenTotalValueLocked(TotalValueLockedResponse),
+    GetLeaderboard(LeaderboardResponse),
+    GetRound(RoundResponse),
+    GetUnbondingStatus(UnbondingStatusResponse),
+    GetSettlementProgress(SettlementProgressResponse),
+    GetTicketRate(TicketRateResponse),
+    GetValidatorSet(ValidatorSetResponse),
+    GetFeeInfo(FeeInfoResponse),
+    GetRewardBreakdown(RewardBreakdownResponse),
+    Dashboard(DashboardResponse),
+    GetCurrentPrizePot(PrizePotResponse),
+    GetDelegatorCount(DelegatorCountResponse),
+    GetTransitions(TransitionsResponse),
+    GetReferral(ReferralResponse),
+    GetDelegators(DelegatorsResponse),
+    HealthCheck(HealthResponse),
+    SimulateMigration(MigrationPreviewResponse),
+}
+
+// Exactly one of `ok` and `error` is set. A failing sub-query doesn't fail the batch.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchItem {
+    pub ok: Option<BatchAnswer>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchResponse {
+    pub results: Vec<BatchItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingFee {
+    pub rate: Decimal,
+    pub recipient: HumanAddr,
+    pub effective_from_pool: u64,
+}
+
+// Rates are shares of realized rewards, 0.05 for 5%.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeInfoResponse {
+    pub rate: Decimal,
+    pub max_rate: Decimal,
+    pub recipient: HumanAddr,
+    pub accrued: Uint128,
+    pub pending: Option<PendingFee>,
+}
+
+// How a settled pool's realized rewards were divided. The prize goes to the winner,
+// or carries over into the next pool when there is none. What is left after the
+// fee and the prize stays in the contract as reserve.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardBreakdownResponse {
+    pub pool_id: u64,
+    pub rewards_realized: Uint128,
+    pub fee_rate: Decimal,
+    pub fees: Uint128,
+    pub winner: Uint128,
+    pub carryover: Uint128,
+    pub reserve: Uint128,
+    // Principal lost to slashing, and the part of it made up from the rewards.
+    // `rewards_realized` is what was left after that cover.
+    pub slashed: Uint128,
+    pub slash_covered: Uint128,
+    // No charity share is configured; always zero for now.
+    pub charity: Uint128,
+}
+
+// Position in an export. Each pool's entries come first, then the codes that referred
+// deposits into it; after the last pool, players, each player's history, referral
+// codes, children, announced winners and events. `start_after` is the last list index
+// seen in the section.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportCursor {
+    Entries {
+        pool_id: u64,
+        start_after: Option<u64>,
+    },
+    Referred {
+        pool_id: u64,
+        start_after: Option<u64>,
+    },
+    Players {
+        start_after: Option<u64>,
+    },
+    // `player` is the player's position in the players section.
+    History {
+        player: u64,
+        start_after: Option<u64>,
+    },
+    Referrals {
+        start_after: Option<u64>,
+    },
+    Children {
+        start_after: Option<u64>,
+    },
+    Winners {
+        start_after: Option<u64>,
+    },
+    Events {
+        start_after: Option<u64>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportEntry {
+    pub address: HumanAddr,
+    pub entry: Entry,
+    pub memo: Option<String>,
+    // Extra denoms of a multi-denom pool.
+    pub balances: Vec<Coin>,
+    pub emission_claimed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportReferred {
+    pub code_hash: Binary,
+    pub amount: Uint128,
+    pub claimed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportPlayer {
+    pub address: HumanAddr,
+    pub player: Player,
+    pub idempotency_keys: Vec<SeenKey>,
+    pub winner_callback: Option<WinnerCallback>,
+}
+
+// A slice of one player's history, oldest first from `start`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportHistory {
+    pub address: HumanAddr,
+    pub start: u64,
+    pub entries: Vec<HistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportReferral {
+    pub code_hash: Binary,
+    pub referrer: HumanAddr,
+    pub deposits: u64,
+    pub referred_amount: Uint128,
+}
+
+// The first page carries the config and pool index. Each page of a pool's entries
+// carries the pool, and the first its transitions; other pages fill only their section.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct ExportResponse {
+    pub config: Option<State>,
+    pub pool_index: Option<PoolIndex>,
     pub pool: Option<Pool>,
+    pub transitions: Vec<Transition>,
+    pub entries: Vec<ExportEntry>,
+    pub referred: Vec<ExportReferred>,
+    pub players: Vec<ExportPlayer>,
+    pub history: Option<ExportHistory>,
+    pub referrals: Vec<ExportReferral>,
+    pub children: Vec<Child>,
+    // Pool ids, as in `GetPastWinners`.
+    pub winners: Vec<u64>,
+    pub events: Vec<Event>,
+    pub next: Option<ExportCursor>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvariantCheck {
+    pub name: String,
+    pub expected: Uint128,
+    pub actual: Uint128,
+    pub passed: bool,
+}
+
+// What `migrate` would do to the current storage. `layout` is "legacy", "current"
+// or "unknown"; `error` is set when migrate would fail outright.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrationPreviewResponse {
+    pub layout: String,
+    pub changes: Vec<String>,
+    // Records that would be dropped or carried over incomplete.
+    pub unconvertible: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HealthResponse {
+    pub healthy: bool,
+    pub checks: Vec<InvariantCheck>,
+}
+
+// `prize_amount` is zero unless `won`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AmITheWinnerResponse {
+    pub pool_id: u64,
+    pub settled: bool,
+    pub won: bool,
+    pub prize_amount: Uint128,
+}
+
+// Timestamps and heights go out as strings, like `Uint128`, so JavaScript clients
+// don't lose precision above 2^53. Use with `#[serde(with = "...")]`.
+mod u64_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+mod opt_u64_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&value.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => value.parse().map(Some).map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
 }
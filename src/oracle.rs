@@ -0,0 +1,100 @@
+use crate::error::ContractError;
+use crate::payout;
+use crate::state::Token;
+use cosmwasm_std::{to_binary, Decimal, Querier, QueryRequest, StdResult, Uint128, WasmQuery};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// The query of a price oracle contract that the lottery makes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    Price { base: String, quote: String },
+}
+
+// `rate` is the price of one unit of `base` in `quote`, as of block time `last_updated`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceResponse {
+    pub rate: Decimal,
+    pub last_updated: u64,
+}
+
+// Values assets in `quote`, refusing prices older than `max_age` seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Oracle {
+    pub contract: Token,
+    pub quote: String,
+    pub max_age: u64,
+}
+
+impl Oracle {
+    pub fn price<Q: Querier>(&self, querier: &Q, denom: &str, now: u64) -> StdResult<Decimal> {
+        if denom == self.quote {
+            return Ok(Decimal::one());
+        }
+        let msg = OracleQueryMsg::Price {
+            base: denom.to_string(),
+            quote: self.quote.clone(),
+        };
+        let price: PriceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: self.contract.address.clone(),
+            callback_code_hash: self.contract.code_hash.clone(),
+            msg: to_binary(&msg)?,
+        }))?;
+        if price.last_updated.saturating_add(self.max_age) < now {
+            return Err(ContractError::StalePrice {
+                denom: denom.to_string(),
+            }
+            .into());
+        }
+        Ok(price.rate)
+    }
+    pub fn value<Q: Querier>(
+        &self,
+        querier: &Q,
+        amount: Uint128,
+        denom: &str,
+        now: u64,
+    ) -> StdResult<Uint128> {
+        Ok(payout::scale(amount, self.price(querier, denom, now)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{HumanAddr, QuerierResult};
+
+    // Answers every price query with the same response.
+    struct FixedPrice(PriceResponse);
+
+    impl Querier for FixedPrice {
+        fn raw_query(&self, _request: &[u8]) -> QuerierResult {
+            Ok(to_binary(&self.0))
+        }
+    }
+
+    #[test]
+    fn test_oracle_price() {
+        let oracle = Oracle {
+            contract: Token {
+                address: HumanAddr::from("oracle"),
+                code_hash: "oracle_hash".to_string(),
+            },
+            quote: "uusd".to_string(),
+            max_age: 60,
+        };
+        let querier = FixedPrice(PriceResponse {
+            rate: Decimal::percent(250),
+            last_updated: 1000,
+        });
+        let value = oracle.value(&querier, Uint128(10), "uscrt", 1060).unwrap();
+        assert_eq!(value, Uint128(25));
+        assert!(oracle.price(&querier, "uscrt", 1061).is_err());
+        // The quote unit itself needs no price.
+        assert_eq!(
+            oracle.price(&querier, "uusd", 5000).unwrap(),
+            Decimal::one()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error::ContractError;
+use crate::math;
+use cosmwasm_std::{Decimal, Uint128};
+
+// Payout math. Fees, slash and emission shares and delegation splits all go
+// through here so they round the same way: down, to the base unit. No split pays
+// out more than it was given; callers decide where the leftover dust goes.
+
+const FRACTIONAL: u128 = 1_000_000_000_000_000_000;
+
+// A rate written in basis points, as fee rates were stored before.
+pub fn rate_from_bps(bps: u64) -> Decimal {
+    Decimal::from_ratio(bps, 10_000u64)
+}
+
+// `rate` of `amount`, for rates up to one. Exact for any amount: the whole and
+// fractional parts are scaled separately, so nothing overflows.
+pub fn portion(amount: Uint128, rate: Decimal) -> Result<Uint128, ContractError> {
+    if rate > Decimal::one() {
+        return Err(ContractError::InvalidRate { rate });
+    }
+    let atomics = (Uint128(FRACTIONAL) * rate).u128();
+    let (whole, rest) = (amount.u128() / FRACTIONAL, amount.u128() % FRACTIONAL);
+    Ok(Uint128(whole * atomics + rest * atomics / FRACTIONAL))
+}
+
+// `amount * rate` for rates of any size, such as prices. Exact like `portion`, with the
+// rate split into whole and fractional parts too; a result past a u128 is an error.
+pub fn scale(amount: Uint128, rate: Decimal) -> Result<Uint128, ContractError> {
+    let rate_whole = (Uint128(1) * rate).u128();
+    let rate_rest = fractional_atomics(rate);
+    let (whole, rest) = (amount.u128() / FRACTIONAL, amount.u128() % FRACTIONAL);
+    let overflow = ContractError::Overflow {
+        lhs: amount,
+        op: '*',
+        rhs: Uint128(rate_whole),
+    };
+    amount
+        .u128()
+        .checked_mul(rate_whole)
+        .and_then(|scaled| scaled.checked_add(whole.checked_mul(rate_rest)?))
+        .and_then(|scaled| scaled.checked_add(rest * rate_rest / FRACTIONAL))
+        .map(Uint128)
+        .ok_or(overflow)
+}
+
+// The fractional part of `rate` in units of 10^-18. `Decimal` doesn't expose its
+// atomics, and `Uint128(FRACTIONAL) * rate` overflows for rates above about 340.
+fn fractional_atomics(rate: Decimal) -> u128 {
+    let text = rate.to_string();
+    match text.find('.') {
+        Some(dot) => {
+            let digits = &text[dot + 1..];
+            let value: u128 = digits.parse().unwrap_or(0);
+            value * 10u128.pow(18 - digits.len() as u32)
+        }
+        None => 0,
+    }
+}
+
+// `amount * part / whole`: one holder's cut of `amount` by their part of the whole.
+pub fn pro_rata(amount: Uint128, part: Uint128, whole: Uint128) -> Result<Uint128, ContractError> {
+    math::mul_ratio(amount, part.u128(), whole.u128())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding() {
+        let rate = rate_from_bps(1_000);
+        assert_eq!(rate, Decimal::percent(10));
+        assert_eq!(portion(Uint128(25), rate).unwrap(), Uint128(2));
+        assert_eq!(portion(Uint128(9), rate).unwrap(), Uint128(0));
+        assert_eq!(portion(Uint128(1), Decimal::one()).unwrap(), Uint128(1));
+        // Full precision at amounts where `amount * rate` would overflow a u128.
+        assert_eq!(
+            portion(Uint128(u128::MAX), Decimal::percent(50)).unwrap(),
+            Uint128(u128::MAX / 2)
+        );
+        assert_eq!(
+            portion(Uint128(1), Decimal::percent(101)).unwrap_err(),
+            ContractError::InvalidRate {
+                rate: Decimal::percent(101)
+            }
+        );
+
+        // Prices can be well above one, and large amounts mustn't wrap or panic.
+        let price = Decimal::from_ratio(25_001u128, 10u128);
+        assert_eq!(scale(Uint128(10), price).unwrap(), Uint128(25_001));
+        assert_eq!(scale(Uint128(3), Decimal::percent(50)).unwrap(), Uint128(1));
+        let price = Decimal::from_ratio(2_500u128, 1u128);
+        assert_eq!(
+            scale(Uint128(10u128.pow(30)), price).unwrap(),
+            Uint128(25 * 10u128.pow(32))
+        );
+        assert_eq!(
+            scale(Uint128(u128::MAX), Decimal::percent(50)).unwrap(),
+            Uint128(u128::MAX / 2)
+        );
+        assert_eq!(
+            scale(Uint128(u128::MAX), Decimal::percent(200)).unwrap_err(),
+            ContractError::Overflow {
+                lhs: Uint128(u128::MAX),
+                op: '*',
+                rhs: Uint128(2),
+            }
+        );
+
+        // Shares round down, so they never add up to more than the amount.
+        let shares: Vec<_> = [1u128, 1, 1]
+            .iter()
+            .map(|part| pro_rata(Uint128(100), Uint128(*part), Uint128(3)).unwrap())
+            .collect();
+        assert_eq!(shares, vec![Uint128(33); 3]);
+        assert_eq!(
+            pro_rata(Uint128(1), Uint128(1), Uint128(0)).unwrap_err(),
+            ContractError::DivideByZero
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use cosmwasm_std::{to_binary, Binary, Coin, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Message the swap router executes once a swap is done, with the proceeds attached.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Callback {
+    pub contract: HumanAddr,
+    pub code_hash: String,
+    pub msg: Binary,
+}
+
+// The handle of the swap router that the lottery calls.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouterHandleMsg {
+    // Swap the attached coins for `ask_denom`, failing below `min_return`.
+    Swap {
+        ask_denom: String,
+        min_return: Uint128,
+        callback: Callback,
+    },
+}
+
+impl RouterHandleMsg {
+    pub fn to_cosmos_msg(
+        &self,
+        router: HumanAddr,
+        router_code_hash: String,
+        send: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: router,
+            callback_code_hash: router_code_hash,
+            msg: to_binary(self)?,
+            send,
+        }))
+    }
+}
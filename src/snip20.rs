@@ -0,0 +1,59 @@
+use cosmwasm_std::{to_binary, Coin, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// The handles of a SNIP-20 token contract that the lottery calls.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip20HandleMsg {
+    // Ask the token to call our `Receive` whenever tokens are sent to us.
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+    Transfer {
+        recipient: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    // Minter only.
+    Mint {
+        recipient: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    // Burns the caller's own tokens.
+    Burn {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    // Wrapped native tokens only: mint tokens for the attached coins.
+    Deposit {
+        padding: Option<String>,
+    },
+    // Wrapped native tokens only: burn tokens and send the caller the coins back.
+    Redeem {
+        amount: Uint128,
+        denom: Option<String>,
+        padding: Option<String>,
+    },
+}
+
+impl Snip20HandleMsg {
+    pub fn to_cosmos_msg(&self, token: HumanAddr, token_code_hash: String) -> StdResult<CosmosMsg> {
+        self.to_cosmos_msg_with_funds(token, token_code_hash, vec![])
+    }
+    pub fn to_cosmos_msg_with_funds(
+        &self,
+        token: HumanAddr,
+        token_code_hash: String,
+        send: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token,
+            callback_code_hash: token_code_hash,
+            msg: to_binary(self)?,
+            send,
+        }))
+    }
+}
@@ -0,0 +1,47 @@
+use cosmwasm_std::{to_binary, CosmosMsg, HumanAddr, StdResult, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+// The handles of a SNIP-721 contract that the lottery calls.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip721HandleMsg {
+    // Ask the contract to call our `ReceiveNft` whenever a token is sent to us.
+    RegisterReceiveNft {
+        code_hash: String,
+        also_implements_batch_receive_nft: Option<bool>,
+        padding: Option<String>,
+    },
+    // Minter only.
+    MintNft {
+        token_id: Option<String>,
+        owner: Option<HumanAddr>,
+        public_metadata: Option<Metadata>,
+        private_metadata: Option<Metadata>,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BurnNft {
+        token_id: String,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+}
+
+impl Snip721HandleMsg {
+    pub fn to_cosmos_msg(&self, contract: HumanAddr, code_hash: String) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract,
+            callback_code_hash: code_hash,
+            msg: to_binary(self)?,
+            send: vec![],
+        }))
+    }
+}
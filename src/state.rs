@@ -1,28 +1,608 @@
+use crate::error::ContractError;
+use crate::hooks::Hook;
+use crate::math;
+use crate::oracle::Oracle;
+use crate::payout;
 use cosmwasm_std::Uint128;
-use cosmwasm_std::{CanonicalAddr, StdError, StdResult, Storage};
-use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use cosmwasm_std::{
+    Binary, BlockInfo, CanonicalAddr, Coin, Decimal, Env, HumanAddr, ReadonlyStorage, StdError,
+    StdResult, Storage,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, PrefixedStorage, ReadonlyBucket,
+    ReadonlyPrefixedStorage, ReadonlySingleton, Singleton,
+};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-use std::error::Error;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::any::type_name;
+use std::fmt;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
 
 pub static CONFIG_KEY: &[u8] = b"config";
-pub static POOL_KEY: &[u8] = b"pool";
+pub static POOLS_KEY: &[u8] = b"pools";
+pub static PLAYERS_KEY: &[u8] = b"players";
+pub static POOL_INDEX_KEY: &[u8] = b"pool_index";
+pub static VIEWING_KEYS_KEY: &[u8] = b"viewing_keys";
+pub static WINNERS_KEY: &[u8] = b"winners";
+pub static HISTORY_KEY: &[u8] = b"history";
+pub static LISTED_KEY: &[u8] = b"listed";
+pub static EVENTS_KEY: &[u8] = b"events";
+pub static PRNG_SEED_KEY: &[u8] = b"prng_seed";
+pub static ENTROPY_KEY: &[u8] = b"entropy";
+pub static REFERRALS_KEY: &[u8] = b"referrals";
+pub static WINNER_CALLBACKS_KEY: &[u8] = b"winner_callbacks";
+pub static PENDING_HOOKS_KEY: &[u8] = b"pending_hooks";
+pub static CHILDREN_KEY: &[u8] = b"children";
+pub static IDEMPOTENCY_KEY: &[u8] = b"idempotency";
+pub static DEPOSIT_COUNT_KEY: &[u8] = b"deposit_count";
+pub static PLAYER_LIST_KEY: &[u8] = b"player_list";
+pub static REFERRAL_LIST_KEY: &[u8] = b"referral_list";
+// Sub-namespaces of a pool's `pool:{id}:` namespace, see `pool_namespace`.
+pub static DELEGATORS_NAMESPACE: &[u8] = b"delegators";
+pub static ENTRIES_NAMESPACE: &[u8] = b"entries";
+pub static MEMOS_NAMESPACE: &[u8] = b"memos";
+pub static TRANSITIONS_NAMESPACE: &[u8] = b"transitions";
+pub static EMISSION_CLAIMS_NAMESPACE: &[u8] = b"emission_claims";
+pub static ENTRY_BALANCES_NAMESPACE: &[u8] = b"entry_balances";
+pub static REFERRED_NAMESPACE: &[u8] = b"referred";
+pub static REFERRAL_CLAIMS_NAMESPACE: &[u8] = b"referral_claims";
+pub static REFERRED_LIST_NAMESPACE: &[u8] = b"referred_list";
+pub static SEALED_SEED_KEY: &[u8] = b"sealed_seed";
+// Singleton key used by deployments that predate pool ids. Only read by migrate.
+pub static LEGACY_POOL_KEY: &[u8] = b"pool";
+// Layout version of everything stored, written by init and migrate. Deployments that
+// predate pool ids never wrote one, so a missing version means the legacy layout.
+pub static VERSION_KEY: &[u8] = b"version";
+pub const LEGACY_STORAGE_VERSION: u32 = 1;
+pub const STORAGE_VERSION: u32 = 2;
 pub const DAYS: u64 = 60 * 60 * 24;
+// Unbonding time of the staking module. Always in seconds, whatever the timing mode.
+pub const UNBONDING_PERIOD: u64 = 21 * DAYS;
+// How long after the estimated maturity the unbonded stake may still arrive. A draw
+// before then only goes ahead once all of it is back.
+pub const UNBONDING_GRACE: u64 = DAYS;
+// How long a guardian's recovery waits before it can replace the owner, in seconds
+// or, in height mode, in blocks of about six seconds.
+pub const RECOVERY_DELAY: u64 = 30 * DAYS;
+pub const RECOVERY_DELAY_BLOCKS: u64 = RECOVERY_DELAY / 6;
+pub const MAX_NAME_LEN: usize = 64;
+pub const MAX_DESCRIPTION_LEN: usize = 512;
+pub const MAX_IMAGE_URL_LEN: usize = 256;
+// Bytes. A memo always fits in one storage block.
+pub const MAX_MEMO_LEN: usize = 64;
+pub const MAX_REFERRAL_CODE_LEN: usize = 32;
+// Public referral deposit counts are rounded down to a multiple of this, unless
+// public totals are exact.
+pub const REFERRAL_COUNT_STEP: u64 = 10;
+// Idempotency keys are remembered this many seconds, at most this many per sender.
+pub const IDEMPOTENCY_WINDOW: u64 = 10 * 60;
+pub const MAX_IDEMPOTENCY_KEYS: usize = 8;
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+// Denoms a multi-denom pool accepts besides its own.
+pub const MAX_EXTRA_DENOMS: usize = 4;
+// Bech32 addresses are at most 90 characters.
+pub const MAX_IBC_RECIPIENT_LEN: usize = 90;
+pub const GENERIC_ERROR: &str = "Request could not be processed.";
+pub const DENOM: &str = "uscrt";
+// Page size bounds for every listing, so no call walks more than MAX_LIMIT entries.
+pub const DEFAULT_LIMIT: u32 = 10;
+pub const MAX_LIMIT: u32 = 30;
+// The leaderboard sorts every listed address on each query, so the list is capped.
+// Once full, a larger depositor takes the smallest one's place, see `list_player`.
+pub const MAX_LISTED_PLAYERS: u64 = 100;
+// Private query responses are padded to a multiple of this many bytes.
+pub const RESPONSE_BLOCK_SIZE: u32 = 256;
+pub const MAX_RESPONSE_BLOCK_SIZE: u32 = 4096;
+// Most delegator slots a pool takes, so drawing and cleanup always fit in a block.
+pub const MAX_DEPOSITORS: u32 = 10_000;
+// Validator operator addresses are bech32 with this human-readable part, and encode
+// a 20-byte address: 32 data characters and a 6-character checksum after the prefix.
+pub const VALOPER_PREFIX: &str = "secretvaloper1";
+pub const VALOPER_ADDRESS_LEN: usize = 52;
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+// Bincode records are padded to a multiple of this many bytes before they are
+// stored, so their ciphertext length doesn't track the values they hold. Amounts
+// encode as decimal strings; one block fits an `Entry` or `Player` at any value.
+pub const STORAGE_BLOCK_SIZE: usize = 128;
+
+fn default_response_block_size() -> u32 {
+    RESPONSE_BLOCK_SIZE
+}
+
+// Highest protocol fee the owner can set, as a share of realized rewards.
+pub fn max_fee_rate() -> Decimal {
+    Decimal::percent(20)
+}
+
+// Largest part of an unbonding stake a slash can take, the double-sign penalty. A
+// bigger shortfall is funds that haven't arrived, not a slash.
+pub fn max_unbonding_slash() -> Decimal {
+    Decimal::percent(5)
+}
+
+// Fee rates used to be stored as integer basis points; read either form.
+fn rate_or_bps<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    struct RateVisitor;
+    impl<'de> Visitor<'de> for RateVisitor {
+        type Value = Decimal;
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a decimal string or basis points")
+        }
+        fn visit_u64<E: de::Error>(self, bps: u64) -> Result<Decimal, E> {
+            Ok(payout::rate_from_bps(bps))
+        }
+        fn visit_str<E: de::Error>(self, rate: &str) -> Result<Decimal, E> {
+            Decimal::from_str(rate).map_err(E::custom)
+        }
+    }
+    deserializer.deserialize_any(RateVisitor)
+}
+
+fn default_max_depositors() -> u32 {
+    MAX_DEPOSITORS
+}
+
+fn default_public_rounding() -> Uint128 {
+    Uint128(1)
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
-    pub count: i32,
     pub owner: CanonicalAddr,
+    // Number of pools ever created. Also the id of the current pool.
+    pub pool_count: u64,
+    pub stats: Stats,
+    #[serde(default)]
+    pub timing: Timing,
+    // Length of the public winners list, see `announce_winner`.
+    #[serde(default)]
+    pub announced_winners: u64,
+    // Where LOCKED pools are staked. Empty means funds stay in the contract.
+    #[serde(default)]
+    pub validators: Vec<Validator>,
+    // Length of the list of addresses that opted into the leaderboard, see `list_player`.
+    #[serde(default)]
+    pub listed_players: u64,
+    // Length of the contract-wide event log, see `record_event`.
+    #[serde(default)]
+    pub event_count: u64,
+    // Zero turns padding of private query responses off.
+    #[serde(default = "default_response_block_size")]
+    pub response_block_size: u32,
+    #[serde(default)]
+    pub fees: Fees,
+    // Recorded at init, or on migrate for older deployments. Queries get no Env.
+    #[serde(default)]
+    pub contract_address: Option<HumanAddr>,
+    // Deposit-derived totals in public queries are rounded to a multiple of this.
+    // One reports them exactly.
+    #[serde(default = "default_public_rounding")]
+    pub public_rounding: Uint128,
+    // Public queries report totals as of the last phase change instead of live ones.
+    #[serde(default)]
+    pub delay_public_totals: bool,
+    // `stats` as of the last lifecycle event, see `record_event`.
+    #[serde(default)]
+    pub published_stats: Option<Stats>,
+    // Delegator lists, balances and winners are public. Set once at init.
+    #[serde(default)]
+    pub transparent: bool,
+    // Failing user-facing messages all return `GENERIC_ERROR`.
+    #[serde(default)]
+    pub generic_errors: bool,
+    // SNIP-20 tokens wrapping `DENOM`, accepted as deposits and unwrapped on receipt,
+    // see `HandleMsg::Receive`.
+    #[serde(default)]
+    pub tokens: Vec<Token>,
+    // SNIP-20 minted 1:1 against deposits, with the lottery as minter.
+    #[serde(default)]
+    pub ticket: Option<Token>,
+    // SNIP-721 minted per entry in rounds created with `nft_tickets`.
+    #[serde(default)]
+    pub nft_contract: Option<Token>,
+    // Reward token handed out to the depositors of every settled round.
+    #[serde(default)]
+    pub emission: Option<Emission>,
+    // Contracts notified of lifecycle transitions, see `hooks`.
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    // Converts rewards paid in other denoms into the prize denom, see `HandleMsg::ConvertRewards`.
+    #[serde(default)]
+    pub swap_router: Option<Token>,
+    // Prices assets in a common unit, see `oracle`.
+    #[serde(default)]
+    pub oracle: Option<Oracle>,
+    // SNIP-20 wrapping the native denom 1:1, such as sSCRT. Deposits of it are unwrapped
+    // to be staked, and `WithdrawWrapped` pays out in it.
+    #[serde(default)]
+    pub wrapped_native: Option<Token>,
+    // Factory mode: rounds run in child lotteries instantiated from this code, see
+    // `children`.
+    #[serde(default)]
+    pub factory: Option<Factory>,
+    #[serde(default)]
+    pub child_count: u64,
+    // The factory that instantiated this lottery, told our address at init.
+    #[serde(default)]
+    pub parent: Option<Token>,
+    // Sends prizes claimed to other chains, see `ics20`.
+    #[serde(default)]
+    pub ibc_gateway: Option<Token>,
+    // Receives collected fees and swept funds, see `treasury`. Fees go to the fee
+    // recipient while it is None.
+    #[serde(default)]
+    pub treasury: Option<Token>,
+    // Every denom a pool was created in or accepts besides `DENOM`. Never swept.
+    #[serde(default)]
+    pub pool_denoms: Vec<String>,
+    // Make up for slashed principal out of the round's rewards before the fee and
+    // prize are taken. Off by default, so depositors bear the loss pro rata.
+    #[serde(default)]
+    pub cover_slashes: bool,
+    // Caps on deposits accepted per block, to blunt storage and gas griefing
+    // right before a lock. None accepts any number.
+    #[serde(default)]
+    pub deposit_limit: Option<DepositLimit>,
+    // New addresses a pool accepts; withdrawn entries keep their slot. At most
+    // `MAX_DEPOSITORS`.
+    #[serde(default = "default_max_depositors")]
+    pub max_depositors: u32,
+    // Prizes drawn but not yet paid out. Past `max_unclaimed_prizes` no new pool
+    // can be created, so old rounds' liabilities can't pile up unnoticed.
+    #[serde(default)]
+    pub unclaimed_prizes: u64,
+    #[serde(default)]
+    pub max_unclaimed_prizes: Option<u64>,
+    // Principal and prizes of settled `DENOM` pools not paid out yet. The contract
+    // holds them on top of the live pools' funds, see `liquid_liabilities`.
+    #[serde(default)]
+    pub settled_principal: Uint128,
+    #[serde(default)]
+    pub settled_prizes: Uint128,
+    // A `DENOM` prize drawn without a winner, held until the next `DENOM` pool is created.
+    #[serde(default)]
+    pub carryover: Uint128,
+    // Part of each round's protocol fee set aside for referrers, split by what their
+    // codes brought in, and what was set aside but not claimed yet.
+    #[serde(default)]
+    pub referral_share: Decimal,
+    #[serde(default)]
+    pub referral_rewards: Uint128,
+    // Can replace the owner through a delayed `Recovery`.
+    #[serde(default)]
+    pub guardian: Option<CanonicalAddr>,
+    #[serde(default)]
+    pub recovery: Option<Recovery>,
+}
+
+// The guardian's proposal to replace the owner. It stays pending for `RECOVERY_DELAY`
+// so the owner, or depositors, can see it coming. Only the guardian can cancel it, so
+// an owner whose key was taken can't veto their own replacement.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Recovery {
+    pub new_owner: CanonicalAddr,
+    // On the contract's clock, see `Timing::now`.
+    pub executable_at: u64,
+}
+
+// Deposits accepted per block in total, and per address. Either may be left unset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositLimit {
+    pub per_block: Option<u32>,
+    pub per_address: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Factory {
+    pub code_id: u64,
+    pub code_hash: String,
+}
+
+// A round spawned in factory mode. `address` is filled in once the child registers.
+// `code_hash` is the factory's at spawn time, which later `SetFactory` calls don't change.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Child {
+    pub label: String,
+    pub address: Option<HumanAddr>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub code_hash: String,
+}
+
+impl Child {
+    // Children spawned before the code hash was recorded run the factory's code.
+    pub fn callback_code_hash(&self, factory: &Factory) -> String {
+        if self.code_hash.is_empty() {
+            factory.code_hash.clone()
+        } else {
+            self.code_hash.clone()
+        }
+    }
+}
+
+// `per_round` is minted for each round settled from `start_pool` on, and halves every
+// `halving_interval` rounds after that. An interval of 0 never halves. Depositors
+// share a round's emission by weight and claim it with `HandleMsg::ClaimEmissions`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Emission {
+    pub token: Token,
+    pub per_round: Uint128,
+    pub start_pool: u64,
+    pub halving_interval: u64,
+}
+
+impl Emission {
+    pub fn amount_for(&self, pool_id: u64) -> Uint128 {
+        if pool_id < self.start_pool {
+            return Uint128::zero();
+        }
+        let halvings = match self.halving_interval {
+            0 => 0,
+            interval => (pool_id - self.start_pool) / interval,
+        };
+        if halvings >= 128 {
+            return Uint128::zero();
+        }
+        Uint128(self.per_round.u128() >> halvings)
+    }
+}
+
+// A SNIP-20 token counted 1:1 with the pool denom, such as sSCRT.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Token {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Validator {
+    pub address: HumanAddr,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Delegation {
+    pub validator: HumanAddr,
+    pub amount: Uint128,
+}
+
+pub fn validate_validators(validators: &[Validator]) -> StdResult<()> {
+    for (i, validator) in validators.iter().enumerate() {
+        if !is_valoper_address(validator.address.as_str()) {
+            return Err(ContractError::InvalidValidator {
+                address: validator.address.to_string(),
+            }
+            .into());
+        }
+        if validator.weight == 0 {
+            return Err(ContractError::ZeroValidatorWeight.into());
+        }
+        if validators[..i]
+            .iter()
+            .any(|v| v.address == validator.address)
+        {
+            return Err(ContractError::DuplicateValidator {
+                address: validator.address.to_string(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Prefix, length and the bech32 checksum, so a mistyped validator is refused here
+// rather than failing every delegation later.
+fn is_valoper_address(address: &str) -> bool {
+    if address.len() != VALOPER_ADDRESS_LEN || !address.starts_with(VALOPER_PREFIX) {
+        return false;
+    }
+    let data: Option<Vec<u8>> = address[VALOPER_PREFIX.len()..]
+        .chars()
+        .map(|c| BECH32_CHARSET.find(c).map(|i| i as u8))
+        .collect();
+    let data = match data {
+        Some(data) => data,
+        None => return false,
+    };
+    let hrp = VALOPER_PREFIX[..VALOPER_PREFIX.len() - 1].as_bytes();
+    let expanded = hrp
+        .iter()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.iter().map(|b| b & 31))
+        .chain(data);
+    bech32_polymod(expanded) == 1
+}
+
+// BIP-173 checksum over the expanded human-readable part and the 5-bit data.
+fn bech32_polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(value);
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= g;
+            }
+        }
+    }
+    checksum
+}
+
+// Split `amount` across the validators by weight. Rounding dust goes to the first
+// validator and zero amounts are left out.
+pub fn split_by_weight(amount: Uint128, validators: &[Validator]) -> StdResult<Vec<Delegation>> {
+    let total_weight: u128 = validators.iter().map(|v| u128::from(v.weight)).sum();
+    let mut delegations = vec![];
+    let mut assigned = Uint128::zero();
+    for validator in validators.iter() {
+        let weight = Uint128(u128::from(validator.weight));
+        let share = payout::pro_rata(amount, weight, Uint128(total_weight))?;
+        assigned = math::add(assigned, share)?;
+        delegations.push(Delegation {
+            validator: validator.address.clone(),
+            amount: share,
+        });
+    }
+    if let Some(first) = delegations.first_mut() {
+        first.amount = math::add(first.amount, math::sub(amount, assigned)?)?;
+    }
+    delegations.retain(|d| !d.amount.is_zero());
+    Ok(delegations)
+}
+
+// Whether status durations are measured in seconds of block time or in block heights.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimingMode {
+    Time,
+    Height,
+}
+
+// How long a pool must stay in each status before it can move on.
+// Durations are in seconds or blocks depending on `mode`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Timing {
+    pub mode: TimingMode,
+    pub open_duration: u64,
+    pub locked_duration: u64,
+    // Block counts a pool must also wait out in each status, whatever the mode.
+    // Block time can be nudged by proposers; requiring both hardens the windows.
+    #[serde(default)]
+    pub open_min_blocks: Option<u64>,
+    #[serde(default)]
+    pub locked_min_blocks: Option<u64>,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            mode: TimingMode::Time,
+            open_duration: DAYS,
+            locked_duration: 21 * DAYS,
+            open_min_blocks: None,
+            locked_min_blocks: None,
+        }
+    }
+}
+
+impl Timing {
+    // A zero duration would let a pool skip a status entirely.
+    pub fn validate(&self) -> StdResult<()> {
+        if self.open_duration == 0 || self.locked_duration == 0 {
+            return Err(ContractError::ZeroDuration.into());
+        }
+        Ok(())
+    }
+    // Current position of the clock this mode is measured on.
+    pub fn now(&self, block: &BlockInfo) -> u64 {
+        match self.mode {
+            TimingMode::Time => block.time,
+            TimingMode::Height => block.height,
+        }
+    }
+    // Point `duration` units after the current block.
+    pub fn deadline(&self, block: &BlockInfo, duration: u64) -> u64 {
+        self.now(block).saturating_add(duration)
+    }
+    // `RECOVERY_DELAY` on this mode's clock.
+    pub fn recovery_delay(&self) -> u64 {
+        match self.mode {
+            TimingMode::Time => RECOVERY_DELAY,
+            TimingMode::Height => RECOVERY_DELAY_BLOCKS,
+        }
+    }
+    pub fn unit(&self) -> &'static str {
+        match self.mode {
+            TimingMode::Time => "seconds",
+            TimingMode::Height => "blocks",
+        }
+    }
+}
+
+// Totals across the life of the contract.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Stats {
+    pub pools_run: u64,
+    // Gross amount ever deposited; withdrawals are not subtracted.
+    pub total_deposited: Uint128,
+    pub total_prizes_paid: Uint128,
+    pub unique_players: u64,
+    #[serde(default)]
+    pub prizes_awarded: u64,
+    // Staking rewards realized by all settled pools.
+    #[serde(default)]
+    pub total_rewards: Uint128,
+}
+
+impl Stats {
+    // Fold a freshly settled pool into the lifetime counters.
+    pub fn record_settlement(&mut self, pool: &Pool) -> StdResult<()> {
+        self.total_rewards = math::add(self.total_rewards, pool.rewards_realized)?;
+        if pool.winner.is_some() {
+            self.prizes_awarded += 1;
+            self.total_prizes_paid = math::add(self.total_prizes_paid, pool.prize_amount)?;
+        }
+        Ok(())
+    }
+}
+
+// Protocol fee on realized rewards. Changes are scheduled and apply from the next
+// pool created, so a running round keeps the rate it opened with.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Fees {
+    #[serde(alias = "rate_bps", deserialize_with = "rate_or_bps")]
+    pub rate: Decimal,
+    // None sends fees to the owner.
+    pub recipient: Option<CanonicalAddr>,
+    // Taken at settlement and not yet withdrawn.
+    pub accrued: Uint128,
+    pub pending: Option<FeeChange>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeChange {
+    #[serde(alias = "rate_bps", deserialize_with = "rate_or_bps")]
+    pub rate: Decimal,
+    pub recipient: Option<CanonicalAddr>,
 }
 
-pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, State> {
+impl Fees {
+    pub fn apply_pending(&mut self) {
+        if let Some(change) = self.pending.take() {
+            self.rate = change.rate;
+            self.recipient = change.recipient;
+        }
+    }
+}
+
+pub fn config<S: Storage>(storage: &mut S) -> Singleton<'_, S, State> {
     singleton(storage, CONFIG_KEY)
 }
 
-pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
+pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, State> {
     singleton_read(storage, CONFIG_KEY)
 }
 
+pub fn storage_version<S: Storage>(storage: &S) -> StdResult<u32> {
+    let version = singleton_read(storage, VERSION_KEY).may_load()?;
+    Ok(version.unwrap_or(LEGACY_STORAGE_VERSION))
+}
+
+pub fn set_storage_version<S: Storage>(storage: &mut S) -> StdResult<()> {
+    singleton(storage, VERSION_KEY).save(&STORAGE_VERSION)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum PoolStatus {
     // Pool is accepting players.
@@ -33,25 +613,331 @@ pub enum PoolStatus {
     CLOSED,
 }
 
-// TODO:
-//   - Add validator node
+impl PoolStatus {
+    // How long a pool stays in this status under `timing`, and the block count it
+    // must also wait out. None for CLOSED, which never expires.
+    pub fn duration(&self, timing: &Timing) -> Option<(u64, Option<u64>)> {
+        match self {
+            PoolStatus::OPEN => Some((timing.open_duration, timing.open_min_blocks)),
+            PoolStatus::LOCKED => Some((timing.locked_duration, timing.locked_min_blocks)),
+            PoolStatus::CLOSED => None,
+        }
+    }
+
+    // Check that a pool in this status may move on: `deadline` has passed on the
+    // contract's clock and the minimum block count has gone by since `entered_height`.
+    // Both timing modes go through here; `timing` picks the clock and durations.
+    pub fn assert_expired(
+        &self,
+        deadline: Option<u64>,
+        entered_height: u64,
+        timing: &Timing,
+        block: &BlockInfo,
+    ) -> Result<(), ContractError> {
+        let (duration, min_blocks) = match self.duration(timing) {
+            Some(durations) => durations,
+            None => return Ok(()),
+        };
+        let too_early = |duration: u64, unit: &str, remaining: u64| ContractError::TooEarly {
+            status: format!("{:?}", self),
+            duration,
+            unit: unit.to_string(),
+            remaining,
+        };
+        let now = timing.now(block);
+        let deadline = deadline.unwrap_or(0);
+        if deadline > now {
+            return Err(too_early(duration, timing.unit(), deadline - now));
+        }
+        // The block count is measured from the last status change, on top of the deadline.
+        let elapsed = block.height.saturating_sub(entered_height);
+        if let Some(min_blocks) = min_blocks.filter(|min| elapsed < *min) {
+            return Err(too_early(min_blocks, "blocks", min_blocks - elapsed));
+        }
+        Ok(())
+    }
+}
+
+// Optional labels for frontends, e.g. a holiday jackpot or community round.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct PoolMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+}
+
+impl PoolMetadata {
+    pub fn validate(&self) -> StdResult<()> {
+        let fields = [
+            ("name", &self.name, MAX_NAME_LEN),
+            ("description", &self.description, MAX_DESCRIPTION_LEN),
+            ("image_url", &self.image_url, MAX_IMAGE_URL_LEN),
+        ];
+        for (field, value, max_len) in fields.iter() {
+            if value.as_ref().map_or(0, |v| v.len()) > *max_len {
+                return Err((ContractError::TooLong {
+                    what: format!("Pool {}", field),
+                    max: *max_len,
+                })
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+// An IBC voucher a pool can be denominated in. `trace` is the full denom trace, port
+// and channel hops followed by the base denom, e.g. `transfer/channel-0/uatom`.
+// `denom` must be the voucher it hashes to, `ibc/{SHA256(trace)}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcDenom {
+    pub denom: String,
+    pub trace: String,
+}
+
+impl IbcDenom {
+    pub fn validate(&self) -> StdResult<()> {
+        let parts: Vec<&str> = self.trace.split('/').collect();
+        let mut hops = 0;
+        while parts.len() > 2 * hops + 2 && is_channel_id(parts[2 * hops + 1]) {
+            if !is_port_id(parts[2 * hops]) {
+                return Err(ContractError::InvalidPort.into());
+            }
+            hops += 1;
+        }
+        if hops == 0 || !is_base_denom(&parts[2 * hops..].join("/")) {
+            return Err(ContractError::InvalidDenomTrace.into());
+        }
+        let hash: String = Sha256::digest(self.trace.as_bytes())
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        if self.denom != format!("ibc/{}", hash) {
+            return Err(ContractError::DenomTraceMismatch.into());
+        }
+        Ok(())
+    }
+}
+
+// A recipient on a chain connected over `channel`, such as a cosmos1... address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcPayout {
+    pub channel: String,
+    pub recipient: String,
+}
+
+impl IbcPayout {
+    // The address format is up to the other chain; only its shape is checked here.
+    pub fn validate(&self) -> StdResult<()> {
+        if !is_channel_id(&self.channel) {
+            return Err(ContractError::InvalidChannel.into());
+        }
+        if self.recipient.is_empty()
+            || self.recipient.len() > MAX_IBC_RECIPIENT_LEN
+            || !self.recipient.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(ContractError::InvalidRecipient.into());
+        }
+        Ok(())
+    }
+}
+
+fn is_port_id(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+-#[]<>".contains(c))
+}
+
+fn is_channel_id(s: &str) -> bool {
+    s.starts_with("channel-") && s.len() > 8 && s[8..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_base_denom(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.len() <= 128
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c))
+}
+
+// Deposit-derived pool totals, captured at each phase change.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct PublishedTotals {
+    pub total_deposits: Uint128,
+    pub unique_depositors: u64,
+    pub total_weight: Uint128,
+    pub delegator_count: u64,
+    pub sponsor_amount: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Pool {
+    pub id: u64,
+    pub metadata: PoolMetadata,
     pub delegated_amt: Uint128,
-    pub delegators: Vec<CanonicalAddr>,
+    // Stake placed when the pool was locked, undelegated when it closes.
+    #[serde(default)]
+    pub delegations: Vec<Delegation>,
+    #[serde(default)]
+    pub unbonding_amount: Uint128,
+    // Block time at which the undelegated stake is back in the contract.
+    #[serde(default)]
+    pub unbonds_at: Option<u64>,
+    // Delegators are stored as an indexed list, see `pool_delegators`.
+    pub delegator_count: u64,
+    // Running aggregates, updated on every deposit and withdrawal.
+    pub total_deposits: Uint128,
+    pub unique_depositors: u64,
+    pub total_weight: Uint128,
+    // Prize funding on top of staking rewards. Never part of any depositor's principal.
+    pub seed_amount: Uint128,
+    pub sponsor_amount: Uint128,
+    pub carryover_amount: Uint128,
     pub status: PoolStatus,
+    pub created_at: u64,
+    // Block time and height of the last status change.
     pub status_updated_at: u64,
+    pub status_updated_height: u64,
+    // Deadlines for leaving each status, set when the status is entered. They are in
+    // seconds or block heights depending on the contract's timing mode.
+    pub locks_at: Option<u64>,
+    pub closes_at: Option<u64>,
+    // Earliest point the draw may run; currently as soon as the pool closes.
+    pub settles_at: Option<u64>,
+    // Settlement record, filled in once a CLOSED pool has been drawn.
+    pub winner: Option<CanonicalAddr>,
+    pub prize_amount: Uint128,
+    pub rewards_realized: Uint128,
+    pub settled_at: Option<u64>,
+    // Whether the winner agreed to be listed publicly.
+    pub winner_announced: bool,
+    // Next delegator index to examine when cleaning up a settled pool.
+    pub cleanup_cursor: u64,
+    // Length of the status transition log, see `pool_transitions`.
+    pub transition_count: u64,
+    // Protocol fee in force when the pool was created.
+    #[serde(alias = "fee_rate_bps", deserialize_with = "rate_or_bps")]
+    pub fee_rate: Decimal,
+    // Fee taken from the realized rewards at settlement.
+    pub fee_amount: Uint128,
+    // Totals shown publicly in delayed mode. None for pools migrated from the legacy layout.
+    #[serde(default)]
+    pub published: Option<PublishedTotals>,
+    // Staking rewards the pool's stake had accumulated when it was undelegated.
+    #[serde(default)]
+    pub harvested_rewards: Uint128,
+    // Each entry holds a SNIP-721 ticket, and the prize goes to whoever sends back the
+    // winning one. One deposit per address and no withdrawals, so tickets never go stale.
+    #[serde(default)]
+    pub nft_tickets: bool,
+    #[serde(default)]
+    pub prize_claimed: bool,
+    // Reward tokens set aside for the depositors at settlement, see `Emission`.
+    #[serde(default)]
+    pub emission_amount: Uint128,
+    // Set for pools denominated in an IBC voucher. Vouchers can't be staked, so these
+    // pools are held in the contract rather than delegated, and earn no rewards.
+    #[serde(default)]
+    pub ibc_denom: Option<IbcDenom>,
+    // Harvested rewards in denoms other than the prize denom, and whether they are out
+    // at the swap router. The draw waits for a conversion in flight.
+    #[serde(default)]
+    pub unconverted_rewards: Vec<Coin>,
+    #[serde(default)]
+    pub converting: bool,
+    // The prize in the oracle's quote unit at settlement, when an oracle is set.
+    #[serde(default)]
+    pub prize_value: Option<Uint128>,
+    // Denoms accepted besides `denom()`, with the pool's holdings of each. They are held
+    // in the contract rather than staked. Entries in such pools are weighted by their
+    // value in the oracle's quote unit, see `deposit_weight`.
+    #[serde(default)]
+    pub extra_denoms: Vec<String>,
+    #[serde(default)]
+    pub denom_balances: Vec<Coin>,
+    // Where the prize went, when the winner claimed it to another chain.
+    #[serde(default)]
+    pub ibc_payout: Option<IbcPayout>,
+    // Principal the staking module didn't return, after any cover from the rewards.
+    // Depositors share the loss, see `principal_share`.
+    #[serde(default)]
+    pub slashed_amount: Uint128,
+    #[serde(default)]
+    pub slash_covered: Uint128,
+    // Principal paid back through `ClaimPrincipal` since settlement.
+    #[serde(default)]
+    pub principal_claimed: Uint128,
+    // Deposits made with a referral code, and the referrers' part of the fee.
+    #[serde(default)]
+    pub referred_deposits: Uint128,
+    #[serde(default)]
+    pub referral_amount: Uint128,
+    // The winner scan, once `PrepareDraw` or `Draw` has started it.
+    #[serde(default)]
+    pub draw: Option<DrawProgress>,
 }
 
 impl Pool {
-    pub fn new(time: u64) -> Self {
+    pub fn new(id: u64, timing: &Timing, block: &BlockInfo) -> Self {
         Pool {
+            id,
+            metadata: PoolMetadata::default(),
             delegated_amt: Uint128(0),
-            delegators: vec![],
+            delegations: vec![],
+            unbonding_amount: Uint128(0),
+            unbonds_at: None,
+            delegator_count: 0,
+            total_deposits: Uint128(0),
+            unique_depositors: 0,
+            total_weight: Uint128(0),
+            seed_amount: Uint128(0),
+            sponsor_amount: Uint128(0),
+            carryover_amount: Uint128(0),
             status: PoolStatus::OPEN,
-            status_updated_at: time,
+            created_at: block.time,
+            status_updated_at: block.time,
+            status_updated_height: block.height,
+            locks_at: Some(timing.deadline(block, timing.open_duration)),
+            closes_at: None,
+            settles_at: None,
+            winner: None,
+            prize_amount: Uint128(0),
+            rewards_realized: Uint128(0),
+            settled_at: None,
+            winner_announced: false,
+            cleanup_cursor: 0,
+            transition_count: 0,
+            fee_rate: Decimal::zero(),
+            fee_amount: Uint128(0),
+            published: Some(PublishedTotals::default()),
+            harvested_rewards: Uint128(0),
+            nft_tickets: false,
+            prize_claimed: false,
+            emission_amount: Uint128(0),
+            ibc_denom: None,
+            unconverted_rewards: vec![],
+            converting: false,
+            prize_value: None,
+            extra_denoms: vec![],
+            denom_balances: vec![],
+            ibc_payout: None,
+            slashed_amount: Uint128(0),
+            slash_covered: Uint128(0),
+            principal_claimed: Uint128(0),
+            referred_deposits: Uint128(0),
+            referral_amount: Uint128(0),
+            draw: None,
         }
     }
+    // The denom deposits, withdrawals and prizes of this pool are in.
+    pub fn denom(&self) -> &str {
+        self.ibc_denom.as_ref().map_or(DENOM, |ibc| &ibc.denom)
+    }
+    pub fn is_multi_denom(&self) -> bool {
+        !self.extra_denoms.is_empty()
+    }
+    pub fn accepts(&self, denom: &str) -> bool {
+        denom == self.denom() || self.extra_denoms.iter().any(|d| d == denom)
+    }
     pub fn is_open(&self) -> bool {
         self.status == PoolStatus::OPEN
     }
@@ -61,42 +947,1785 @@ impl Pool {
     pub fn is_closed(&self) -> bool {
         self.status == PoolStatus::CLOSED
     }
-    pub fn lock(&mut self, time: u64) {
+    pub fn lock(&mut self, timing: &Timing, block: &BlockInfo) {
         self.status = PoolStatus::LOCKED;
-        self.status_updated_at = time;
+        self.status_updated_at = block.time;
+        self.status_updated_height = block.height;
+        self.closes_at = Some(timing.deadline(block, timing.locked_duration));
+        self.publish();
     }
-    pub fn close(&mut self, time: u64) {
+    pub fn close(&mut self, timing: &Timing, block: &BlockInfo) {
         self.status = PoolStatus::CLOSED;
-        self.status_updated_at = time;
-    }
-    pub fn assert_status_has_expired(&self, curr_time: u64) -> StdResult<()> {
-        match self.status {
-            PoolStatus::OPEN => {
-                if self.status_updated_at + 1 * DAYS > curr_time {
-                    return Err(StdError::generic_err(format!(
-                        "Pool has to be OPEN for {} day",
-                        1
-                    )));
-                }
+        self.status_updated_at = block.time;
+        self.status_updated_height = block.height;
+        self.settles_at = Some(timing.now(block));
+        self.publish();
+    }
+    fn publish(&mut self) {
+        self.published = Some(PublishedTotals {
+            total_deposits: self.total_deposits,
+            unique_depositors: self.unique_depositors,
+            total_weight: self.total_weight,
+            delegator_count: self.delegator_count,
+            sponsor_amount: self.sponsor_amount,
+        });
+    }
+    // Replace the live totals with the published ones, if any.
+    pub fn with_published_totals(mut self) -> Self {
+        if let Some(published) = self.published.clone() {
+            self.total_deposits = published.total_deposits;
+            self.unique_depositors = published.unique_depositors;
+            self.total_weight = published.total_weight;
+            self.delegator_count = published.delegator_count;
+            self.sponsor_amount = published.sponsor_amount;
+        }
+        self
+    }
+    // An entry's principal less its pro-rata part of any slash.
+    pub fn principal_share(&self, entry: &Entry) -> StdResult<Uint128> {
+        self.principal_for(entry.amount)
+    }
+    // `amount` of principal less its pro-rata part of any slash. Rounded down, so the
+    // shares never add up to more than came back from staking.
+    pub fn principal_for(&self, amount: Uint128) -> StdResult<Uint128> {
+        if self.slashed_amount.is_zero() {
+            return Ok(amount);
+        }
+        let returned = math::sub(self.total_deposits, self.slashed_amount)?;
+        Ok(payout::pro_rata(amount, returned, self.total_deposits)?)
+    }
+    // Principal placed with validators at the lock, whether still staked or unbonding.
+    // Zero for pools that stake nothing, which hold their deposits in the contract.
+    pub fn staked_amount(&self) -> StdResult<Uint128> {
+        let staked = self
+            .delegations
+            .iter()
+            .try_fold(Uint128::zero(), |sum, d| math::add(sum, d.amount))?;
+        Ok(staked)
+    }
+    // Cheap consistency checks on the running aggregates, see `check_invariants`.
+    pub fn check_totals(&self) -> StdResult<()> {
+        let broken = |what: &str| -> StdResult<()> {
+            Err(ContractError::InvariantBroken {
+                pool_id: self.id,
+                what: what.to_string(),
             }
-            PoolStatus::LOCKED => {
-                if self.status_updated_at + 21 * DAYS > curr_time {
-                    return Err(StdError::generic_err(format!(
-                        "Pool has to be LOCKED for {} day",
-                        21
-                    )));
+            .into())
+        };
+        if (self.unique_depositors == 0) != self.total_weight.is_zero() {
+            return broken("depositor count and total weight disagree");
+        }
+        if self.unique_depositors > self.delegator_count {
+            return broken("more depositors than delegators");
+        }
+        if !self.total_deposits.is_zero() && self.total_weight.is_zero() {
+            return broken("deposits without weight");
+        }
+        if !self.delegations.is_empty() && self.staked_amount()? != self.delegated_amt {
+            return broken("delegations don't add up to the stake");
+        }
+        Ok(())
+    }
+    pub fn is_settled(&self) -> bool {
+        self.settled_at.is_some()
+    }
+    // Record the draw result. A pool without delegators settles with no winner.
+    // The protocol fee comes out of the rewards first; the prize fits in the rest,
+    // plus the pool's seed, sponsorship and carryover.
+    pub fn settle(
+        &mut self,
+        winner: Option<CanonicalAddr>,
+        rewards_realized: Uint128,
+        prize_amount: Uint128,
+        time: u64,
+    ) -> StdResult<()> {
+        if !self.is_closed() {
+            return Err(ContractError::PoolNotClosed.into());
+        }
+        if self.is_settled() {
+            return Err(ContractError::AlreadySettled.into());
+        }
+        let fee_amount = payout::portion(rewards_realized, self.fee_rate)?;
+        let available = math::add(
+            math::sub(rewards_realized, fee_amount)?,
+            self.prize_funding()?,
+        )?;
+        if prize_amount > available {
+            return Err(ContractError::PrizeExceedsRewards.into());
+        }
+        self.winner = winner;
+        self.fee_amount = fee_amount;
+        self.rewards_realized = rewards_realized;
+        self.prize_amount = prize_amount;
+        self.settled_at = Some(time);
+        Ok(())
+    }
+    // A depositor's cut of the round's emission.
+    pub fn emission_share(&self, entry: &Entry) -> StdResult<Uint128> {
+        if self.total_weight.is_zero() {
+            return Ok(Uint128::zero());
+        }
+        Ok(payout::pro_rata(
+            self.emission_amount,
+            entry.weight,
+            self.total_weight,
+        )?)
+    }
+    // What the prize holds besides the rewards: the seed, sponsorship and carryover.
+    pub fn prize_funding(&self) -> StdResult<Uint128> {
+        let funding = math::add(self.seed_amount, self.sponsor_amount)?;
+        Ok(math::add(funding, self.carryover_amount)?)
+    }
+}
+
+// Ids of the pools currently in each live status, so handlers never scan history.
+// At most one pool is OPEN or LOCKED at a time.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct PoolIndex {
+    pub current_open: Option<u64>,
+    pub current_locked: Option<u64>,
+    // CLOSED but not yet settled.
+    pub pending_settlement: Option<u64>,
+    // The most recently settled pool. Indexes stored before it was kept leave it unset
+    // until the next draw.
+    #[serde(default)]
+    pub last_settled: Option<u64>,
+}
+
+impl PoolIndex {
+    pub fn opened(&mut self, pool_id: u64) {
+        self.current_open = Some(pool_id);
+    }
+    pub fn locked(&mut self, pool_id: u64) {
+        if self.current_open == Some(pool_id) {
+            self.current_open = None;
+        }
+        self.current_locked = Some(pool_id);
+    }
+    // Only one pool awaits settlement at a time.
+    pub fn closed(&mut self, pool_id: u64) -> StdResult<()> {
+        if let Some(pending) = self.pending_settlement.filter(|id| *id != pool_id) {
+            return Err(ContractError::SettlementPending { pool_id: pending }.into());
+        }
+        if self.current_locked == Some(pool_id) {
+            self.current_locked = None;
+        }
+        self.pending_settlement = Some(pool_id);
+        Ok(())
+    }
+    pub fn settled(&mut self, pool_id: u64) {
+        if self.pending_settlement == Some(pool_id) {
+            self.pending_settlement = None;
+        }
+        self.last_settled = Some(pool_id);
+    }
+    // Index entries implied by a single pool's status.
+    pub fn from_pool(pool: &Pool) -> StdResult<Self> {
+        let mut index = PoolIndex::default();
+        match pool.status {
+            PoolStatus::OPEN => index.opened(pool.id),
+            PoolStatus::LOCKED => index.locked(pool.id),
+            PoolStatus::CLOSED if !pool.is_settled() => index.closed(pool.id)?,
+            PoolStatus::CLOSED => index.settled(pool.id),
+        }
+        Ok(index)
+    }
+}
+
+pub fn pool_index<S: Storage>(storage: &mut S) -> Singleton<'_, S, PoolIndex> {
+    singleton(storage, POOL_INDEX_KEY)
+}
+
+pub fn pool_index_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, PoolIndex> {
+    singleton_read(storage, POOL_INDEX_KEY)
+}
+
+// Pools are keyed by their big-endian id so keys sort in creation order.
+pub fn pool_key(pool_id: u64) -> [u8; 8] {
+    pool_id.to_be_bytes()
+}
+
+// Every record belonging to a single pool (delegators, entries, ...) lives under
+// `pool:{id}:`, with one length-prefixed sub-namespace per subsystem. Keys of
+// different pools and subsystems therefore never collide, and a pool can be
+// pruned without touching anything else. The Pool itself stays in `pools`.
+pub fn pool_namespace(pool_id: u64) -> Vec<u8> {
+    format!("pool:{}:", pool_id).into_bytes()
+}
+
+pub fn pool_storage<S: Storage>(storage: &mut S) -> Bucket<'_, S, Pool> {
+    bucket(POOLS_KEY, storage)
+}
+
+pub fn pool_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Pool> {
+    bucket_read(POOLS_KEY, storage)
+}
+
+// Run after every handler that moves balances. The running aggregates of each live
+// pool are always checked; debug builds also re-add every entry, which is too
+// costly to do on chain.
+pub fn check_invariants<S: Storage>(storage: &S) -> StdResult<()> {
+    let index = pool_index_read(storage).may_load()?.unwrap_or_default();
+    let live = [
+        index.current_open,
+        index.current_locked,
+        index.pending_settlement,
+    ];
+    for pool_id in live.iter().flatten() {
+        let pool = pool_read(storage).load(&pool_key(*pool_id))?;
+        pool.check_totals()?;
+        #[cfg(debug_assertions)]
+        check_entry_totals(storage, &pool)?;
+    }
+    Ok(())
+}
+
+// Pool totals must equal the sum of its entries.
+#[cfg(debug_assertions)]
+fn check_entry_totals<S: Storage>(storage: &S, pool: &Pool) -> StdResult<()> {
+    let delegators = pool_delegators_read(storage, pool.id);
+    let mut amount = Uint128::zero();
+    let mut weight = Uint128::zero();
+    let mut depositors = 0u64;
+    for index in 0..pool.delegator_count {
+        let delegator = match delegators.may_load(&index.to_be_bytes())? {
+            Some(delegator) => delegator,
+            None => continue,
+        };
+        if let Some(entry) = may_load_entry(storage, pool.id, &delegator)? {
+            if entry.index != index {
+                return Err(ContractError::InvariantBroken {
+                    pool_id: pool.id,
+                    what: "delegator listed twice".to_string(),
                 }
+                .into());
+            }
+            amount = math::add(amount, entry.amount)?;
+            weight = math::add(weight, entry.weight)?;
+            if !entry.weight.is_zero() {
+                depositors += 1;
             }
-            _ => {}
         }
-        Ok(())
     }
+    if amount != pool.total_deposits
+        || weight != pool.total_weight
+        || depositors != pool.unique_depositors
+    {
+        return Err(ContractError::InvariantBroken {
+            pool_id: pool.id,
+            what: "totals don't match the entries".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// Resolve a cursor into the index range [start, end) of a list holding `len` entries.
+// `start_after` is the last index seen by the caller; None starts from the beginning.
+pub fn page_range(start_after: Option<u64>, limit: Option<u32>, len: u64) -> (u64, u64) {
+    let limit = u64::from(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT));
+    let start = start_after.map_or(0, |x| x.saturating_add(1)).min(len);
+    let end = start.saturating_add(limit).min(len);
+    (start, end)
+}
+
+// Like `page_range`, but walking backwards from `start_before`; None starts from the end.
+// The caller iterates the range in reverse.
+pub fn page_range_rev(start_before: Option<u64>, limit: Option<u32>, len: u64) -> (u64, u64) {
+    let limit = u64::from(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT));
+    let end = start_before.unwrap_or(len).min(len);
+    (end.saturating_sub(limit), end)
+}
+
+// Read one page of an index-keyed list of `len` entries.
+// Slots removed by cleanup are skipped, so a page may hold fewer than `limit` items.
+pub fn read_page<S: Storage, T: Serialize + DeserializeOwned>(
+    list: &ReadonlyBucket<S, T>,
+    len: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<(u64, T)>> {
+    let (start, end) = page_range(start_after, limit, len);
+    let mut page = vec![];
+    for index in start..end {
+        if let Some(item) = list.may_load(&index.to_be_bytes())? {
+            page.push((index, item));
+        }
+    }
+    Ok(page)
+}
+
+// Keys of a map that is otherwise only looked up by key, in insertion order, so an
+// export can walk it. The list's length is stored under the empty key.
+fn push_key<S: Storage>(storage: &mut S, namespace: &[&[u8]], key: &[u8]) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(namespace, storage);
+    let len: u64 = bin_may_load(&store, &[])?.unwrap_or_default();
+    store.set(&len.to_be_bytes(), key);
+    bin_save(&mut store, &[], &(len + 1))
+}
+
+// One page of a key list, and the list's length.
+pub fn read_keys<S: Storage>(
+    storage: &S,
+    namespace: &[&[u8]],
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<(u64, Vec<u8>)>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(namespace, storage);
+    let len: u64 = bin_may_load(&store, &[])?.unwrap_or_default();
+    let (start, end) = page_range(start_after, limit, len);
+    let mut page = vec![];
+    for index in start..end {
+        if let Some(key) = store.get(&index.to_be_bytes()) {
+            page.push((index, key));
+        }
+    }
+    Ok((page, len))
+}
+
+pub fn pool_delegators<S: Storage>(storage: &mut S, pool_id: u64) -> Bucket<'_, S, CanonicalAddr> {
+    Bucket::multilevel(&[&pool_namespace(pool_id), DELEGATORS_NAMESPACE], storage)
+}
+
+pub fn pool_delegators_read<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+) -> ReadonlyBucket<'_, S, CanonicalAddr> {
+    ReadonlyBucket::multilevel(&[&pool_namespace(pool_id), DELEGATORS_NAMESPACE], storage)
+}
+
+// Append a delegator to the pool's list. The caller persists the pool.
+// An address gets one slot and one entry per pool, however often it deposits.
+pub fn push_delegator<S: Storage>(
+    storage: &mut S,
+    pool: &mut Pool,
+    delegator: &CanonicalAddr,
+) -> StdResult<u64> {
+    if may_load_entry(storage, pool.id, delegator)?.is_some() {
+        return Err(ContractError::DuplicateEntry.into());
+    }
+    let index = pool.delegator_count;
+    pool_delegators(storage, pool.id).save(&index.to_be_bytes(), delegator)?;
+    pool.delegator_count += 1;
+    Ok(index)
+}
+
+pub fn read_delegators<S: Storage>(
+    storage: &S,
+    pool: &Pool,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<(u64, CanonicalAddr)>> {
+    let list = pool_delegators_read(storage, pool.id);
+    read_page(&list, pool.delegator_count, start_after, limit)
+}
+
+// A delegator's position in a pool.
+// Weight is one unit per deposited unit, tracked apart from the balance so
+// entries can later be weighted differently without touching balances.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Entry {
+    // Position in the pool's delegator list.
+    pub index: u64,
+    pub amount: Uint128,
+    pub weight: Uint128,
+}
+
+// Entries are written on every deposit, so they are stored with bincode
+// rather than JSON. Config and query responses stay JSON.
+pub fn save_entry<S: Storage>(
+    storage: &mut S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+    entry: &Entry,
+) -> StdResult<()> {
+    let mut store =
+        PrefixedStorage::multilevel(&[&pool_namespace(pool_id), ENTRIES_NAMESPACE], storage);
+    bin_save(&mut store, delegator.as_slice(), entry)
+}
+
+pub fn may_load_entry<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+) -> StdResult<Option<Entry>> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), ENTRIES_NAMESPACE],
+        storage,
+    );
+    bin_may_load(&store, delegator.as_slice())
+}
+
+// The entry's memo goes with it.
+pub fn remove_entry<S: Storage>(storage: &mut S, pool_id: u64, delegator: &CanonicalAddr) {
+    let mut store =
+        PrefixedStorage::multilevel(&[&pool_namespace(pool_id), ENTRIES_NAMESPACE], storage);
+    store.remove(delegator.as_slice());
+    let mut store =
+        PrefixedStorage::multilevel(&[&pool_namespace(pool_id), MEMOS_NAMESPACE], storage);
+    store.remove(delegator.as_slice());
+}
+
+// A client-chosen key seen recently, stored hashed so records have one size.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SeenKey {
+    pub hash: Vec<u8>,
+    pub expires_at: u64,
 }
 
-pub fn pool_storage<S: Storage>(storage: &mut S) -> Singleton<S, Pool> {
-    singleton(storage, POOL_KEY)
+pub fn load_seen_keys<S: Storage>(storage: &S, sender: &CanonicalAddr) -> StdResult<Vec<SeenKey>> {
+    let store = ReadonlyPrefixedStorage::new(IDEMPOTENCY_KEY, storage);
+    Ok(bin_may_load(&store, sender.as_slice())?.unwrap_or_default())
 }
 
-pub fn pool_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Pool> {
-    singleton_read(storage, POOL_KEY)
+pub fn save_seen_keys<S: Storage>(
+    storage: &mut S,
+    sender: &CanonicalAddr,
+    seen: &[SeenKey],
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(IDEMPOTENCY_KEY, storage);
+    bin_save(&mut store, sender.as_slice(), &seen)
+}
+
+// Remember `key` for the sender, failing if it was used within the window. Expired
+// keys are dropped on the way; past the cap the oldest is forgotten early.
+pub fn use_idempotency_key<S: Storage>(
+    storage: &mut S,
+    sender: &CanonicalAddr,
+    key: &str,
+    now: u64,
+) -> StdResult<()> {
+    if key.is_empty() {
+        return Err(ContractError::EmptyIdempotencyKey.into());
+    }
+    if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(ContractError::TooLong {
+            what: "Idempotency key".to_string(),
+            max: MAX_IDEMPOTENCY_KEY_LEN,
+        }
+        .into());
+    }
+    let hash = Sha256::digest(key.as_bytes()).to_vec();
+    let mut seen = load_seen_keys(storage, sender)?;
+    seen.retain(|k| k.expires_at > now);
+    if seen.iter().any(|k| k.hash == hash) {
+        return Err(ContractError::DuplicateRequest.into());
+    }
+    if seen.len() >= MAX_IDEMPOTENCY_KEYS {
+        seen.remove(0);
+    }
+    seen.push(SeenKey {
+        hash,
+        expires_at: now.saturating_add(IDEMPOTENCY_WINDOW),
+    });
+    save_seen_keys(storage, sender, &seen)
+}
+
+// Deposits seen at `height`; a count from an earlier block is stale.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+struct BlockCount {
+    height: u64,
+    count: u32,
+}
+
+// Count a deposit by `sender` against the limits for this block. The per-address
+// counts sit next to the total, which is stored under the empty key. Both are
+// checked before either is written.
+pub fn count_deposit<S: Storage>(
+    storage: &mut S,
+    limit: &DepositLimit,
+    sender: &CanonicalAddr,
+    height: u64,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(DEPOSIT_COUNT_KEY, storage);
+    let mut counts = vec![];
+    for (key, max) in &[
+        (&[][..], limit.per_block),
+        (sender.as_slice(), limit.per_address),
+    ] {
+        if let Some(max) = max {
+            let seen: BlockCount = bin_may_load(&store, key)?
+                .filter(|c: &BlockCount| c.height == height)
+                .unwrap_or_default();
+            if seen.count >= *max {
+                return Err(ContractError::DepositLimitReached.into());
+            }
+            counts.push((*key, seen.count + 1));
+        }
+    }
+    for (key, count) in counts {
+        bin_save(&mut store, key, &BlockCount { height, count })?;
+    }
+    Ok(())
+}
+
+pub fn validate_memo(memo: &str) -> StdResult<()> {
+    if memo.len() > MAX_MEMO_LEN {
+        return Err(ContractError::TooLong {
+            what: "Memo".to_string(),
+            max: MAX_MEMO_LEN,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// A depositor's private note on their entry. Contract storage is encrypted by the
+// chain, the memo is padded like every bincode record, and it is only returned
+// by viewing-key queries.
+pub fn save_memo<S: Storage>(
+    storage: &mut S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+    memo: &str,
+) -> StdResult<()> {
+    validate_memo(memo)?;
+    let mut store =
+        PrefixedStorage::multilevel(&[&pool_namespace(pool_id), MEMOS_NAMESPACE], storage);
+    bin_save(&mut store, delegator.as_slice(), &memo.to_string())
+}
+
+pub fn may_load_memo<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+) -> StdResult<Option<String>> {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[&pool_namespace(pool_id), MEMOS_NAMESPACE], storage);
+    bin_may_load(&store, delegator.as_slice())
+}
+
+// Emission shares are claimed once per pool and depositor.
+pub fn has_claimed_emission<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+) -> bool {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), EMISSION_CLAIMS_NAMESPACE],
+        storage,
+    );
+    store.get(delegator.as_slice()).is_some()
+}
+
+pub fn mark_emission_claimed<S: Storage>(storage: &mut S, pool_id: u64, delegator: &CanonicalAddr) {
+    let mut store = PrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), EMISSION_CLAIMS_NAMESPACE],
+        storage,
+    );
+    store.set(delegator.as_slice(), &[1]);
+}
+
+// Amounts of a multi-denom pool's extra denoms deposited by one address.
+pub fn save_entry_balances<S: Storage>(
+    storage: &mut S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+    balances: &[Coin],
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), ENTRY_BALANCES_NAMESPACE],
+        storage,
+    );
+    bin_save(&mut store, delegator.as_slice(), &balances.to_vec())
+}
+
+pub fn load_entry_balances<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+) -> StdResult<Vec<Coin>> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), ENTRY_BALANCES_NAMESPACE],
+        storage,
+    );
+    Ok(bin_may_load(&store, delegator.as_slice())?.unwrap_or_default())
+}
+
+// Add `amount` of `denom` to a list of balances.
+pub fn add_coin(balances: &mut Vec<Coin>, denom: &str, amount: Uint128) -> StdResult<()> {
+    match balances.iter_mut().find(|c| c.denom == denom) {
+        Some(coin) => coin.amount = math::add(coin.amount, amount)?,
+        None => balances.push(Coin {
+            denom: denom.to_string(),
+            amount,
+        }),
+    }
+    Ok(())
+}
+
+pub fn load_entry<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    delegator: &CanonicalAddr,
+) -> StdResult<Entry> {
+    may_load_entry(storage, pool_id, delegator)?.ok_or_else(|| StdError::not_found("Entry"))
+}
+
+// Round `amount` to the nearest multiple of `step`, halves rounding up. A step of
+// zero or one leaves it unchanged.
+pub fn round_to_step(amount: Uint128, step: Uint128) -> Uint128 {
+    let step = step.u128();
+    if step <= 1 {
+        return amount;
+    }
+    let remainder = amount.u128() % step;
+    let down = amount.u128() - remainder;
+    if remainder * 2 >= step {
+        Uint128(down.saturating_add(step))
+    } else {
+        Uint128(down)
+    }
+}
+
+// Extend `data` with `fill` up to the next multiple of `block_size`. Zero disables padding.
+pub fn pad_to_block(data: &mut Vec<u8>, block_size: usize, fill: u8) {
+    if block_size > 0 {
+        let surplus = data.len() % block_size;
+        if surplus > 0 {
+            data.resize(data.len() + block_size - surplus, fill);
+        }
+    }
+}
+
+// Bincode ignores trailing bytes, so the padding needs no stripping on load.
+pub fn bin_save<S: Storage, T: Serialize>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
+    let mut bytes =
+        bincode2::serialize(value).map_err(|e| StdError::serialize_err(type_name::<T>(), e))?;
+    pad_to_block(&mut bytes, STORAGE_BLOCK_SIZE, 0);
+    storage.set(key, &bytes);
+    Ok(())
+}
+
+pub fn bin_may_load<S: ReadonlyStorage, T: DeserializeOwned>(
+    storage: &S,
+    key: &[u8],
+) -> StdResult<Option<T>> {
+    match storage.get(key) {
+        Some(bytes) => bincode2::deserialize(&bytes)
+            .map(Some)
+            .map_err(|e| StdError::parse_err(type_name::<T>(), e)),
+        None => Ok(None),
+    }
+}
+
+// Lifetime record of an address across all pools. Written on every deposit,
+// so stored with bincode like entries.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Player {
+    // Gross amount ever deposited; withdrawals are not subtracted.
+    pub total_deposited: Uint128,
+    pub rounds_played: u64,
+    pub prizes_won: u64,
+    pub total_won: Uint128,
+    // Length of the address's activity log, see `push_history`.
+    pub history_count: u64,
+    // Slot in the leaderboard list while the address opts into public listing.
+    pub listing_index: Option<u64>,
+}
+
+pub fn listed_players<S: Storage>(storage: &mut S) -> Bucket<'_, S, CanonicalAddr> {
+    bucket(LISTED_KEY, storage)
+}
+
+pub fn listed_players_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, CanonicalAddr> {
+    bucket_read(LISTED_KEY, storage)
+}
+
+// Add the address to the leaderboard list. The caller persists the state and the record.
+// A full list drops its smallest depositor for a larger one, who has to opt in again
+// to come back; an address that wouldn't outrank anyone is turned away.
+pub fn list_player<S: Storage>(
+    storage: &mut S,
+    state: &mut State,
+    player: &CanonicalAddr,
+    record: &mut Player,
+) -> StdResult<()> {
+    if record.listing_index.is_some() {
+        return Ok(());
+    }
+    if state.listed_players < MAX_LISTED_PLAYERS {
+        let index = state.listed_players;
+        listed_players(storage).save(&index.to_be_bytes(), player)?;
+        state.listed_players += 1;
+        record.listing_index = Some(index);
+        return Ok(());
+    }
+    let mut smallest: Option<(u64, CanonicalAddr, Player)> = None;
+    for index in 0..state.listed_players {
+        let listed = listed_players_read(storage).load(&index.to_be_bytes())?;
+        let listed_record = may_load_player(storage, &listed)?.unwrap_or_default();
+        if smallest.as_ref().map_or(true, |s| {
+            listed_record.total_deposited < s.2.total_deposited
+        }) {
+            smallest = Some((index, listed, listed_record));
+        }
+    }
+    match smallest {
+        Some((index, evicted, mut evicted_record))
+            if record.total_deposited > evicted_record.total_deposited =>
+        {
+            evicted_record.listing_index = None;
+            save_player(storage, &evicted, &evicted_record)?;
+            listed_players(storage).save(&index.to_be_bytes(), player)?;
+            record.listing_index = Some(index);
+            Ok(())
+        }
+        _ => Err(ContractError::LeaderboardFull.into()),
+    }
+}
+
+// Remove the address from the leaderboard list by moving the last listed address into
+// its slot. The caller persists the state and the record.
+pub fn unlist_player<S: Storage>(
+    storage: &mut S,
+    state: &mut State,
+    record: &mut Player,
+) -> StdResult<()> {
+    let index = match record.listing_index.take() {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+    let last = state.listed_players - 1;
+    if index != last {
+        let moved = listed_players_read(storage).load(&last.to_be_bytes())?;
+        let mut moved_record = may_load_player(storage, &moved)?.unwrap_or_default();
+        moved_record.listing_index = Some(index);
+        save_player(storage, &moved, &moved_record)?;
+        listed_players(storage).save(&index.to_be_bytes(), &moved)?;
+    }
+    listed_players(storage).remove(&last.to_be_bytes());
+    state.listed_players = last;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Deposit,
+    Withdraw,
+    Claim,
+    Win,
+}
+
+// One line of an address's activity log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HistoryEntry {
+    pub action: Action,
+    pub pool_id: u64,
+    pub amount: Uint128,
+    pub time: u64,
+}
+
+pub fn player_history<'a, S: Storage>(
+    storage: &'a mut S,
+    player: &CanonicalAddr,
+) -> Bucket<'a, S, HistoryEntry> {
+    Bucket::multilevel(&[HISTORY_KEY, player.as_slice()], storage)
+}
+
+pub fn player_history_read<'a, S: Storage>(
+    storage: &'a S,
+    player: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, HistoryEntry> {
+    ReadonlyBucket::multilevel(&[HISTORY_KEY, player.as_slice()], storage)
+}
+
+// Append to the address's activity log. The caller persists the player record.
+pub fn push_history<S: Storage>(
+    storage: &mut S,
+    player: &CanonicalAddr,
+    record: &mut Player,
+    entry: &HistoryEntry,
+) -> StdResult<()> {
+    let index = record.history_count;
+    player_history(storage, player).save(&index.to_be_bytes(), entry)?;
+    record.history_count += 1;
+    Ok(())
+}
+
+// Newest first; `start_before` is the last index seen by the caller.
+pub fn read_history<S: Storage>(
+    storage: &S,
+    player: &CanonicalAddr,
+    record: &Player,
+    start_before: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<(u64, HistoryEntry)>> {
+    let list = player_history_read(storage, player);
+    let (start, end) = page_range_rev(start_before, limit, record.history_count);
+    let mut page = vec![];
+    for index in (start..end).rev() {
+        page.push((index, list.load(&index.to_be_bytes())?));
+    }
+    Ok(page)
+}
+
+pub fn save_player<S: Storage>(
+    storage: &mut S,
+    player: &CanonicalAddr,
+    record: &Player,
+) -> StdResult<()> {
+    if may_load_player(storage, player)?.is_none() {
+        push_key(storage, &[PLAYER_LIST_KEY], player.as_slice())?;
+    }
+    let mut store = PrefixedStorage::new(PLAYERS_KEY, storage);
+    bin_save(&mut store, player.as_slice(), record)
+}
+
+pub fn may_load_player<S: Storage>(
+    storage: &S,
+    player: &CanonicalAddr,
+) -> StdResult<Option<Player>> {
+    let store = ReadonlyPrefixedStorage::new(PLAYERS_KEY, storage);
+    bin_may_load(&store, player.as_slice())
+}
+
+// Set by contracts that want a `WinnerNotification` when they claim a prize. With
+// `attach_prize` the prize is sent along with it, instead of in a plain transfer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WinnerCallback {
+    pub code_hash: String,
+    pub attach_prize: bool,
+}
+
+pub fn winner_callbacks<S: Storage>(storage: &mut S) -> Bucket<'_, S, WinnerCallback> {
+    bucket(WINNER_CALLBACKS_KEY, storage)
+}
+
+pub fn winner_callbacks_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, WinnerCallback> {
+    bucket_read(WINNER_CALLBACKS_KEY, storage)
+}
+
+// Hook registrations waiting for the owner's approval, keyed by the registering
+// contract. Kept out of `State::hooks` so they can't crowd out approved hooks.
+pub fn pending_hooks<S: Storage>(storage: &mut S) -> Bucket<'_, S, Token> {
+    bucket(PENDING_HOOKS_KEY, storage)
+}
+
+pub fn pending_hooks_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Token> {
+    bucket_read(PENDING_HOOKS_KEY, storage)
+}
+
+// Children in spawn order, keyed by index like other lists.
+pub fn children<S: Storage>(storage: &mut S) -> Bucket<'_, S, Child> {
+    bucket(CHILDREN_KEY, storage)
+}
+
+pub fn children_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Child> {
+    bucket_read(CHILDREN_KEY, storage)
+}
+
+// Credit for deposits made with a referral code, stored under the code's hash.
+// Referred depositors aren't recorded, so the referral graph can't be rebuilt.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Referral {
+    pub referrer: CanonicalAddr,
+    pub deposits: u64,
+    pub referred_amount: Uint128,
+}
+
+pub fn referral_code_hash(code: &str) -> Vec<u8> {
+    Sha256::digest(code.as_bytes()).to_vec()
+}
+
+pub fn save_referral<S: Storage>(
+    storage: &mut S,
+    code_hash: &[u8],
+    referral: &Referral,
+) -> StdResult<()> {
+    if may_load_referral(storage, code_hash)?.is_none() {
+        push_key(storage, &[REFERRAL_LIST_KEY], code_hash)?;
+    }
+    let mut store = PrefixedStorage::new(REFERRALS_KEY, storage);
+    bin_save(&mut store, code_hash, referral)
+}
+
+pub fn may_load_referral<S: Storage>(storage: &S, code_hash: &[u8]) -> StdResult<Option<Referral>> {
+    let store = ReadonlyPrefixedStorage::new(REFERRALS_KEY, storage);
+    bin_may_load(&store, code_hash)
+}
+
+// What one referral code brought into a pool. Kept per pool and looked up by code
+// hash, so it says nothing about who was referred.
+pub fn add_referred<S: Storage>(
+    storage: &mut S,
+    pool_id: u64,
+    code_hash: &[u8],
+    amount: Uint128,
+) -> StdResult<()> {
+    let referred = load_referred(storage, pool_id, code_hash)?;
+    if referred.is_zero() {
+        push_key(
+            storage,
+            &[&pool_namespace(pool_id), REFERRED_LIST_NAMESPACE],
+            code_hash,
+        )?;
+    }
+    let referred = math::add(referred, amount)?;
+    let mut store =
+        PrefixedStorage::multilevel(&[&pool_namespace(pool_id), REFERRED_NAMESPACE], storage);
+    bin_save(&mut store, code_hash, &referred)
+}
+
+pub fn load_referred<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+    code_hash: &[u8],
+) -> StdResult<Uint128> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), REFERRED_NAMESPACE],
+        storage,
+    );
+    Ok(bin_may_load(&store, code_hash)?.unwrap_or_default())
+}
+
+// Referral rewards are claimed once per pool and code.
+pub fn has_claimed_referral<S: Storage>(storage: &S, pool_id: u64, code_hash: &[u8]) -> bool {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), REFERRAL_CLAIMS_NAMESPACE],
+        storage,
+    );
+    store.get(code_hash).is_some()
+}
+
+pub fn mark_referral_claimed<S: Storage>(storage: &mut S, pool_id: u64, code_hash: &[u8]) {
+    let mut store = PrefixedStorage::multilevel(
+        &[&pool_namespace(pool_id), REFERRAL_CLAIMS_NAMESPACE],
+        storage,
+    );
+    store.set(code_hash, &[1]);
+}
+
+pub const VIEWING_KEY_PREFIX: &str = "api_key_";
+
+// Seed for generated viewing keys. Kept out of `State` so no query returns it.
+pub fn write_prng_seed<S: Storage>(storage: &mut S, seed: &[u8]) {
+    storage.set(PRNG_SEED_KEY, &Sha256::digest(seed));
+}
+
+pub fn has_prng_seed<S: Storage>(storage: &S) -> bool {
+    storage.get(PRNG_SEED_KEY).is_some()
+}
+
+// Fold new entropy into the seed. The old seed stays an input, so weak entropy can't
+// make it any worse.
+pub fn rotate_prng_seed<S: Storage>(storage: &mut S, entropy: &[u8]) {
+    let seed = storage.get(PRNG_SEED_KEY).unwrap_or_default();
+    storage.set(
+        PRNG_SEED_KEY,
+        &Sha256::digest(&[&seed[..], entropy].concat()),
+    );
+}
+
+// Fold transaction data into the contract's entropy pool. Like the seed, it never
+// leaves contract storage.
+pub fn add_entropy<S: Storage>(storage: &mut S, data: &[u8]) {
+    let entropy = storage.get(ENTROPY_KEY).unwrap_or_default();
+    storage.set(ENTROPY_KEY, &Sha256::digest(&[&entropy[..], data].concat()));
+}
+
+// Snapshot the seed and entropy for `pool_id`'s draw as it locks. Later seed
+// rotations, viewing keys and deposits into the next pool move the live seed and
+// entropy, but not this, so nothing done after the lock can steer the draw. The live
+// seed is ratcheted past the snapshot.
+pub fn seal_draw_seed<S: Storage>(storage: &mut S, pool_id: u64) {
+    let sealed = live_draw_state(storage, pool_id);
+    let next_seed = Sha256::digest(&[&sealed[..], b"seed"].concat());
+    storage.set(PRNG_SEED_KEY, &next_seed);
+    PrefixedStorage::new(&pool_namespace(pool_id), storage).set(SEALED_SEED_KEY, &sealed);
+}
+
+fn live_draw_state<S: Storage>(storage: &S, pool_id: u64) -> Vec<u8> {
+    let seed = storage.get(PRNG_SEED_KEY).unwrap_or_default();
+    let entropy = storage.get(ENTROPY_KEY).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(&seed);
+    hasher.update(&entropy);
+    hasher.update(pool_id.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+// Randomness for a pool's draw, from the seed sealed at lock only. Nothing in the
+// draw message or its block goes in, so whoever sends it can't steer or predict the
+// result. Pools locked before seeds were sealed draw from the live seed.
+pub fn draw_randomness<S: Storage>(storage: &S, pool_id: u64) -> Vec<u8> {
+    let sealed = ReadonlyPrefixedStorage::new(&pool_namespace(pool_id), storage)
+        .get(SEALED_SEED_KEY)
+        .unwrap_or_else(|| live_draw_state(storage, pool_id));
+    Sha256::digest(&[&sealed[..], b"draw"].concat()).to_vec()
+}
+
+// A winner scan over the pool's delegator slots, which may span several transactions.
+// The winning point is fixed when the scan starts, so resuming it can't change the
+// result.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DrawProgress {
+    // Point on the running total of entry weights that the winner's entry covers.
+    pub target: Uint128,
+    // Next slot to scan, and the weight of the slots before it.
+    pub cursor: u64,
+    pub scanned_weight: Uint128,
+    pub winner: Option<CanonicalAddr>,
+}
+
+impl DrawProgress {
+    pub fn start(pool: &Pool, randomness: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&randomness[..16]);
+        let target = match pool.total_weight.u128() {
+            0 => 0,
+            total => u128::from_be_bytes(bytes) % total,
+        };
+        DrawProgress {
+            target: Uint128(target),
+            cursor: 0,
+            scanned_weight: Uint128::zero(),
+            winner: None,
+        }
+    }
+    pub fn is_done(&self, pool: &Pool) -> bool {
+        self.cursor >= pool.delegator_count
+    }
+}
+
+// Scan the slots up to `end`. The scan carries on past the winner, so how far it has
+// got says nothing about where the winner is listed.
+pub fn advance_draw<S: Storage>(
+    storage: &S,
+    pool: &Pool,
+    progress: &mut DrawProgress,
+    end: u64,
+) -> StdResult<()> {
+    let delegators = pool_delegators_read(storage, pool.id);
+    let end = end.min(pool.delegator_count);
+    for index in progress.cursor..end {
+        let delegator = delegators.load(&index.to_be_bytes())?;
+        // An entry only counts at its own slot, so a repeated slot can't add weight.
+        let weight = match may_load_entry(storage, pool.id, &delegator)? {
+            Some(entry) if entry.index == index => entry.weight,
+            _ => continue,
+        };
+        progress.scanned_weight = math::add(progress.scanned_weight, weight)?;
+        if progress.winner.is_none() && progress.target < progress.scanned_weight {
+            progress.winner = Some(delegator);
+        }
+    }
+    progress.cursor = progress.cursor.max(end);
+    if progress.is_done(pool) && progress.winner.is_none() && !pool.total_weight.is_zero() {
+        return Err(ContractError::WeightsMismatch.into());
+    }
+    Ok(())
+}
+
+// Pick a delegator with odds proportional to entry weight. None when nobody holds weight.
+pub fn pick_winner<S: Storage>(
+    storage: &S,
+    pool: &Pool,
+    randomness: &[u8],
+) -> StdResult<Option<CanonicalAddr>> {
+    let mut progress = DrawProgress::start(pool, randomness);
+    advance_draw(storage, pool, &mut progress, pool.delegator_count)?;
+    Ok(progress.winner)
+}
+
+// Derive a viewing key from the seed, the block, the sender and their entropy, then
+// ratchet the seed. Key and next seed are hashed apart, so a key says nothing about
+// the seed that produces the next one.
+pub fn generate_viewing_key<S: Storage>(storage: &mut S, env: &Env, entropy: &str) -> String {
+    let seed = storage.get(PRNG_SEED_KEY).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(&seed);
+    hasher.update(env.block.height.to_be_bytes());
+    hasher.update(env.block.time.to_be_bytes());
+    hasher.update(env.message.sender.as_str().as_bytes());
+    hasher.update(entropy.as_bytes());
+    let rng_state = hasher.finalize();
+    let key = Sha256::digest(&[&rng_state[..], b"key"].concat());
+    let next_seed = Sha256::digest(&[&rng_state[..], b"seed"].concat());
+    storage.set(PRNG_SEED_KEY, &next_seed);
+    format!(
+        "{}{}",
+        VIEWING_KEY_PREFIX,
+        Binary::from(&key[..]).to_base64()
+    )
+}
+
+// Only the sha256 hash of a viewing key is stored.
+pub fn write_viewing_key<S: Storage>(storage: &mut S, owner: &CanonicalAddr, key: &str) {
+    let mut store = PrefixedStorage::new(VIEWING_KEYS_KEY, storage);
+    store.set(owner.as_slice(), &Sha256::digest(key.as_bytes()));
+}
+
+// Compares in constant time, and also hashes when no key is set, so the
+// response time does not reveal whether an address has a key.
+pub fn check_viewing_key<S: Storage>(storage: &S, owner: &CanonicalAddr, key: &str) -> bool {
+    let store = ReadonlyPrefixedStorage::new(VIEWING_KEYS_KEY, storage);
+    let stored = store.get(owner.as_slice());
+    let hash = Sha256::digest(key.as_bytes());
+    match stored {
+        Some(stored) => bool::from(hash[..].ct_eq(&stored)),
+        None => {
+            let _ = hash[..].ct_eq(&[0u8; 32]);
+            false
+        }
+    }
+}
+
+// One status change of a pool. `from` is None for the creation of the pool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Transition {
+    pub from: Option<PoolStatus>,
+    pub to: PoolStatus,
+    pub time: u64,
+    pub sender: CanonicalAddr,
+}
+
+pub fn pool_transitions<S: Storage>(storage: &mut S, pool_id: u64) -> Bucket<'_, S, Transition> {
+    Bucket::multilevel(&[&pool_namespace(pool_id), TRANSITIONS_NAMESPACE], storage)
+}
+
+pub fn pool_transitions_read<S: Storage>(
+    storage: &S,
+    pool_id: u64,
+) -> ReadonlyBucket<'_, S, Transition> {
+    ReadonlyBucket::multilevel(&[&pool_namespace(pool_id), TRANSITIONS_NAMESPACE], storage)
+}
+
+// Log the change from `from` into the pool's current status. The caller persists the pool.
+pub fn push_transition<S: Storage>(
+    storage: &mut S,
+    pool: &mut Pool,
+    from: Option<PoolStatus>,
+    sender: &CanonicalAddr,
+    time: u64,
+) -> StdResult<()> {
+    let transition = Transition {
+        from,
+        to: pool.status.clone(),
+        time,
+        sender: sender.clone(),
+    };
+    let index = pool.transition_count;
+    pool_transitions(storage, pool.id).save(&index.to_be_bytes(), &transition)?;
+    pool.transition_count += 1;
+    Ok(())
+}
+
+pub fn read_transitions<S: Storage>(
+    storage: &S,
+    pool: &Pool,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<(u64, Transition)>> {
+    let list = pool_transitions_read(storage, pool.id);
+    read_page(&list, pool.transition_count, start_after, limit)
+}
+
+// Pool ids of settled pools whose winner opted into public listing, in announcement order.
+pub fn winners<S: Storage>(storage: &mut S) -> Bucket<'_, S, u64> {
+    bucket(WINNERS_KEY, storage)
+}
+
+pub fn winners_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, u64> {
+    bucket_read(WINNERS_KEY, storage)
+}
+
+// Make the winner of a settled pool public. The caller persists the state and the pool.
+pub fn announce_winner<S: Storage>(
+    storage: &mut S,
+    state: &mut State,
+    pool: &mut Pool,
+) -> StdResult<()> {
+    if pool.winner.is_none() {
+        return Err(ContractError::NoWinner.into());
+    }
+    if pool.winner_announced {
+        return Err(ContractError::AlreadyAnnounced.into());
+    }
+    let index = state.announced_winners;
+    winners(storage).save(&index.to_be_bytes(), &pool.id)?;
+    state.announced_winners += 1;
+    pool.winner_announced = true;
+    Ok(())
+}
+
+pub fn read_winners<S: Storage>(
+    storage: &S,
+    state: &State,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<(u64, u64)>> {
+    read_page(
+        &winners_read(storage),
+        state.announced_winners,
+        start_after,
+        limit,
+    )
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    PoolCreated,
+    PoolLocked,
+    PoolClosed,
+    WinnerDrawn,
+    PrizeClaimed,
+}
+
+// Lifecycle milestone, numbered by its position in the event log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Event {
+    pub kind: EventKind,
+    pub pool_id: u64,
+    pub time: u64,
+}
+
+pub fn events<S: Storage>(storage: &mut S) -> Bucket<'_, S, Event> {
+    bucket(EVENTS_KEY, storage)
+}
+
+pub fn events_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Event> {
+    bucket_read(EVENTS_KEY, storage)
+}
+
+// Append to the event log and return the event's sequence number.
+// The caller persists the state.
+pub fn record_event<S: Storage>(
+    storage: &mut S,
+    state: &mut State,
+    kind: EventKind,
+    pool_id: u64,
+    time: u64,
+) -> StdResult<u64> {
+    let seq = state.event_count;
+    let event = Event {
+        kind,
+        pool_id,
+        time,
+    };
+    events(storage).save(&seq.to_be_bytes(), &event)?;
+    state.event_count += 1;
+    // Every milestone is a phase change, so the public stats catch up here.
+    state.published_stats = Some(state.stats.clone());
+    Ok(seq)
+}
+
+// Load the most recently created pool, if any.
+pub fn current_pool<S: Storage>(storage: &S) -> StdResult<Option<Pool>> {
+    let state = config_read(storage).load()?;
+    if state.pool_count == 0 {
+        return Ok(None);
+    }
+    pool_read(storage).may_load(&pool_key(state.pool_count))
+}
+
+// Layout of the config and pool before pools were keyed by id.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyState {
+    pub count: i32,
+    pub owner: CanonicalAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyPool {
+    pub delegated_amt: Uint128,
+    pub delegators: Vec<CanonicalAddr>,
+    pub status: PoolStatus,
+    pub status_updated_at: u64,
+}
+
+impl LegacyPool {
+    // Delegators are not carried over; the caller pushes them onto the indexed list.
+    // Deadlines are derived from the last status change under `timing`.
+    pub fn to_pool(&self, id: u64, timing: &Timing) -> Pool {
+        let deadline = |duration: u64| Some(self.status_updated_at.saturating_add(duration));
+        let (locks_at, closes_at, settles_at) = match self.status {
+            PoolStatus::OPEN => (deadline(timing.open_duration), None, None),
+            PoolStatus::LOCKED => (None, deadline(timing.locked_duration), None),
+            PoolStatus::CLOSED => (None, None, deadline(0)),
+        };
+        Pool {
+            id,
+            metadata: PoolMetadata::default(),
+            delegated_amt: self.delegated_amt,
+            delegations: vec![],
+            unbonding_amount: Uint128(0),
+            unbonds_at: None,
+            delegator_count: 0,
+            total_deposits: self.delegated_amt,
+            unique_depositors: self.delegators.len() as u64,
+            total_weight: self.delegated_amt,
+            seed_amount: Uint128(0),
+            sponsor_amount: Uint128(0),
+            carryover_amount: Uint128(0),
+            status: self.status.clone(),
+            // The creation time was not recorded; the last status change is the earliest known.
+            created_at: self.status_updated_at,
+            status_updated_at: self.status_updated_at,
+            status_updated_height: 0,
+            locks_at,
+            closes_at,
+            settles_at,
+            winner: None,
+            prize_amount: Uint128(0),
+            rewards_realized: Uint128(0),
+            settled_at: None,
+            winner_announced: false,
+            cleanup_cursor: 0,
+            transition_count: 0,
+            fee_rate: Decimal::zero(),
+            fee_amount: Uint128(0),
+            published: None,
+            harvested_rewards: Uint128(0),
+            nft_tickets: false,
+            prize_claimed: false,
+            emission_amount: Uint128(0),
+            ibc_denom: None,
+            unconverted_rewards: vec![],
+            converting: false,
+            prize_value: None,
+            extra_denoms: vec![],
+            denom_balances: vec![],
+            ibc_payout: None,
+            slashed_amount: Uint128(0),
+            slash_covered: Uint128(0),
+            principal_claimed: Uint128(0),
+            referred_deposits: Uint128(0),
+            referral_amount: Uint128(0),
+            draw: None,
+        }
+    }
+}
+
+pub fn legacy_config_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, LegacyState> {
+    singleton_read(storage, CONFIG_KEY)
+}
+
+pub fn legacy_pool_storage<S: Storage>(storage: &mut S) -> Singleton<'_, S, LegacyPool> {
+    singleton(storage, LEGACY_POOL_KEY)
+}
+
+pub fn legacy_pool_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, LegacyPool> {
+    singleton_read(storage, LEGACY_POOL_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+    use cosmwasm_std::{from_slice, to_vec};
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(page_range(None, None, 0), (0, 0));
+        assert_eq!(page_range(None, None, 100), (0, u64::from(DEFAULT_LIMIT)));
+        assert_eq!(page_range(Some(4), Some(3), 100), (5, 8));
+        assert_eq!(page_range(Some(95), Some(10), 100), (96, 100));
+        assert_eq!(page_range(Some(150), Some(10), 100), (100, 100));
+        assert_eq!(page_range(Some(u64::MAX), None, 100), (100, 100));
+        // Limit is capped regardless of what the caller asks for.
+        assert_eq!(page_range(None, Some(1000), 100), (0, u64::from(MAX_LIMIT)));
+
+        assert_eq!(page_range_rev(None, None, 100), (90, 100));
+        assert_eq!(page_range_rev(Some(90), Some(5), 100), (85, 90));
+        assert_eq!(page_range_rev(Some(3), None, 100), (0, 3));
+        assert_eq!(page_range_rev(Some(500), Some(2), 100), (98, 100));
+    }
+
+    fn block_at(time: u64) -> BlockInfo {
+        BlockInfo {
+            height: time / 5,
+            time,
+            chain_id: "secret-testnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_generate_viewing_key() {
+        let mut storage = MockStorage::new();
+        write_prng_seed(&mut storage, b"seed");
+        let env = mock_env("alice", &[]);
+        let first = generate_viewing_key(&mut storage, &env, "entropy");
+        let second = generate_viewing_key(&mut storage, &env, "entropy");
+        assert!(first.starts_with(VIEWING_KEY_PREFIX));
+        // The seed moves on, so the same inputs never repeat a key.
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_viewing_key() {
+        let mut storage = MockStorage::new();
+        let owner = CanonicalAddr::from(vec![1; 20]);
+        assert!(!check_viewing_key(&storage, &owner, "key"));
+        write_viewing_key(&mut storage, &owner, "key");
+        assert!(check_viewing_key(&storage, &owner, "key"));
+        assert!(!check_viewing_key(&storage, &owner, "kez"));
+        assert!(!check_viewing_key(
+            &storage,
+            &CanonicalAddr::from(vec![2; 20]),
+            "key"
+        ));
+    }
+
+    #[test]
+    fn test_split_by_weight() {
+        let validators = vec![
+            Validator {
+                address: HumanAddr::from("secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"),
+                weight: 1,
+            },
+            Validator {
+                address: HumanAddr::from("secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem"),
+                weight: 2,
+            },
+        ];
+        let split = split_by_weight(Uint128(100), &validators).unwrap();
+        assert_eq!(
+            split,
+            vec![
+                Delegation {
+                    validator: HumanAddr::from(
+                        "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm"
+                    ),
+                    amount: Uint128(34),
+                },
+                Delegation {
+                    validator: HumanAddr::from(
+                        "secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem"
+                    ),
+                    amount: Uint128(66),
+                },
+            ]
+        );
+        let split = split_by_weight(Uint128(1), &validators).unwrap();
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].amount, Uint128(1));
+        assert!(split_by_weight(Uint128(u128::MAX), &validators).is_err());
+
+        assert!(validate_validators(&validators).is_ok());
+        let duplicate = vec![validators[0].clone(), validators[0].clone()];
+        assert!(validate_validators(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_record_settlement() {
+        let mut pool = Pool::new(1, &Timing::default(), &block_at(1000));
+        pool.lock(&Timing::default(), &block_at(1000 + DAYS));
+        pool.close(&Timing::default(), &block_at(1000 + 22 * DAYS));
+        let mut empty = pool.clone();
+        pool.settle(
+            Some(CanonicalAddr::from(vec![9; 20])),
+            Uint128(10),
+            Uint128(8),
+            0,
+        )
+        .unwrap();
+        empty.settle(None, Uint128(4), Uint128(4), 0).unwrap();
+
+        let mut stats = Stats::default();
+        stats.record_settlement(&pool).unwrap();
+        stats.record_settlement(&empty).unwrap();
+        assert_eq!(stats.prizes_awarded, 1);
+        assert_eq!(stats.total_prizes_paid, Uint128(8));
+        assert_eq!(stats.total_rewards, Uint128(14));
+    }
+
+    #[test]
+    fn test_settle_pool() {
+        let winner = CanonicalAddr::from(vec![9; 20]);
+        let mut pool = Pool::new(1, &Timing::default(), &block_at(1000));
+        assert!(pool
+            .settle(Some(winner.clone()), Uint128(10), Uint128(10), 2000)
+            .is_err());
+
+        pool.lock(&Timing::default(), &block_at(1000 + DAYS));
+        assert_eq!(pool.closes_at, Some(1000 + 22 * DAYS));
+        pool.close(&Timing::default(), &block_at(1000 + 22 * DAYS));
+        assert_eq!(pool.settles_at, Some(1000 + 22 * DAYS));
+        assert!(pool
+            .settle(Some(winner.clone()), Uint128(10), Uint128(11), 2000)
+            .is_err());
+        pool.settle(Some(winner.clone()), Uint128(10), Uint128(8), 2000)
+            .unwrap();
+        assert!(pool.is_settled());
+        assert_eq!(pool.winner, Some(winner.clone()));
+        assert_eq!(pool.rewards_realized, Uint128(10));
+        assert_eq!(pool.prize_amount, Uint128(8));
+        assert_eq!(pool.settled_at, Some(2000));
+
+        // A pool is settled exactly once.
+        assert!(pool.settle(None, Uint128(0), Uint128(0), 3000).is_err());
+
+        // The fee is reserved before the prize.
+        let mut pool = Pool::new(2, &Timing::default(), &block_at(1000));
+        pool.fee_rate = Decimal::percent(10);
+        pool.lock(&Timing::default(), &block_at(1000 + DAYS));
+        pool.close(&Timing::default(), &block_at(1000 + 22 * DAYS));
+        assert!(pool.settle(None, Uint128(25), Uint128(24), 2000).is_err());
+        pool.settle(None, Uint128(25), Uint128(23), 2000).unwrap();
+        assert_eq!(pool.fee_amount, Uint128(2));
+    }
+
+    #[test]
+    fn test_fee_rate_reads_basis_points() {
+        let fees: Fees = from_slice(br#"{"rate_bps":250,"recipient":null,"accrued":"7","pending":{"rate_bps":2000,"recipient":null}}"#).unwrap();
+        assert_eq!(fees.rate, Decimal::permille(25));
+        assert_eq!(fees.pending.unwrap().rate, Decimal::percent(20));
+        let fees = Fees {
+            rate: Decimal::percent(5),
+            ..Fees::default()
+        };
+        let stored = to_vec(&fees).unwrap();
+        assert_eq!(from_slice::<Fees>(&stored).unwrap(), fees);
+    }
+
+    #[test]
+    fn test_entry_binary_round_trip() {
+        let mut storage = MockStorage::new();
+        let delegator = CanonicalAddr::from(vec![7; 20]);
+        let entry = Entry {
+            index: 3,
+            amount: Uint128(u128::MAX),
+            weight: Uint128(42),
+        };
+        assert_eq!(may_load_entry(&storage, 1, &delegator).unwrap(), None);
+        save_entry(&mut storage, 1, &delegator, &entry).unwrap();
+        assert_eq!(load_entry(&storage, 1, &delegator).unwrap(), entry);
+        // Entries are keyed per pool.
+        assert_eq!(may_load_entry(&storage, 2, &delegator).unwrap(), None);
+        // The stored bytes are bincode, not JSON.
+        let store =
+            ReadonlyPrefixedStorage::multilevel(&[&pool_namespace(1), ENTRIES_NAMESPACE], &storage);
+        let raw = store.get(delegator.as_slice()).unwrap();
+        assert!(from_slice::<Entry>(&raw).is_err());
+        assert_eq!(raw.len(), STORAGE_BLOCK_SIZE);
+        let small = Entry {
+            amount: Uint128(1),
+            ..entry.clone()
+        };
+        save_entry(&mut storage, 2, &delegator, &small).unwrap();
+        let store =
+            ReadonlyPrefixedStorage::multilevel(&[&pool_namespace(2), ENTRIES_NAMESPACE], &storage);
+        assert_eq!(store.get(delegator.as_slice()).unwrap().len(), raw.len());
+
+        // Garbage is reported as a parse error rather than a panic.
+        let mut store =
+            PrefixedStorage::multilevel(&[&pool_namespace(1), ENTRIES_NAMESPACE], &mut storage);
+        store.set(delegator.as_slice(), b"{}");
+        match may_load_entry(&storage, 1, &delegator) {
+            Err(StdError::ParseErr { .. }) => {}
+            _ => panic!("Must return parse error"),
+        }
+    }
+
+    #[test]
+    fn test_pool_namespaces_do_not_collide() {
+        let mut storage = MockStorage::new();
+        let delegator = CanonicalAddr::from(vec![7; 20]);
+        let entry = Entry {
+            index: 0,
+            amount: Uint128(5),
+            weight: Uint128(5),
+        };
+        let mut pool = Pool::new(1, &Timing::default(), &block_at(1000));
+        push_delegator(&mut storage, &mut pool, &delegator).unwrap();
+        save_entry(&mut storage, 1, &delegator, &entry).unwrap();
+
+        // Records sit under the pool's namespace, split by subsystem.
+        let pool_store = ReadonlyPrefixedStorage::new(&pool_namespace(1), &storage);
+        let entries = ReadonlyPrefixedStorage::new(ENTRIES_NAMESPACE, &pool_store);
+        assert!(entries.get(delegator.as_slice()).is_some());
+        let delegators = ReadonlyPrefixedStorage::new(DELEGATORS_NAMESPACE, &pool_store);
+        assert!(delegators.get(delegator.as_slice()).is_none());
+
+        // Pool 11 shares the textual prefix of pool 1 but not its keys.
+        assert_eq!(may_load_entry(&storage, 11, &delegator).unwrap(), None);
+        assert!(read_delegators(
+            &storage,
+            &Pool::new(11, &Timing::default(), &block_at(1000)),
+            None,
+            None
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn test_read_delegators_pages() {
+        let mut storage = MockStorage::new();
+        let mut pool = Pool::new(1, &Timing::default(), &block_at(1000));
+        for i in 0..5u8 {
+            push_delegator(&mut storage, &mut pool, &CanonicalAddr::from(vec![i; 20])).unwrap();
+        }
+        assert_eq!(pool.delegator_count, 5);
+
+        let page = read_delegators(&storage, &pool, None, Some(2)).unwrap();
+        assert_eq!(
+            page,
+            vec![
+                (0, CanonicalAddr::from(vec![0; 20])),
+                (1, CanonicalAddr::from(vec![1; 20]))
+            ]
+        );
+        let page = read_delegators(&storage, &pool, Some(1), Some(10)).unwrap();
+        assert_eq!(page.len(), 3);
+        assert_eq!(page[2], (4, CanonicalAddr::from(vec![4; 20])));
+        let page = read_delegators(&storage, &pool, Some(4), None).unwrap();
+        assert!(page.is_empty());
+
+        // Lists of different pools do not overlap.
+        let other = Pool::new(2, &Timing::default(), &block_at(1000));
+        assert!(read_delegators(&storage, &other, None, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_round_to_step() {
+        assert_eq!(round_to_step(Uint128(1499), Uint128(1000)), Uint128(1000));
+        assert_eq!(round_to_step(Uint128(1500), Uint128(1000)), Uint128(2000));
+        assert_eq!(round_to_step(Uint128(499), Uint128(1000)), Uint128(0));
+        assert_eq!(round_to_step(Uint128(1234), Uint128(1)), Uint128(1234));
+        assert_eq!(round_to_step(Uint128(1234), Uint128(0)), Uint128(1234));
+        assert_eq!(
+            round_to_step(Uint128(u128::MAX), Uint128(10)),
+            Uint128(u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_pick_winner_follows_weights() {
+        let mut storage = MockStorage::new();
+        let env = mock_env("owner", &[]);
+        let mut pool = Pool::new(1, &Timing::default(), &env.block);
+        for (byte, weight) in &[(1u8, 10u128), (2, 0), (3, 30)] {
+            let addr = CanonicalAddr(Binary::from(vec![*byte]));
+            let index = push_delegator(&mut storage, &mut pool, &addr).unwrap();
+            let entry = Entry {
+                index,
+                amount: Uint128(*weight),
+                weight: Uint128(*weight),
+            };
+            save_entry(&mut storage, pool.id, &addr, &entry).unwrap();
+            pool.total_weight += Uint128(*weight);
+        }
+        let pick = |target: u128| {
+            let mut randomness = target.to_be_bytes().to_vec();
+            randomness.extend_from_slice(&[0; 16]);
+            pick_winner(&storage, &pool, &randomness).unwrap().unwrap()
+        };
+        assert_eq!(pick(0), CanonicalAddr(Binary::from(vec![1])));
+        assert_eq!(pick(9), CanonicalAddr(Binary::from(vec![1])));
+        assert_eq!(pick(10), CanonicalAddr(Binary::from(vec![3])));
+        assert_eq!(pick(49), CanonicalAddr(Binary::from(vec![1])));
+
+        // A delegator with an entry can't be listed again.
+        let addr = CanonicalAddr(Binary::from(vec![3]));
+        let res = push_delegator(&mut storage, &mut pool, &addr);
+        assert_eq!(res.unwrap_err(), ContractError::DuplicateEntry.into());
+        // Even a repeated slot written behind its back only counts once.
+        pool_delegators(&mut storage, pool.id)
+            .save(&pool.delegator_count.to_be_bytes(), &addr)
+            .unwrap();
+        pool.delegator_count += 1;
+        pool.total_weight += Uint128(30);
+        let mut randomness = 45u128.to_be_bytes().to_vec();
+        randomness.extend_from_slice(&[0; 16]);
+        let res = pick_winner(&storage, &pool, &randomness);
+        assert_eq!(res.unwrap_err(), ContractError::WeightsMismatch.into());
+    }
+
+    #[test]
+    fn test_draw_resumes_in_batches() {
+        let mut storage = MockStorage::new();
+        let env = mock_env("owner", &[]);
+        let mut pool = Pool::new(1, &Timing::default(), &env.block);
+        for byte in 1u8..=5 {
+            let addr = CanonicalAddr(Binary::from(vec![byte]));
+            let index = push_delegator(&mut storage, &mut pool, &addr).unwrap();
+            let weight = Uint128(u128::from(byte) * 10);
+            let entry = Entry {
+                index,
+                amount: weight,
+                weight,
+            };
+            save_entry(&mut storage, pool.id, &addr, &entry).unwrap();
+            pool.total_weight += weight;
+        }
+        for target in (0..150u128).step_by(7) {
+            let mut randomness = target.to_be_bytes().to_vec();
+            randomness.extend_from_slice(&[0; 16]);
+            let mut progress = DrawProgress::start(&pool, &randomness);
+            while !progress.is_done(&pool) {
+                let end = progress.cursor + 2;
+                advance_draw(&storage, &pool, &mut progress, end).unwrap();
+                // Scanning the same slots again adds nothing.
+                let before = progress.clone();
+                advance_draw(&storage, &pool, &mut progress, end).unwrap();
+                assert_eq!(progress, before);
+            }
+            // The whole list is scanned, wherever the winner was.
+            assert_eq!(progress.scanned_weight, pool.total_weight);
+            let winner = pick_winner(&storage, &pool, &randomness).unwrap();
+            assert_eq!(progress.winner, winner);
+        }
+    }
+
+    #[test]
+    fn test_emission_schedule() {
+        let emission = Emission {
+            token: Token {
+                address: HumanAddr::from("reward"),
+                code_hash: "hash".to_string(),
+            },
+            per_round: Uint128(1000),
+            start_pool: 3,
+            halving_interval: 2,
+        };
+        assert_eq!(emission.amount_for(2), Uint128(0));
+        assert_eq!(emission.amount_for(3), Uint128(1000));
+        assert_eq!(emission.amount_for(4), Uint128(1000));
+        assert_eq!(emission.amount_for(5), Uint128(500));
+        assert_eq!(emission.amount_for(9), Uint128(125));
+        assert_eq!(emission.amount_for(1000), Uint128(0));
+        let flat = Emission {
+            halving_interval: 0,
+            ..emission
+        };
+        assert_eq!(flat.amount_for(1000), Uint128(1000));
+    }
+
+    #[test]
+    fn test_ibc_denom_validation() {
+        let atom = IbcDenom {
+            denom: "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+                .to_string(),
+            trace: "transfer/channel-0/uatom".to_string(),
+        };
+        assert!(atom.validate().is_ok());
+        let lowercase = IbcDenom {
+            denom: atom.denom.to_lowercase(),
+            ..atom.clone()
+        };
+        assert!(lowercase.validate().is_err());
+        let wrong_channel = IbcDenom {
+            trace: "transfer/channel-1/uatom".to_string(),
+            ..atom.clone()
+        };
+        assert!(wrong_channel.validate().is_err());
+        for trace in &[
+            "uatom",
+            "transfer/uatom",
+            "transfer/channel-0/",
+            "/channel-0/uatom",
+        ] {
+            let invalid = IbcDenom {
+                trace: trace.to_string(),
+                ..atom.clone()
+            };
+            assert!(invalid.validate().is_err(), "{}", trace);
+        }
+    }
 }
@@ -0,0 +1,433 @@
+//! Off-chain stand-ins for the chain around the contract, for integrators and our
+//! own tests. `Chain` runs handles against the mock dependencies and applies the
+//! bank and staking messages they return, simulating delegations, rewards, slashes
+//! and unbonding, so a pool can go through lock, harvest and settle off-chain.
+//!
+//! Native only. Enable the `testing` feature to use it from another crate.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_std::{
+    coin, BankMsg, Coin, CosmosMsg, Env, Extern, FullDelegation, HandleResponse, HumanAddr,
+    StakingMsg, StdResult,
+};
+
+use crate::contract::{handle, init};
+use crate::msg::{HandleMsg, InitMsg};
+use crate::state::{Validator, DENOM, UNBONDING_PERIOD};
+
+// Seconds per block; heights follow the clock at this pace.
+pub const BLOCK_TIME: u64 = 5;
+
+// An env for `sender` at `time`, with the matching height.
+pub fn env_at<U: Into<HumanAddr>>(sender: U, funds: &[Coin], time: u64) -> Env {
+    let mut env = mock_env(sender, funds);
+    env.block.time = time;
+    env.block.height = time / BLOCK_TIME;
+    env
+}
+
+// Default settings, staking with `validators` in equal weight.
+pub fn init_msg(validators: &[&str]) -> InitMsg {
+    let validators = validators
+        .iter()
+        .map(|address| Validator {
+            address: HumanAddr::from(*address),
+            weight: 1,
+        })
+        .collect::<Vec<_>>();
+    InitMsg {
+        timing: None,
+        validators: if validators.is_empty() {
+            None
+        } else {
+            Some(validators)
+        },
+        response_block_size: None,
+        prng_seed: None,
+        public_rounding: None,
+        delay_public_totals: None,
+        transparent: None,
+        generic_errors: None,
+        parent: None,
+        guardian: None,
+    }
+}
+
+#[derive(Default)]
+struct Stake {
+    amount: u128,
+    rewards: BTreeMap<String, u128>,
+}
+
+struct Unbonding {
+    validator: HumanAddr,
+    amount: u128,
+    matures_at: u64,
+}
+
+pub struct Chain {
+    pub deps: Extern<MockStorage, MockApi, MockQuerier>,
+    pub time: u64,
+    // Coins by address, then denom.
+    balances: BTreeMap<String, BTreeMap<String, u128>>,
+    // The contract's delegations, by validator.
+    stakes: BTreeMap<String, Stake>,
+    unbonding: Vec<Unbonding>,
+}
+
+impl Chain {
+    // The contract instantiated by `owner` at the mock env's time.
+    pub fn new(owner: &str, msg: InitMsg) -> Self {
+        let mut deps = mock_dependencies(20, &[]);
+        let time = mock_env(owner, &[]).block.time;
+        init(&mut deps, env_at(owner, &[], time), msg).unwrap();
+        Chain {
+            deps,
+            time,
+            balances: BTreeMap::new(),
+            stakes: BTreeMap::new(),
+            unbonding: vec![],
+        }
+    }
+
+    pub fn contract() -> HumanAddr {
+        HumanAddr::from(MOCK_CONTRACT_ADDR)
+    }
+
+    // What `address` holds of `denom`. Only funds moved by the contract are tracked.
+    pub fn balance(&self, address: &str, denom: &str) -> u128 {
+        self.balances
+            .get(address)
+            .and_then(|coins| coins.get(denom))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // The contract's stake with `validator`, not counting what is unbonding.
+    pub fn staked(&self, validator: &str) -> u128 {
+        self.stakes.get(validator).map_or(0, |stake| stake.amount)
+    }
+
+    // Move the clock on. Unbonding that matures by then lands in the contract's balance.
+    pub fn advance(&mut self, seconds: u64) {
+        self.time += seconds;
+        let time = self.time;
+        let (matured, unbonding) = self
+            .unbonding
+            .drain(..)
+            .partition::<Vec<_>, _>(|u| u.matures_at <= time);
+        self.unbonding = unbonding;
+        for u in matured {
+            self.credit(&Self::contract(), &coin(u.amount, DENOM));
+        }
+        self.sync();
+    }
+
+    // Rewards accrue on the contract's delegation with `validator`, to be paid out
+    // when it withdraws or undelegates.
+    pub fn add_rewards(&mut self, validator: &str, reward: Coin) {
+        let stake = self.stakes.entry(validator.to_string()).or_default();
+        *stake.rewards.entry(reward.denom).or_default() += reward.amount.u128();
+        self.sync();
+    }
+
+    // Slash `percent` of the stake with `validator`, and of what is unbonding from it.
+    pub fn slash(&mut self, validator: &str, percent: u128) {
+        if let Some(stake) = self.stakes.get_mut(validator) {
+            stake.amount -= stake.amount * percent / 100;
+        }
+        for u in self
+            .unbonding
+            .iter_mut()
+            .filter(|u| u.validator.as_str() == validator)
+        {
+            u.amount -= u.amount * percent / 100;
+        }
+        self.sync();
+    }
+
+    // Run `msg` from `sender` as one transaction. Storage is rolled back if it fails;
+    // the bank and staking messages of a success are applied. Messages to other
+    // contracts are returned but not run.
+    pub fn execute(
+        &mut self,
+        sender: &str,
+        funds: &[Coin],
+        msg: HandleMsg,
+    ) -> StdResult<HandleResponse> {
+        let snapshot = self.deps.storage.clone();
+        for c in funds {
+            self.credit(&Self::contract(), c);
+        }
+        self.sync();
+        let res = handle(&mut self.deps, env_at(sender, funds, self.time), msg);
+        match res.as_ref() {
+            Ok(response) => {
+                for msg in response.messages.iter() {
+                    self.apply(msg);
+                }
+            }
+            Err(_) => {
+                self.deps.storage = snapshot;
+                for c in funds {
+                    self.debit(&Self::contract(), c);
+                }
+            }
+        }
+        self.sync();
+        res
+    }
+
+    fn apply(&mut self, msg: &CosmosMsg) {
+        let contract = Self::contract();
+        match msg {
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address,
+                to_address,
+                amount,
+            }) => {
+                for c in amount {
+                    self.debit(from_address, c);
+                    self.credit(to_address, c);
+                }
+            }
+            CosmosMsg::Staking(StakingMsg::Delegate { validator, amount }) => {
+                self.debit(&contract, amount);
+                let stake = self.stakes.entry(validator.to_string()).or_default();
+                stake.amount += amount.amount.u128();
+            }
+            // Like the staking module, undelegating pays out the rewards first.
+            CosmosMsg::Staking(StakingMsg::Undelegate { validator, amount }) => {
+                self.withdraw_rewards(validator, &contract);
+                let stake = self
+                    .stakes
+                    .get_mut(validator.as_str())
+                    .expect("no delegation");
+                stake.amount = stake
+                    .amount
+                    .checked_sub(amount.amount.u128())
+                    .expect("undelegated more than was delegated");
+                self.unbonding.push(Unbonding {
+                    validator: validator.clone(),
+                    amount: amount.amount.u128(),
+                    matures_at: self.time + UNBONDING_PERIOD,
+                });
+            }
+            CosmosMsg::Staking(StakingMsg::Withdraw {
+                validator,
+                recipient,
+            }) => {
+                let recipient = recipient.clone().unwrap_or(contract);
+                self.withdraw_rewards(validator, &recipient);
+            }
+            CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator,
+                dst_validator,
+                amount,
+            }) => {
+                let src = self
+                    .stakes
+                    .get_mut(src_validator.as_str())
+                    .expect("no delegation");
+                src.amount -= amount.amount.u128();
+                let dst = self.stakes.entry(dst_validator.to_string()).or_default();
+                dst.amount += amount.amount.u128();
+            }
+            _ => {}
+        }
+    }
+
+    fn withdraw_rewards(&mut self, validator: &HumanAddr, recipient: &HumanAddr) {
+        let rewards = match self.stakes.get_mut(validator.as_str()) {
+            Some(stake) => std::mem::take(&mut stake.rewards),
+            None => return,
+        };
+        for (denom, amount) in rewards {
+            self.credit(recipient, &coin(amount, &denom));
+        }
+    }
+
+    fn credit(&mut self, address: &HumanAddr, c: &Coin) {
+        let coins = self.balances.entry(address.to_string()).or_default();
+        *coins.entry(c.denom.clone()).or_default() += c.amount.u128();
+    }
+
+    // Paying out more than an address holds fails the test, as it would the transaction.
+    fn debit(&mut self, address: &HumanAddr, c: &Coin) {
+        let held = self
+            .balances
+            .get_mut(address.as_str())
+            .and_then(|coins| coins.get_mut(&c.denom))
+            .filter(|held| **held >= c.amount.u128())
+            .unwrap_or_else(|| panic!("{} can't send {}{}", address, c.amount, c.denom));
+        *held -= c.amount.u128();
+    }
+
+    // Show the querier the contract's balance and delegations.
+    fn sync(&mut self) {
+        let contract = Self::contract();
+        let balance = self
+            .balances
+            .get(contract.as_str())
+            .map(|coins| {
+                coins
+                    .iter()
+                    .filter(|(_, amount)| **amount > 0)
+                    .map(|(denom, amount)| coin(*amount, denom))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.deps.querier.update_balance(contract.clone(), balance);
+        let delegations = self
+            .stakes
+            .iter()
+            .filter(|(_, stake)| stake.amount > 0 || !stake.rewards.is_empty())
+            .map(|(validator, stake)| FullDelegation {
+                delegator: contract.clone(),
+                validator: HumanAddr::from(validator.as_str()),
+                amount: coin(stake.amount, DENOM),
+                can_redelegate: coin(stake.amount, DENOM),
+                accumulated_rewards: stake
+                    .rewards
+                    .iter()
+                    .map(|(denom, amount)| coin(*amount, denom))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        self.deps.querier.update_staking(DENOM, &[], &delegations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{current_pool, DAYS};
+    use cosmwasm_std::{coins, Api};
+
+    #[test]
+    fn test_lock_harvest_settle() {
+        let validators = [
+            "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm",
+            "secretvaloper1k6tt04vpp4dvex909q7993g48rhgldujltvyem",
+        ];
+        let mut chain = Chain::new("creator", init_msg(&validators));
+        let create = HandleMsg::CrtePool {
+            metadata: None,
+            nft_tickets: None,
+            extra_denoms: None,
+            ibc_denom: None,
+            padding: None,
+        };
+        chain.execute("creator", &[], create).unwrap();
+        let deposit = HandleMsg::Deposit {
+            memo: None,
+            referral: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        chain
+            .execute("alice", &coins(300, DENOM), deposit.clone())
+            .unwrap();
+        chain.execute("bob", &coins(100, DENOM), deposit).unwrap();
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 400);
+
+        chain.advance(DAYS);
+        let lock = HandleMsg::LockPool { padding: None };
+        chain.execute("creator", &[], lock).unwrap();
+        assert_eq!(chain.staked(validators[0]), 200);
+        assert_eq!(chain.staked(validators[1]), 200);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 0);
+
+        // Rewards accrue and are paid out by the undelegation; a slash takes 10%
+        // of one validator's stake on the way out.
+        chain.add_rewards(validators[0], coin(30, DENOM));
+        chain.add_rewards(validators[1], coin(20, DENOM));
+        chain.advance(21 * DAYS);
+        let close = HandleMsg::ClsePool { padding: None };
+        chain.execute("creator", &[], close).unwrap();
+        assert_eq!(chain.staked(validators[0]), 0);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 50);
+        chain.slash(validators[1], 10);
+
+        let draw = HandleMsg::Draw { padding: None };
+        assert!(chain.execute("creator", &[], draw.clone()).is_err());
+        chain.advance(UNBONDING_PERIOD);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 430);
+        chain.execute("creator", &[], draw).unwrap();
+        let pool = current_pool(&chain.deps.storage).unwrap().unwrap();
+        assert_eq!(pool.harvested_rewards.u128(), 50);
+        assert_eq!(pool.slashed_amount.u128(), 20);
+        assert_eq!(pool.prize_amount.u128(), 50);
+
+        let winner = chain.deps.api.human_address(&pool.winner.unwrap()).unwrap();
+        let claim = HandleMsg::ClaimPrize {
+            pool_id: pool.id,
+            ibc_payout: None,
+            idempotency_key: None,
+            padding: None,
+        };
+        chain.execute(winner.as_str(), &[], claim).unwrap();
+        assert_eq!(chain.balance(winner.as_str(), DENOM), 50);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 380);
+    }
+
+    #[test]
+    fn test_slash_next_to_unclaimed_principal() {
+        let validator = "secretvaloper17sn4232mx6c7lg04my57alev009y0ms43ef5pm";
+        let mut chain = Chain::new("creator", init_msg(&[validator]));
+        let round = |chain: &mut Chain, depositor: &str, slash: u128| {
+            let create = HandleMsg::CrtePool {
+                metadata: None,
+                nft_tickets: None,
+                extra_denoms: None,
+                ibc_denom: None,
+                padding: None,
+            };
+            chain.execute("creator", &[], create).unwrap();
+            let deposit = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            chain
+                .execute(depositor, &coins(100, DENOM), deposit)
+                .unwrap();
+            chain.advance(DAYS);
+            let lock = HandleMsg::LockPool { padding: None };
+            chain.execute("creator", &[], lock).unwrap();
+            chain.advance(21 * DAYS);
+            let close = HandleMsg::ClsePool { padding: None };
+            chain.execute("creator", &[], close).unwrap();
+            chain.slash(validator, slash);
+            chain.advance(UNBONDING_PERIOD);
+            let draw = HandleMsg::Draw { padding: None };
+            chain.execute("creator", &[], draw).unwrap();
+            current_pool(&chain.deps.storage).unwrap().unwrap()
+        };
+        let claim = |pool_id: u64| HandleMsg::ClaimPrincipal {
+            pool_id,
+            idempotency_key: None,
+            padding: None,
+        };
+
+        // Alice leaves her principal in the first round. The second round's stake
+        // comes back 10 short, which her funds must not hide.
+        let first = round(&mut chain, "alice", 0);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 100);
+        let second = round(&mut chain, "bob", 10);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 190);
+        assert_eq!(second.slashed_amount.u128(), 10);
+
+        chain.execute("bob", &[], claim(second.id)).unwrap();
+        assert_eq!(chain.balance("bob", DENOM), 90);
+        chain.execute("alice", &[], claim(first.id)).unwrap();
+        assert_eq!(chain.balance("alice", DENOM), 100);
+        assert_eq!(chain.balance(MOCK_CONTRACT_ADDR, DENOM), 0);
+        assert!(chain.execute("bob", &[], claim(second.id)).is_err());
+    }
+}
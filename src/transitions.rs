@@ -0,0 +1,346 @@
+use crate::error::ContractError;
+use crate::state::{Pool, PoolStatus, Timing};
+use cosmwasm_std::{BlockInfo, CanonicalAddr};
+
+// The pool lifecycle, in one place:
+//
+//   OPEN --lock--> LOCKED --close--> CLOSED --settle--> CLOSED, settled
+//
+// Every move is made by the owner. Lock and close wait out the status's duration
+// on the contract's clock. A closed pool whose stake is still unbonding stays
+// CLOSED until `unbonds_at`, and settles once its rewards are converted. Pools are
+// never cancelled; one with no depositors settles without a winner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transition {
+    Lock,
+    Close,
+    Settle,
+}
+
+impl Transition {
+    pub const ALL: [Transition; 3] = [Transition::Lock, Transition::Close, Transition::Settle];
+
+    // Status the pool must be in.
+    pub fn from(self) -> PoolStatus {
+        match self {
+            Transition::Lock => PoolStatus::OPEN,
+            Transition::Close => PoolStatus::LOCKED,
+            Transition::Settle => PoolStatus::CLOSED,
+        }
+    }
+    // Status the pool is in afterwards.
+    pub fn to(self) -> PoolStatus {
+        match self {
+            Transition::Lock => PoolStatus::LOCKED,
+            Transition::Close | Transition::Settle => PoolStatus::CLOSED,
+        }
+    }
+    fn wrong_status(self) -> ContractError {
+        match self {
+            Transition::Lock => ContractError::CannotLock,
+            Transition::Close => ContractError::PoolNotLocked,
+            Transition::Settle => ContractError::PoolNotClosed,
+        }
+    }
+}
+
+// Check `transition` against the sender, then the pool's status and the clock.
+pub fn assert_allowed(
+    transition: Transition,
+    pool: &Pool,
+    sender: &CanonicalAddr,
+    owner: &CanonicalAddr,
+    timing: &Timing,
+    block: &BlockInfo,
+) -> Result<(), ContractError> {
+    if sender != owner {
+        return Err(ContractError::Unauthorized);
+    }
+    assert_ready(transition, pool, timing, block)
+}
+
+// The status and clock checks alone, for work toward a transition that anyone may do.
+pub fn assert_ready(
+    transition: Transition,
+    pool: &Pool,
+    timing: &Timing,
+    block: &BlockInfo,
+) -> Result<(), ContractError> {
+    if pool.status != transition.from() {
+        return Err(transition.wrong_status());
+    }
+    match transition {
+        Transition::Lock | Transition::Close => {
+            let deadline = next(pool).map(|(_, at)| at);
+            let entered_height = pool.status_updated_height;
+            pool.status
+                .assert_expired(deadline, entered_height, timing, block)
+        }
+        Transition::Settle => {
+            if pool.is_settled() {
+                return Err(ContractError::AlreadySettled);
+            }
+            // Unbonding is measured in seconds, whatever the timing mode.
+            if pool.unbonds_at.unwrap_or(0) > block.time {
+                return Err(ContractError::StillUnbonding);
+            }
+            if pool.converting {
+                return Err(ContractError::RewardsConverting);
+            }
+            Ok(())
+        }
+    }
+}
+
+// The next timed move of the pool and the earliest point it is allowed, on the
+// contract's clock. A minimum block count may hold it back further. None once the
+// pool is CLOSED.
+pub fn next(pool: &Pool) -> Option<(Transition, u64)> {
+    match pool.status {
+        PoolStatus::OPEN => pool.locks_at.map(|at| (Transition::Lock, at)),
+        PoolStatus::LOCKED => pool.closes_at.map(|at| (Transition::Close, at)),
+        PoolStatus::CLOSED => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{TimingMode, DAYS};
+    use cosmwasm_std::Binary;
+
+    fn block_at(time: u64) -> BlockInfo {
+        BlockInfo {
+            height: time / 5,
+            time,
+            chain_id: "secret-testnet".to_string(),
+        }
+    }
+
+    fn expired(pool: &Pool, timing: &Timing, block: &BlockInfo) -> Result<(), ContractError> {
+        let deadline = next(pool).map(|(_, at)| at);
+        pool.status
+            .assert_expired(deadline, pool.status_updated_height, timing, block)
+    }
+
+    fn addr(byte: u8) -> CanonicalAddr {
+        CanonicalAddr(Binary::from(vec![byte; 20]))
+    }
+
+    // A pool created at 1000 and moved along as far as `status`, each move made
+    // as soon as it was allowed.
+    fn pool_in(status: &PoolStatus, settled: bool, timing: &Timing) -> Pool {
+        let mut pool = Pool::new(1, timing, &block_at(1000));
+        if *status != PoolStatus::OPEN {
+            pool.lock(timing, &block_at(1000 + DAYS));
+        }
+        if *status == PoolStatus::CLOSED {
+            pool.close(timing, &block_at(1000 + 22 * DAYS));
+        }
+        if settled {
+            pool.settled_at = Some(1000 + 22 * DAYS);
+        }
+        pool
+    }
+
+    #[test]
+    fn test_transition_matrix() {
+        let timing = Timing::default();
+        let owner = addr(1);
+        let cases = [
+            (PoolStatus::OPEN, false),
+            (PoolStatus::LOCKED, false),
+            (PoolStatus::CLOSED, false),
+            (PoolStatus::CLOSED, true),
+        ];
+        for transition in Transition::ALL.iter() {
+            for (status, settled) in cases.iter() {
+                let pool = pool_in(status, *settled, &timing);
+                let check = |sender: &CanonicalAddr, time: u64| {
+                    assert_allowed(*transition, &pool, sender, &owner, &timing, &block_at(time))
+                };
+                let late = 1000 + 100 * DAYS;
+
+                // Nobody but the owner moves a pool, whatever its status.
+                assert_eq!(check(&addr(2), late), Err(ContractError::Unauthorized));
+
+                let expected = if *status != transition.from() {
+                    Err(transition.wrong_status())
+                } else if *settled {
+                    Err(ContractError::AlreadySettled)
+                } else {
+                    Ok(())
+                };
+                assert_eq!(
+                    check(&owner, late),
+                    expected,
+                    "{:?} {:?}",
+                    transition,
+                    status
+                );
+            }
+        }
+
+        // Lock and close wait for their status's duration, settling for nothing.
+        let open = pool_in(&PoolStatus::OPEN, false, &timing);
+        let lock = |time: u64| {
+            assert_allowed(
+                Transition::Lock,
+                &open,
+                &owner,
+                &owner,
+                &timing,
+                &block_at(time),
+            )
+        };
+        assert!(lock(1000 + DAYS - 1).is_err());
+        assert_eq!(lock(1000 + DAYS), Ok(()));
+        let locked = pool_in(&PoolStatus::LOCKED, false, &timing);
+        let close = |time: u64| {
+            assert_allowed(
+                Transition::Close,
+                &locked,
+                &owner,
+                &owner,
+                &timing,
+                &block_at(time),
+            )
+        };
+        assert!(close(1000 + 22 * DAYS - 1).is_err());
+        assert_eq!(close(1000 + 22 * DAYS), Ok(()));
+        let mut closed = pool_in(&PoolStatus::CLOSED, false, &timing);
+        let settle = |pool: &Pool, time: u64| {
+            assert_allowed(
+                Transition::Settle,
+                pool,
+                &owner,
+                &owner,
+                &timing,
+                &block_at(time),
+            )
+        };
+        assert_eq!(settle(&closed, 1000 + 22 * DAYS), Ok(()));
+
+        // Unless the stake is still unbonding or rewards are out for conversion.
+        closed.unbonds_at = Some(5000 * DAYS);
+        let res = settle(&closed, 5000 * DAYS - 1);
+        assert_eq!(res, Err(ContractError::StillUnbonding));
+        assert_eq!(settle(&closed, 5000 * DAYS), Ok(()));
+        closed.converting = true;
+        let res = settle(&closed, 5000 * DAYS);
+        assert_eq!(res, Err(ContractError::RewardsConverting));
+    }
+
+    #[test]
+    fn test_next_transition() {
+        let timing = Timing::default();
+        let pool = pool_in(&PoolStatus::OPEN, false, &timing);
+        assert_eq!(next(&pool), Some((Transition::Lock, 1000 + DAYS)));
+        let pool = pool_in(&PoolStatus::LOCKED, false, &timing);
+        assert_eq!(next(&pool), Some((Transition::Close, 1000 + 22 * DAYS)));
+        let pool = pool_in(&PoolStatus::CLOSED, false, &timing);
+        assert_eq!(next(&pool), None);
+        // Every transition lands where the next one starts, or in CLOSED.
+        for transition in Transition::ALL.iter() {
+            let to = transition.to();
+            assert!(to == PoolStatus::CLOSED || Transition::ALL.iter().any(|t| t.from() == to));
+        }
+    }
+
+    #[test]
+    fn test_status_expiry_by_time_and_height() {
+        let timing = Timing::default();
+        let pool = Pool::new(1, &timing, &block_at(1000));
+        assert_eq!(pool.locks_at, Some(1000 + DAYS));
+        assert!(expired(&pool, &timing, &block_at(1000 + DAYS - 1)).is_err());
+        expired(&pool, &timing, &block_at(1000 + DAYS)).unwrap();
+
+        let timing = Timing {
+            mode: TimingMode::Height,
+            open_duration: 100,
+            locked_duration: 500,
+            ..Timing::default()
+        };
+        // Pool is created at height 200.
+        let pool = Pool::new(1, &timing, &block_at(1000));
+        assert_eq!(pool.locks_at, Some(300));
+        let mut block = block_at(1000 + DAYS);
+        block.height = 299;
+        let err = expired(&pool, &timing, &block).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Pool has to be OPEN for 100 blocks; 1 to go"
+        );
+        block.height = 300;
+        expired(&pool, &timing, &block).unwrap();
+
+        // Lock and close wait out the configured durations, whatever they are.
+        let timing = Timing {
+            open_duration: 3 * DAYS,
+            locked_duration: 7 * DAYS,
+            ..Timing::default()
+        };
+        let owner = addr(1);
+        let mut pool = Pool::new(1, &timing, &block_at(1000));
+        let res = assert_allowed(
+            Transition::Lock,
+            &pool,
+            &owner,
+            &owner,
+            &timing,
+            &block_at(1000 + 2 * DAYS),
+        );
+        let err = ContractError::TooEarly {
+            status: "OPEN".to_string(),
+            duration: 3 * DAYS,
+            unit: "seconds".to_string(),
+            remaining: DAYS,
+        };
+        assert_eq!(res, Err(err));
+        pool.lock(&timing, &block_at(1000 + 3 * DAYS));
+        let err = expired(&pool, &timing, &block_at(1000 + 9 * DAYS)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Pool has to be LOCKED for 604800 seconds; 86400 to go"
+        );
+        expired(&pool, &timing, &block_at(1000 + 10 * DAYS)).unwrap();
+        // A CLOSED pool has nothing left to wait for.
+        pool.close(&timing, &block_at(1000 + 10 * DAYS));
+        assert_eq!(PoolStatus::CLOSED.duration(&timing), None);
+        expired(&pool, &timing, &block_at(0)).unwrap();
+    }
+
+    #[test]
+    fn test_time_and_height_thresholds() {
+        let timing = Timing {
+            open_min_blocks: Some(20_000),
+            locked_min_blocks: Some(300_000),
+            ..Timing::default()
+        };
+        // Created at height 200, locks at 1000 + DAYS, or height 17480 on a 5s clock.
+        let mut pool = Pool::new(1, &timing, &block_at(1000));
+        let mut block = block_at(1000 + DAYS);
+        let err = expired(&pool, &timing, &block).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Pool has to be OPEN for 20000 blocks; 2720 to go"
+        );
+        // A skewed clock doesn't help; the blocks have to be there too.
+        block.time += 30 * DAYS;
+        assert!(expired(&pool, &timing, &block).is_err());
+        block.height = 20_200;
+        expired(&pool, &timing, &block).unwrap();
+        block.time = 1000 + DAYS - 1;
+        assert!(expired(&pool, &timing, &block).is_err());
+
+        block.time = 1000 + DAYS;
+        pool.lock(&timing, &block);
+        block.time += 21 * DAYS;
+        block.height += 299_999;
+        let owner = addr(1);
+        let res = assert_allowed(Transition::Close, &pool, &owner, &owner, &timing, &block);
+        assert!(res.is_err());
+        block.height += 1;
+        assert_allowed(Transition::Close, &pool, &owner, &owner, &timing, &block).unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+use cosmwasm_std::{to_binary, Coin, CosmosMsg, HumanAddr, StdResult, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Why funds were sent, so the treasury can book them apart.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TreasuryPurpose {
+    // Protocol fees taken at settlement, see `HandleMsg::CollectFees`.
+    Fees,
+    // Coins no pool accounts for, see `HandleMsg::SweepToTreasury`.
+    Sweep,
+}
+
+// The handle of the treasury contract that the lottery calls. The attached coins
+// are the deposit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TreasuryHandleMsg {
+    Deposit { purpose: TreasuryPurpose },
+}
+
+impl TreasuryHandleMsg {
+    pub fn to_cosmos_msg(
+        &self,
+        treasury: HumanAddr,
+        treasury_code_hash: String,
+        send: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: treasury,
+            callback_code_hash: treasury_code_hash,
+            msg: to_binary(self)?,
+            send,
+        }))
+    }
+}
@@ -0,0 +1,292 @@
+//! Property tests for the pool lifecycle. Random sequences of deposits, withdrawals,
+//! transitions and claims run against the contract, which is checked against a
+//! simple model of who deposited what after every step.
+//!
+//! They take a while, so they sit behind a feature. Run them with `cargo property-test`.
+#![cfg(feature = "proptest")]
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{coins, Api, Extern, HumanAddr, StdResult, Uint128};
+use proptest::prelude::*;
+
+use secret_lottery::contract::{handle, init};
+use secret_lottery::msg::{HandleMsg, InitMsg};
+use secret_lottery::state::{
+    check_invariants, config_read, current_pool, pool_index_read, pool_key, pool_read, Pool,
+    PoolStatus, DAYS, DENOM,
+};
+
+type Deps = Extern<MockStorage, MockApi, MockQuerier>;
+
+const PLAYERS: [&str; 3] = ["alice", "bob", "carol"];
+const OWNER: &str = "creator";
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit { player: usize, amount: u128 },
+    Withdraw { player: usize, amount: u128 },
+    WithdrawAll { player: usize },
+    Wait { seconds: u64 },
+    Create { as_owner: bool },
+    Lock { as_owner: bool },
+    Close { as_owner: bool },
+    PrepareDraw { limit: u32 },
+    Draw { as_owner: bool },
+    Claim { player: usize },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let player = 0..PLAYERS.len();
+    prop_oneof![
+        4 => (player.clone(), 0..1_000u128).prop_map(|(player, amount)| Op::Deposit { player, amount }),
+        2 => (player.clone(), 0..1_000u128).prop_map(|(player, amount)| Op::Withdraw { player, amount }),
+        1 => player.clone().prop_map(|player| Op::WithdrawAll { player }),
+        3 => (0..25 * DAYS).prop_map(|seconds| Op::Wait { seconds }),
+        1 => any::<bool>().prop_map(|as_owner| Op::Create { as_owner }),
+        1 => any::<bool>().prop_map(|as_owner| Op::Lock { as_owner }),
+        1 => any::<bool>().prop_map(|as_owner| Op::Close { as_owner }),
+        1 => (1..3u32).prop_map(|limit| Op::PrepareDraw { limit }),
+        1 => any::<bool>().prop_map(|as_owner| Op::Draw { as_owner }),
+        1 => player.prop_map(|player| Op::Claim { player }),
+    ]
+}
+
+// What the contract should hold, kept without looking at its storage.
+#[derive(Default)]
+struct Model {
+    time: u64,
+    // Deposits of each player in the current pool.
+    deposits: BTreeMap<usize, u128>,
+    // The CLOSED pool waiting for a draw, and its players' deposits.
+    pending: Option<(u64, BTreeMap<usize, u128>)>,
+    // Settled pools with a winner who hasn't claimed yet.
+    unclaimed: BTreeMap<u64, HumanAddr>,
+}
+
+fn setup() -> Deps {
+    let mut deps = mock_dependencies(20, &[]);
+    let msg = InitMsg {
+        timing: None,
+        validators: None,
+        response_block_size: None,
+        prng_seed: None,
+        public_rounding: None,
+        delay_public_totals: None,
+        transparent: None,
+        generic_errors: None,
+        parent: None,
+        guardian: None,
+    };
+    init(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+    let msg = HandleMsg::CrtePool {
+        metadata: None,
+        nft_tickets: None,
+        extra_denoms: None,
+        ibc_denom: None,
+        padding: None,
+    };
+    handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+    deps
+}
+
+fn sender(as_owner: bool) -> &'static str {
+    if as_owner {
+        OWNER
+    } else {
+        PLAYERS[0]
+    }
+}
+
+fn live_pool(deps: &Deps) -> Option<Pool> {
+    current_pool(&deps.storage)
+        .unwrap()
+        .filter(|pool| !pool.is_closed())
+}
+
+// Run `msg` as a transaction: on error the storage is rolled back, as on chain.
+fn execute(deps: &mut Deps, sender: &str, funds: u128, time: u64, msg: HandleMsg) -> StdResult<()> {
+    let snapshot = deps.storage.clone();
+    let funds = if funds == 0 {
+        vec![]
+    } else {
+        coins(funds, DENOM)
+    };
+    let mut env = mock_env(sender, &funds);
+    env.block.time = time;
+    env.block.height = time / 5;
+    let res = handle(deps, env, msg).map(|_| ());
+    if res.is_err() {
+        deps.storage = snapshot;
+    }
+    res
+}
+
+fn step(deps: &mut Deps, model: &mut Model, op: &Op) {
+    let before = live_pool(deps);
+    let time = model.time;
+    match *op {
+        Op::Deposit { player, amount } => {
+            let msg = HandleMsg::Deposit {
+                memo: None,
+                referral: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            let res = execute(deps, PLAYERS[player], amount, time, msg);
+            let open = before.as_ref().map_or(false, |p| p.is_open());
+            assert_eq!(res.is_ok(), open && amount > 0, "{:?} {:?}", op, res);
+            if res.is_ok() {
+                *model.deposits.entry(player).or_default() += amount;
+            }
+        }
+        Op::Withdraw { player, .. } | Op::WithdrawAll { player } => {
+            let held = model.deposits.get(&player).copied().unwrap_or(0);
+            let amount = match *op {
+                Op::Withdraw { amount, .. } => amount,
+                _ => held,
+            };
+            let msg = HandleMsg::Withdraw {
+                amount: Uint128(amount),
+                padding: None,
+            };
+            let res = execute(deps, PLAYERS[player], 0, time, msg);
+            let open = before.as_ref().map_or(false, |p| p.is_open());
+            let allowed = open && amount > 0 && held >= amount;
+            assert_eq!(res.is_ok(), allowed, "{:?} {:?}", op, res);
+            if res.is_ok() {
+                model.deposits.insert(player, held - amount);
+            }
+        }
+        Op::Wait { seconds } => model.time += seconds,
+        Op::Create { as_owner } => {
+            let msg = HandleMsg::CrtePool {
+                metadata: None,
+                nft_tickets: None,
+                extra_denoms: None,
+                ibc_denom: None,
+                padding: None,
+            };
+            let res = execute(deps, sender(as_owner), 0, time, msg);
+            let allowed = as_owner && before.is_none() && model.pending.is_none();
+            assert_eq!(res.is_ok(), allowed, "{:?}", res);
+        }
+        Op::Lock { as_owner } | Op::Close { as_owner } => {
+            let (msg, from, deadline) = match op {
+                Op::Lock { .. } => (
+                    HandleMsg::LockPool { padding: None },
+                    PoolStatus::OPEN,
+                    before.as_ref().and_then(|p| p.locks_at),
+                ),
+                _ => (
+                    HandleMsg::ClsePool { padding: None },
+                    PoolStatus::LOCKED,
+                    before.as_ref().and_then(|p| p.closes_at),
+                ),
+            };
+            let res = execute(deps, sender(as_owner), 0, time, msg);
+            let allowed = as_owner
+                && before.as_ref().map(|p| &p.status) == Some(&from)
+                && deadline.map_or(false, |at| at <= time);
+            assert_eq!(res.is_ok(), allowed, "{:?} {:?}", op, res);
+            if res.is_ok() && from == PoolStatus::LOCKED {
+                let deposits = std::mem::take(&mut model.deposits);
+                model.pending = before.map(|p| (p.id, deposits));
+            }
+        }
+        Op::PrepareDraw { limit } => {
+            let msg = HandleMsg::PrepareDraw {
+                limit: Some(limit),
+                padding: None,
+            };
+            let res = execute(deps, PLAYERS[0], 0, time, msg);
+            assert_eq!(res.is_ok(), model.pending.is_some(), "{:?}", res);
+        }
+        Op::Draw { as_owner } => {
+            let res = execute(
+                deps,
+                sender(as_owner),
+                0,
+                time,
+                HandleMsg::Draw { padding: None },
+            );
+            assert_eq!(
+                res.is_ok(),
+                as_owner && model.pending.is_some(),
+                "{:?}",
+                res
+            );
+            if res.is_ok() {
+                let (pool_id, deposits) = model.pending.take().unwrap();
+                let pool = pool_read(&deps.storage).load(&pool_key(pool_id)).unwrap();
+                assert!(pool.is_settled());
+                // Only someone who still had a deposit can win.
+                match pool.winner {
+                    Some(winner) => {
+                        let winner = deps.api.human_address(&winner).unwrap();
+                        let player = PLAYERS.iter().position(|p| winner.as_str() == *p);
+                        assert!(deposits.get(&player.unwrap()).map_or(false, |d| *d > 0));
+                        model.unclaimed.insert(pool_id, winner);
+                    }
+                    None => assert!(deposits.values().all(|d| *d == 0)),
+                }
+            }
+        }
+        Op::Claim { player } => {
+            let pool_id = model.unclaimed.keys().next().copied().unwrap_or(1);
+            let msg = HandleMsg::ClaimPrize {
+                pool_id,
+                ibc_payout: None,
+                idempotency_key: None,
+                padding: None,
+            };
+            let res = execute(deps, PLAYERS[player], 0, time, msg);
+            let winner = model.unclaimed.get(&pool_id);
+            let allowed = winner.map_or(false, |w| w.as_str() == PLAYERS[player]);
+            assert_eq!(res.is_ok(), allowed, "{:?}", res);
+            if res.is_ok() {
+                model.unclaimed.remove(&pool_id);
+            }
+        }
+    }
+}
+
+// Checks that hold between any two steps.
+fn check(deps: &Deps, model: &Model) {
+    check_invariants(&deps.storage).unwrap();
+    let state = config_read(&deps.storage).load().unwrap();
+    assert_eq!(state.unclaimed_prizes, model.unclaimed.len() as u64);
+    let index = pool_index_read(&deps.storage).load().unwrap();
+    assert_eq!(
+        index.pending_settlement,
+        model.pending.as_ref().map(|p| p.0)
+    );
+    // At most one pool takes deposits or holds stake at a time.
+    assert!(index.current_open.is_none() || index.current_locked.is_none());
+    if let Some(pool) = current_pool(&deps.storage).unwrap() {
+        if !pool.is_closed() {
+            let total: u128 = model.deposits.values().sum();
+            assert_eq!(pool.total_deposits, Uint128(total));
+            let depositors = model.deposits.values().filter(|d| **d > 0).count();
+            assert_eq!(pool.unique_depositors, depositors as u64);
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn pool_lifecycle_keeps_its_invariants(ops in prop::collection::vec(op(), 1..80)) {
+        let mut deps = setup();
+        let mut model = Model {
+            time: mock_env(OWNER, &[]).block.time,
+            ..Model::default()
+        };
+        for op in ops.iter() {
+            step(&mut deps, &mut model, op);
+            check(&deps, &model);
+        }
+    }
+}